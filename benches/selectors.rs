@@ -0,0 +1,63 @@
+//! Compares compiling the selectors of the news parser on every call (as it was done before) with the precompiled ones \
+//! Run with `cargo bench --bench selectors`, criterion reports the change against the last run.
+
+use std::hint::black_box;
+use criterion::{criterion_group, criterion_main, Criterion};
+use once_cell::sync::Lazy;
+use scraper::{Html, Selector};
+use stud_ip_scraper::news::parse_news_box;
+use stud_ip_scraper::ref_source::ReferenceSource;
+
+const SELECTORS: [&str; 7] = [
+    "article[id].studip",
+    "header h1",
+    "header .news_user",
+    "header .news_date",
+    "header .news_visits",
+    "header .news_comments_indicator",
+    "section > article .formatted-content",
+];
+static PRECOMPILED: Lazy<Vec<Selector>> = Lazy::new(|| SELECTORS.iter().map(|selector| Selector::parse(selector).unwrap()).collect());
+
+fn news_box_html() -> String {
+    let article = |i: usize| format!(r#"
+        <article id="news{i}" class="studip">
+            <header>
+                <h1>Ankündigung {i}</h1>
+                <a class="news_user" href="https://studip.example.com/dispatch.php/profile?username=mmustermann">Max Mustermann</a>
+                <span class="news_date">14.02.2025</span>
+                <span class="news_visits">1.234</span>
+                <span class="news_comments_indicator">3</span>
+            </header>
+            <section><article><div class="formatted-content"><p>Inhalt {i}</p></div></article></section>
+        </article>"#);
+    format!("<article class=\"studip\">{}</article>", (0..10).map(article).collect::<String>())
+}
+
+fn select_all(html: &Html, selectors: &[Selector]) -> usize {
+    selectors.iter().map(|selector| html.select(selector).count()).sum()
+}
+
+fn selectors(c: &mut Criterion) {
+    let html = Html::parse_fragment(&news_box_html());
+    let mut group = c.benchmark_group("news selectors");
+    group.bench_function("compiling the selectors only", |b| b.iter(|| {
+        black_box(SELECTORS.iter().map(|selector| Selector::parse(selector).unwrap()).collect::<Vec<_>>())
+    }));
+    group.bench_function("compiling selectors on every call", |b| b.iter(|| {
+        let selectors = SELECTORS.iter().map(|selector| Selector::parse(selector).unwrap()).collect::<Vec<_>>();
+        black_box(select_all(&html, &selectors))
+    }));
+    group.bench_function("precompiled selectors", |b| b.iter(|| {
+        black_box(select_all(&html, &PRECOMPILED))
+    }));
+    group.finish();
+
+    let news_box = html.root_element().first_child().and_then(scraper::ElementRef::wrap).unwrap();
+    c.bench_function("parse_news_box (10 articles)", |b| b.iter(|| {
+        black_box(parse_news_box(news_box, &ReferenceSource::Unspecified).unwrap())
+    }));
+}
+
+criterion_group!(benches, selectors);
+criterion_main!(benches);
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use stud_ip_scraper::course_modules::file::parse_folder_contents;
+
+fuzz_target!(|html: &str| {
+    let _ = parse_folder_contents(html, "c1");
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use stud_ip_scraper::course_modules::members::parse_members_page;
+use stud_ip_scraper::ref_source::ReferenceSource;
+
+fuzz_target!(|html: &str| {
+    let _ = parse_members_page(html, ReferenceSource::Course("c1".into()));
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use stud_ip_scraper::search::parse_search_response;
+
+fuzz_target!(|json: &str| {
+    let _ = parse_search_response(json);
+});
@@ -0,0 +1,386 @@
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+use crate::course::{Course, COURSE_URL};
+use crate::course_modules::{FileModule, MembersModule, WikiModule};
+use crate::course_modules::file::{DownloadOptions, File, FolderTree, SavedTree};
+use crate::error::WithUrl;
+use crate::ids::FileId;
+use crate::course_modules::wiki::WikiExportFormat;
+use crate::{get_module, SendThrough};
+use crate::news::NewsArticle;
+use crate::util::{escape_html, expect_one, parse_page, selector};
+
+/// The version of the archive layout, is written into the manifest
+const ARCHIVE_VERSION: u32 = 1;
+
+/// Selects the sections of a course, that are written by [`Course::archive_to()`] \
+/// By default all sections, except the wiki, are included.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveOptions {
+    /// Downloads all files into `files/`, preserving the folder structure
+    pub include_files: bool,
+    /// Writes the announcements to `news.json` and `news.html`
+    pub include_news: bool,
+    /// Exports all wiki pages into `wiki/`, with an `index.html` \
+    /// Disabled by default, as many courses do not use (or installations do not offer) the wiki, which would make the archive incomplete.
+    pub include_wiki: bool,
+    /// Writes the members to `members.json`
+    pub include_members: bool,
+    /// Writes the course details to `details.json` and `details.html`
+    pub include_details: bool,
+}
+
+impl Default for ArchiveOptions {
+    fn default() -> Self {
+        Self {
+            include_files: true,
+            include_news: true,
+            include_wiki: false,
+            include_members: true,
+            include_details: true,
+        }
+    }
+}
+
+/// A section of a course archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArchiveSection {
+    Files,
+    News,
+    Wiki,
+    Members,
+    Details,
+}
+
+/// A failure, that occurred while archiving a section (or a single file of it)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveFailure {
+    pub section: ArchiveSection,
+    pub error: String,
+}
+
+/// The result of [`Course::archive_to()`], which is also written into the `manifest.json`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveReport {
+    /// The sections, that were written (possibly with failures of single files)
+    pub archived: Vec<ArchiveSection>,
+    /// The sections, that were included, but the course does not have the module for
+    pub skipped: Vec<ArchiveSection>,
+    pub failures: Vec<ArchiveFailure>,
+    pub files_downloaded: usize,
+    /// The names on the server of the files and folders, which were saved under a sanitized name, by their path in the archive (e.g. `files/_CON.pdf`)
+    #[serde(default)]
+    pub renamed_files: BTreeMap<String, String>,
+    /// The path in the archive of every saved file, by its id
+    #[serde(default)]
+    pub saved_files: BTreeMap<FileId, String>,
+}
+
+impl ArchiveReport {
+
+    /// Checks if every included section was archived without failures
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    fn record(&mut self, section: ArchiveSection, result: anyhow::Result<bool>) {
+        match result {
+            Ok(true) => self.archived.push(section),
+            Ok(false) => self.skipped.push(section),
+            Err(e) => self.failures.push(ArchiveFailure { section, error: format!("{:#}", e) }),
+        }
+    }
+
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    version: u32,
+    course_id: &'a str,
+    course_name: &'a str,
+    exported_at: DateTime<Utc>,
+    report: &'a ArchiveReport,
+}
+
+fn write_json(path: PathBuf, value: &impl Serialize) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(value)?;
+    std::fs::write(&path, json).with_context(|| format!("Could not write {}", path.display()))
+}
+
+pub(crate) fn write_html(path: PathBuf, title: &str, body: &str) -> anyhow::Result<()> {
+    let html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n{}\n</body>\n</html>\n",
+        escape_html(title), body
+    );
+    std::fs::write(&path, html).with_context(|| format!("Could not write {}", path.display()))
+}
+
+/// Renders the news articles as a standalone html page body
+fn render_news(course_name: &str, articles: &[NewsArticle]) -> String {
+    let mut body = format!("<h1>{}</h1>\n", escape_html(course_name));
+    for article in articles {
+        body.push_str(&format!(
+            "<article>\n<h2>{}</h2>\n<p><small>{} – {}</small></p>\n{}\n</article>\n",
+            escape_html(&article.title),
+            escape_html(&article.author.display_name),
+            article.date.format("%d.%m.%Y"),
+            article.html_content
+        ));
+    }
+    body
+}
+
+/// Parses the label value pairs of the details page, together with the html of its content
+pub(crate) fn parse_details(html: &Html) -> anyhow::Result<(BTreeMap<String, String>, String)> {
+    let content = expect_one(html.root_element(), selector!("#content"), "details content")?;
+    let row_selector = selector!("tr");
+    let cell_selector = selector!("th, td");
+    let fields = content.select(row_selector)
+        .filter_map(|row| {
+            let mut cells = row.select(cell_selector)
+                .map(|cell| cell.text().collect::<Vec<_>>().join(" ").split_whitespace().collect::<Vec<_>>().join(" "));
+            let label = cells.next()?.trim_end_matches(':').to_string();
+            let value = cells.collect::<Vec<_>>().join(" ");
+            (!label.is_empty()).then_some((label, value))
+        })
+        .collect();
+    Ok((fields, content.inner_html()))
+}
+
+fn archive_files(course: &mut Course, dest: &Path, report: &mut ArchiveReport) -> anyhow::Result<bool> {
+    let Some(file_module) = get_module!(course, FileModule) else {
+        return Ok(false);
+    };
+    let tree = file_module.get_tree()?;
+    let options = DownloadOptions::default();
+    let mut saved = SavedTree::default();
+    file_module.save_tree(&tree, &dest.join("files"), &options, &mut saved)?;
+    collect_renamed_folders(&tree, "files", &options, &mut report.renamed_files);
+    record_saved_files(dest, &saved.files, report);
+    report.failures.extend(saved.failures.into_iter().map(|(path, e)| ArchiveFailure {
+        section: ArchiveSection::Files,
+        error: format!("{}: {:#}", path.display(), e),
+    }));
+    Ok(true)
+}
+
+// Paths in the report are relative to the archive and separated by slashes, independent of the OS
+fn archive_path(dest: &Path, path: &Path) -> String {
+    path.strip_prefix(dest).unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn record_saved_files(dest: &Path, files: &[(PathBuf, &File)], report: &mut ArchiveReport) {
+    for (path, file) in files {
+        let archive_path = archive_path(dest, path);
+        // The name differs, if it was sanitized or a suffix was added to it
+        if path.file_name() != Some(OsStr::new(&file.object.name)) {
+            report.renamed_files.insert(archive_path.clone(), file.object.name.clone());
+        }
+        report.saved_files.insert(file.id(), archive_path);
+    }
+    report.files_downloaded += files.len();
+}
+
+// Follows the names, under which FileModule::save_tree_to() saves the folders
+fn collect_renamed_folders(tree: &FolderTree, dir: &str, options: &DownloadOptions, renamed: &mut BTreeMap<String, String>) {
+    for child in &tree.children {
+        let name = child.folder.as_ref().map(|folder| folder.object.name.as_str()).unwrap_or_default();
+        let saved = options.file_name(name);
+        let path = format!("{}/{}", dir, saved);
+        if saved != name {
+            renamed.insert(path.clone(), name.to_string());
+        }
+        collect_renamed_folders(child, &path, options, renamed);
+    }
+}
+
+fn archive_news(course: &Course, dest: &Path) -> anyhow::Result<bool> {
+    let articles = course.query_news()?;
+    write_json(dest.join("news.json"), &articles)?;
+    write_html(dest.join("news.html"), &course.name, &render_news(&course.name, &articles))?;
+    Ok(true)
+}
+
+fn archive_wiki(course: &mut Course, dest: &Path, report: &mut ArchiveReport) -> anyhow::Result<bool> {
+    let Some(wiki_module) = get_module!(course, WikiModule) else {
+        return Ok(false);
+    };
+    let export = wiki_module.export_all(&dest.join("wiki"), WikiExportFormat::Html)?;
+    report.failures.extend(export.failures.into_iter().map(|(page, e)| ArchiveFailure {
+        section: ArchiveSection::Wiki,
+        error: format!("{}: {:#}", page.title, e),
+    }));
+    Ok(true)
+}
+
+fn archive_members(course: &mut Course, dest: &Path) -> anyhow::Result<bool> {
+    let Some(members_module) = get_module!(course, MembersModule) else {
+        return Ok(false);
+    };
+    write_json(dest.join("members.json"), &members_module.get_members()?)?;
+    Ok(true)
+}
+
+fn archive_details(course: &Course, dest: &Path) -> anyhow::Result<bool> {
+    let client = course.client()?;
+    let response = client.get(format!("{}/details", COURSE_URL))
+        .query(&[("cid", course.id.as_str())])
+        .send_through(client)?;
+    if !response.status().is_success() {
+        bail!("Details page returned {}", response.status());
+    }
+    let url = response.url().clone();
+    let (fields, content) = parse_details(&parse_page(&response.text()?)?).with_url(url)?;
+    write_json(dest.join("details.json"), &fields)?;
+    write_html(dest.join("details.html"), &course.name, &content)?;
+    Ok(true)
+}
+
+/// Writes the selected sections of the course into `dest`, see [`Course::archive_to()`]
+pub(crate) fn archive_course(course: &mut Course, dest: &Path, options: &ArchiveOptions) -> anyhow::Result<ArchiveReport> {
+    std::fs::create_dir_all(dest).context("Could not create archive directory")?;
+    let mut report = ArchiveReport::default();
+    if (options.include_files || options.include_wiki || options.include_members) && course.modules.is_empty() {
+        match course.query_modules() {
+            Ok(modules_report) => for failure in modules_report.failures {
+                let section = match failure.name.as_str() {
+                    "files" if options.include_files => ArchiveSection::Files,
+                    "wiki" if options.include_wiki => ArchiveSection::Wiki,
+                    "members" if options.include_members => ArchiveSection::Members,
+                    _ => continue,
+                };
+                report.failures.push(ArchiveFailure { section, error: failure.to_string() });
+            },
+            Err(e) => {
+                let error = format!("Could not query modules: {:#}", e);
+                let sections = [(options.include_files, ArchiveSection::Files), (options.include_wiki, ArchiveSection::Wiki), (options.include_members, ArchiveSection::Members)];
+                for (included, section) in sections {
+                    if included {
+                        report.failures.push(ArchiveFailure { section, error: error.clone() });
+                    }
+                }
+            },
+        }
+    }
+    if options.include_files && !course.modules.is_empty() {
+        let result = archive_files(course, dest, &mut report);
+        report.record(ArchiveSection::Files, result);
+    }
+    if options.include_news {
+        report.record(ArchiveSection::News, archive_news(course, dest));
+    }
+    if options.include_wiki && !course.modules.is_empty() {
+        let result = archive_wiki(course, dest, &mut report);
+        report.record(ArchiveSection::Wiki, result);
+    }
+    if options.include_members && !course.modules.is_empty() {
+        report.record(ArchiveSection::Members, archive_members(course, dest));
+    }
+    if options.include_details {
+        report.record(ArchiveSection::Details, archive_details(course, dest));
+    }
+    write_json(dest.join("manifest.json"), &Manifest {
+        version: ARCHIVE_VERSION,
+        course_id: &course.id,
+        course_name: &course.name,
+        exported_at: Utc::now(),
+        report: &report,
+    })?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use super::*;
+    use crate::course::MyCourses;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_archive_to() {
+        let server = MockServer::start();
+        server.route("GET", "/seminar_main.php", 200, r#"<ul id="tabs"><li id="nav_course_members"></li></ul>"#)
+            .route("GET", "/dispatch.php/course?", 200, "<div id=\"content\"></div>")
+            .route("GET", "/dispatch.php/course/members", 200, "<div id=\"content\"></div>")
+            .route("GET", "/dispatch.php/course/details", 200, r#"<div id="content"><table>
+                <tr><th>Veranstaltungsnummer:</th><td> 101 </td></tr>
+                <tr><th>Semester</th><td>WiSe 2024/25</td></tr>
+            </table></div>"#);
+        let mut my_courses: MyCourses = serde_json::from_str(r#"{
+            "courses": {"c1": {"id": "c1", "name": "Algorithmen", "number": "101", "group": 0}},
+            "groups": [], "user_id": "u1", "config": {}
+        }"#).unwrap();
+        my_courses.attach_client(Arc::new(server.client()));
+        let course = my_courses.courses.get_mut("c1").unwrap();
+        let dest = std::env::temp_dir().join(format!("stud_ip_archive_{}", std::process::id()));
+
+        let report = course.archive_to(&dest, ArchiveOptions::default()).unwrap();
+        assert_eq!(report.archived, vec![ArchiveSection::News, ArchiveSection::Members, ArchiveSection::Details]);
+        // The course has no files module
+        assert_eq!(report.skipped, vec![ArchiveSection::Files]);
+        assert!(report.is_complete());
+
+        let details: BTreeMap<String, String> = serde_json::from_str(&std::fs::read_to_string(dest.join("details.json")).unwrap()).unwrap();
+        assert_eq!(details.get("Veranstaltungsnummer").map(String::as_str), Some("101"));
+        let manifest: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(dest.join("manifest.json")).unwrap()).unwrap();
+        assert_eq!(manifest["course_id"], "c1");
+        assert_eq!(manifest["report"]["skipped"][0], "Files");
+        assert!(dest.join("news.html").exists() && dest.join("members.json").exists());
+
+        // A wiki, that is included, but not offered by the course, is skipped as well
+        let report = course.archive_to(&dest, ArchiveOptions { include_wiki: true, ..Default::default() }).unwrap();
+        assert_eq!(report.skipped, vec![ArchiveSection::Files, ArchiveSection::Wiki]);
+        assert!(report.is_complete());
+        std::fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn test_renamed_files() {
+        let object = |name: &str| serde_json::json!({
+            "id": name, "name": name, "change_date": "2025-03-12T10:00:00Z", "icon": "file-pdf", "mime_type": "application/pdf",
+            "author": {"display_name": "Max Mustermann", "username": "mmustermann", "avatar_src": null, "source": "Unspecified"},
+        });
+        let file = |name: &str| serde_json::json!({
+            "object": object(name), "size": 0, "downloads": 0, "restricted_terms_of_use": false, "new": false, "is_editable": false, "is_accessible": true,
+        });
+        let tree: FolderTree = serde_json::from_value(serde_json::json!({
+            "folder": null,
+            "files": [file("CON.pdf"), file("a.pdf")],
+            "children": [{
+                "folder": {"object": object("Slides: Week 1"), "object_count": 1, "permissions": "rd"},
+                "files": [file("b?.pdf")],
+                "children": [],
+            }],
+        })).unwrap();
+
+        let mut report = ArchiveReport::default();
+        collect_renamed_folders(&tree, "files", &DownloadOptions::default(), &mut report.renamed_files);
+        let dest = Path::new("archive");
+        let files = vec![
+            (dest.join("files").join("_CON.pdf"), &tree.files[0]),
+            (dest.join("files").join("a.pdf"), &tree.files[1]),
+            (dest.join("files").join("Slides_ Week 1").join("b_.pdf"), &tree.children[0].files[0]),
+        ];
+        record_saved_files(dest, &files, &mut report);
+        assert_eq!(report.renamed_files, BTreeMap::from([
+            ("files/_CON.pdf".to_string(), "CON.pdf".to_string()),
+            ("files/Slides_ Week 1".to_string(), "Slides: Week 1".to_string()),
+            ("files/Slides_ Week 1/b_.pdf".to_string(), "b?.pdf".to_string()),
+        ]));
+        assert_eq!(report.saved_files.get("a.pdf").map(String::as_str), Some("files/a.pdf"));
+        assert_eq!(report.files_downloaded, 3);
+
+        let mut renamed = BTreeMap::new();
+        collect_renamed_folders(&tree, "files", &DownloadOptions::default().raw_names(true), &mut renamed);
+        assert!(renamed.is_empty());
+    }
+}
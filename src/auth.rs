@@ -0,0 +1,270 @@
+//! Ways of authenticating the requests of a [`StudIpClient`](crate::StudIpClient) \
+//! Besides the session cookie of a SSO login, personal API tokens and OAuth2 access tokens are supported.
+//! These are only accepted by the JSON:API, so html pages can not be queried with them. \
+//! The [`Credentials`] for a SSO login can be read from a file or, with the `keyring` feature, from the keychain of the OS.
+
+use std::fmt::{Debug, Formatter};
+use anyhow::{bail, Context};
+use chrono::{DateTime, TimeDelta, Utc};
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::Deserialize;
+use url::Url;
+
+pub(crate) const OAUTH2_TOKEN_URL: &str = "https://studip.example.com/dispatch.php/api/oauth2/token";
+
+/// Tokens are refreshed this long before they expire, so that they do not expire in flight
+const EXPIRY_MARGIN: TimeDelta = TimeDelta::seconds(30);
+
+/// How the requests of a [`StudIpClient`](crate::StudIpClient) are authenticated
+#[derive(Debug, Clone)]
+pub enum AuthMethod {
+    /// The session cookie, that is set by logging in through an [`IdentityProvider`](crate::IdentityProvider)
+    Session,
+    /// A personal API token, sent as `Authorization: Bearer` header
+    Bearer(String),
+    /// Username and password, sent as `Authorization: Basic` header
+    Basic {
+        username: String,
+        password: String,
+    },
+    /// An OAuth2 access token, which is refreshed automatically, if its expiry is known
+    OAuth2(OAuth2Token),
+}
+
+impl AuthMethod {
+
+    /// Whether the html pages can be accessed, which is only the case for a session
+    pub fn allows_html(&self) -> bool {
+        matches!(self, AuthMethod::Session)
+    }
+
+    /// Attaches the `Authorization` header to the request, sessions are authenticated by their cookie instead
+    pub(crate) fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match self {
+            AuthMethod::Session => request,
+            AuthMethod::Bearer(token) => request.bearer_auth(token),
+            AuthMethod::Basic { username, password } => request.basic_auth(username, Some(password)),
+            AuthMethod::OAuth2(token) => request.bearer_auth(&token.access_token),
+        }
+    }
+
+}
+
+/// The tokens of the OAuth2 authorization code flow
+#[derive(Debug, Clone)]
+pub struct OAuth2Token {
+    pub access_token: String,
+    /// Used to obtain a new access token, once it expired
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub client_id: String,
+    /// Only required for confidential clients
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    /// In seconds
+    #[serde(default)]
+    expires_in: Option<i64>,
+}
+
+impl OAuth2Token {
+
+    /// Whether the access token has expired or is about to, tokens with an unknown expiry never expire
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at - EXPIRY_MARGIN <= Utc::now())
+    }
+
+    /// Obtains a new access token through the `refresh_token` grant at the `token_url`
+    pub(crate) fn refresh(&mut self, client: &Client, token_url: Url) -> anyhow::Result<()> {
+        let refresh_token = self.refresh_token.as_deref().context("Can not refresh OAuth2 token without a refresh token")?;
+        let mut form = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", &self.client_id),
+        ];
+        if let Some(client_secret) = &self.client_secret {
+            form.push(("client_secret", client_secret));
+        }
+        let response = client.post(token_url)
+            .header("Accept", "application/json")
+            .form(&form)
+            .send()?;
+        if !response.status().is_success() {
+            bail!("Could not refresh OAuth2 token. Status code: {}", response.status());
+        }
+        let response: TokenResponse = serde_json::from_str(&response.text()?)
+            .context("Could not parse OAuth2 token response")?;
+        self.access_token = response.access_token;
+        // The refresh token is only replaced, if the server rotates it
+        if let Some(refresh_token) = response.refresh_token {
+            self.refresh_token = Some(refresh_token);
+        }
+        self.expires_at = response.expires_in.map(|expires_in| Utc::now() + TimeDelta::seconds(expires_in));
+        Ok(())
+    }
+
+}
+
+/// The username and password, with which a user logs in through an [`IdentityProvider`](crate::IdentityProvider)
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Never prints the password, e.g. into logs
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<hidden>")
+            .finish()
+    }
+}
+
+impl Credentials {
+
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+
+    /// Reads the credentials from a file (e.g. `creds.txt`), that contains the username and the password on separate lines
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let creds = std::fs::read_to_string(path.as_ref())
+            .with_context(|| format!("Could not read from {}", path.as_ref().display()))?;
+        Self::parse(&creds)
+    }
+
+    fn parse(creds: &str) -> anyhow::Result<Self> {
+        let (username, password) = creds.split_once('\n')
+            .context("creds.txt did not have newline seperated username and password")?;
+        Ok(Self::new(username.trim(), password.trim()))
+    }
+
+    /// Reads the password of the `username` from the keychain of the OS, where it is stored under the `service` (e.g. "stud_ip_scraper") \
+    /// Fails with [`StudIpError::CredentialsNotFound`](crate::error::StudIpError::CredentialsNotFound), if there is no such entry,
+    /// or with [`StudIpError::KeyringUnavailable`](crate::error::StudIpError::KeyringUnavailable), if the keychain is locked or can not be accessed.
+    #[cfg(feature = "keyring")]
+    pub fn from_keyring(service: &str, username: &str) -> anyhow::Result<Self> {
+        let password = keyring::Entry::new(service, username)
+            .and_then(|entry| entry.get_password())
+            .map_err(|e| keyring_error(e, service, username))?;
+        Ok(Self::new(username, password))
+    }
+
+    /// Stores the password in the keychain of the OS under the `service`, so that it can be read with [`Credentials::from_keyring()`] \
+    /// Overwrites an existing entry for the same username.
+    #[cfg(feature = "keyring")]
+    pub fn store_in_keyring(&self, service: &str) -> anyhow::Result<()> {
+        keyring::Entry::new(service, &self.username)
+            .and_then(|entry| entry.set_password(&self.password))
+            .map_err(|e| keyring_error(e, service, &self.username))
+    }
+
+}
+
+// Distinguishes a missing entry and an inaccessible keychain from other keyring errors
+#[cfg(feature = "keyring")]
+fn keyring_error(e: keyring::Error, service: &str, username: &str) -> anyhow::Error {
+    use crate::error::StudIpError;
+    match e {
+        keyring::Error::NoEntry => StudIpError::CredentialsNotFound {
+            service: service.to_string(),
+            username: username.to_string(),
+        }.into(),
+        keyring::Error::NoStorageAccess(_) | keyring::Error::PlatformFailure(_) => StudIpError::KeyringUnavailable {
+            message: e.to_string(),
+        }.into(),
+        e => anyhow::Error::new(e).context(format!("Could not access the keyring entry of {} for {}", service, username)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use super::*;
+    use crate::mock::MockServer;
+    use crate::SendThrough;
+
+    fn expired_token() -> OAuth2Token {
+        OAuth2Token {
+            access_token: "old".to_string(),
+            refresh_token: Some("refresh".to_string()),
+            expires_at: Some(Utc::now() - TimeDelta::minutes(1)),
+            client_id: "scraper".to_string(),
+            client_secret: None,
+        }
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let server = MockServer::start();
+        server.route_json("GET", "/jsonapi.php/v1/users/me", 200, r#"{"data": {"type": "users", "id": "u1", "attributes": {}}}"#);
+        let client = server.client();
+        client.set_auth(AuthMethod::Bearer("secret".to_string()));
+        client.get("https://studip.example.com/jsonapi.php/v1/users/me").send().unwrap();
+        let request = &server.requests()[0];
+        assert_eq!(request.header("Authorization"), Some("Bearer secret"));
+
+        // Html pages can not be accessed with a token, not even through endpoints, that do not check it themselves
+        let error = client.require_session().unwrap_err().to_string();
+        assert!(error.contains("JSON:API"), "{}", error);
+        let error = client.get(crate::ref_source::START_URL).send_through(&client).unwrap_err().to_string();
+        assert!(error.contains("JSON:API"), "{}", error);
+        client.get("https://studip.example.com/jsonapi.php/v1/users/me").send_through(&client).unwrap();
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_oauth2_refresh() {
+        let server = MockServer::start();
+        server.route_json("POST", "/dispatch.php/api/oauth2/token", 200, r#"{"access_token": "new", "expires_in": 3600, "token_type": "Bearer"}"#)
+            .route_json("GET", "/jsonapi.php/v1/users/me", 200, r#"{"data": {"type": "users", "id": "u1", "attributes": {}}}"#);
+        let client = Arc::new(server.client());
+        client.set_auth(AuthMethod::OAuth2(expired_token()));
+        client.get("https://studip.example.com/jsonapi.php/v1/users/me").send().unwrap();
+        client.get("https://studip.example.com/jsonapi.php/v1/users/me").send().unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3, "The token should only be refreshed once");
+        assert!(requests[0].body.contains("grant_type=refresh_token&refresh_token=refresh"), "{}", requests[0].body);
+        assert_eq!(requests[1].header("Authorization"), Some("Bearer new"));
+        let AuthMethod::OAuth2(token) = client.auth() else {panic!("Expected OAuth2 token")};
+        assert_eq!(token.refresh_token.as_deref(), Some("refresh"));
+        assert!(!token.is_expired());
+    }
+
+    #[test]
+    fn test_parse_credentials() {
+        let creds = Credentials::parse("user\r\n secret \n").unwrap();
+        assert_eq!(creds, Credentials::new("user", "secret"));
+        assert!(!format!("{:?}", creds).contains("secret"));
+        assert!(Credentials::parse("user").is_err());
+    }
+
+    #[cfg(feature = "keyring")]
+    #[test]
+    fn test_keyring_errors() {
+        use crate::error::StudIpError;
+        keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+        // Every mock entry starts out empty
+        let error = Credentials::from_keyring("stud_ip_scraper", "user").unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<StudIpError>(),
+            Some(&StudIpError::CredentialsNotFound { service: "stud_ip_scraper".into(), username: "user".into() })
+        );
+        let error = keyring_error(keyring::Error::NoStorageAccess("locked".into()), "stud_ip_scraper", "user");
+        assert!(matches!(error.downcast_ref::<StudIpError>(), Some(StudIpError::KeyringUnavailable { .. })), "{:?}", error);
+        let error = keyring_error(keyring::Error::TooLong("password".into(), 10), "stud_ip_scraper", "user");
+        assert!(error.downcast_ref::<StudIpError>().is_none());
+        Credentials::new("user", "secret").store_in_keyring("stud_ip_scraper").unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::Arc;
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use scraper::{ElementRef, Html};
+use serde::{Deserialize, Serialize};
+use crate::error::check_status;
+use crate::user::{parse_simple_user, User};
+use crate::flash;
+use crate::util::{expect_one, local_to_utc, parse_localized_date_time, parse_page, selector};
+use crate::{SendThrough, StudIpClient};
+
+/// A category of the bulletin board, like "Wohnungen" or "Bücher"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Category {
+    pub id: String,
+    pub title: String,
+    /// The number of ads, if it is shown next to the category
+    pub ad_count: Option<usize>,
+}
+
+/// An ad posted on the bulletin board
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ad {
+    pub id: String,
+    pub title: String,
+    pub author: User,
+    pub created_at: DateTime<Utc>,
+    /// The rendered html content of the ad
+    pub html_content: String,
+}
+
+/// Enables reading and posting ads of the bulletin board ("Schwarzes Brett") plugin \
+/// Can be obtained with [`StudIp::bulletin_board()`](crate::StudIp::bulletin_board())
+#[derive(Debug, Clone)]
+pub struct BulletinBoard {
+    client: Arc<StudIpClient>,
+    base_url: String,
+}
+
+impl BulletinBoard {
+
+    /// The path of the plugin on most installations
+    pub const DEFAULT_BASE_PATH: &'static str = "plugins.php/schwarzesbrettplugin";
+
+    /// Creates a handle to the plugin at the `base_path` (e.g. [`BulletinBoard::DEFAULT_BASE_PATH`]), which differs between installations
+    pub fn new(client: Arc<StudIpClient>, base_path: &str) -> Self {
+        Self {
+            client,
+            base_url: format!("https://studip.example.com/{}", base_path.trim_matches('/')),
+        }
+    }
+
+    /// Returns all categories of the bulletin board
+    pub fn categories(&self) -> anyhow::Result<Vec<Category>> {
+        let response = self.client.get(&self.base_url).send_through(&self.client)?;
+        check_status(response.status())?;
+        parse_categories(&parse_page(&response.text()?)?)
+    }
+
+    /// Returns the ads on the `page` (starting at 1) of the category, newest first \
+    /// Pages after the last one are empty.
+    pub fn list(&self, category_id: &str, page: usize) -> anyhow::Result<Vec<Ad>> {
+        let response = self.client.get(format!("{}/category/view/{}", self.base_url, category_id))
+            .query(&[("page", page.max(1))])
+            .send_through(&self.client)?;
+        check_status(response.status())?;
+        parse_ads(&parse_page(&response.text()?)?, self.client.timezone())
+    }
+
+    /// Posts a new ad with the `title` and the `body` into the category
+    pub fn post(&self, category_id: &str, title: &str, body: &str) -> anyhow::Result<()> {
+        let url = format!("{}/article/create/{}", self.base_url, category_id);
+        let response = self.client.get(&url)
+            .header("X-Requested-With", "XMLHttpRequest")
+            .send_through(&self.client)?;
+        check_status(response.status())?;
+        let form = parse_create_form(&parse_page(&response.text()?)?, title, body)?;
+        let action = match form.action {
+            Some(action) => self.client.absolutize(&action)?.to_string(),
+            None => url,
+        };
+        let response = self.client.post(action)
+            .form(&form.fields)
+            .send_through(&self.client)?;
+        check_status(response.status())?;
+        if !response.status().is_success() {
+            bail!("Could not post the ad. Status Code: {}", response.status());
+        }
+        flash::check(&parse_page(&response.text()?)?)?;
+        Ok(())
+    }
+
+}
+
+fn text_of(element: ElementRef) -> String {
+    element.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// The id is the last segment of the link to the category
+fn parse_categories(html: &Html) -> anyhow::Result<Vec<Category>> {
+    let mut categories: Vec<Category> = vec![];
+    for link in html.select(selector!("#content a[href*=\"/category/view/\"]")) {
+        let href = link.attr("href").unwrap();
+        let id = href.split("/category/view/")
+            .nth(1)
+            .and_then(|rest| rest.split(['?', '/', '#']).next())
+            .filter(|id| !id.is_empty())
+            .with_context(|| format!("Expected category id in {}", href))?;
+        if categories.iter().any(|category| category.id == id) {
+            continue;
+        }
+        // The count is a badge inside of the link
+        let badge = link.select(selector!(".badge, .count")).next().map(text_of);
+        let title = text_of(link);
+        let title = match &badge {
+            Some(badge) => title.trim_end_matches(badge.as_str()).trim_end().to_string(),
+            None => title,
+        };
+        categories.push(Category {
+            id: id.to_string(),
+            title,
+            ad_count: badge.and_then(|badge| badge.parse().ok()),
+        });
+    }
+    Ok(categories)
+}
+
+fn parse_ads(html: &Html, timezone: Tz) -> anyhow::Result<Vec<Ad>> {
+    html.select(selector!("#content article[data-article-id]"))
+        .map(|article| parse_ad(article, timezone))
+        .collect()
+}
+
+fn parse_ad(article: ElementRef, timezone: Tz) -> anyhow::Result<Ad> {
+    let id = article.attr("data-article-id").unwrap().to_string();
+    let title = article.select(selector!("header h1, header h2, header h3"))
+        .next()
+        .map(text_of)
+        .with_context(|| format!("Expected title of ad {}", id))?;
+    let author = article.select(selector!("a[href*=\"username=\"]"))
+        .next()
+        .with_context(|| format!("Expected author of ad {}", id))
+        .and_then(parse_simple_user)?;
+    // The date is either machine readable or localized, like "12.03.2025 14:05"
+    let created_at = article.select(selector!("time")).next()
+        .and_then(|time| match time.attr("datetime") {
+            Some(datetime) => DateTime::parse_from_rfc3339(datetime).ok().map(|date| date.with_timezone(&Utc)),
+            None => parse_localized_date_time(&text_of(time)).and_then(|date_time| local_to_utc(date_time, timezone)),
+        })
+        .with_context(|| format!("Expected date of ad {}", id))?;
+    let html_content = article.select(selector!(".article-content, .content"))
+        .next()
+        .map(|content| content.inner_html().trim().to_string())
+        .unwrap_or_default();
+    Ok(Ad { id, title, author, created_at, html_content })
+}
+
+struct CreateForm {
+    action: Option<String>,
+    fields: Vec<(String, String)>,
+}
+
+// The names of the fields differ between versions of the plugin, so the title is put into the first text input and the body into the textarea
+fn parse_create_form(html: &Html, title: &str, body: &str) -> anyhow::Result<CreateForm> {
+    let form = expect_one(html.root_element(), selector!("form"), "form to create an ad")?;
+    let mut fields = vec![];
+    let mut has_title = false;
+    for input in form.select(selector!("input[name]")) {
+        let name = input.attr("name").unwrap().to_string();
+        match input.attr("type").unwrap_or("text") {
+            "text" if !has_title => {
+                has_title = true;
+                fields.push((name, title.to_string()));
+            },
+            "hidden" | "text" => fields.push((name, input.attr("value").unwrap_or_default().to_string())),
+            "checkbox" if input.attr("checked").is_some() => fields.push((name, input.attr("value").unwrap_or("on").to_string())),
+            _ => {},
+        }
+    }
+    let textarea = expect_one(form, selector!("textarea[name]"), "body of the ad")?;
+    if !has_title {
+        bail!("Expected title of the ad");
+    }
+    fields.push((textarea.attr("name").unwrap().to_string(), body.to_string()));
+    Ok(CreateForm {
+        action: form.attr("action").map(str::to_string),
+        fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_bulletin_board() {
+        let server = MockServer::start();
+        server.route("GET", "/studip/plugins.php/sb", 200, include_str!("../testdata/bulletin_board/index.html"))
+            .route("GET", "/studip/plugins.php/sb/category/view/k1", 200, include_str!("../testdata/bulletin_board/category.html"))
+            .route("GET", "/studip/plugins.php/sb/category/view/k1?page=2", 200, r#"<div id="content"></div>"#)
+            .route("GET", "/studip/plugins.php/sb/article/create/k1", 200, include_str!("../testdata/bulletin_board/create.html"))
+            .route("POST", "/studip/plugins.php/sb/article/store", 200, r#"<div class="messagebox messagebox_success">Die Anzeige wurde erstellt.</div>"#);
+        let board = BulletinBoard::new(Arc::new(server.client()), "/studip/plugins.php/sb/");
+
+        let categories = board.categories().unwrap();
+        assert_eq!(categories, vec![
+            Category { id: "k1".to_string(), title: "Wohnungen".to_string(), ad_count: Some(2) },
+            Category { id: "k2".to_string(), title: "Bücher".to_string(), ad_count: None },
+        ]);
+
+        let ads = board.list("k1", 1).unwrap();
+        assert_eq!(ads.len(), 2);
+        assert_eq!((ads[0].id.as_str(), ads[0].title.as_str()), ("a1", "WG-Zimmer frei"));
+        assert_eq!(ads[0].author.username, "mmustermann");
+        assert_eq!(ads[0].created_at, DateTime::parse_from_rfc3339("2025-03-12T14:05:00+01:00").unwrap());
+        assert_eq!(ads[0].html_content, "<p>20 m² ab <b>April</b></p>");
+        assert_eq!(ads[1].created_at, Utc.with_ymd_and_hms(2025, 3, 10, 8, 30, 0).unwrap());
+        assert!(board.list("k1", 2).unwrap().is_empty());
+
+        board.post("k1", "Verkaufe Analysis I", "Kaum benutzt").unwrap();
+        let requests = server.requests();
+        let post = requests.last().unwrap();
+        assert_eq!((post.method.as_str(), post.path.as_str()), ("POST", "/studip/plugins.php/sb/article/store"));
+        assert_eq!(post.body, "security_token=tok%3D&category_id=k1&titel=Verkaufe+Analysis+I&visible=0&visible=1&description=Kaum+benutzt");
+    }
+}
@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use anyhow::{bail, Context};
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+use crate::ids::CourseId;
+use crate::resources::RoomRef;
+use crate::{SendThrough, StudIpClient};
+use crate::flash;
+use crate::util::{local_to_utc, parse_page, parse_security_token, selector};
+
+const CALENDAR_URL: &str = "https://studip.example.com/dispatch.php/calendar/calendar";
+const CALENDAR_DATE_URL: &str = "https://studip.example.com/dispatch.php/calendar/date";
+const SCHEDULE_URL: &str = "https://studip.example.com/dispatch.php/calendar/schedule";
+
+/// The kind of [`CalendarEntry`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntryKind {
+    /// A date of a course
+    CourseDate { course_id: CourseId },
+    /// A personal appointment
+    Personal,
+    /// A booked consultation slot
+    Consultation,
+    /// A kind, that is not known (yet)
+    Unknown(String),
+}
+
+/// A single (concrete) entry of the personal calendar \
+/// Recurring appointments are returned as one entry per occurrence
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CalendarEntry {
+    pub id: String,
+    pub title: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub location: Option<RoomRef>,
+    pub kind: EntryKind,
+    pub course_id: Option<CourseId>,
+}
+
+/// How a [`NewAppointment`] repeats
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Recurrence {
+    /// Repeats every `interval` days
+    Daily { interval: u32, until: Option<NaiveDate> },
+    /// Repeats every `interval` weeks on the given weekdays. \
+    /// If `weekdays` is empty, the weekday of the appointment's start is used.
+    Weekly { interval: u32, weekdays: Vec<Weekday>, until: Option<NaiveDate> },
+}
+
+/// The specification of a new personal appointment, that can be created with [`Calendar::create()`] \
+/// All times are in the local time of the campus (the server's timezone), just as they are entered in the web UI.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewAppointment {
+    pub title: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    pub recurrence: Option<Recurrence>,
+}
+
+/// A regular weekly slot of the [`Timetable`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimetableSlot {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    /// The course of the slot, or `None` for slots that were added manually
+    pub course_id: Option<CourseId>,
+    pub label: String,
+    pub room: Option<RoomRef>,
+}
+
+impl TimetableSlot {
+    /// Checks if this slot overlaps with another one (touching slots do not overlap)
+    pub fn overlaps(&self, other: &TimetableSlot) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+}
+
+/// The regular weekly schedule ("Stundenplan") of the current user, independent of concrete dates \
+/// Can be queried with [`StudIp::timetable()`](crate::StudIp::timetable())
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timetable {
+    /// The slots of each weekday, sorted by their start
+    pub days: HashMap<Weekday, Vec<TimetableSlot>>,
+}
+
+impl Timetable {
+
+    /// Returns the slots of the given `weekday`, sorted by their start
+    pub fn slots(&self, weekday: Weekday) -> &[TimetableSlot] {
+        self.days.get(&weekday).map(|slots| slots.as_slice()).unwrap_or_default()
+    }
+
+    /// Returns all pairs of overlapping slots, together with their weekday (ordered from monday to sunday)
+    pub fn conflicts(&self) -> Vec<(Weekday, &TimetableSlot, &TimetableSlot)> {
+        let mut conflicts = vec![];
+        for weekday in (0..7).filter_map(|day| Weekday::try_from(day).ok()) {
+            let slots = self.slots(weekday);
+            for (i, slot) in slots.iter().enumerate() {
+                // The slots are sorted, so only the following slots, that start before this one ends can overlap
+                for other in slots[i + 1..].iter().take_while(|other| other.start < slot.end) {
+                    if slot.overlaps(other) {
+                        conflicts.push((weekday, slot, other));
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+
+}
+
+/// Queries the [`Timetable`] of the given `semester` (its id), or the current one if `None`
+pub(crate) fn get_timetable(client: &StudIpClient, semester: Option<&str>) -> anyhow::Result<Timetable> {
+    let mut request = client.get(SCHEDULE_URL);
+    if let Some(semester) = semester {
+        request = request.query(&[("semester_id", semester)]);
+    }
+    let response = request.send_through(client)?;
+    if !response.status().is_success() {
+        bail!("Could not get timetable. Status Code: {}", response.status());
+    }
+    let events = parse_embedded_events(&parse_page(&response.text()?)?)?
+        .context("Expected timetable entries")?;
+    Ok(timetable_from_events(events, client.timezone()))
+}
+
+/// Enables reading the personal calendar of the current user, which aggregates course dates, personal appointments and consultations \
+/// Can be obtained with [`StudIp::calendar()`](crate::StudIp::calendar())
+#[derive(Debug, Clone)]
+pub struct Calendar {
+    client: Arc<StudIpClient>,
+}
+
+impl Calendar {
+
+    pub(crate) fn from_client(client: Arc<StudIpClient>) -> Self {
+        Self { client }
+    }
+
+    /// Returns all entries of the week starting at `start` (inclusive) and ending seven days later (exclusive), ordered by their start
+    pub fn week(&self, start: NaiveDate) -> anyhow::Result<Vec<CalendarEntry>> {
+        let end = start.checked_add_days(Days::new(7)).context("Week end out of range")?;
+        self.range(start, end)
+    }
+
+    /// Returns all entries of the given `day`, ordered by their start
+    pub fn day(&self, day: NaiveDate) -> anyhow::Result<Vec<CalendarEntry>> {
+        let end = day.checked_add_days(Days::new(1)).context("Day end out of range")?;
+        self.range(day, end)
+    }
+
+    /// Creates a personal appointment and returns its id
+    pub fn create(&self, appointment: NewAppointment) -> anyhow::Result<String> {
+        if appointment.end < appointment.start {
+            bail!("Appointment must not end before it starts");
+        }
+        let add_url = format!("{}/add", CALENDAR_DATE_URL);
+        let response = self.client.get(&add_url)
+            .header("X-Requested-With", "XMLHttpRequest")
+            .send_through(&self.client)?;
+        let security_token = parse_security_token(&parse_page(&response.text()?)?)?;
+        let mut params = appointment_params(&appointment);
+        params.push(("security_token", security_token));
+        let response = self.client.post(&add_url)
+            .form(&params)
+            .send_through(&self.client)?;
+        if !response.status().is_success() {
+            bail!("Could not create appointment. Status Code: {}", response.status());
+        }
+        flash::check(&parse_page(&response.text()?)?)?;
+        // The id is not part of the response, so the appointment is looked up on its day
+        let entries = self.day(appointment.start.date())?;
+        find_created_id(&entries, &appointment, self.client.timezone()).context("Could not find created appointment")
+    }
+
+    /// Deletes the personal appointment with the given id (all occurrences, if it is recurring)
+    pub fn delete(&self, id: &str) -> anyhow::Result<()> {
+        // Opens the confirmation dialog and then confirms it with the dialog's security token
+        let url = format!("{}/delete/{}", CALENDAR_DATE_URL, id);
+        let response = self.client.get(&url)
+            .header("X-Requested-With", "XMLHttpRequest")
+            .send_through(&self.client)?;
+        let security_token = parse_security_token(&parse_page(&response.text()?)?)?;
+        let response = self.client.post(&url)
+            .form(&[("security_token", security_token.as_str()), ("yes", "1")])
+            .send_through(&self.client)?;
+        if !response.status().is_success() {
+            bail!("Could not delete appointment. Status Code: {}", response.status());
+        }
+        flash::check(&parse_page(&response.text()?)?)?;
+        Ok(())
+    }
+
+    pub(crate) fn range(&self, start: NaiveDate, end: NaiveDate) -> anyhow::Result<Vec<CalendarEntry>> {
+        let start_string = start.format("%Y-%m-%d").to_string();
+        let response = self.client.get(format!("{}/week", CALENDAR_URL))
+            .query(&[("start", start_string.as_str())])
+            .send_through(&self.client)?;
+        if !response.status().is_success() {
+            bail!("Could not get calendar. Status Code: {}", response.status());
+        }
+        let html = parse_page(&response.text()?)?;
+        let events = match parse_embedded_events(&html)? {
+            Some(events) => events,
+            // Newer installations load the events from a separate feed
+            None => {
+                let end_string = end.format("%Y-%m-%d").to_string();
+                let response = self.client.get(format!("{}/feed", CALENDAR_URL))
+                    .query(&[("start", start_string.as_str()), ("end", end_string.as_str())])
+                    .header("X-Requested-With", "XMLHttpRequest")
+                    .send_through(&self.client)?;
+                if !response.status().is_success() {
+                    bail!("Could not get calendar feed. Status Code: {}", response.status());
+                }
+                serde_json::from_str(&response.text()?).context("Could not parse calendar feed json")?
+            }
+        };
+        expand_events(events, start, end, self.client.timezone())
+    }
+
+}
+
+// Times are submitted in local time, exactly as they are entered into the form, so that the server handles DST
+fn appointment_params(appointment: &NewAppointment) -> Vec<(&'static str, String)> {
+    let mut params = vec![
+        ("title", appointment.title.clone()),
+        ("begin_date", appointment.start.format("%d.%m.%Y").to_string()),
+        ("begin_time", appointment.start.format("%H:%M").to_string()),
+        ("end_date", appointment.end.format("%d.%m.%Y").to_string()),
+        ("end_time", appointment.end.format("%H:%M").to_string()),
+        ("location", appointment.location.clone().unwrap_or_default()),
+        ("description", appointment.description.clone().unwrap_or_default()),
+    ];
+    let (repetition_type, interval, until) = match &appointment.recurrence {
+        None => ("SINGLE", 1, None),
+        Some(Recurrence::Daily { interval, until }) => ("DAILY", *interval, *until),
+        Some(Recurrence::Weekly { interval, weekdays, until }) => {
+            if weekdays.is_empty() {
+                params.push(("repetition_dow[]", appointment.start.weekday().number_from_monday().to_string()));
+            }
+            params.extend(weekdays.iter().map(|weekday| ("repetition_dow[]", weekday.number_from_monday().to_string())));
+            ("WEEKLY", *interval, *until)
+        }
+    };
+    params.push(("repetition_type", repetition_type.to_string()));
+    params.push(("repetition_interval", interval.max(1).to_string()));
+    match until {
+        Some(until) => {
+            params.push(("repetition_end_type", "date".to_string()));
+            params.push(("repetition_end_date", until.format("%d.%m.%Y").to_string()));
+        },
+        None => params.push(("repetition_end_type", "never".to_string())),
+    }
+    params
+}
+
+// Compares in local campus time, so that appointments across DST boundaries are found
+fn find_created_id(entries: &[CalendarEntry], appointment: &NewAppointment, timezone: Tz) -> Option<String> {
+    let start = local_to_utc(appointment.start, timezone)?;
+    entries.iter()
+        .rev()
+        .find(|entry| entry.kind == EntryKind::Personal && entry.title == appointment.title && entry.start == start)
+        .map(|entry| entry.id.clone())
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TheirExtendedProps {
+    object_type: Option<String>,
+    range_id: Option<String>,
+    course_id: Option<String>,
+    /// The name of the room, which may be linked to its resource
+    location: Option<String>,
+}
+
+// The event format of the fullcalendar library, that is used by Stud.IP
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TheirEvent {
+    id: serde_json::Value,
+    title: String,
+    start: Option<String>,
+    end: Option<String>,
+    // Simple recurrence
+    days_of_week: Option<Vec<u32>>,
+    start_time: Option<String>,
+    end_time: Option<String>,
+    start_recur: Option<String>,
+    end_recur: Option<String>,
+    #[serde(default)]
+    extended_props: TheirExtendedProps,
+}
+
+// The week view embeds its events into the config of the calendar element
+fn parse_embedded_events(html: &Html) -> anyhow::Result<Option<Vec<TheirEvent>>> {
+    let calendar_selector = selector!("[data-fullcalendar], [data-config]");
+    for elem in html.select(calendar_selector) {
+        let Some(config) = elem.attr("data-fullcalendar").or_else(|| elem.attr("data-config")) else {continue};
+        let config: serde_json::Value = serde_json::from_str(config).context("Could not parse calendar config json")?;
+        if let Some(events) = config.get("events").filter(|events| events.is_array()) {
+            return Ok(Some(serde_json::from_value(events.clone()).context("Could not parse calendar events")?));
+        }
+    }
+    Ok(None)
+}
+
+// Dates are either in RFC 3339 or in the local time of the server
+fn parse_event_date_time(text: &str, timezone: Tz) -> Option<DateTime<Utc>> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(text) {
+        return Some(date_time.to_utc());
+    }
+    let date_time = NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S"))
+        .or_else(|_| NaiveDate::parse_from_str(text, "%Y-%m-%d").map(|date| date.and_time(NaiveTime::MIN)))
+        .ok()?;
+    local_to_utc(date_time, timezone)
+}
+
+fn parse_event_time(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M"))
+        .ok()
+}
+
+fn entry_kind(props: &TheirExtendedProps) -> EntryKind {
+    let object_type = props.object_type.clone().unwrap_or_default();
+    match object_type.as_str() {
+        "CourseDate" | "CourseExDate" => match props.course_id.as_ref().or(props.range_id.as_ref()) {
+            Some(course_id) => EntryKind::CourseDate { course_id: course_id.as_str().into() },
+            None => EntryKind::Unknown(object_type),
+        },
+        "CalendarDate" | "CalendarDateAssignment" => EntryKind::Personal,
+        "ConsultationBooking" | "ConsultationSlot" => EntryKind::Consultation,
+        _ => EntryKind::Unknown(object_type),
+    }
+}
+
+fn timetable_from_events(events: Vec<TheirEvent>, timezone: Tz) -> Timetable {
+    let mut timetable = Timetable::default();
+    for event in events {
+        let course_id = match entry_kind(&event.extended_props) {
+            EntryKind::CourseDate { course_id } => Some(course_id),
+            _ => None,
+        };
+        // Slots are either weekly recurring or placed in an arbitrary week
+        let (weekdays, start, end) = match &event.days_of_week {
+            Some(days_of_week) => {
+                let Some(start) = event.start_time.as_deref().and_then(parse_event_time) else {continue};
+                let end = event.end_time.as_deref().and_then(parse_event_time).unwrap_or(start);
+                let weekdays = days_of_week.iter()
+                    .filter_map(|day| Weekday::try_from(((day + 6) % 7) as u8).ok())
+                    .collect::<Vec<_>>();
+                (weekdays, start, end)
+            },
+            None => {
+                let to_local = |text: &str| parse_event_date_time(text, timezone).map(|date_time| date_time.with_timezone(&timezone).naive_local());
+                let Some(start) = event.start.as_deref().and_then(to_local) else {continue};
+                let end = event.end.as_deref().and_then(to_local).unwrap_or(start);
+                (vec![start.weekday()], start.time(), end.time())
+            }
+        };
+        for weekday in weekdays {
+            timetable.days.entry(weekday).or_default().push(TimetableSlot {
+                start,
+                end,
+                course_id: course_id.clone(),
+                label: event.title.clone(),
+                room: event.extended_props.location.as_deref().and_then(RoomRef::from_html),
+            });
+        }
+    }
+    for slots in timetable.days.values_mut() {
+        slots.sort_by_key(|slot| (slot.start, slot.end));
+    }
+    timetable
+}
+
+// Converts the events into entries within the range, expanding recurring events into their occurrences
+fn expand_events(events: Vec<TheirEvent>, start: NaiveDate, end: NaiveDate, timezone: Tz) -> anyhow::Result<Vec<CalendarEntry>> {
+    let range_start = local_to_utc(start.and_time(NaiveTime::MIN), timezone).context("Invalid range start")?;
+    let range_end = local_to_utc(end.and_time(NaiveTime::MIN), timezone).context("Invalid range end")?;
+    let mut entries = vec![];
+    for event in events {
+        let id = match &event.id {
+            serde_json::Value::String(id) => id.clone(),
+            id => id.to_string(),
+        };
+        let kind = entry_kind(&event.extended_props);
+        let course_id = match &kind {
+            EntryKind::CourseDate { course_id } => Some(course_id.clone()),
+            _ => None,
+        };
+        let make_entry = |start, end| CalendarEntry {
+            id: id.clone(),
+            title: event.title.clone(),
+            start,
+            end,
+            location: event.extended_props.location.as_deref().and_then(RoomRef::from_html),
+            kind: kind.clone(),
+            course_id: course_id.clone(),
+        };
+        if let Some(days_of_week) = &event.days_of_week {
+            let start_time = event.start_time.as_deref().and_then(parse_event_time).unwrap_or(NaiveTime::MIN);
+            let end_time = event.end_time.as_deref().and_then(parse_event_time).unwrap_or(start_time);
+            let start_recur = event.start_recur.as_deref().and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+            // The end of the recurrence is exclusive
+            let end_recur = event.end_recur.as_deref().and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+            for day in start.iter_days().take_while(|day| *day < end) {
+                if !days_of_week.contains(&day.weekday().num_days_from_sunday())
+                    || start_recur.is_some_and(|start_recur| day < start_recur)
+                    || end_recur.is_some_and(|end_recur| day >= end_recur) {
+                    continue;
+                }
+                let (Some(occurrence_start), Some(occurrence_end)) = (local_to_utc(day.and_time(start_time), timezone), local_to_utc(day.and_time(end_time), timezone)) else {continue};
+                entries.push(make_entry(occurrence_start, occurrence_end));
+            }
+            continue;
+        }
+        let event_start = event.start.as_deref()
+            .and_then(|text| parse_event_date_time(text, timezone))
+            .with_context(|| format!("Expected start of calendar event {}", id))?;
+        let event_end = event.end.as_deref()
+            .and_then(|text| parse_event_date_time(text, timezone))
+            .unwrap_or(event_start);
+        if event_end < range_start || event_start >= range_end {
+            continue;
+        }
+        entries.push(make_entry(event_start, event_end));
+    }
+    entries.sort_by_key(|entry| entry.start);
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::DEFAULT_TIMEZONE;
+
+    #[test]
+    fn test_parse_and_expand_events() {
+        let html = Html::parse_document(r#"
+            <div data-fullcalendar='{"events": [
+                {"id": "d1", "title": "Algorithms", "start": "2025-03-11T10:00:00", "end": "2025-03-11T12:00:00",
+                 "extendedProps": {"objectType": "CourseDate", "rangeId": "c1", "location": "<a href=\"/dispatch.php/resources/room/index/r1\">HS 1</a>"}},
+                {"id": 7, "title": "Gym", "daysOfWeek": [1, 3], "startTime": "18:00", "endTime": "19:30",
+                 "startRecur": "2025-01-01", "extendedProps": {"objectType": "CalendarDate", "location": ""}},
+                {"id": "b1", "title": "Consultation", "start": "2025-03-20T09:00:00",
+                 "extendedProps": {"objectType": "ConsultationBooking"}}
+            ]}'></div>
+        "#);
+        let events = parse_embedded_events(&html).unwrap().unwrap();
+        let start = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let entries = expand_events(events, start, start.checked_add_days(Days::new(7)).unwrap(), DEFAULT_TIMEZONE).unwrap();
+        // Monday gym, course date, Wednesday gym (the consultation is outside of the range)
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].title, "Gym");
+        assert_eq!(entries[0].id, "7");
+        assert_eq!(entries[0].kind, EntryKind::Personal);
+        assert_eq!(entries[0].location, None);
+        assert_eq!(entries[0].end - entries[0].start, chrono::Duration::minutes(90));
+        assert_eq!(entries[1].kind, EntryKind::CourseDate { course_id: "c1".into() });
+        assert_eq!(entries[1].course_id.as_deref(), Some("c1"));
+        assert_eq!(entries[1].location, Some(RoomRef { id: Some("r1".into()), name: "HS 1".into() }));
+        assert_eq!(entries[2].start, Utc.with_ymd_and_hms(2025, 3, 12, 17, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_appointment_across_dst() {
+        // The clocks are changed on 30.03.2025 in Germany
+        let appointment = NewAppointment {
+            title: "Study session".to_string(),
+            start: NaiveDate::from_ymd_opt(2025, 3, 29).unwrap().and_hms_opt(22, 0, 0).unwrap(),
+            end: NaiveDate::from_ymd_opt(2025, 3, 30).unwrap().and_hms_opt(4, 0, 0).unwrap(),
+            location: None,
+            description: None,
+            recurrence: Some(Recurrence::Weekly { interval: 1, weekdays: vec![], until: None }),
+        };
+        let params = appointment_params(&appointment);
+        let get = |key: &str| params.iter().filter(|(k, _)| *k == key).map(|(_, v)| v.as_str()).collect::<Vec<_>>();
+        assert_eq!(get("begin_time"), vec!["22:00"]);
+        assert_eq!(get("end_date"), vec!["30.03.2025"]);
+        assert_eq!(get("end_time"), vec!["04:00"]);
+        assert_eq!(get("repetition_type"), vec!["WEEKLY"]);
+        assert_eq!(get("repetition_dow[]"), vec!["6"]);
+        assert_eq!(get("repetition_end_type"), vec!["never"]);
+
+        // The server lists the appointment in campus time, which is UTC+1 before and UTC+2 after the change
+        let berlin = chrono_tz::Europe::Berlin;
+        let events = serde_json::from_str(r#"[
+            {"id": "a1", "title": "Study session", "start": "2025-03-29T22:00:00", "end": "2025-03-30T04:00:00", "extendedProps": {"objectType": "CalendarDate"}},
+            {"id": "g1", "title": "Gym", "daysOfWeek": [6], "startTime": "22:00", "endTime": "23:00", "extendedProps": {"objectType": "CalendarDate"}}
+        ]"#).unwrap();
+        let start = NaiveDate::from_ymd_opt(2025, 3, 24).unwrap();
+        let entries = expand_events(events, start, start.checked_add_days(Days::new(14)).unwrap(), berlin).unwrap();
+        let instants = entries.iter().map(|entry| (entry.id.as_str(), entry.start, entry.end)).collect::<Vec<_>>();
+        let utc = |month, day, hour| Utc.with_ymd_and_hms(2025, month, day, hour, 0, 0).unwrap();
+        assert_eq!(instants, vec![
+            // Only five hours long, as an hour is skipped
+            ("a1", utc(3, 29, 21), utc(3, 30, 2)),
+            // A weekly event stays at 22:00 campus time
+            ("g1", utc(3, 29, 21), utc(3, 29, 22)),
+            ("g1", utc(4, 5, 20), utc(4, 5, 21)),
+        ]);
+        assert_eq!(find_created_id(&entries, &appointment, berlin).as_deref(), Some("a1"));
+        // Compared in another timezone, the same wall clock time is a different instant
+        assert_eq!(find_created_id(&entries, &appointment, chrono_tz::America::New_York), None);
+    }
+
+    #[test]
+    fn test_timetable_conflicts() {
+        let events = serde_json::from_str(r#"[
+            {"id": "s1", "title": "Algorithms", "daysOfWeek": [1], "startTime": "10:00", "endTime": "12:00",
+             "extendedProps": {"objectType": "CourseDate", "courseId": "c1", "location": "HS 1"}},
+            {"id": "s2", "title": "Databases", "daysOfWeek": [1, 3], "startTime": "11:00", "endTime": "13:00",
+             "extendedProps": {"objectType": "CourseDate", "courseId": "c2"}},
+            {"id": "s3", "title": "Job", "daysOfWeek": [1], "startTime": "08:00", "endTime": "10:00",
+             "extendedProps": {"objectType": "ScheduleEntry"}}
+        ]"#).unwrap();
+        let timetable = timetable_from_events(events, DEFAULT_TIMEZONE);
+        let monday = timetable.slots(Weekday::Mon);
+        assert_eq!(monday.iter().map(|slot| slot.label.as_str()).collect::<Vec<_>>(), vec!["Job", "Algorithms", "Databases"]);
+        assert_eq!(monday[0].course_id, None);
+        assert_eq!(monday[1].room, Some(RoomRef { id: None, name: "HS 1".into() }));
+        assert_eq!(timetable.slots(Weekday::Wed).len(), 1);
+        assert!(timetable.slots(Weekday::Sun).is_empty());
+        let conflicts = timetable.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!((conflicts[0].0, conflicts[0].1.label.as_str(), conflicts[0].2.label.as_str()), (Weekday::Mon, "Algorithms", "Databases"));
+    }
+
+    #[test]
+    fn test_missing_embedded_events() {
+        assert!(parse_embedded_events(&Html::parse_document("<div id='calendar'></div>")).unwrap().is_none());
+    }
+}
@@ -0,0 +1,152 @@
+//! Conditional requests, which let Stud.IP answer with `304 Not Modified`, if a page did not change since it was last fetched \
+//! Enabled with [StudIpClientBuilder::conditional_requests()](crate::StudIpClientBuilder::conditional_requests()), the [`StudIpClient`](crate::StudIpClient) then remembers the `ETag` and `Last-Modified` headers per url.
+//! Pages read through a course (see [Course::set_cache_freshness()](crate::course::Course::set_cache_freshness())) are revalidated instead of being fetched again, once they are no longer fresh.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use reqwest::blocking::{RequestBuilder, Response};
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+
+/// The outcome of [StudIpClient::get_conditional()](crate::StudIpClient::get_conditional())
+#[derive(Debug)]
+pub enum Conditional {
+    /// The page changed (or was never fetched before), with the new response
+    Modified(Response),
+    /// The page did not change since the last response for the same url, which can be reused
+    NotModified,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// Counts every use, to find the longest unused url
+    tick: u64,
+    /// The validators by url, with the tick of their last use
+    by_url: HashMap<String, (u64, Validators)>,
+}
+
+/// Remembers the validators of at most `max_entries` urls, forgetting the longest unused one first
+#[derive(Debug, Default)]
+pub(crate) struct ValidatorCache {
+    max_entries: usize,
+    entries: Mutex<Entries>,
+}
+
+impl ValidatorCache {
+
+    /// A cache for `max_entries` urls, 0 disables conditional requests
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            entries: Default::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_entries > 0
+    }
+
+    /// Adds the conditional headers for the `url` to the `request`, if its validators are known
+    pub fn apply(&self, url: &str, mut request: RequestBuilder) -> RequestBuilder {
+        let mut entries = self.entries.lock().unwrap();
+        let Entries { tick, by_url } = &mut *entries;
+        let Some((used, validators)) = by_url.get_mut(url) else {
+            return request;
+        };
+        *tick += 1;
+        *used = *tick;
+        if let Some(etag) = &validators.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+
+    /// Stores the validators of a successful `response` for the `url`, or forgets the url, if the response has none
+    pub fn remember(&self, url: &str, response: &Response) {
+        if !self.is_enabled() {
+            return;
+        }
+        let validators = validators_of(response.headers());
+        let mut entries = self.entries.lock().unwrap();
+        let Entries { tick, by_url } = &mut *entries;
+        if !response.status().is_success() || validators == Validators::default() {
+            by_url.remove(url);
+            return;
+        }
+        if !by_url.contains_key(url) && by_url.len() >= self.max_entries {
+            let oldest = by_url.iter().min_by_key(|(_, (used, _))| *used).map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                by_url.remove(&oldest);
+            }
+        }
+        *tick += 1;
+        by_url.insert(url.to_string(), (*tick, validators));
+    }
+
+}
+
+fn validators_of(headers: &HeaderMap) -> Validators {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok()).map(|value| value.to_string());
+    Validators {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+    use crate::page_cache::PageCache;
+    use crate::SendThrough;
+    use crate::auth::AuthMethod;
+
+    #[test]
+    fn test_conditional_requests() {
+        let server = MockServer::start();
+        let headers = [("ETag", "\"v1\""), ("Last-Modified", "Wed, 13 Nov 2024 10:00:00 GMT")];
+        server.route_with_headers("GET", "/dispatch.php/course/overview", 200, &headers, "<p>Overview</p>");
+        let client = server.client_builder().conditional_requests(2).build(AuthMethod::Session).unwrap();
+        let url = "https://studip.example.com/dispatch.php/course/overview";
+        let page_cache = PageCache::default();
+        page_cache.set_freshness(std::time::Duration::from_millis(1));
+        assert_eq!(page_cache.get(&client, url, &[("cid", "c1")]).unwrap(), "<p>Overview</p>");
+
+        // Once the page is stale, it is revalidated and the cached body is reused
+        server.route("GET", "/dispatch.php/course/overview", 304, "");
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert_eq!(page_cache.get(&client, url, &[("cid", "c1")]).unwrap(), "<p>Overview</p>");
+        assert!(matches!(client.get_conditional(url, &[("cid", "c1")]).unwrap(), Conditional::NotModified));
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].header("If-None-Match"), None);
+        for request in &requests[1..] {
+            assert_eq!(request.header("If-None-Match"), Some("\"v1\""));
+            assert_eq!(request.header("If-Modified-Since"), Some("Wed, 13 Nov 2024 10:00:00 GMT"));
+        }
+
+        // Other urls are requested unconditionally
+        client.get_conditional(url, &[("cid", "c2")]).unwrap();
+        assert_eq!(server.requests()[3].header("If-None-Match"), None);
+
+        // The least recently used url is forgotten first
+        server.route_with_headers("GET", "/dispatch.php/course/overview", 200, &headers, "<p>Overview</p>");
+        let cache = ValidatorCache::new(2);
+        let key = |cid: &str| format!("{}?cid={}", url, cid);
+        for cid in ["c1", "c2", "c1", "c3"] {
+            let request = cache.apply(&key(cid), client.get(url).query(&[("cid", cid)]));
+            cache.remember(&key(cid), &request.send_through(&client).unwrap());
+        }
+        let by_url = &cache.entries.lock().unwrap().by_url;
+        assert_eq!(by_url.len(), 2);
+        assert!(by_url.contains_key(&key("c1")) && by_url.contains_key(&key("c3")));
+    }
+}
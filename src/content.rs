@@ -0,0 +1,300 @@
+//! Conversion of the formatted html content of Stud.IP (news, comments, profile categories, ...) into plain text or Markdown
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{ElementRef, Html, Node};
+
+static RAW_CODE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)\[code\](.*?)\[/code\]").unwrap());
+static BLANK_LINES_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\n{3,}").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Markdown,
+}
+
+/// Converts Stud.IP html into plain text \
+/// Paragraphs and line breaks are preserved, list items are prefixed with bullets and link targets are appended in parentheses.
+pub fn html_to_text(html: &str) -> String {
+    render(html, Format::Text)
+}
+
+/// Converts Stud.IP html into Markdown \
+/// Code blocks (also unrendered `[code]` markup) become fenced blocks and smileys are replaced by their text.
+pub fn html_to_markdown(html: &str) -> String {
+    render(html, Format::Markdown)
+}
+
+fn render(html: &str, format: Format) -> String {
+    // Stud.IP markup, that was not rendered by the server
+    let html = RAW_CODE_REGEX.replace_all(html, |captures: &regex::Captures| {
+        format!("<pre class=\"usercode\"><code>{}</code></pre>", &captures[1])
+    });
+    let fragment = Html::parse_fragment(&html);
+    let mut renderer = Renderer { format, out: String::new(), lists: vec![] };
+    renderer.children(fragment.root_element());
+    let text = renderer.out.lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n");
+    BLANK_LINES_REGEX.replace_all(text.trim_matches('\n'), "\n\n").to_string()
+}
+
+struct Renderer {
+    format: Format,
+    out: String,
+    /// The currently open lists, with the next number of ordered ones
+    lists: Vec<Option<usize>>,
+}
+
+impl Renderer {
+
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n')
+    }
+
+    // Checks if only the bullet of a list item has been written to the current line
+    fn at_item_start(&self) -> bool {
+        let line = self.out.rsplit('\n').next().unwrap_or_default().trim();
+        !self.lists.is_empty() && (line == "•" || line == "-" || line.strip_suffix('.').is_some_and(|number| number.parse::<usize>().is_ok()))
+    }
+
+    fn newline(&mut self) {
+        if !self.at_line_start() && !self.at_item_start() {
+            self.out.push('\n');
+        }
+    }
+
+    fn blank_line(&mut self) {
+        if self.lists.is_empty() {
+            self.newline();
+            if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+                self.out.push('\n');
+            }
+        } else {
+            self.newline();
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let mut collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.starts_with(char::is_whitespace) && !collapsed.is_empty() {
+            collapsed.insert(0, ' ');
+        }
+        if text.ends_with(char::is_whitespace) && !text.trim().is_empty() {
+            collapsed.push(' ');
+        }
+        if collapsed.trim().is_empty() {
+            if !text.is_empty() && !self.at_line_start() && !self.out.ends_with(' ') {
+                self.out.push(' ');
+            }
+            return;
+        }
+        if self.at_line_start() || self.out.ends_with(' ') {
+            collapsed = collapsed.trim_start().to_string();
+        }
+        self.out.push_str(&collapsed);
+    }
+
+    /// Renders the children into a separate string, used for inline wrappers like links
+    fn inline(&mut self, element: ElementRef) -> String {
+        let mut renderer = Renderer { format: self.format, out: String::new(), lists: self.lists.clone() };
+        renderer.children(element);
+        renderer.out.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn push_inline(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if !self.at_line_start() && !self.out.ends_with(' ') && self.out.ends_with(|c: char| c.is_alphanumeric()) {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+    }
+
+    fn children(&mut self, element: ElementRef) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.text(text),
+                Node::Element(_) => self.element(ElementRef::wrap(child).unwrap()),
+                _ => {}
+            }
+        }
+    }
+
+    fn element(&mut self, element: ElementRef) {
+        let markdown = self.format == Format::Markdown;
+        let value = element.value();
+        match value.name() {
+            "script" | "style" | "head" => {},
+            "br" => self.out.push('\n'),
+            "p" | "div" | "section" | "article" | "header" | "footer" => {
+                self.blank_line();
+                self.children(element);
+                self.blank_line();
+            },
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.blank_line();
+                if markdown {
+                    let level = value.name()[1..].parse().unwrap_or(1);
+                    self.out.push_str(&format!("{} ", "#".repeat(level)));
+                }
+                let heading = self.inline(element);
+                self.out.push_str(&heading);
+                self.blank_line();
+            },
+            "ul" | "ol" => {
+                self.blank_line();
+                self.lists.push((value.name() == "ol").then_some(1));
+                self.children(element);
+                self.lists.pop();
+                self.blank_line();
+            },
+            "li" => {
+                self.newline();
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                let bullet = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    },
+                    _ if markdown => "- ".to_string(),
+                    _ => "• ".to_string(),
+                };
+                self.out.push_str(&indent);
+                self.out.push_str(&bullet);
+                self.children(element);
+                self.newline();
+            },
+            "pre" => {
+                self.blank_line();
+                let code = element.text().collect::<String>();
+                let code = code.trim_matches('\n');
+                if markdown {
+                    self.out.push_str(&format!("```\n{}\n```", code));
+                } else {
+                    self.out.push_str(code);
+                }
+                self.blank_line();
+            },
+            "blockquote" => {
+                self.blank_line();
+                let mut renderer = Renderer { format: self.format, out: String::new(), lists: vec![] };
+                renderer.children(element);
+                let quoted = renderer.out.trim_matches('\n')
+                    .lines()
+                    .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                self.out.push_str(&quoted);
+                self.blank_line();
+            },
+            "hr" => {
+                self.blank_line();
+                self.out.push_str(if markdown { "---" } else { "----------" });
+                self.blank_line();
+            },
+            "tr" => {
+                self.newline();
+                let cells = element.children()
+                    .filter_map(ElementRef::wrap)
+                    .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+                    .map(|cell| self.inline(cell))
+                    .collect::<Vec<_>>();
+                self.out.push_str(&cells.join(" | "));
+                self.newline();
+            },
+            "a" => {
+                let label = self.inline(element);
+                let href = value.attr("href").unwrap_or_default();
+                let rendered = if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") {
+                    label
+                } else if markdown && (label.is_empty() || label == href) {
+                    format!("<{}>", href)
+                } else if markdown {
+                    format!("[{}]({})", label, href)
+                } else if label.is_empty() || label == href || label == href.trim_start_matches("mailto:") {
+                    href.trim_start_matches("mailto:").to_string()
+                } else {
+                    format!("{} ({})", label, href)
+                };
+                self.push_inline(&rendered);
+            },
+            "img" => {
+                let alt = value.attr("alt").or(value.attr("title")).unwrap_or_default();
+                let src = value.attr("src").unwrap_or_default();
+                let is_smiley = value.has_class("smiley", scraper::CaseSensitivity::AsciiCaseInsensitive)
+                    || src.contains("/smile/");
+                if is_smiley || !markdown {
+                    self.push_inline(alt);
+                } else {
+                    self.push_inline(&format!("![{}]({})", alt, src));
+                }
+            },
+            "strong" | "b" | "em" | "i" | "code" | "del" | "s" if markdown => {
+                let marker = match value.name() {
+                    "strong" | "b" => "**",
+                    "em" | "i" => "*",
+                    "code" => "`",
+                    _ => "~~",
+                };
+                let inner = self.inline(element);
+                if inner.is_empty() {
+                    return;
+                }
+                if element.text().next().is_some_and(|text| text.starts_with(char::is_whitespace)) && !self.at_line_start() {
+                    self.out.push(' ');
+                }
+                self.out.push_str(&format!("{}{}{}", marker, inner, marker));
+                if element.text().last().is_some_and(|text| text.ends_with(char::is_whitespace)) {
+                    self.out.push(' ');
+                }
+            },
+            _ => self.children(element),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each golden case consists of the captured html and the expected text and markdown output
+    const GOLDEN: [(&str, &str, &str, &str); 3] = [
+        (
+            "news",
+            include_str!("../testdata/content/news.html"),
+            include_str!("../testdata/content/news.txt"),
+            include_str!("../testdata/content/news.md"),
+        ),
+        (
+            "code_and_smileys",
+            include_str!("../testdata/content/code_and_smileys.html"),
+            include_str!("../testdata/content/code_and_smileys.txt"),
+            include_str!("../testdata/content/code_and_smileys.md"),
+        ),
+        (
+            "profile",
+            include_str!("../testdata/content/profile.html"),
+            include_str!("../testdata/content/profile.txt"),
+            include_str!("../testdata/content/profile.md"),
+        ),
+    ];
+
+    #[test]
+    fn test_golden_files() {
+        for (name, html, text, markdown) in GOLDEN {
+            assert_eq!(html_to_text(html), text.trim_end(), "text of {}", name);
+            assert_eq!(html_to_markdown(html), markdown.trim_end(), "markdown of {}", name);
+        }
+    }
+
+    #[test]
+    fn test_inline_whitespace() {
+        assert_eq!(html_to_markdown("Hello <b>big</b> world"), "Hello **big** world");
+        assert_eq!(html_to_text("see <a href=\"https://example.com\">here</a>."), "see here (https://example.com).");
+        assert_eq!(html_to_text("a<br>b"), "a\nb");
+    }
+}
@@ -0,0 +1,259 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Mutex;
+use chrono::{DateTime, Duration, NaiveDateTime, SubsecRound, Utc};
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::header::HeaderValue;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// The name of the cookie, which holds the session of Stud.IP
+pub const SESSION_COOKIE_NAME: &str = "Seminar_Session";
+
+/// The session cookie of Stud.IP, as returned by [StudIpClient::session_cookie()](crate::StudIpClient::session_cookie()) \
+/// The value is left out of the [`Debug`] output.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionCookie {
+    pub name: String,
+    pub value: String,
+    /// When the cookie expires, `None` if it only lasts for the browser session
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl Debug for SessionCookie {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionCookie")
+            .field("name", &self.name)
+            .field("value", &"<redacted>")
+            .field("expires", &self.expires)
+            .finish()
+    }
+}
+
+/// A cookie of the client, as exported by [StudIpClient::export_cookies()](crate::StudIpClient::export_cookies()) \
+/// The value is left out of the [`Debug`] output.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CookieRecord {
+    pub name: String,
+    pub value: String,
+    /// The domain, without a leading dot
+    pub domain: String,
+    /// If the cookie is only sent to the `domain` itself, not to its subdomains
+    pub host_only: bool,
+    pub path: String,
+    /// When the cookie expires, `None` if it only lasts for the browser session
+    pub expires: Option<DateTime<Utc>>,
+    pub secure: bool,
+    pub http_only: bool,
+}
+
+impl Debug for CookieRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CookieRecord")
+            .field("name", &self.name)
+            .field("value", &"<redacted>")
+            .field("domain", &self.domain)
+            .field("host_only", &self.host_only)
+            .field("path", &self.path)
+            .field("expires", &self.expires)
+            .field("secure", &self.secure)
+            .field("http_only", &self.http_only)
+            .finish()
+    }
+}
+
+impl CookieRecord {
+
+    /// Parses a `Set-Cookie` header, that was received from the `url`
+    fn parse(set_cookie: &str, url: &Url) -> Option<Self> {
+        let mut parts = set_cookie.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        // Paths default to the directory of the url
+        let default_path = match url.path().rfind('/') {
+            Some(0) | None => "/",
+            Some(index) => &url.path()[..index],
+        };
+        let mut record = Self {
+            name: name.to_string(),
+            value: value.trim().trim_matches('"').to_string(),
+            domain: url.host_str()?.to_string(),
+            host_only: true,
+            path: default_path.to_string(),
+            expires: None,
+            secure: false,
+            http_only: false,
+        };
+        let mut max_age = None;
+        for attribute in parts {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "expires" => record.expires = record.expires.or_else(|| parse_cookie_date(value)),
+                "max-age" => max_age = value.parse::<i64>().ok(),
+                "domain" if !value.is_empty() => {
+                    record.domain = value.trim_start_matches('.').to_lowercase();
+                    record.host_only = false;
+                },
+                "path" if value.starts_with('/') => record.path = value.to_string(),
+                "secure" => record.secure = true,
+                "httponly" => record.http_only = true,
+                _ => {},
+            }
+        }
+        // Max-Age takes precedence over Expires, both only have a precision of seconds
+        if let Some(max_age) = max_age {
+            record.expires = Some(Utc::now().trunc_subsecs(0) + Duration::seconds(max_age));
+        }
+        Some(record)
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|expires| expires <= Utc::now())
+    }
+
+    fn matches_host(&self, host: &str) -> bool {
+        host == self.domain || (!self.host_only && host.ends_with(&format!(".{}", self.domain)))
+    }
+
+    fn to_set_cookie(&self) -> String {
+        let mut set_cookie = format!("{}={}; Path={}", self.name, self.value, self.path);
+        if !self.host_only {
+            set_cookie.push_str(&format!("; Domain={}", self.domain));
+        }
+        if let Some(expires) = self.expires {
+            set_cookie.push_str(&expires.format("; Expires=%a, %d %b %Y %H:%M:%S GMT").to_string());
+        }
+        if self.secure {
+            set_cookie.push_str("; Secure");
+        }
+        if self.http_only {
+            set_cookie.push_str("; HttpOnly");
+        }
+        set_cookie
+    }
+
+}
+
+// Dates are formatted like "Wed, 21 Oct 2015 07:28:00 GMT", PHP separates the date with dashes instead ("Wed, 21-Oct-2015 07:28:00 GMT")
+fn parse_cookie_date(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.replace('-', " ");
+    DateTime::parse_from_rfc2822(&text).ok()
+        .map(|date| date.with_timezone(&Utc))
+        .or_else(|| NaiveDateTime::parse_from_str(&text, "%a, %d %b %Y %H:%M:%S GMT").ok().map(|date| date.and_utc()))
+}
+
+/// The cookie store of a [`StudIpClient`](crate::StudIpClient) \
+/// Cookies are sent by a [`Jar`], while their attributes are kept alongside, so that they can be exported.
+#[derive(Default)]
+pub(crate) struct CookieJar {
+    jar: Jar,
+    records: Mutex<Vec<CookieRecord>>,
+}
+
+impl Debug for CookieJar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CookieJar")
+            .field("cookies", &self.records.lock().unwrap().len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl CookieJar {
+
+    fn store(&self, record: CookieRecord) {
+        let mut records = self.records.lock().unwrap();
+        records.retain(|other| (&other.domain, &other.path, &other.name) != (&record.domain, &record.path, &record.name));
+        // Servers delete cookies by letting them expire
+        if !record.is_expired() {
+            records.push(record);
+        }
+    }
+
+    /// The cookies, that are sent to the `host`
+    pub(crate) fn export(&self, host: &str) -> Vec<CookieRecord> {
+        self.records.lock().unwrap().iter()
+            .filter(|record| !record.is_expired() && record.matches_host(host))
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn import(&self, records: &[CookieRecord]) -> anyhow::Result<()> {
+        for record in records {
+            let url = Url::parse(&format!("https://{}{}", record.domain, record.path))?;
+            let header = HeaderValue::from_str(&record.to_set_cookie())?;
+            self.set_cookies(&mut std::iter::once(&header), &url);
+        }
+        Ok(())
+    }
+
+}
+
+impl CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let cookie_headers = cookie_headers.collect::<Vec<_>>();
+        for header in &cookie_headers {
+            if let Some(record) = header.to_str().ok().and_then(|set_cookie| CookieRecord::parse(set_cookie, url)) {
+                self.store(record);
+            }
+        }
+        self.jar.set_cookies(&mut cookie_headers.into_iter(), url);
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        self.jar.cookies(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_parse_set_cookie() {
+        let url = Url::parse("https://studip.example.com/studip/dispatch.php/start").unwrap();
+        let record = CookieRecord::parse("Seminar_Session=abc123; expires=Wed, 21-Oct-2037 07:28:00 GMT; path=/; secure; HttpOnly", &url).unwrap();
+        assert_eq!((record.name.as_str(), record.value.as_str(), record.path.as_str()), ("Seminar_Session", "abc123", "/"));
+        assert_eq!(record.expires, DateTime::parse_from_rfc3339("2037-10-21T07:28:00Z").ok().map(|date| date.with_timezone(&Utc)));
+        assert!(record.secure && record.http_only && record.host_only);
+        let record = CookieRecord::parse("lang=deutsch; Domain=.example.com", &url).unwrap();
+        assert_eq!((record.domain.as_str(), record.path.as_str()), ("example.com", "/studip/dispatch.php"));
+        assert!(record.matches_host("studip.example.com"));
+        assert!(CookieRecord::parse("=abc", &url).is_none());
+        // The value is not part of the debug output
+        assert!(!format!("{:?}", record).contains("deutsch"));
+    }
+
+    #[test]
+    fn test_session_cookie_round_trip() {
+        let server = MockServer::start();
+        server.route_with_headers("GET", "/dispatch.php/start", 200, &[
+            ("Set-Cookie", "Seminar_Session=s3cr3t; Max-Age=3600; path=/; HttpOnly"),
+            ("Set-Cookie", "cache_session=1; path=/"),
+            ("Set-Cookie", "old=1; expires=Thu, 01-Jan-1970 00:00:01 GMT; path=/"),
+        ], "")
+            .route("GET", "/dispatch.php/profile", 200, "");
+        let client = server.client();
+        assert!(client.session_cookie().is_none());
+        client.get("https://studip.example.com/dispatch.php/start").send().unwrap();
+
+        let session_cookie = client.session_cookie().unwrap();
+        assert_eq!(session_cookie.value, "s3cr3t");
+        assert!(session_cookie.expires.unwrap() > Utc::now());
+        assert!(!format!("{:?}", session_cookie).contains("s3cr3t"));
+        let mut names = client.export_cookies().into_iter().map(|record| record.name).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["Seminar_Session", "cache_session"]);
+
+        // Another client sends the imported cookies
+        let other = server.client();
+        other.import_cookies(&client.export_cookies()).unwrap();
+        assert_eq!(other.session_cookie(), Some(session_cookie));
+        other.get("https://studip.example.com/dispatch.php/profile").send().unwrap();
+        let cookie_header = server.requests().last().unwrap().header("Cookie").unwrap().to_string();
+        assert!(cookie_header.contains("Seminar_Session=s3cr3t"), "{}", cookie_header);
+    }
+}
@@ -1,14 +1,38 @@
-use std::collections::HashMap;
-use std::sync::Arc;
-use anyhow::Context;
-use scraper::{Html, Selector};
-use serde::{Deserialize, Serialize};
-use crate::course_modules::{COURSE_MODULE_REGISTRY, CourseModule, CourseModuleData, register_default_course_modules, REGISTERED_DEFAULT_COURSE_MODULES};
-use crate::StudIpClient;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{ElementRef, Html};
+use serde::{Deserialize, Deserializer, Serialize};
+use url::Url;
+use crate::archive::{archive_course, parse_details, ArchiveOptions, ArchiveReport};
+use crate::news::{parse_news_box, NewsArticle};
+use crate::questionnaire::{parse_questionnaire, Questionnaire};
+use crate::ref_source::ReferenceSource;
+use crate::course_modules::{fetch_course_page, CourseModule, CourseModuleData, ModuleFailure, ModulesReport, UnknownModule};
+use crate::enrollment::{parse_admission, AdmissionProcedure};
+use crate::error::check_status;
+use crate::ids::CourseId;
+use crate::page_cache::PageCache;
+use crate::resources::RoomRef;
+use crate::labels::{self, Language};
+use crate::{SendThrough, StudIpClient};
+use crate::util::{local_to_utc, normalize_text, parse_localized_date_time, parse_page, selector};
+use crate::logging::log_parse;
+use crate::warnings::{ParseWarning, ParseWarnings, Parsed};
 
 const MY_COURSES_URL: &str = "https://studip.example.com/dispatch.php/my_courses";
 pub(crate) const COURSE_URL: &str = "https://studip.example.com/dispatch.php/course";
 const MODULES_QUERY_URL : &str = "https://studip.example.com/seminar_main.php";
+const COURSE_DATES_URL: &str = "https://studip.example.com/dispatch.php/course/dates";
+const WHATS_NEW_URL: &str = "https://studip.example.com/dispatch.php/course/whats_new";
+/// The version of the snapshot format, needs to be increased whenever the serialized structs change
+const SNAPSHOT_VERSION: u32 = 1;
 
 /// Represents a course and it's modules \
 /// A singular module can be accessed, by type with the [get_module!()](crate::get_module!()) macro.
@@ -16,14 +40,23 @@ const MODULES_QUERY_URL : &str = "https://studip.example.com/seminar_main.php";
 pub struct Course {
     // Json data
     /// The course ID
-    pub id: String,
+    pub id: CourseId,
     /// The courses Name
     pub name: String,
     #[serde(rename = "number")]
     _number: String, // No Idea what this is for
-    /// The group index in which the current user has added this course \
-    /// Corresponds to the `groups` filed of the [`MyCourses`] struct
+    /// The (color) group index in which the current user has added this course
     pub group: usize,
+    /// If the current user is a teacher of this course
+    #[serde(default)]
+    pub is_teacher: bool,
+    /// If this course is a study group
+    #[serde(default)]
+    pub is_studygroup: bool,
+    /// The icons of the modules, as shown on the my courses page, which mark new contents \
+    /// `None`, if the course was not read from the my courses page or its markers were reset.
+    #[serde(default, deserialize_with = "deserialize_navigation")]
+    navigation: Option<Vec<NavigationItem>>,
 
     // Custom data
     #[serde(skip)]
@@ -31,33 +64,800 @@ pub struct Course {
     /// Needs to be queried with [`Course::query_modules()`]
     pub modules: Vec<Box<dyn CourseModule>>,
     #[serde(skip)]
-    client: Arc<StudIpClient>
+    client: Option<Arc<StudIpClient>>,
+    #[serde(skip)]
+    page_cache: Arc<PageCache>,
 }
 
 impl Course {
 
+    /// Creates a course from data, that was not read from the my courses page (e.g. from the JSON:API)
+    #[cfg_attr(not(feature = "jsonapi"), allow(dead_code))]
+    pub(crate) fn from_parts(id: CourseId, name: String, number: String, client: Arc<StudIpClient>) -> Self {
+        Self {
+            id,
+            name,
+            _number: number,
+            group: 0,
+            is_teacher: false,
+            is_studygroup: false,
+            navigation: None,
+            modules: vec![],
+            client: Some(client),
+            page_cache: Default::default(),
+        }
+    }
+
+    /// Attaches a client to the course, which is required after deserializing it
+    pub fn attach_client(&mut self, client: Arc<StudIpClient>) {
+        self.client = Some(client);
+    }
+
+    pub(crate) fn client(&self) -> anyhow::Result<&Arc<StudIpClient>> {
+        self.client.as_ref().context("No client attached to course. Call MyCourses::attach_client() after deserializing")
+    }
+
+    /// Sets the time, for which pages of this course (and its modules) are reused by consecutive reads \
+    /// Defaults to 30 seconds, [`Duration::ZERO`] disables reusing pages.
+    pub fn set_cache_freshness(&self, freshness: Duration) {
+        self.page_cache.set_freshness(freshness);
+    }
+
+    /// Discards all pages of this course (and its modules), that are reused by consecutive reads, so that they are fetched again
+    pub fn refresh(&self) {
+        self.page_cache.clear();
+    }
+
+    /// Fetches a page of this course by its path (e.g. `plugins.php/meetingplugin/index`), with the `cid` of the course added to the `extra_query` \
+    /// For pages, that are not modeled by any module, see [`CourseModuleData::fetch_page()`](crate::course_modules::CourseModuleData::fetch_page()).
+    pub fn fetch_page(&self, relative_path: &str, extra_query: &[(&str, &str)]) -> anyhow::Result<String> {
+        fetch_course_page(self.client()?, &self.id, relative_path, extra_query, false)
+    }
+
+    /// Like [`Course::fetch_page()`], but requested like the web UI requests its dialogs and updates, returning the parsed JSON
+    pub fn fetch_xhr_json(&self, relative_path: &str, extra_query: &[(&str, &str)]) -> anyhow::Result<serde_json::Value> {
+        let body = fetch_course_page(self.client()?, &self.id, relative_path, extra_query, true)?;
+        serde_json::from_str(&body).with_context(|| format!("Expected JSON from {}", relative_path))
+    }
+
+    fn get_overview(&self) -> anyhow::Result<Html> {
+        let body = self.page_cache.get(self.client()?, COURSE_URL, &[("cid", &self.id)])?;
+        Ok(Html::parse_document(&body))
+    }
+
+    /// Queries the news (announcements) shown on the overview page of this course
+    pub fn query_news(&self) -> anyhow::Result<Vec<NewsArticle>> {
+        let html = self.get_overview()?;
+        let article_selector = selector!("#content article.studip:not([id])");
+        let news_header_selector = selector!("header .icon-shape-news");
+        let news_elem = html.select(article_selector)
+            .find(|elem| elem.select(news_header_selector).next().is_some());
+        match news_elem {
+            Some(news_elem) => parse_news_box(news_elem, &ReferenceSource::Course(self.id.clone())),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Queries the [`CourseDate`]s of this course's schedule, including cancelled ones
+    pub fn query_dates(&self) -> anyhow::Result<Vec<CourseDate>> {
+        let client = self.client()?;
+        let response = client.get(COURSE_DATES_URL)
+            .query(&[("cid", self.id.as_str())])
+            .send_through(client)?;
+        parse_course_dates(&parse_page(&response.text()?)?, client.timezone())
+    }
+
+    /// Queries the [`Questionnaire`]s shown on the overview page of this course
+    pub fn query_questionnaires(&self) -> anyhow::Result<Vec<Questionnaire>> {
+        let source = ReferenceSource::Course(self.id.clone());
+        let html = self.get_overview()?;
+        let questionnaire_selector = selector!("#questionnaire_area > article[data-questionnaire_id]");
+        html.select(questionnaire_selector)
+            .map(|elem| parse_questionnaire(elem, source.clone()))
+            .collect()
+    }
+
+    /// Queries the [`CourseDetails`] from the details page of this course \
+    /// The location includes the id of the room, if the page links to it, and the admission procedure is detected from the admission rules.
+    pub fn query_details(&self) -> anyhow::Result<CourseDetails> {
+        let body = self.page_cache.get(self.client()?, &format!("{}/details", COURSE_URL), &[("cid", &self.id)])?;
+        let html = Html::parse_document(&body);
+        let (fields, _) = parse_details(&html)?;
+        let mut details = CourseDetails::from_fields(fields);
+        if let Some(location) = parse_location(&html) {
+            details.location = Some(location);
+        }
+        details.admission = parse_admission(&html);
+        Ok(details)
+    }
+
+    /// Returns what changed in this course since the last visit (new files, forum posts, news and wiki changes) \
+    /// This is a cheaper alternative to reading every module and comparing it with an earlier state.
+    ///
+    /// *Note: Visiting the "Was gibt's Neues" page resets the markers on the server, so the changes are only reported once.*
+    /// With `peek`, the page is not visited. Instead the markers of the module icons on the my courses page are used,
+    /// which are less detailed, but are kept until the modules themselves are visited.
+    pub fn whats_new(&mut self, peek: bool) -> anyhow::Result<Vec<ChangeItem>> {
+        let client = self.client()?.clone();
+        if peek {
+            if self.navigation.is_none() {
+                self.navigation = query_my_courses_data(&client)?
+                    .value.courses.remove(&self.id)
+                    .and_then(|course| course.navigation);
+            }
+            let navigation = self.navigation.as_deref()
+                .context("The my courses page does not show the module icons of the course")?;
+            return navigation.iter()
+                .filter(|item| item.is_new())
+                .map(|item| item.to_change_item(&client))
+                .collect();
+        }
+        let response = client.get(WHATS_NEW_URL)
+            .query(&[("cid", self.id.as_str())])
+            .send_through(&client)?;
+        check_status(response.status())?;
+        let changes = parse_whats_new(&parse_page(&response.text()?)?, &client)?;
+        // The markers were reset by the visit
+        self.navigation = None;
+        Ok(changes)
+    }
+
+    /// Writes an offline copy of the selected sections of this course into the `dest` directory \
+    /// A `manifest.json` with the course metadata and the [`ArchiveReport`] is written last. \
+    /// Failures of single sections are recorded in the report and do not abort the archive. \
+    /// *Note: This makes a request for every folder and file*
+    pub fn archive_to(&mut self, dest: &Path, options: ArchiveOptions) -> anyhow::Result<ArchiveReport> {
+        archive_course(self, dest, &options)
+    }
 
     /// Queries the available modules for this course and stores them in the `modules` field. \
+    /// Tabs without a registered module are left out. Modules, that fail to be constructed, are reported instead of failing the query. \
     /// *Note: This is not done automatically*
-    pub fn query_modules(&mut self) -> anyhow::Result<()> {
-        REGISTERED_DEFAULT_COURSE_MODULES.get_or_init(register_default_course_modules);
-        let module_reg = COURSE_MODULE_REGISTRY.lock().unwrap();
-        let response = self.client.get(MODULES_QUERY_URL)
+    pub fn query_modules(&mut self) -> anyhow::Result<ModulesReport> {
+        self.query_modules_including(false)
+    }
+
+    /// Like [`Course::query_modules()`], but also stores an [`UnknownModule`] for every tab without a registered module
+    pub fn query_modules_with_unknown(&mut self) -> anyhow::Result<ModulesReport> {
+        self.query_modules_including(true)
+    }
+
+    fn query_modules_including(&mut self, unknown: bool) -> anyhow::Result<ModulesReport> {
+        let client = self.client()?.clone();
+        let response = client.get(MODULES_QUERY_URL)
             .query(&[("auswahl", &self.id)])
-            .send()?;
-        let html = Html::parse_document(&response.text().unwrap());
-        let tabs_selector = Selector::parse("#tabs li").unwrap();
-        let module_data = Arc::new(CourseModuleData {
-            course_id: self.id.clone(),
-            client: self.client.clone(),
-        });
-        self.modules = html.select(&tabs_selector).filter_map(|tab_ref| {
+            .send_through(&client)?;
+        let html = parse_page(&response.text()?)?;
+        let tabs_selector = selector!("#tabs li");
+        let base_url = Url::parse(MODULES_QUERY_URL)?;
+        let mut report = ModulesReport::default();
+        self.modules = html.select(tabs_selector).filter_map(|tab_ref| {
             let tab = tab_ref.value();
-            let module_name = tab.id().unwrap().replace("nav_course_", "");
-            module_reg.get(module_name.as_str())
-                .map(|module_constructor| module_constructor(module_data.clone()))
+            let module_name = tab.id()?.replace("nav_course_", "");
+            // Installations with url rewriting link their tabs to different paths
+            let tab_url = tab_ref.select(selector!("a[href]")).next()
+                .and_then(|link| base_url.join(link.value().attr("href")?).ok());
+            let module_data = Arc::new(CourseModuleData {
+                course_id: self.id.clone(),
+                client: client.clone(),
+                page_cache: self.page_cache.clone(),
+                tab_url,
+            });
+            match client.module_registry().construct(&module_name, module_data.clone()) {
+                Some(Ok(module)) => Some(module),
+                Some(Err(error)) => {
+                    report.failures.push(ModuleFailure { name: module_name, error });
+                    None
+                },
+                None if unknown => {
+                    let title = tab_ref.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ");
+                    Some(Box::new(UnknownModule::from_tab(module_data, module_name, title)) as Box<dyn CourseModule>)
+                },
+                None => None,
+            }
         }).collect();
-        Ok(())
+        Ok(report)
+    }
+
+}
+
+/// The kind of content, that changed in a course
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    Files,
+    Forum,
+    News,
+    Wiki,
+    Unknown,
+}
+
+impl ChangeKind {
+
+    // Determines the kind from the shape of an icon, like "files+new" or "forum"
+    fn from_icon_shape(shape: &str) -> Self {
+        match shape.split('+').next().unwrap_or_default() {
+            "files" | "folder-full" | "folder-empty" | "file" => Self::Files,
+            "forum" | "forum2" => Self::Forum,
+            "news" => Self::News,
+            "wiki" => Self::Wiki,
+            _ => Self::Unknown,
+        }
+    }
+
+}
+
+/// A change in a course, as reported by [`Course::whats_new()`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChangeItem {
+    pub kind: ChangeKind,
+    pub title: String,
+    /// The url of the changed content or of the module, that contains it
+    pub url: String,
+    /// The number of new items (e.g. "3 neue Dateien"), if it is shown
+    pub count: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct NavigationIcon {
+    shape: String,
+    #[serde(default)]
+    role: String,
+}
+
+/// A module icon of a course on the my courses page
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct NavigationItem {
+    url: String,
+    #[serde(default)]
+    icon: Option<NavigationIcon>,
+    /// Set for modules with new contents
+    #[serde(default)]
+    important: bool,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    label: String,
+}
+
+impl NavigationItem {
+
+    fn is_new(&self) -> bool {
+        self.important || self.icon.as_ref().is_some_and(|icon| icon.role == "attention" || icon.shape.ends_with("+new"))
+    }
+
+    fn to_change_item(&self, client: &StudIpClient) -> anyhow::Result<ChangeItem> {
+        let title = if self.title.trim().is_empty() { &self.label } else { &self.title };
+        Ok(ChangeItem {
+            kind: self.icon.as_ref().map_or(ChangeKind::Unknown, |icon| ChangeKind::from_icon_shape(&icon.shape)),
+            title: title.trim().to_string(),
+            url: client.absolutize(&self.url)?.to_string(),
+            count: first_count(title).map(|count| count as usize),
+        })
+    }
+
+}
+
+// Modules without an icon are `false`
+fn deserialize_navigation<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<NavigationItem>>, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Array(items) => Some(items.into_iter()
+            .filter_map(|item| serde_json::from_value(item).ok())
+            .collect()),
+        // Some versions key the icons by the name of the module
+        serde_json::Value::Object(items) => Some(items.into_iter()
+            .filter_map(|(_, item)| serde_json::from_value(item).ok())
+            .collect()),
+        _ => None,
+    })
+}
+
+// Every change is a row (or list item) with the icon of its kind and a link to it
+fn parse_whats_new(html: &Html, client: &StudIpClient) -> anyhow::Result<Vec<ChangeItem>> {
+    let text_of = |elem: ElementRef| elem.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut changes = vec![];
+    for row in html.select(selector!("#content tr, #content li")) {
+        let (Some(link), Some(icon)) = (row.select(selector!("a[href]")).next(), row.select(selector!("[class*=\"icon-shape-\"]")).next()) else {
+            continue;
+        };
+        let shape = icon.value().classes()
+            .find_map(|class| class.strip_prefix("icon-shape-"))
+            .unwrap_or_default();
+        let title = text_of(link);
+        // The count is either a badge or a number next to the link
+        let count = match row.select(selector!(".badge, .count")).next() {
+            Some(badge) => first_count(&text_of(badge)),
+            None => first_count(&text_of(row).replacen(&title, "", 1)),
+        };
+        changes.push(ChangeItem {
+            kind: ChangeKind::from_icon_shape(shape),
+            title,
+            url: client.absolutize(link.value().attr("href").unwrap())?.to_string(),
+            count: count.map(|count| count as usize),
+        });
+    }
+    Ok(changes)
+}
+
+// Reads the MyCoursesData json, which is embedded in the my courses page
+fn query_my_courses_data(client: &StudIpClient) -> anyhow::Result<Parsed<MyCourses>> {
+    let response = client.get(MY_COURSES_URL).send_through(client)?;
+    parse_my_courses_data(&response.text()?)
+}
+
+fn parse_my_courses_data(body: &str) -> anyhow::Result<Parsed<MyCourses>> {
+    let html = Html::parse_document(body);
+    // I LOVE JAVASCRIPT! HAHAHHAH
+    let script_tag_selector = selector!("script[type=\"text/javascript\"]");
+    let json_string = html.select(script_tag_selector).find_map(|element| {
+        let inner = element.inner_html();
+        if !inner.contains("window.STUDIP.MyCoursesData") {
+            return None;
+        }
+        let (_, json_str) = inner.split_once('=').unwrap();
+        let json_string = json_str.replace('\n', "");
+        Some(json_string)
+    }).context("Expected MyCoursesData to be present in html")?;
+    parse_my_courses_store_with_warnings(json_string.trim().trim_end_matches(';'))
+}
+
+/// Parses the JSON of the courses store of the "Meine Veranstaltungen" page (`window.STUDIP.MyCoursesData`), without requesting anything \
+/// The returned [`MyCourses`] has no client, so it has to be attached (see [`MyCourses::attach_client()`]), before its courses can be queried.
+pub fn parse_my_courses_store(json: &str) -> anyhow::Result<MyCourses> {
+    let parsed = parse_my_courses_store_with_warnings(json)?;
+    parsed.log_summary();
+    Ok(parsed.value)
+}
+
+/// Like [`parse_my_courses_store()`], but also returns what did not match the expected schema \
+/// If the store can not be deserialized as a whole (e.g. because a newer Stud.IP version changed a field),
+/// the courses and set groups are read one by one: Fields with an unexpected type are defaulted and
+/// courses without an id or name are skipped, each with a warning.
+/// Fails, if the JSON is invalid or not a single course could be read.
+pub fn parse_my_courses_store_with_warnings(json: &str) -> anyhow::Result<Parsed<MyCourses>> {
+    let schema_error = match serde_json::from_str::<MyCourses>(json) {
+        Ok(my_courses) => return Ok(ParseWarnings::default().into_parsed(my_courses)),
+        Err(error) => error,
+    };
+    let store: serde_json::Value = serde_json::from_str(json).context("Could not parse MyCoursesData")?;
+    let mut warnings = ParseWarnings::default();
+    warnings.push("my courses store", format!("Does not match the expected schema, reading it field by field: {}", schema_error));
+    let my_courses = parse_lenient_store(&store, &mut warnings);
+    if my_courses.courses.is_empty() {
+        return Err(anyhow::Error::new(schema_error).context("Could not parse MyCoursesData"));
+    }
+    Ok(warnings.into_parsed(my_courses))
+}
+
+// The courses are keyed by their id in 4.x, but may also be a plain list
+fn parse_lenient_store(store: &serde_json::Value, warnings: &mut ParseWarnings) -> MyCourses {
+    let course_values: Vec<&serde_json::Value> = match &store["courses"] {
+        serde_json::Value::Object(courses) => courses.values().collect(),
+        serde_json::Value::Array(courses) => courses.iter().collect(),
+        _ => vec![],
+    };
+    let mut courses = HashMap::new();
+    for (i, value) in course_values.into_iter().enumerate() {
+        let context = format!("my courses store course {}", i + 1);
+        let course = parse_lenient_course(value, warnings);
+        if let Some(course) = warnings.skip_err(context, course) {
+            courses.insert(course.id.clone(), course);
+        }
+    }
+    let set_groups = match &store["groups"] {
+        serde_json::Value::Array(groups) => groups.iter().enumerate()
+            .filter_map(|(i, group)| warnings.skip_err(format!("my courses store group {}", i + 1), parse_lenient_set_group(group)))
+            .collect(),
+        _ => {
+            warnings.push("my courses store", "Expected a list of groups, the semesters of the courses are unknown");
+            vec![]
+        }
+    };
+    MyCourses {
+        courses,
+        set_groups,
+        user_id: lenient_id(&store["user_id"]).unwrap_or_default(),
+        config: store["config"].as_object()
+            .map(|config| config.clone().into_iter().collect())
+            .unwrap_or_default(),
+        client: None,
+        fetched_at: None,
+    }
+}
+
+fn parse_lenient_course(value: &serde_json::Value, warnings: &mut ParseWarnings) -> anyhow::Result<Course> {
+    if let Ok(course) = Course::deserialize(value) {
+        return Ok(course);
+    }
+    let id = lenient_id(&value["id"]).context("Expected id of course")?;
+    let name = value["name"].as_str().context("Expected name of course")?.to_string();
+    let context = format!("my courses store course {}", id);
+    let number = lenient_field(value, "number", &context, warnings).unwrap_or_default();
+    // Some versions send the group as a string
+    let group = match &value["group"] {
+        serde_json::Value::String(group) => group.parse().ok(),
+        _ => None,
+    }.or_else(|| lenient_field(value, "group", &context, warnings)).unwrap_or_default();
+    let is_teacher = lenient_bool(value, "is_teacher", &context, warnings);
+    let is_studygroup = lenient_bool(value, "is_studygroup", &context, warnings);
+    Ok(Course {
+        id: id.into(),
+        name,
+        _number: number,
+        group,
+        is_teacher,
+        is_studygroup,
+        navigation: deserialize_navigation(value["navigation"].clone()).unwrap_or_default(),
+        modules: vec![],
+        client: None,
+        page_cache: Default::default(),
+    })
+}
+
+fn parse_lenient_set_group(value: &serde_json::Value) -> anyhow::Result<SetGroup> {
+    if let Ok(set_group) = SetGroup::deserialize(value) {
+        return Ok(set_group);
+    }
+    let name = value["name"].as_str().context("Expected name of group")?.to_string();
+    let data = value["data"].as_array().context("Expected courses of group")?.iter()
+        .map(|entry| SetGroupEntry {
+            id: lenient_id(&entry["id"]).unwrap_or_default(),
+            label: entry["label"].as_str().map(|label| label.to_string()),
+            ids: entry["ids"].as_array()
+                .map(|ids| ids.iter().filter_map(lenient_id).collect())
+                .unwrap_or_default(),
+        })
+        .collect();
+    Ok(SetGroup { id: lenient_id(&value["id"]).unwrap_or_default(), name, data })
+}
+
+// Ids are sometimes sent as numbers
+fn lenient_id(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(id) => Some(id.clone()),
+        serde_json::Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+// Missing fields are defaulted silently, fields with an unexpected type with a warning
+fn lenient_field<T: serde::de::DeserializeOwned>(value: &serde_json::Value, name: &str, context: &str, warnings: &mut ParseWarnings) -> Option<T> {
+    match value.get(name) {
+        None | Some(serde_json::Value::Null) => None,
+        Some(field) => serde_json::from_value(field.clone())
+            .map_err(|error| warnings.push(context, format!("Defaulted {}: {}", name, error)))
+            .ok(),
+    }
+}
+
+// Flags are sometimes sent as 0 and 1
+fn lenient_bool(value: &serde_json::Value, name: &str, context: &str, warnings: &mut ParseWarnings) -> bool {
+    match &value[name] {
+        serde_json::Value::Number(flag) => flag.as_u64().is_some_and(|flag| flag != 0),
+        _ => lenient_field(value, name, context, warnings).unwrap_or_default(),
+    }
+}
+
+/// A single date of a course, as listed on its schedule
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CourseDate {
+    pub id: String,
+    pub start: Option<DateTime<Utc>>,
+    /// The title or type of the date (e.g. "Vorlesung")
+    pub title: String,
+    /// If the date was cancelled ("fällt aus")
+    pub cancelled: bool,
+    #[serde(default)]
+    pub room: Option<RoomRef>,
+}
+
+impl PartialEq for CourseDate {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+// The room is the linked resource, or otherwise the text of the room column (after the date, type and topic)
+fn parse_date_room(cell_elements: &[ElementRef]) -> Option<RoomRef> {
+    cell_elements.iter()
+        .filter_map(|cell| RoomRef::from_element(*cell))
+        .find(|room| room.id.is_some())
+        .or_else(|| cell_elements.iter()
+            .find(|cell| cell.value().classes().any(|class| class.contains("room")))
+            .or(cell_elements.get(3))
+            .and_then(|cell| RoomRef::from_element(*cell)))
+}
+
+// Cancelled dates are marked by a class or a localized note
+fn parse_course_dates(html: &Html, timezone: Tz) -> anyhow::Result<Vec<CourseDate>> {
+    let row_selector = selector!("tr[id^=\"date_\"]");
+    let cell_selector = selector!("td");
+    let mut dates = vec![];
+    for row in html.select(row_selector) {
+        let id = row.attr("id").unwrap().trim_start_matches("date_").to_string();
+        let cell_elements = row.select(cell_selector).collect::<Vec<_>>();
+        let cells = cell_elements.iter()
+            .map(|cell| cell.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>();
+        let start = cells.iter()
+            .find_map(|cell| parse_localized_date_time(cell))
+            .and_then(|date_time| local_to_utc(date_time, timezone));
+        let row_text = cells.join(" ").to_lowercase();
+        let cancelled = row.value().classes().any(|class| class.contains("cancel") || class.contains("ausfall") || class == "ex-date")
+            || row_text.contains("fällt aus")
+            || row_text.contains("cancelled")
+            || row_text.contains("canceled");
+        dates.push(CourseDate {
+            id,
+            start,
+            title: cells.get(1).cloned().unwrap_or_default(),
+            cancelled,
+            room: parse_date_room(&cell_elements),
+        });
+    }
+    Ok(dates)
+}
+
+/// The details of a course, as shown on its details page
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CourseDetails {
+    /// All label value pairs of the page (e.g. "Veranstaltungsnummer"), with the trailing colon removed from the labels
+    pub fields: BTreeMap<String, String>,
+    /// The current number of participants, 0 if the page does not show it
+    pub participants: u64,
+    pub max_participants: Option<u64>,
+    /// The number of people on the waiting list
+    pub waitlist: Option<u64>,
+    /// The number of guests, who can only read the course
+    pub guests: Option<u64>,
+    /// The default meeting place ("Ort")
+    #[serde(default)]
+    pub location: Option<RoomRef>,
+    /// How the places are given, as described by the admission rules ("Anmelderegeln") \
+    /// Only known to [`Course::query_details()`], as the fields do not contain it.
+    #[serde(default)]
+    pub admission: AdmissionProcedure,
+}
+
+/// The labels of the default meeting place on the details page
+const LOCATION_LABELS: &[&str] = &["ort", "raum", "veranstaltungsort", "location", "room"];
+
+// The value cell of the location row, which may link to the resource of the room
+fn parse_location(html: &Html) -> Option<RoomRef> {
+    html.select(selector!("#content tr"))
+        .find_map(|row| {
+            let mut cells = row.select(selector!("th, td"));
+            let label = cells.next()?.text().collect::<String>();
+            LOCATION_LABELS.contains(&label.trim().trim_end_matches(':').to_lowercase().as_str())
+                .then(|| cells.next())
+                .flatten()
+        })
+        .and_then(RoomRef::from_element)
+}
+
+static COUNT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d{1,3}(?:[.,\u{a0}]\d{3})+\b|\d+").unwrap());
+// Commas only separate parts, if they are followed by a space, as they are also used as thousands separators
+static PART_SEPARATOR_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"[();]|,\s").unwrap());
+static MAX_COUNT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)(?:max\w*\.?|höchstens|von|of|/)\s*(?P<count>\d{1,3}(?:[.,\u{a0}]\d{3})+\b|\d+)").unwrap());
+
+// The first number in the text, ignoring thousands separators
+fn first_count(text: &str) -> Option<u64> {
+    COUNT_REGEX.find(text)?.as_str()
+        .chars()
+        .filter(char::is_ascii_digit)
+        .collect::<String>()
+        .parse()
+        .ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CountKind {
+    Participants,
+    MaxParticipants,
+    Waitlist,
+    Guests,
+}
+
+impl CountKind {
+
+    // Determines the kind of count from a (lowercase) label or a part of a value
+    fn detect(text: &str) -> Option<Self> {
+        let contains_any = |words: &[&str]| words.iter().any(|word| text.contains(word));
+        if contains_any(&["warteliste", "waiting list", "waitlist"]) {
+            Some(Self::Waitlist)
+        } else if contains_any(&["gast", "gäst", "guest", "lesend"]) {
+            Some(Self::Guests)
+        } else if contains_any(&["teilnehm", "participant"]) && contains_any(&["max", "höchst"]) {
+            Some(Self::MaxParticipants)
+        } else if contains_any(&["teilnehm", "participant"]) {
+            Some(Self::Participants)
+        } else {
+            None
+        }
+    }
+
+}
+
+impl CourseDetails {
+
+    /// Creates the details from the label value pairs of the details page, extracting the participant counts from whatever phrasing surrounds them \
+    /// Counts, that can not be extracted, are left empty. The location is only known by its name, see [`Course::query_details()`] for its id.
+    pub fn from_fields(fields: BTreeMap<String, String>) -> Self {
+        let mut details = Self::default();
+        for (label, value) in &fields {
+            match CountKind::detect(&label.to_lowercase()) {
+                Some(CountKind::Participants) => details.parse_participants(value),
+                Some(CountKind::MaxParticipants) => details.max_participants = details.max_participants.or(first_count(value)),
+                Some(CountKind::Waitlist) => details.waitlist = details.waitlist.or(first_count(value)),
+                Some(CountKind::Guests) => details.guests = details.guests.or(first_count(value)),
+                None if LOCATION_LABELS.contains(&label.to_lowercase().as_str()) => details.location = RoomRef::from_text(value),
+                None => log_parse!(debug, "Kept course detail {:?} only as a field", label),
+            }
+        }
+        details.fields = fields;
+        details
+    }
+
+    /// The participant counts and the location with their names in the `language` (see [`labels::display_name()`]), in the order, in which they should be shown \
+    /// Counts, that are unknown, are left out.
+    pub fn display(&self, language: Language) -> Vec<(&'static str, String)> {
+        let counts = [
+            (labels::NUMBER_OF_PARTICIPANTS, Some(self.participants)),
+            (labels::MAX_PARTICIPANTS, self.max_participants),
+            (labels::WAITLIST, self.waitlist),
+            (labels::GUESTS, self.guests),
+        ];
+        counts.into_iter()
+            .filter_map(|(key, count)| Some((key, count?.to_string())))
+            .chain(self.location.as_ref().map(|location| (labels::LOCATION, location.name.clone())))
+            .map(|(key, value)| (labels::display_name(key, language), value))
+            .collect()
+    }
+
+    // Parses a combined value like "120 (max. 150)" or "120 of 150 (7 on the waiting list, 3 guests)"
+    fn parse_participants(&mut self, value: &str) {
+        self.participants = first_count(value).unwrap_or_default();
+        for part in PART_SEPARATOR_REGEX.split(value) {
+            match CountKind::detect(&part.to_lowercase()) {
+                Some(CountKind::Waitlist) => self.waitlist = self.waitlist.or(first_count(part)),
+                Some(CountKind::Guests) => self.guests = self.guests.or(first_count(part)),
+                _ => if let Some(captures) = MAX_COUNT_REGEX.captures(part) {
+                    self.max_participants = self.max_participants.or(first_count(&captures["count"]));
+                },
+            }
+        }
+    }
+
+}
+
+/// A group of courses, as displayed on the my courses page (usually a semester)
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SetGroup {
+    pub id: String,
+    /// The name of the group, for example the semester name "WiSe 2024/25"
+    pub name: String,
+    pub data: Vec<SetGroupEntry>,
+}
+
+/// The actual course ids of a [`SetGroup`], optionally with a label
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SetGroupEntry {
+    pub id: String,
+    #[serde(deserialize_with = "deserialize_label")]
+    pub label: Option<String>,
+    pub ids: Vec<String>,
+}
+
+impl SetGroup {
+
+    /// Iterates over the ids of all courses in this group
+    pub fn course_ids(&self) -> impl Iterator<Item = &str> {
+        self.data.iter()
+            .flat_map(|entry| entry.ids.iter())
+            .map(|id| id.as_str())
+    }
+
+}
+
+// Labels are `false` if there is no label
+fn deserialize_label<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(label) => Some(label),
+        _ => None,
+    })
+}
+
+// The semester, that a course or set group name refers to, like "SoSe 2024", "WiSe 2024/25", "WS 23/24" or "Sommersemester 2025"
+static SEMESTER_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(
+    r"(?i)\b(?P<season>sommersemester|wintersemester|sommer|winter|sose|wise|ss|ws)\s*-?\s*(?P<year>\d{4}|\d{2})(?:\s*/\s*(?:\d{4}|\d{2}))?\b"
+).unwrap());
+static YEAR_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b(?:19|20)\d{2}(?:\s*/\s*\d{2,4})?\b").unwrap());
+// A leading course number, like "4.01.123 ", "INF-101: " or "B.Inf.1101 - "
+static COURSE_NUMBER_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*[\p{L}.\-]*\d[\d.\-/]*[a-z]?(?:\s*[:\-–]\s*|\s+)").unwrap());
+
+/// Returns the words of a course name without its semester and leading course number, keeping their case \
+/// The semester of courses is often part of their name, so that the same course in different semesters only differs by it.
+pub(crate) fn course_name_words(name: &str) -> String {
+    let name = SEMESTER_REGEX.replace_all(name, " ");
+    let name = YEAR_REGEX.replace_all(&name, " ");
+    let name = COURSE_NUMBER_REGEX.replace(&name, "");
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Normalizes the name of a course, so that the same course of different semesters has the same name \
+/// Semesters (e.g. "(SoSe 2024)"), years, leading course numbers, punctuation, case and diacritics are removed.
+/// Numbers within the name are kept, as "Analysis I" and "Analysis II" are different courses.
+pub fn normalize_course_name(name: &str) -> String {
+    normalize_text(&course_name_words(name))
+}
+
+/// Returns a sortable key (year, 0 for the summer and 1 for the winter semester) of a semester name like "WiSe 2024/25"
+fn semester_order(name: &str) -> Option<(u32, u8)> {
+    let captures = SEMESTER_REGEX.captures(name)?;
+    let year = captures["year"].parse::<u32>().ok()?;
+    let year = if year < 100 { 2000 + year } else { year };
+    let season = if captures["season"].to_lowercase().starts_with('s') { 0 } else { 1 };
+    Some((year, season))
+}
+
+/// A query for [`MyCourses::search()`] \
+/// All specified criteria have to match.
+///
+/// ```
+/// use stud_ip_scraper::course::CourseQuery;
+///
+/// let query = CourseQuery::new()
+///     .name_contains("algorithmen")
+///     .semester("WiSe 2024/25")
+///     .is_teacher(false);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CourseQuery {
+    name_contains: Option<String>,
+    semester: Option<String>,
+    is_teacher: Option<bool>,
+    is_studygroup: Option<bool>,
+    group_index: Option<usize>,
+}
+
+impl CourseQuery {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only matches courses, which name contains the given text (ignoring case and diacritics)
+    pub fn name_contains(mut self, text: impl Into<String>) -> Self {
+        self.name_contains = Some(text.into());
+        self
+    }
+
+    /// Only matches courses in [`SetGroup`]s, which name contains the given semester name (e.g. "SoSe 2024")
+    pub fn semester(mut self, semester: impl Into<String>) -> Self {
+        self.semester = Some(semester.into());
+        self
+    }
+
+    /// Only matches courses, in which the user is (or is not) a teacher
+    pub fn is_teacher(mut self, is_teacher: bool) -> Self {
+        self.is_teacher = Some(is_teacher);
+        self
+    }
+
+    /// Only matches courses, which are (or are not) study groups
+    pub fn is_studygroup(mut self, is_studygroup: bool) -> Self {
+        self.is_studygroup = Some(is_studygroup);
+        self
+    }
+
+    /// Only matches courses in the given (color) group
+    pub fn group_index(mut self, group_index: usize) -> Self {
+        self.group_index = Some(group_index);
+        self
     }
 
 }
@@ -65,12 +865,42 @@ impl Course {
 /// Contains all the courses, and some addition data, of the current user
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MyCourses {
-    pub courses: HashMap<String, Course>,
-    pub groups: Vec<serde_json::Value>,
+    pub courses: HashMap<CourseId, Course>,
+    /// The groups of courses, which the list is sorted into (e.g. by semester), formerly the untyped `groups`
+    #[serde(alias = "groups")]
+    pub set_groups: Vec<SetGroup>,
     pub user_id: String,
     pub config: HashMap<String, serde_json::Value>,
     #[serde(skip)]
-    client: Arc<StudIpClient>
+    client: Option<Arc<StudIpClient>>,
+    #[serde(skip)]
+    fetched_at: Option<DateTime<Utc>>,
+}
+
+/// Metadata of a [`MyCourses`] snapshot
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotMeta {
+    /// When the courses were queried from the server
+    pub fetched_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    version: u32,
+    meta: SnapshotMeta,
+    my_courses: &'a MyCourses,
+}
+
+// Only the version is deserialized first, so that old snapshots can be detected, even if their structure changed
+#[derive(Deserialize)]
+struct SnapshotVersion {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct OwnedSnapshot {
+    meta: SnapshotMeta,
+    my_courses: MyCourses,
 }
 
 impl MyCourses {
@@ -78,41 +908,183 @@ impl MyCourses {
     pub(crate) fn from_client(client: Arc<StudIpClient>) -> Self {
         Self {
             courses: Default::default(),
-            groups: Default::default(),
+            set_groups: Default::default(),
             user_id: Default::default(),
             config: Default::default(),
-            client,
+            client: Some(client),
+            fetched_at: None,
+        }
+    }
+
+    /// Saves the courses, together with the time they were fetched, as a JSON snapshot to `path`
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let snapshot = Snapshot {
+            version: SNAPSHOT_VERSION,
+            meta: SnapshotMeta {
+                fetched_at: self.fetched_at.context("Courses have not been queried yet")?,
+            },
+            my_courses: self,
+        };
+        std::fs::write(path, serde_json::to_string(&snapshot)?)?;
+        Ok(())
+    }
+
+    /// Loads a snapshot saved by [`MyCourses::save_snapshot()`] and attaches the `client` to it. \
+    /// Fails, if the snapshot was saved by an incompatible version of this crate, in which case the courses should be queried again.
+    pub fn load_snapshot(path: impl AsRef<Path>, client: Arc<StudIpClient>) -> anyhow::Result<(Self, SnapshotMeta)> {
+        let json = std::fs::read_to_string(path).context("Could not read snapshot")?;
+        let SnapshotVersion { version } = serde_json::from_str(&json).context("Could not parse snapshot version")?;
+        if version != SNAPSHOT_VERSION {
+            bail!("Snapshot version {} is not supported (expected {})", version, SNAPSHOT_VERSION);
+        }
+        let OwnedSnapshot { meta, mut my_courses } = serde_json::from_str(&json).context("Could not parse snapshot")?;
+        my_courses.attach_client(client);
+        my_courses.fetched_at = Some(meta.fetched_at);
+        Ok((my_courses, meta))
+    }
+
+    /// Queries the courses again, if they were never fetched or were fetched more than `max_age` ago. \
+    /// Returns if the courses were refreshed.
+    pub fn refresh_if_older_than(&mut self, max_age: Duration) -> anyhow::Result<bool> {
+        let is_fresh = self.fetched_at.is_some_and(|fetched_at| {
+            (Utc::now() - fetched_at).to_std().is_ok_and(|age| age <= max_age)
+        });
+        if is_fresh {
+            return Ok(false);
+        }
+        self.query()?;
+        Ok(true)
+    }
+
+    /// Attaches a client to these courses and every contained [`Course`]. \
+    /// This is required after deserializing, as the client is not serialized.
+    pub fn attach_client(&mut self, client: Arc<StudIpClient>) {
+        for course in self.courses.values_mut() {
+            course.attach_client(client.clone());
         }
+        self.client = Some(client);
     }
 
     /// Queries the available courses of the current user. \
     /// *Note: This is not done automatically*
     pub fn query(&mut self) -> anyhow::Result<()> {
-        // Find MyCoursesData json in html
-        let r = self.client.get(MY_COURSES_URL).send().unwrap();
-        let html = Html::parse_document(&r.text().unwrap());
-        // I LOVE JAVASCRIPT! HAHAHHAH
-        let script_tag_selector = Selector::parse("script[type=\"text/javascript\"]").unwrap();
-        let json_string = html.select(&script_tag_selector).find_map(|element| {
-            let inner = element.inner_html();
-            if !inner.contains("window.STUDIP.MyCoursesData") {
-                return None;
-            }
-            let (_, json_str) = inner.split_once('=').unwrap();
-            let json_string = json_str.replace('\n', "");
-            Some(json_string)
-        }).context("Expected MyCoursesData to be present in html")?;
-        // Parse MyCoursersData
-        let json_str = json_string.trim()
-            .trim_end_matches(';');
-        let mut new_my_courses: Self = serde_json::from_str(json_str)
-            .context("Could not parse MyCoursesData")?;
+        let warnings = self.query_with_warnings()?;
+        Parsed { value: (), warnings }.log_summary();
+        Ok(())
+    }
+
+    /// Like [`MyCourses::query()`], but returns what did not match the expected schema of the courses store \
+    /// (see [`parse_my_courses_store_with_warnings()`])
+    pub fn query_with_warnings(&mut self) -> anyhow::Result<Vec<ParseWarning>> {
+        let client = self.client.clone().context("No client attached to courses. Call MyCourses::attach_client() after deserializing")?;
+        let Parsed { value: mut new_my_courses, warnings } = query_my_courses_data(&client)?;
         // Copy api handle to courses
-        for course in new_my_courses.courses.values_mut() {
-            course.client = self.client.clone();
-        }
+        new_my_courses.attach_client(client);
+        new_my_courses.fetched_at = Some(Utc::now());
         *self = new_my_courses;
-        Ok(())
+        Ok(warnings)
+    }
+
+    /// Calls `f` for every course, on up to `concurrency` threads at once and returns the results by course id (ordered by it) \
+    /// All threads share the same client, so the rate limit applies to all of them together. \
+    /// A failing course does not stop the other ones.
+    pub fn for_each_course_parallel<T: Send>(&mut self, concurrency: usize, f: impl Fn(&mut Course) -> anyhow::Result<T> + Sync) -> Vec<(CourseId, anyhow::Result<T>)> {
+        let n_courses = self.courses.len();
+        let courses = Mutex::new(self.courses.values_mut().collect::<Vec<_>>());
+        let results = Mutex::new(Vec::with_capacity(n_courses));
+        let n_threads = concurrency.clamp(1, n_courses.max(1));
+        std::thread::scope(|scope| {
+            for _ in 0..n_threads {
+                scope.spawn(|| loop {
+                    let Some(course) = courses.lock().unwrap().pop() else {break};
+                    let result = f(course);
+                    results.lock().unwrap().push((course.id.clone(), result));
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap();
+        results.sort_by(|(a, _), (b, _)| a.cmp(b));
+        results
+    }
+
+    /// Finds all courses matching the [`CourseQuery`], ordered by name
+    pub fn search(&self, query: &CourseQuery) -> Vec<&Course> {
+        let name_contains = query.name_contains.as_deref().map(normalize_text);
+        let semester_course_ids = query.semester.as_deref().map(|semester| {
+            let semester = normalize_text(semester);
+            self.set_groups.iter()
+                .filter(|set_group| normalize_text(&set_group.name).contains(&semester))
+                .flat_map(|set_group| set_group.course_ids())
+                .collect::<HashSet<_>>()
+        });
+        let mut courses = self.courses.values()
+            .filter(|course| name_contains.as_ref().is_none_or(|text| normalize_text(&course.name).contains(text)))
+            .filter(|course| semester_course_ids.as_ref().is_none_or(|ids| ids.contains(course.id.as_str())))
+            .filter(|course| query.is_teacher.is_none_or(|is_teacher| course.is_teacher == is_teacher))
+            .filter(|course| query.is_studygroup.is_none_or(|is_studygroup| course.is_studygroup == is_studygroup))
+            .filter(|course| query.group_index.is_none_or(|group| course.group == group))
+            .collect::<Vec<_>>();
+        courses.sort_by(|a, b| normalize_text(&a.name).cmp(&normalize_text(&b.name)).then_with(|| a.id.cmp(&b.id)));
+        courses
+    }
+
+    /// Finds the same course in earlier semesters, newest first \
+    /// Courses match, if their names are equal after [`normalize_course_name()`]. The semesters are taken from the names of the [`SetGroup`]s;
+    /// if they can not be told apart, the matching courses of all other set groups are returned.
+    pub fn find_predecessors(&self, course: &Course) -> Vec<&Course> {
+        let name = normalize_course_name(&course.name);
+        let semester_of = |id: &str| self.set_groups.iter()
+            .position(|set_group| set_group.course_ids().any(|course_id| course_id == id))
+            .map(|position| (position, semester_order(&self.set_groups[position].name)));
+        let own = semester_of(course.id.as_str());
+        let mut predecessors = self.courses.values()
+            .filter(|other| other.id != course.id && normalize_course_name(&other.name) == name)
+            .filter_map(|other| {
+                let (position, order) = semester_of(other.id.as_str())?;
+                let is_earlier = match own {
+                    Some((own_position, _)) if own_position == position => false,
+                    Some((_, Some(own_order))) => order.is_none_or(|order| order < own_order),
+                    _ => true,
+                };
+                is_earlier.then_some((order, position, other))
+            })
+            .collect::<Vec<_>>();
+        // Unknown semesters come last, in the order of the set groups
+        predecessors.sort_by(|(a_order, a_position, _), (b_order, b_position, _)| b_order.cmp(a_order).then(a_position.cmp(b_position)));
+        predecessors.into_iter().map(|(_, _, course)| course).collect()
+    }
+
+    /// Returns mutable references to the courses with the given ids, in the order of the ids \
+    /// Unknown ids are skipped and duplicate ids only yield their course once.
+    pub fn courses_mut<'a>(&mut self, ids: impl IntoIterator<Item = &'a str>) -> Vec<&mut Course> {
+        let mut positions = HashMap::new();
+        for id in ids {
+            let n_positions = positions.len();
+            positions.entry(id).or_insert(n_positions);
+        }
+        let mut courses = self.courses.values_mut()
+            .filter_map(|course| positions.get(course.id.as_str()).map(|position| (*position, course)))
+            .collect::<Vec<_>>();
+        courses.sort_by_key(|(position, _)| *position);
+        courses.into_iter().map(|(_, course)| course).collect()
+    }
+
+    /// Returns the [`MyCourses::set_groups`] as untyped JSON, like the former `groups` field
+    #[deprecated(note = "Use the typed MyCourses::set_groups instead")]
+    pub fn groups(&self) -> Vec<serde_json::Value> {
+        self.set_groups.iter()
+            .map(|set_group| serde_json::to_value(set_group).expect("Set groups are serializable"))
+            .collect()
+    }
+
+    /// Returns mutable references to the courses of the [`SetGroup`] with the given name (e.g. "WiSe 2024/25") \
+    /// Returns an empty list, if there is no such group.
+    pub fn get_courses_by_set_group_name(&mut self, name: &str) -> Vec<&mut Course> {
+        let ids = self.set_groups.iter()
+            .find(|set_group| set_group.name == name)
+            .map(|set_group| set_group.course_ids().map(|id| id.to_string()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        self.courses_mut(ids.iter().map(|id| id.as_str()))
     }
 
     /// Finds a course, give its name. Returns an immutable reference to it
@@ -129,4 +1101,557 @@ impl MyCourses {
             .map(|(_, course)| course)
     }
 
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::course_modules::MembersModule;
+    use crate::course_modules::members::Group;
+    use crate::error::StudIpError;
+    use crate::get_module;
+    use crate::mock::MockServer;
+    use crate::DEFAULT_TIMEZONE;
+    use chrono::TimeZone;
+
+    const MY_COURSES_JSON: &str = r#"{
+        "courses": {
+            "c1": {"id": "c1", "name": "Algorithmen und Datenstrukturen", "number": "101", "group": 0, "is_teacher": false, "is_studygroup": false},
+            "c2": {"id": "c2", "name": "Lineare Algebra", "number": "102", "group": 1, "is_teacher": false, "is_studygroup": false},
+            "c3": {"id": "c3", "name": "ALGORITHMEN und Datenstrukturen", "number": "101", "group": 0, "is_teacher": true, "is_studygroup": false},
+            "c4": {"id": "c4", "name": "Lerngruppe Älgorithmen", "number": "", "group": 2, "is_teacher": false, "is_studygroup": true}
+        },
+        "groups": [
+            {"id": "s1", "name": "WiSe 2023/24", "data": [{"id": "g1", "label": false, "ids": ["c1", "c2"]}]},
+            {"id": "s2", "name": "WiSe 2024/25", "data": [{"id": "g2", "label": "Tutorials", "ids": ["c3", "c4"]}]}
+        ],
+        "user_id": "u1",
+        "config": {}
+    }"#;
+
+    fn ids(courses: Vec<&Course>) -> Vec<&str> {
+        courses.into_iter().map(|course| course.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_search() {
+        let my_courses: MyCourses = serde_json::from_str(MY_COURSES_JSON).unwrap();
+        assert_eq!(my_courses.set_groups[1].data[0].label.as_deref(), Some("Tutorials"));
+        assert_eq!(my_courses.set_groups[0].data[0].label, None);
+        #[allow(deprecated)]
+        let groups = my_courses.groups();
+        assert_eq!(groups[1]["data"][0]["label"], "Tutorials");
+
+        assert_eq!(ids(my_courses.search(&CourseQuery::new())), vec!["c1", "c3", "c4", "c2"]);
+        assert_eq!(ids(my_courses.search(&CourseQuery::new().name_contains("algorithmen"))), vec!["c1", "c3", "c4"]);
+        assert_eq!(ids(my_courses.search(&CourseQuery::new().name_contains("algorithmen").semester("wise 2024"))), vec!["c3", "c4"]);
+        assert_eq!(ids(my_courses.search(&CourseQuery::new().semester("2023/24"))), vec!["c1", "c2"]);
+        assert_eq!(ids(my_courses.search(&CourseQuery::new().is_teacher(true))), vec!["c3"]);
+        assert_eq!(ids(my_courses.search(&CourseQuery::new().is_studygroup(true))), vec!["c4"]);
+        assert_eq!(ids(my_courses.search(&CourseQuery::new().group_index(1))), vec!["c2"]);
+        assert!(my_courses.search(&CourseQuery::new().semester("SoSe 2030")).is_empty());
+    }
+
+    #[test]
+    fn test_normalize_course_name() {
+        let same = [
+            ("Algorithmen und Datenstrukturen (SoSe 2024)", "Algorithmen und Datenstrukturen (SoSe 2025)"),
+            ("Grundlagen der Programmierung WiSe 2023/24", "Grundlagen der Programmierung - WiSe 2024/25"),
+            ("B.Inf.1101: Grundlagen der Informatik", "B.Inf.1101: Grundlagen der Informatik (WS 24/25)"),
+            ("4.01.123 Lineare Algebra I", "4.01.124 Lineare Algebra I"),
+            ("Übung zu Analysis II - SS 2024", "Übung zu Analysis II (Sommersemester 2025)"),
+            ("Einführung in die Theoretische Informatik, Wintersemester 2022/2023", "Einführung in die theoretische Informatik"),
+            ("INF-101: Rechnernetze [SS24]", "Rechnernetze 2025"),
+            ("Seminar: Maschinelles Lernen WiSe24/25", "Seminar Maschinelles Lernen (WiSe 2025/26)"),
+        ];
+        for (a, b) in same {
+            assert_eq!(normalize_course_name(a), normalize_course_name(b), "{:?} and {:?}", a, b);
+        }
+        let different = [
+            ("Analysis I (SoSe 2024)", "Analysis II (SoSe 2025)"),
+            ("Lineare Algebra", "Lineare Algebra II"),
+            ("Mathematik für Informatiker 1", "Mathematik für Informatiker 2"),
+            ("3D-Modellierung", "Modellierung"),
+        ];
+        for (a, b) in different {
+            assert_ne!(normalize_course_name(a), normalize_course_name(b), "{:?} and {:?}", a, b);
+        }
+        assert_eq!(normalize_course_name("Algorithmen und Datenstrukturen (SoSe 2024)"), "algorithmen und datenstrukturen");
+        assert_eq!(course_name_words("4.01.123 Übung zu Analysis II - SS 2024"), "Übung zu Analysis II");
+
+        assert_eq!(semester_order("WiSe 2024/25"), Some((2024, 1)));
+        assert_eq!(semester_order("SoSe 2025"), Some((2025, 0)));
+        assert_eq!(semester_order("WS 23/24"), Some((2023, 1)));
+        assert_eq!(semester_order("Studiengruppen"), None);
+    }
+
+    #[test]
+    fn test_find_predecessors() {
+        let my_courses: MyCourses = serde_json::from_str(r#"{
+            "courses": {
+                "c1": {"id": "c1", "name": "Algorithmen und Datenstrukturen (SoSe 2025)", "number": "", "group": 0},
+                "c2": {"id": "c2", "name": "Algorithmen und Datenstrukturen (SoSe 2024)", "number": "", "group": 0},
+                "c3": {"id": "c3", "name": "Algorithmen und Datenstrukturen", "number": "", "group": 0},
+                "c4": {"id": "c4", "name": "Übung: Algorithmen und Datenstrukturen", "number": "", "group": 0},
+                "c5": {"id": "c5", "name": "algorithmen und datenstrukturen", "number": "", "group": 0},
+                "c6": {"id": "c6", "name": "Algorithmen und Datenstrukturen (SoSe 2026)", "number": "", "group": 0}
+            },
+            "groups": [
+                {"id": "s4", "name": "SoSe 2026", "data": [{"id": "g4", "label": false, "ids": ["c6"]}]},
+                {"id": "s3", "name": "SoSe 2025", "data": [{"id": "g3", "label": false, "ids": ["c1", "c4"]}]},
+                {"id": "s1", "name": "SoSe 2023", "data": [{"id": "g1", "label": false, "ids": ["c3"]}]},
+                {"id": "s2", "name": "SoSe 2024", "data": [{"id": "g2", "label": false, "ids": ["c2"]}]},
+                {"id": "s0", "name": "Archiv", "data": [{"id": "g0", "label": false, "ids": ["c5"]}]}
+            ],
+            "user_id": "u1",
+            "config": {}
+        }"#).unwrap();
+
+        assert_eq!(ids(my_courses.find_predecessors(&my_courses.courses["c1"])), vec!["c2", "c3", "c5"]);
+        assert_eq!(ids(my_courses.find_predecessors(&my_courses.courses["c3"])), vec!["c5"]);
+        assert_eq!(ids(my_courses.find_predecessors(&my_courses.courses["c5"])), vec!["c6", "c1", "c2", "c3"]);
+        assert!(my_courses.find_predecessors(&my_courses.courses["c4"]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_my_courses_page() {
+        let page = format!("<script type=\"text/javascript\">\n  window.STUDIP.MyCoursesData = {};\n</script>", MY_COURSES_JSON);
+        let my_courses = parse_my_courses_data(&page).unwrap();
+        assert!(my_courses.warnings.is_empty());
+        let my_courses = my_courses.value;
+        assert_eq!(my_courses.courses.len(), 4);
+        assert_eq!(my_courses.set_groups, parse_my_courses_store(MY_COURSES_JSON).unwrap().set_groups);
+        assert!(parse_my_courses_data("<script type=\"text/javascript\">window.STUDIP.Other = {};</script>").is_err());
+        assert!(parse_my_courses_store("{\"courses\": []}").is_err());
+    }
+
+    #[test]
+    fn test_parse_my_courses_store_versions() {
+        let store_4_6 = parse_my_courses_store_with_warnings(include_str!("../testdata/course/store_4_6.json")).unwrap();
+        assert!(store_4_6.warnings.is_empty(), "{:?}", store_4_6.warnings);
+        let store_4_6 = store_4_6.value;
+        assert_eq!(store_4_6.courses.len(), 3);
+
+        // Fields with changed types are defaulted (or converted), courses without a name are skipped
+        let store_5_x = parse_my_courses_store_with_warnings(include_str!("../testdata/course/store_5_x.json")).unwrap();
+        let contexts = store_5_x.warnings.iter().map(|warning| warning.context.as_str()).collect::<Vec<_>>();
+        assert_eq!(contexts.len(), 3, "{:?}", store_5_x.warnings);
+        assert!(contexts.contains(&"my courses store"));
+        assert!(contexts.contains(&"my courses store course 00000000000000000000000000000003"));
+        assert!(store_5_x.warnings.iter().any(|warning| warning.detail == "Expected name of course"));
+        let store_5_x = store_5_x.value;
+        assert_eq!(store_5_x.courses.len(), 3);
+        assert_eq!(store_5_x.set_groups, store_4_6.set_groups);
+        assert_eq!(store_5_x.user_id, store_4_6.user_id);
+        assert_eq!(store_5_x.config["group_by"], "sem_number");
+        for (id, course) in &store_4_6.courses {
+            let other = &store_5_x.courses[id];
+            assert_eq!((&other.name, other.group, other.is_teacher, other.is_studygroup), (&course.name, course.group, course.is_teacher, course.is_studygroup));
+            let new_items = |course: &Course| course.navigation.as_ref().map(|items| items.iter().filter(|item| item.is_new()).count());
+            assert_eq!(new_items(other), new_items(course), "{}", id);
+        }
+        assert_eq!(store_5_x.courses["a1b2c3d4e5f60718293a4b5c6d7e8f90"]._number, "4.01.123");
+
+        // Without a single readable course, the store is still rejected
+        assert!(parse_my_courses_store("{\"courses\": {\"c1\": {\"id\": \"c1\"}}, \"groups\": []}").is_err());
+        assert!(parse_my_courses_store("{\"courses\": ").is_err());
+    }
+
+    #[test]
+    fn test_courses_mut() {
+        let mut my_courses: MyCourses = serde_json::from_str(MY_COURSES_JSON).unwrap();
+        let ids = |courses: Vec<&mut Course>| courses.into_iter().map(|course| course.id.to_string()).collect::<Vec<_>>();
+        assert_eq!(ids(my_courses.courses_mut(["c4", "c1", "c4", "c9"])), vec!["c4", "c1"]);
+        assert_eq!(ids(my_courses.get_courses_by_set_group_name("WiSe 2024/25")), vec!["c3", "c4"]);
+        assert!(my_courses.get_courses_by_set_group_name("SoSe 2030").is_empty());
+        for course in my_courses.get_courses_by_set_group_name("WiSe 2023/24") {
+            course.name.push_str(" (old)");
+        }
+        assert_eq!(my_courses.courses["c2"].name, "Lineare Algebra (old)");
+    }
+
+    #[test]
+    fn test_parse_course_dates() {
+        let html = Html::parse_document(r#"
+            <table><tbody>
+                <tr id="date_d1"><td>Mo., 10.03.2025 10:00 - 12:00</td><td>Vorlesung</td><td>Einführung</td>
+                    <td><a href="https://studip.example.com/dispatch.php/resources/room/index/r1">HS 1</a></td></tr>
+                <tr id="date_d2" class="ex-date"><td>Mo., 17.03.2025 10:00 - 12:00</td><td>Vorlesung</td><td>fällt aus</td></tr>
+                <tr id="date_d3"><td>Mo., 24.03.2025 10:00 - 12:00</td><td>Übung</td><td></td><td>(Raum 1.12)</td></tr>
+            </tbody></table>
+        "#);
+        let dates = parse_course_dates(&html, DEFAULT_TIMEZONE).unwrap();
+        assert_eq!(dates.len(), 3);
+        assert_eq!(dates[0].title, "Vorlesung");
+        assert!(!dates[0].cancelled);
+        assert_eq!(dates[0].start, Some(Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap()));
+        assert!(dates[1].cancelled);
+        assert_eq!(dates[0].room, Some(RoomRef { id: Some("r1".into()), name: "HS 1".into() }));
+        assert_eq!(dates[1].room, None);
+        assert_eq!(dates[2].room, Some(RoomRef { id: None, name: "(Raum 1.12)".into() }));
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let path = std::env::temp_dir().join(format!("stud_ip_snapshot_{}.json", std::process::id()));
+        let mut my_courses: MyCourses = serde_json::from_str(MY_COURSES_JSON).unwrap();
+        assert!(my_courses.save_snapshot(&path).is_err());
+        let fetched_at = Utc::now();
+        my_courses.fetched_at = Some(fetched_at);
+        my_courses.save_snapshot(&path).unwrap();
+
+        let (mut loaded, meta) = MyCourses::load_snapshot(&path, Arc::new(StudIpClient::default())).unwrap();
+        assert_eq!(meta.fetched_at, fetched_at);
+        assert_eq!(loaded.courses.len(), 4);
+        assert_eq!(loaded.set_groups, my_courses.set_groups);
+        // Fresh snapshots are not queried again
+        assert!(!loaded.refresh_if_older_than(Duration::from_secs(60)).unwrap());
+
+        let outdated = std::fs::read_to_string(&path).unwrap().replacen("\"version\":1", "\"version\":0", 1);
+        std::fs::write(&path, outdated).unwrap();
+        let error = MyCourses::load_snapshot(&path, Arc::new(StudIpClient::default())).unwrap_err();
+        assert!(error.to_string().contains("not supported"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_attach_client_after_round_trip() {
+        let server = MockServer::start();
+        server.route("GET", "/seminar_main.php", 200, r#"<ul id="tabs"><li id="nav_course_files"></li><li id="nav_course_unknown"></li></ul>"#);
+        let my_courses: MyCourses = serde_json::from_str(MY_COURSES_JSON).unwrap();
+        let serialized = serde_json::to_string(&my_courses).unwrap();
+        let mut my_courses: MyCourses = serde_json::from_str(&serialized).unwrap();
+        let course = my_courses.courses.get_mut("c1").unwrap();
+        assert!(course.query_modules().unwrap_err().to_string().contains("No client attached"));
+
+        my_courses.attach_client(Arc::new(server.client()));
+        let course = my_courses.courses.get_mut("c1").unwrap();
+        course.query_modules().unwrap();
+        assert_eq!(course.modules.len(), 1);
+        let request = &server.requests()[0];
+        assert_eq!((request.method.as_str(), request.path.as_str()), ("GET", "/seminar_main.php?auswahl=c1"));
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn test_page_cache() {
+        let server = MockServer::start();
+        server.route("GET", "/seminar_main.php", 200, r#"<ul id="tabs"><li id="nav_course_members"></li></ul>"#)
+            .route("GET", "/dispatch.php/course?", 200, "<div id=\"content\"></div>")
+            .route("GET", "/dispatch.php/course/members", 200, "<div id=\"content\"></div>")
+            .route("GET", "/dispatch.php/course/statusgroups", 200, "<div id=\"content\"></div>");
+        let mut my_courses: MyCourses = serde_json::from_str(MY_COURSES_JSON).unwrap();
+        my_courses.attach_client(Arc::new(server.client()));
+        let course = my_courses.courses.get_mut("c1").unwrap();
+        course.query_modules().unwrap();
+        let count = |prefix: &str| server.requests().iter().filter(|request| request.path.starts_with(prefix)).count();
+
+        // News and questionnaires share the overview page
+        course.query_news().unwrap();
+        course.query_questionnaires().unwrap();
+        assert_eq!(count("/dispatch.php/course?"), 1);
+
+        let members_module = get_module!(course, MembersModule).unwrap();
+        members_module.get_members().unwrap();
+        members_module.get_members().unwrap();
+        assert_eq!(count("/dispatch.php/course/members"), 1);
+
+        // Joining a group changes the members and groups pages
+        let group = Group { name: "A".to_string(), id: "g1".into(), entered: false, enables_entry_at: None, members: 0, max_members: 0, waitlist: None };
+        members_module.get_groups().unwrap();
+        members_module.try_join_group(&group).unwrap();
+        members_module.get_groups().unwrap();
+        members_module.get_members().unwrap();
+        assert_eq!(count("/dispatch.php/course/statusgroups?"), 2);
+        assert_eq!(count("/dispatch.php/course/members"), 2);
+
+        course.refresh();
+        course.query_news().unwrap();
+        assert_eq!(count("/dispatch.php/course?"), 2);
+        course.set_cache_freshness(Duration::ZERO);
+        course.query_news().unwrap();
+        course.query_news().unwrap();
+        assert_eq!(count("/dispatch.php/course?"), 4);
+    }
+
+    #[test]
+    fn test_for_each_course_parallel() {
+        let server = MockServer::start();
+        // The responses are only sent, once all 3 requests arrived, which only happens, if they are sent concurrently
+        server.route("GET", "/dispatch.php/course?", 200, "<div id=\"content\"></div>")
+            .hold_responses(3);
+        let mut my_courses: MyCourses = serde_json::from_str(MY_COURSES_JSON).unwrap();
+        my_courses.attach_client(Arc::new(server.client()));
+        let results = my_courses.for_each_course_parallel(4, |course| {
+            if course.id == "c2" {
+                bail!("Failed on purpose");
+            }
+            Ok(course.query_news()?.len())
+        });
+        assert!(!server.hold_timed_out());
+        assert_eq!(results.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(), vec!["c1", "c2", "c3", "c4"]);
+        assert!(results[1].1.is_err());
+        assert!(results.iter().filter(|(id, _)| id != "c2").all(|(_, result)| matches!(result, Ok(0))));
+
+        let mut requests = server.requests();
+        requests.sort_by_key(|request| request.received_at);
+        assert_eq!(requests.len(), 3);
+        // But they never exceed the rate limit, which a slow machine can only make slower
+        #[cfg(feature = "rate_limiting")]
+        assert!(requests.windows(2).all(|pair| pair[1].received_at - pair[0].received_at >= Duration::from_millis(100)));
+    }
+
+    #[derive(Debug)]
+    struct MeetingsModule;
+
+    impl CourseModule for MeetingsModule {
+        fn new(_data: Arc<CourseModuleData>) -> Self {
+            Self
+        }
+
+        fn name() -> &'static str {
+            "meetings"
+        }
+
+        fn as_any(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+    }
+
+    #[test]
+    fn test_module_registry_per_client() {
+        let server = MockServer::start();
+        server.route("GET", "/seminar_main.php", 200, r#"<ul id="tabs"><li id="nav_course_files"></li><li id="nav_course_meetings"></li></ul>"#);
+        let with_meetings = Arc::new(server.client());
+        with_meetings.module_registry().register::<MeetingsModule>();
+        let without_meetings = Arc::new(server.client());
+        assert!(with_meetings.module_registry().contains("meetings"));
+        assert!(!without_meetings.module_registry().contains("meetings"));
+
+        let mut my_courses: MyCourses = serde_json::from_str(MY_COURSES_JSON).unwrap();
+        my_courses.attach_client(with_meetings);
+        let course = my_courses.courses.get_mut("c1").unwrap();
+        course.query_modules().unwrap();
+        assert_eq!(course.modules.len(), 2);
+        assert!(get_module!(course, MeetingsModule).is_some());
+
+        my_courses.attach_client(without_meetings);
+        let course = my_courses.courses.get_mut("c1").unwrap();
+        course.query_modules().unwrap();
+        assert_eq!(course.modules.len(), 1);
+        assert!(get_module!(course, MeetingsModule).is_none());
+    }
+
+    #[test]
+    fn test_module_urls() {
+        let server = MockServer::start();
+        server.route("GET", "/seminar_main.php", 200, r#"<ul id="tabs">
+                <li id="nav_course_members"><a href="/studip/dispatch.php/course/members?cid=c1">Teilnehmende</a></li>
+                <li id="nav_course_files"><a href="https://studip.example.com/studip/dispatch.php/course/files?cid=c1">Dateien</a></li>
+                <li id="nav_course_meetings"><a href="/studip/plugins.php/meetingplugin/index?cid=c1">Meetings</a></li>
+            </ul>"#)
+            .route("GET", "/studip/dispatch.php/course/members", 200, "<div id=\"content\"></div>")
+            .route("GET", "/studip/dispatch.php/course/statusgroups", 200, "<div id=\"content\"></div>")
+            .route("GET", "/studip/dispatch.php/course/files", 403, "");
+        let client = Arc::new(server.client());
+        client.module_registry().register::<MeetingsModule>();
+        let mut my_courses: MyCourses = serde_json::from_str(MY_COURSES_JSON).unwrap();
+        my_courses.attach_client(client);
+        let course = my_courses.courses.get_mut("c1").unwrap();
+        course.query_modules().unwrap();
+
+        let members_module = get_module!(course, MembersModule).unwrap();
+        assert_eq!(members_module.url().unwrap().as_str(), "https://studip.example.com/studip/dispatch.php/course/members?cid=c1");
+        members_module.probe().unwrap();
+        members_module.get_members().unwrap();
+        members_module.get_groups().unwrap();
+        assert!(server.requests().iter().any(|request| request.path == "/studip/dispatch.php/course/statusgroups?cid=c1"));
+        assert!(get_module!(course, crate::course_modules::FileModule).unwrap().probe().unwrap_err().to_string().contains("403"));
+        // Modules, that do not expose their data, have no url
+        let meetings_module = get_module!(course, MeetingsModule).unwrap();
+        assert!(meetings_module.url().is_none());
+        assert!(meetings_module.probe().is_err());
+    }
+
+    #[test]
+    fn test_unknown_modules() {
+        let server = MockServer::start();
+        server.route("GET", "/seminar_main.php", 200, r#"<ul id="tabs">
+                <li id="nav_course_files"><a href="/dispatch.php/course/files?cid=c1">Dateien</a></li>
+                <li id="nav_course_meetings"><a href="/plugins.php/meetingplugin/index?cid=c1"> Meetings </a></li>
+                <li id="nav_course_empty"></li>
+            </ul>"#)
+            .route("GET", "/plugins.php/meetingplugin/index", 200, "<div id=\"content\">Raum 1</div>");
+        let mut my_courses: MyCourses = serde_json::from_str(MY_COURSES_JSON).unwrap();
+        my_courses.attach_client(Arc::new(server.client()));
+        let course = my_courses.courses.get_mut("c1").unwrap();
+        course.query_modules().unwrap();
+        assert_eq!(course.modules.len(), 1);
+
+        course.query_modules_with_unknown().unwrap();
+        assert_eq!(course.modules.len(), 3);
+        assert!(get_module!(course, crate::course_modules::FileModule).is_some());
+        let unknown = course.modules.iter_mut()
+            .filter_map(|module| module.as_any().downcast_mut::<UnknownModule>())
+            .collect::<Vec<_>>();
+        assert_eq!((unknown[0].tab_id(), unknown[0].title()), ("meetings", "Meetings"));
+        assert!(unknown[0].fetch_raw_html().unwrap().contains("Raum 1"));
+        assert_eq!(unknown[1].tab_id(), "empty");
+        assert!(unknown[1].fetch_raw_html().is_err());
+    }
+
+    #[derive(Debug)]
+    struct BrokenModule;
+
+    impl CourseModule for BrokenModule {
+        fn new(_data: Arc<CourseModuleData>) -> Self {
+            Self
+        }
+
+        fn try_new(_data: Arc<CourseModuleData>) -> anyhow::Result<Self> {
+            bail!("Expected a files table")
+        }
+
+        fn name() -> &'static str {
+            "files"
+        }
+
+        fn as_any(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+    }
+
+    #[test]
+    fn test_module_failures() {
+        let server = MockServer::start();
+        server.route("GET", "/seminar_main.php", 200, r#"<ul id="tabs"><li id="nav_course_files"></li><li id="nav_course_members"></li></ul>"#);
+        let client = Arc::new(server.client());
+        client.module_registry().register::<BrokenModule>();
+        let mut my_courses: MyCourses = serde_json::from_str(MY_COURSES_JSON).unwrap();
+        my_courses.attach_client(client);
+        let course = my_courses.courses.get_mut("c1").unwrap();
+        let report = course.query_modules().unwrap();
+        assert!(!report.is_complete());
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].to_string(), "the files module failed to initialize: Expected a files table");
+        // The other modules are still constructed
+        assert_eq!(course.modules.len(), 1);
+        assert!(get_module!(course, MembersModule).is_some());
+    }
+
+    #[test]
+    fn test_course_details() {
+        let details = |html| CourseDetails::from_fields(parse_details(&Html::parse_document(html)).unwrap().0);
+        let combined = details(include_str!("../testdata/course_details/combined.html"));
+        assert_eq!(
+            (combined.participants, combined.max_participants, combined.waitlist, combined.guests),
+            (120, Some(150), Some(12), Some(3))
+        );
+        assert_eq!(combined.fields.get("Semester").map(String::as_str), Some("WiSe 2024/25"));
+        // The fields only contain the name of the room
+        assert_eq!(combined.location, Some(RoomRef { id: None, name: "HS 1".into() }));
+        assert_eq!(parse_location(&Html::parse_document(include_str!("../testdata/course_details/combined.html"))), Some(RoomRef { id: Some("r1".into()), name: "HS 1".into() }));
+        // The waiting list is shown, but not enabled
+        let separate = details(include_str!("../testdata/course_details/separate.html"));
+        assert_eq!(
+            (separate.participants, separate.max_participants, separate.waitlist, separate.guests),
+            (1204, Some(1500), None, None)
+        );
+        let english = details(include_str!("../testdata/course_details/english.html"));
+        assert_eq!(
+            (english.participants, english.max_participants, english.waitlist, english.guests),
+            (98, Some(100), Some(7), Some(2))
+        );
+        assert_eq!(english.location.as_ref().map(|room| room.name.as_str()), Some("Lecture hall 2"));
+        // The scraped values can be shown in another language, than the page was in
+        assert_eq!(english.display(Language::German), vec![
+            ("Teilnehmende", "98".to_string()),
+            ("Maximale Teilnehmendenanzahl", "100".to_string()),
+            ("Warteliste", "7".to_string()),
+            ("Lesende Gäste", "2".to_string()),
+            ("Ort", "Lecture hall 2".to_string()),
+        ]);
+        assert_eq!(separate.display(Language::English), vec![("Participants", "1204".to_string()), ("Maximum number of participants", "1500".to_string())]);
+
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/course/details", 200, r#"<div id="content"><table>
+                <tr><th>Teilnehmende:</th><td>unbekannt</td></tr>
+                <tr><th>Raum:</th><td><a href="/dispatch.php/resources/room_planning/booking_plan/r2">Seminarraum 3</a> (wöchentlich)</td></tr>
+            </table></div>"#);
+        let mut my_courses: MyCourses = serde_json::from_str(MY_COURSES_JSON).unwrap();
+        my_courses.attach_client(Arc::new(server.client()));
+        let details = my_courses.courses["c1"].query_details().unwrap();
+        assert_eq!((details.participants, details.max_participants), (0, None));
+        assert_eq!(details.location, Some(RoomRef { id: Some("r2".into()), name: "Seminarraum 3".into() }));
+        assert_eq!(details.admission, AdmissionProcedure::Open);
+        assert_eq!(server.requests()[0].path, "/dispatch.php/course/details?cid=c1");
+    }
+
+    #[test]
+    fn test_fetch_page() {
+        let server = MockServer::start();
+        server.route("GET", "/plugins.php/meetingplugin/index", 200, "<div id=\"content\">Meetings</div>")
+            .route_typed("GET", "/plugins.php/meetingplugin/api/rooms", 200, "application/json", r#"{"rooms": [{"id": 1}]}"#)
+            .route("GET", "/plugins.php/hidden", 403, "");
+        let mut my_courses: MyCourses = serde_json::from_str(MY_COURSES_JSON).unwrap();
+        my_courses.attach_client(Arc::new(server.client()));
+        let course = &my_courses.courses["c1"];
+
+        assert!(course.fetch_page("plugins.php/meetingplugin/index", &[("page", "2")]).unwrap().contains("Meetings"));
+        let rooms = course.fetch_xhr_json("/plugins.php/meetingplugin/api/rooms", &[]).unwrap();
+        assert_eq!(rooms["rooms"][0]["id"], 1);
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/plugins.php/meetingplugin/index?cid=c1&page=2");
+        assert!(requests[0].header("X-Requested-With").is_none());
+        assert_eq!(requests[1].path, "/plugins.php/meetingplugin/api/rooms?cid=c1");
+        assert_eq!(requests[1].header("X-Requested-With"), Some("XMLHttpRequest"));
+
+        let error = course.fetch_page("plugins.php/hidden", &[]).unwrap_err();
+        assert!(matches!(error.downcast_ref::<StudIpError>(), Some(StudIpError::PermissionDenied { .. })), "{:?}", error);
+        assert!(course.fetch_xhr_json("plugins.php/meetingplugin/index", &[]).is_err());
+    }
+
+    #[test]
+    fn test_whats_new() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/my_courses", 200, r#"<script type="text/javascript">
+                window.STUDIP.MyCoursesData = {"courses": {"c1": {"id": "c1", "name": "Algorithmen und Datenstrukturen", "number": "101", "group": 0,
+                    "navigation": [
+                        {"url": "/dispatch.php/course/files?cid=c1", "icon": {"shape": "files+new", "role": "attention"}, "important": true, "title": "2 neue Dateien", "label": "Dateien"},
+                        false,
+                        {"url": "/dispatch.php/course/wiki?cid=c1", "icon": {"shape": "wiki", "role": "clickable"}, "important": false, "title": "", "label": "Wiki"}
+                    ]}},
+                    "groups": [], "user_id": "u1", "config": {}};
+            </script>"#)
+            .route("GET", "/dispatch.php/course/whats_new", 200, r#"<div id="content"><table class="default"><tbody>
+                <tr><td><img class="icon-role-info icon-shape-forum"></td><td><a href="/dispatch.php/course/forum/thread/t1?cid=c1">Frage zu Blatt 3</a></td><td>4 neue Beiträge</td></tr>
+                <tr><td><img class="icon-shape-news"></td><td><a href="/dispatch.php/course/overview?cid=c1">Klausurtermin</a></td></tr>
+                <tr><td><img class="icon-shape-vote"></td><td><a href="/dispatch.php/course/questionnaire?cid=c1">Evaluation</a> <span class="badge">1</span></td></tr>
+            </tbody></table></div>"#);
+        let mut my_courses: MyCourses = serde_json::from_str(MY_COURSES_JSON).unwrap();
+        my_courses.attach_client(Arc::new(server.client()));
+        let course = my_courses.courses.get_mut("c1").unwrap();
+        let count = |prefix: &str| server.requests().iter().filter(|request| request.path.starts_with(prefix)).count();
+
+        // Peeking reads the markers of the my courses page, only the marked modules are reported
+        let changes = course.whats_new(true).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].kind, changes[0].title.as_str(), changes[0].count), (ChangeKind::Files, "2 neue Dateien", Some(2)));
+        assert!(changes[0].url.ends_with("/dispatch.php/course/files?cid=c1"), "{}", changes[0].url);
+        course.whats_new(true).unwrap();
+        assert_eq!(count("/dispatch.php/my_courses"), 1);
+        assert_eq!(count("/dispatch.php/course/whats_new"), 0);
+
+        let changes = course.whats_new(false).unwrap();
+        assert_eq!(changes.iter().map(|change| (change.kind, change.title.as_str(), change.count)).collect::<Vec<_>>(), vec![
+            (ChangeKind::Forum, "Frage zu Blatt 3", Some(4)),
+            (ChangeKind::News, "Klausurtermin", None),
+            (ChangeKind::Unknown, "Evaluation", Some(1)),
+        ]);
+        assert_eq!(server.requests().last().unwrap().path, "/dispatch.php/course/whats_new?cid=c1");
+        // The visit reset the markers, so they are read again
+        course.whats_new(true).unwrap();
+        assert_eq!(count("/dispatch.php/my_courses"), 2);
+    }
+}
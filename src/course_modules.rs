@@ -1,38 +1,170 @@
+pub mod courseware;
 pub mod file;
 pub mod members;
+pub mod unknown;
+pub mod wiki;
 
 use std::any::Any;
 use std::collections::HashMap;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
 use std::sync::{Arc, Mutex};
 
+pub use courseware::CoursewareModule;
 pub use file::FileModule;
 pub use members::MembersModule;
-use crate::StudIpClient;
+pub use unknown::UnknownModule;
+pub use wiki::WikiModule;
+use anyhow::{bail, Context};
+use url::Url;
+use crate::error::{check_page_text, check_status};
+use crate::ids::CourseId;
+use crate::page_cache::PageCache;
+use crate::{SendThrough, StudIpClient};
 
-type ModuleConstructor = fn(Arc<CourseModuleData>) -> Box<dyn CourseModule>;
+type ModuleConstructor = fn(Arc<CourseModuleData>) -> anyhow::Result<Box<dyn CourseModule>>;
 
+#[deprecated(note = "Modules are registered per client, see `ModuleRegistry`")]
 pub(crate) static COURSE_MODULE_REGISTRY: once_cell::sync::Lazy<Arc<Mutex<HashMap<&'static str, ModuleConstructor>>>> = once_cell::sync::Lazy::new(Default::default);
-// Simply keeps track, if the default models have already been registered
-pub(crate) static REGISTERED_DEFAULT_COURSE_MODULES: once_cell::sync::OnceCell<()> = once_cell::sync::OnceCell::new();
 
+/// The course modules, that can be detected by [Course::query_modules()](crate::course::Course::query_modules()) \
+/// Every [`StudIpClient`] has its own registry, so that e.g. two instances with different plugins can use different modules. \
+/// Modules registered with the deprecated [`register_course_module()`] are shared by all clients, but only used for names, that a client did not register itself.
+#[derive(Debug)]
+pub struct ModuleRegistry {
+    constructors: Mutex<HashMap<&'static str, ModuleConstructor>>,
+}
+
+impl Default for ModuleRegistry {
+    fn default() -> Self {
+        Self::with_defaults()
+    }
+}
+
+impl ModuleRegistry {
+
+    /// Creates a registry without any modules
+    pub fn empty() -> Self {
+        Self {
+            constructors: Default::default(),
+        }
+    }
+
+    /// Creates a registry with the modules of this crate ([`FileModule`], [`MembersModule`], [`WikiModule`] and [`CoursewareModule`])
+    pub fn with_defaults() -> Self {
+        let registry = Self::empty();
+        registry.register::<FileModule>();
+        registry.register::<MembersModule>();
+        registry.register::<WikiModule>();
+        registry.register::<CoursewareModule>();
+        registry
+    }
+
+    /// Registers a course module, replacing any module with the same name
+    pub fn register<M: CourseModule + 'static>(&self) {
+        self.constructors.lock().unwrap().insert(M::name(), construct::<M>);
+    }
+
+    /// Whether a module with the `name` is registered
+    pub fn contains(&self, name: &str) -> bool {
+        #[allow(deprecated)]
+        let registered_globally = COURSE_MODULE_REGISTRY.lock().unwrap().contains_key(name);
+        self.constructors.lock().unwrap().contains_key(name) || registered_globally
+    }
+
+    /// Constructs the module with the `name`, also considering modules, that were registered globally with the deprecated [`register_course_module()`] \
+    /// Returns `None`, if no such module is registered.
+    pub(crate) fn construct(&self, name: &str, data: Arc<CourseModuleData>) -> Option<anyhow::Result<Box<dyn CourseModule>>> {
+        #[allow(deprecated)]
+        let constructor = self.constructors.lock().unwrap().get(name).copied()
+            .or_else(|| COURSE_MODULE_REGISTRY.lock().unwrap().get(name).copied())?;
+        Some(constructor(data))
+    }
 
-pub trait CourseModule: Debug + Any {
+}
+
+
+/// A module (tab) of a course \
+/// Modules need to be [`Send`], so that courses can be processed in parallel (see [MyCourses::for_each_course_parallel()](crate::course::MyCourses::for_each_course_parallel())).
+pub trait CourseModule: Debug + Any + Send {
     /// Constructs a new instance of the Module, for a specific [Course](crate::course::Course)
     fn new(data: Arc<CourseModuleData>) -> Self where Self: Sized;
 
+    /// Constructs a new instance of the Module like [`CourseModule::new()`], but can fail (e.g. if the layout of its page changed) \
+    /// This is what [Course::query_modules()](crate::course::Course::query_modules()) calls, by default it just calls [`CourseModule::new()`].
+    fn try_new(data: Arc<CourseModuleData>) -> anyhow::Result<Self> where Self: Sized {
+        Ok(Self::new(data))
+    }
+
     /// The name of the course module. \
     /// Needs to correspond to the id of the tab in the HTML (without the prefix: `nav_course_`)
     fn name() -> &'static str where Self: Sized;
 
     /// Converts the Module to [`Any`], required for downcasting back to a concrete type
     fn as_any(&mut self) -> &mut dyn Any;
+
+    /// The data, with which the module was constructed, which the default implementations of [`CourseModule::url()`] and [`CourseModule::probe()`] rely on
+    fn data(&self) -> Option<&CourseModuleData> {
+        None
+    }
+
+    /// The entry url of the module, as linked by its tab
+    fn url(&self) -> Option<Url> {
+        self.data()?.tab_url.clone()
+    }
+
+    /// Checks, that the module is actually accessible for the current user, by requesting its entry page
+    fn probe(&self) -> anyhow::Result<()> {
+        let data = self.data().context("The module does not expose its data")?;
+        let url = self.url().context("The module has no url")?;
+        let response = data.client.get(url.clone()).send_through(&data.client)?;
+        if !response.status().is_success() {
+            bail!("The module at {} returned {}", url, response.status());
+        }
+        check_page_text(&response.text()?)?;
+        Ok(())
+    }
 }
 
-/// Registers a course module globally, only registered modules can be detected by [Course::query_modules()](crate::course::Course::query_modules())
+/// Registers a course module globally, for all clients \
+/// Only registered modules can be detected by [Course::query_modules()](crate::course::Course::query_modules())
+#[deprecated(note = "Use `StudIp::register_course_module()` or `ModuleRegistry::register()`, which only affect a single client")]
 pub fn register_course_module<M: CourseModule + 'static>() {
+    #[allow(deprecated)]
     let mut registry = COURSE_MODULE_REGISTRY.lock().unwrap();
-    registry.insert(M::name(), |data| Box::new(M::new(data)));
+    registry.insert(M::name(), construct::<M>);
+}
+
+fn construct<M: CourseModule + 'static>(data: Arc<CourseModuleData>) -> anyhow::Result<Box<dyn CourseModule>> {
+    Ok(Box::new(M::try_new(data)?))
+}
+
+/// A registered module, that failed to be constructed for a tab
+#[derive(Debug)]
+pub struct ModuleFailure {
+    /// The name of the module (see [`CourseModule::name()`])
+    pub name: String,
+    pub error: anyhow::Error,
+}
+
+impl Display for ModuleFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the {} module failed to initialize: {:#}", self.name, self.error)
+    }
+}
+
+/// The outcome of [Course::query_modules()](crate::course::Course::query_modules()), modules that failed to be constructed are left out of the course
+#[derive(Debug, Default)]
+pub struct ModulesReport {
+    pub failures: Vec<ModuleFailure>,
+}
+
+impl ModulesReport {
+
+    /// Whether all modules were constructed
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+
 }
 
 /// Gets a downcasted [`CourseModule`], by its Type on a [Course](crate::course::Course)
@@ -46,11 +178,71 @@ macro_rules! get_module {
 /// Some data, that is required for any [`CourseModule`]
 #[derive(Debug)]
 pub struct CourseModuleData {
-    pub course_id: String,
+    pub course_id: CourseId,
     pub client: Arc<StudIpClient>,
+    /// Shared with the [Course](crate::course::Course) and all of its other modules
+    pub(crate) page_cache: Arc<PageCache>,
+    /// The url, that the tab of the module links to, which differs across installations with url rewriting
+    pub tab_url: Option<Url>,
 }
 
-pub (crate) fn register_default_course_modules() {
-    register_course_module::<FileModule>();
-    register_course_module::<MembersModule>();
-}
\ No newline at end of file
+impl CourseModuleData {
+
+    /// The url of the tab without its query (e.g. the `cid`), or the `default`, if the tab did not link anywhere
+    pub fn base_url(&self, default: &str) -> String {
+        match &self.tab_url {
+            Some(tab_url) => tab_url[..url::Position::AfterPath].to_string(),
+            None => default.to_string(),
+        }
+    }
+
+    /// Returns the body of a page of this course, reusing it, if it was fetched recently (see [Course::set_cache_freshness()](crate::course::Course::set_cache_freshness())) \
+    /// Modules, that change a page, need to call [`CourseModuleData::invalidate()`] afterwards.
+    pub fn get_page(&self, url: &str, query: &[(&str, &str)]) -> anyhow::Result<String> {
+        self.page_cache.get(&self.client, url, query)
+    }
+
+    /// Removes all cached pages, which url starts with the `url_prefix`
+    pub fn invalidate(&self, url_prefix: &str) {
+        self.page_cache.invalidate(url_prefix);
+    }
+
+    /// Removes all cached pages of the course, so that they are fetched again on the next read
+    pub fn refresh(&self) {
+        self.page_cache.clear();
+    }
+
+    /// Fetches a page of this course, that no module of this crate models (e.g. of a plugin), for custom parsing \
+    /// This is the extension point for [`CourseModule`]s outside of this crate: The `relative_path` (e.g. `plugins.php/meetingplugin/index`)
+    /// is resolved against the host of the client and the `cid` of the course is added to the `extra_query`. Unlike [`CourseModuleData::get_page()`],
+    /// the page is always fetched. Fails with [`StudIpError`](crate::error::StudIpError), if the page is not accessible.
+    pub fn fetch_page(&self, relative_path: &str, extra_query: &[(&str, &str)]) -> anyhow::Result<String> {
+        fetch_course_page(&self.client, &self.course_id, relative_path, extra_query, false)
+    }
+
+    /// Like [`CourseModuleData::fetch_page()`], but requested like the web UI requests its dialogs and updates (with the `X-Requested-With` header), returning the parsed JSON
+    pub fn fetch_xhr_json(&self, relative_path: &str, extra_query: &[(&str, &str)]) -> anyhow::Result<serde_json::Value> {
+        let body = fetch_course_page(&self.client, &self.course_id, relative_path, extra_query, true)?;
+        serde_json::from_str(&body).with_context(|| format!("Expected JSON from {}", relative_path))
+    }
+
+}
+
+/// Fetches a page of the course with the `course_id` (see [`CourseModuleData::fetch_page()`])
+pub(crate) fn fetch_course_page(client: &StudIpClient, course_id: &CourseId, relative_path: &str, extra_query: &[(&str, &str)], xhr: bool) -> anyhow::Result<String> {
+    let url = client.absolutize(relative_path)?;
+    let mut request = client.get(url.clone())
+        .query(&[("cid", course_id.as_str())])
+        .query(extra_query);
+    if xhr {
+        request = request.header("X-Requested-With", "XMLHttpRequest");
+    }
+    let response = request.send_through(client)?;
+    check_status(response.status())?;
+    if !response.status().is_success() {
+        bail!("Could not fetch {}. Status Code: {}", url, response.status());
+    }
+    let body = response.text()?;
+    check_page_text(&body)?;
+    Ok(body)
+}
@@ -0,0 +1,296 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+use anyhow::{bail, Context};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::course_modules::{CourseModule, CourseModuleData};
+use crate::error::check_status;
+use crate::SendThrough;
+
+const COURSEWARE_URL : &str = "https://studip.example.com/dispatch.php/course/courseware";
+const JSONAPI_URL : &str = "https://studip.example.com/jsonapi.php/v1";
+
+/// The block types, which are completed by just viewing them \
+/// Other blocks (e.g. videos or tests) track their completion themselves.
+const SIMPLE_COMPLETION_BLOCK_TYPES: &[&str] = &[
+    "text", "headline", "key-point", "link", "typewriter", "date", "code",
+    "download", "folder", "gallery", "embed", "image-map", "before-after",
+];
+
+/// Module, that enables reading the Courseware of a course and the progress of the current user in it \
+/// The Courseware frontend loads its data from the JSON:API with the session, so this works without the `jsonapi` feature.
+#[derive(Debug)]
+pub struct CoursewareModule {
+    module_data: Arc<CourseModuleData>,
+}
+
+impl CourseModule for CoursewareModule {
+    fn new(data: Arc<CourseModuleData>) -> Self {
+        Self { module_data: data }
+    }
+
+    fn name() -> &'static str {
+        "courseware"
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn data(&self) -> Option<&CourseModuleData> {
+        Some(&self.module_data)
+    }
+}
+
+/// A block of the Courseware, which the current user did not complete yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockSummary {
+    pub id: String,
+    pub title: String,
+    /// The type of the block (e.g. "text" or "video")
+    pub block_type: String,
+    /// The title of the chapter, that contains the block
+    pub chapter: String,
+    /// The title of the page (structural element) inside of the chapter, that contains the block
+    pub page: String,
+}
+
+/// The progress of the current user in the Courseware of a course
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoursewareProgress {
+    /// The title of every top level chapter, with the percentage (0 to 100) of its completed blocks, including those of its subchapters
+    pub per_chapter: Vec<(String, f32)>,
+    pub incomplete_blocks: Vec<BlockSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Resource {
+    #[serde(rename = "type")]
+    kind: String,
+    id: String,
+    #[serde(default)]
+    attributes: serde_json::Value,
+    #[serde(default)]
+    relationships: serde_json::Value,
+    #[serde(default)]
+    meta: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct Document<D> {
+    data: D,
+    #[serde(default)]
+    included: Vec<Resource>,
+}
+
+impl Resource {
+
+    fn attribute(&self, name: &str) -> Option<&serde_json::Value> {
+        self.attributes.get(name)
+    }
+
+    fn title(&self) -> String {
+        self.attribute("title").and_then(|title| title.as_str()).unwrap_or_default().to_string()
+    }
+
+    // The id of a to-one relationship
+    fn related_id(&self, name: &str) -> Option<&str> {
+        self.relationships.pointer(&format!("/{}/data/id", name))?.as_str()
+    }
+
+    // The ids of a to-many relationship
+    fn related_ids(&self, name: &str) -> Vec<&str> {
+        self.relationships.pointer(&format!("/{}/data", name))
+            .and_then(|data| data.as_array())
+            .map(|data| data.iter().filter_map(|item| item.get("id")?.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+}
+
+impl CoursewareModule {
+
+    // The JSON:API lives next to the dispatch.php, that the tab links to
+    fn jsonapi_url(&self) -> String {
+        let url = self.module_data.base_url(COURSEWARE_URL);
+        match url.find("/dispatch.php") {
+            Some(index) => format!("{}/jsonapi.php/v1", &url[..index]),
+            None => JSONAPI_URL.to_string(),
+        }
+    }
+
+    fn get<D: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> anyhow::Result<Document<D>> {
+        let client = &self.module_data.client;
+        let response = client.get(format!("{}{}", self.jsonapi_url(), path))
+            .query(query)
+            .header("Accept", "application/vnd.api+json")
+            .send_through(client)?;
+        check_status(response.status())?;
+        if !response.status().is_success() {
+            bail!("Courseware request to {} failed with status {}", path, response.status());
+        }
+        serde_json::from_str(&response.text()?).with_context(|| format!("Could not parse Courseware data of {}", path))
+    }
+
+    /// Returns the progress of the current user, per chapter and as the blocks, which are not completed yet \
+    /// The percentages are taken from Stud.IP, if it provides them, and are otherwise computed from the number of completed blocks.
+    /// A chapter without blocks counts as completed.
+    pub fn get_progress(&self) -> anyhow::Result<CoursewareProgress> {
+        let instance: Document<Resource> = self.get(&format!("/courses/{}/courseware", self.module_data.course_id), &[])?;
+        let root_id = instance.data.related_id("root")
+            .context("Expected the root element of the Courseware")?
+            .to_string();
+        let descendants: Document<Vec<Resource>> = self.get(
+            &format!("/courseware-structural-elements/{}/descendants", root_id),
+            &[("include", "containers,containers.blocks,containers.blocks.user-progress")],
+        )?;
+        Ok(compute_progress(&root_id, &descendants.data, &descendants.included))
+    }
+
+    /// Marks the block as completed by the current user \
+    /// Only supported for blocks, which are completed by viewing them (e.g. text blocks), as the others track their completion themselves.
+    pub fn mark_block_completed(&self, block_id: &str) -> anyhow::Result<()> {
+        let block: Document<Resource> = self.get(&format!("/courseware-blocks/{}", block_id), &[("include", "user-progress")])?;
+        let block_type = block.data.attribute("block-type").and_then(|block_type| block_type.as_str()).unwrap_or_default();
+        if !SIMPLE_COMPLETION_BLOCK_TYPES.contains(&block_type) {
+            bail!("The completion of {} blocks can not be set, it is tracked by the block itself", block_type);
+        }
+        let progress_id = block.data.related_id("user-progress")
+            .with_context(|| format!("Expected the progress of block {}", block_id))?;
+        let client = &self.module_data.client;
+        let response = client.patch(format!("{}/courseware-user-progresses/{}", self.jsonapi_url(), progress_id))
+            .header("Accept", "application/vnd.api+json")
+            .header("Content-Type", "application/vnd.api+json")
+            .body(json!({"data": {"type": "courseware-user-progresses", "id": progress_id, "attributes": {"grade": 1.0}}}).to_string())
+            .send_through(client)?;
+        check_status(response.status())?;
+        if !response.status().is_success() {
+            bail!("Could not mark block {} as completed, status {}", block_id, response.status());
+        }
+        Ok(())
+    }
+
+}
+
+fn is_completed(block: &Resource, progresses: &HashMap<&str, &Resource>) -> bool {
+    block.related_id("user-progress")
+        .and_then(|id| progresses.get(id))
+        .and_then(|progress| progress.attribute("grade")?.as_f64())
+        .is_some_and(|grade| grade >= 1.0)
+}
+
+// Walks up the parents of the element, until the chapter directly below the root
+fn chapter_of<'a>(element: &'a Resource, root_id: &str, elements_by_id: &HashMap<&str, &'a Resource>) -> &'a Resource {
+    let mut current = element;
+    // Bounded, in case the parents form a cycle
+    for _ in 0..elements_by_id.len() {
+        match current.related_id("parent") {
+            Some(parent_id) if parent_id != root_id => match elements_by_id.get(parent_id) {
+                Some(parent) => current = parent,
+                None => break,
+            },
+            _ => break,
+        }
+    }
+    current
+}
+
+fn compute_progress(root_id: &str, elements: &[Resource], included: &[Resource]) -> CoursewareProgress {
+    let by_type = |kind: &str| included.iter()
+        .filter(|resource| resource.kind == kind)
+        .map(|resource| (resource.id.as_str(), resource))
+        .collect::<HashMap<_, _>>();
+    let containers = by_type("courseware-containers");
+    let blocks = by_type("courseware-blocks");
+    let progresses = by_type("courseware-user-progresses");
+    let elements_by_id = elements.iter().map(|element| (element.id.as_str(), element)).collect::<HashMap<_, _>>();
+
+    let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+    let mut incomplete_blocks = vec![];
+    for element in elements {
+        let chapter = chapter_of(element, root_id, &elements_by_id);
+        let element_blocks = element.related_ids("containers").into_iter()
+            .filter_map(|id| containers.get(id))
+            .flat_map(|container| container.related_ids("blocks"))
+            .filter_map(|id| blocks.get(id));
+        let (completed, total) = counts.entry(chapter.id.as_str()).or_default();
+        for block in element_blocks {
+            *total += 1;
+            if is_completed(block, &progresses) {
+                *completed += 1;
+                continue;
+            }
+            incomplete_blocks.push(BlockSummary {
+                id: block.id.clone(),
+                title: block.title(),
+                block_type: block.attribute("block-type").and_then(|block_type| block_type.as_str()).unwrap_or_default().to_string(),
+                chapter: chapter.title(),
+                page: element.title(),
+            });
+        }
+    }
+
+    let per_chapter = elements.iter()
+        .filter(|element| element.related_id("parent") == Some(root_id))
+        .map(|chapter| {
+            let aggregate = chapter.meta.pointer("/progress/cumulative").and_then(|progress| progress.as_f64());
+            let percentage = match (aggregate, counts.get(chapter.id.as_str())) {
+                (Some(aggregate), _) => aggregate as f32,
+                (None, Some(&(completed, total))) if total > 0 => completed as f32 / total as f32 * 100.0,
+                _ => 100.0,
+            };
+            (chapter.title(), percentage)
+        })
+        .collect();
+    CoursewareProgress { per_chapter, incomplete_blocks }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_courseware_progress() {
+        let server = MockServer::start();
+        server.route_json("GET", "/studip/jsonapi.php/v1/courses/c1/courseware", 200, r#"{"data": {"type": "courseware-instances", "id": "c1",
+                "relationships": {"root": {"data": {"type": "courseware-structural-elements", "id": "e0"}}}}}"#)
+            .route_json("GET", "/studip/jsonapi.php/v1/courseware-structural-elements/e0/descendants", 200, include_str!("../../testdata/courseware/descendants.json"))
+            .route_json("GET", "/studip/jsonapi.php/v1/courseware-blocks/b2", 200, r#"{"data": {"type": "courseware-blocks", "id": "b2",
+                "attributes": {"title": "Einleitung", "block-type": "text"},
+                "relationships": {"user-progress": {"data": {"type": "courseware-user-progresses", "id": "u1_b2"}}}}}"#)
+            .route_json("GET", "/studip/jsonapi.php/v1/courseware-blocks/b3", 200, r#"{"data": {"type": "courseware-blocks", "id": "b3",
+                "attributes": {"title": "Vorlesung", "block-type": "video"},
+                "relationships": {"user-progress": {"data": {"type": "courseware-user-progresses", "id": "u1_b3"}}}}}"#)
+            .route_json("PATCH", "/studip/jsonapi.php/v1/courseware-user-progresses/u1_b2", 200, r#"{"data": {"type": "courseware-user-progresses", "id": "u1_b2",
+                "attributes": {"grade": 1}}}"#);
+        let module = CoursewareModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: Some(url::Url::parse("https://studip.example.com/studip/dispatch.php/course/courseware?cid=c1").unwrap()),
+        }));
+
+        let progress = module.get_progress().unwrap();
+        assert_eq!(progress.per_chapter, vec![
+            // Computed from the blocks of the chapter and its subchapter
+            ("Grundlagen".to_string(), 50.0),
+            // Provided by Stud.IP
+            ("Sortieren".to_string(), 25.0),
+            ("Leer".to_string(), 100.0),
+        ]);
+        assert_eq!(progress.incomplete_blocks.iter().map(|block| (block.id.as_str(), block.page.as_str())).collect::<Vec<_>>(),
+            vec![("b2", "Einführung"), ("b3", "Vertiefung"), ("b5", "Quicksort")]);
+        assert_eq!(progress.incomplete_blocks[1].chapter, "Grundlagen");
+        assert_eq!(progress.incomplete_blocks[1].block_type, "video");
+
+        module.mark_block_completed("b2").unwrap();
+        let request = server.requests().into_iter().find(|request| request.method == "PATCH").unwrap();
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body["data"]["attributes"]["grade"], 1.0);
+        // Videos track their completion themselves
+        assert!(module.mark_block_completed("b3").unwrap_err().to_string().contains("video"));
+    }
+}
@@ -1,18 +1,42 @@
 use std::any::Any;
-use std::path::Path;
-use std::str::FromStr;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use anyhow::Context;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use anyhow::{bail, Context};
 use chrono::{DateTime, Utc};
-use scraper::{Html, Selector};
+use reqwest::blocking::multipart::{Form, Part};
+use reqwest::blocking::Response;
+use scraper::Html;
 use serde::{Deserialize, Deserializer, Serialize};
 use chrono::serde::ts_seconds;
 use crate::user::{get_username_from_url, User};
 use crate::ref_source::ReferenceSource;
 use crate::course_modules::{CourseModule, CourseModuleData};
+use crate::ids::{FileId, FolderId, UserId};
+use crate::{SendThrough, StudIpClient};
+use crate::throttle::BandwidthLimiter;
+use crate::error::{check_not_found, check_status, upload_rejection, StudIpError};
+use crate::flash::{self, Severity};
+use crate::warnings::{ParseWarnings, Parsed};
+use crate::logging::log_parse;
+use crate::util::{glob_match, local_to_utc, parse_localized_date_time, parse_page, parse_security_token, parse_size, sanitize_file_name, selector};
 
 const FILE_MODULE_URL : &str = "https://studip.example.com/dispatch.php/course/files";
 const DOWNLOAD_URL : &str = "https://studip.example.com/sendfile.php";
+const FILE_UPLOAD_URL : &str = "https://studip.example.com/dispatch.php/file/upload";
+const FILE_DETAILS_URL : &str = "https://studip.example.com/dispatch.php/file/details";
+const FILE_RESTORE_URL : &str = "https://studip.example.com/dispatch.php/file/restore";
+const FILE_DELETE_URL : &str = "https://studip.example.com/dispatch.php/file/delete";
+/// The names of the trash folder, if it is not marked by its icon
+const TRASH_FOLDER_NAMES: [&str; 4] = ["papierkorb", "gelöschte dateien", "trash", "deleted files"];
+/// How often the progress of a running upload is checked
+const UPLOAD_PROGRESS_INTERVAL: Duration = Duration::from_millis(25);
+/// The name of the file, in which the names of the saved files are kept by their id, in each directory files are saved to
+pub const SAVED_NAMES_FILE: &str = ".stud_ip_files.json";
 
 /// Module, that enables operating on the files and folders of a course
 #[derive(Debug)]
@@ -34,73 +58,773 @@ impl CourseModule for FileModule {
     fn as_any(&mut self) -> &mut dyn Any {
         self
     }
+
+    fn data(&self) -> Option<&CourseModuleData> {
+        Some(&self.module_data)
+    }
 }
 
 impl FileModule {
 
-    fn parse_into_folder_contents(&self, response_text: &str) -> anyhow::Result<FolderContents> {
-        let html = Html::parse_document(response_text);
-        let files_form = html.select(&Selector::parse("#files_table_form").unwrap())
-            .next()
-            .context("Could not find files table form")?;
-        let file_form_element = files_form.value();
-        let data_files = file_form_element.attr("data-files")
-            .context("Could not get files")?;
-        let data_folders = file_form_element.attr("data-folders")
-            .context("Could not get folders")?;
-
-        let their_files: Vec<TheirFile> = serde_json::from_str(data_files)?;
-        let their_folders: Vec<TheirFolder> = serde_json::from_str(data_folders)?;
-        Ok(FolderContents {
-            folders: their_folders.into_iter()
-                .map(|f| try_folder_from_their(f, &self.module_data.course_id))
-                .collect::<Result<_, _>>()?,
-            files: their_files.into_iter()
-                .map(|f| try_file_from_their(f, &self.module_data.course_id))
-                .collect::<Result<_, _>>()?,
-        })
+    // The url of the module, as linked by its tab
+    fn module_url(&self) -> String {
+        self.module_data.base_url(FILE_MODULE_URL)
+    }
+
+    // The JSON:API is preferred, as it does not depend on the html of the files page.
+    // If it is disabled on the installation, the html pages are scraped instead.
+    #[cfg(feature = "jsonapi")]
+    fn jsonapi(&self) -> crate::jsonapi::JsonApi<'_> {
+        crate::jsonapi::JsonApi::new(&self.module_data.client)
+    }
+
+    // Makes the `request` through the JSON:API, `None` if it is not available, so that the html pages are used instead
+    // Only the JSON:API being unavailable falls back, every other error is returned. Once it is known to be unavailable, it is not tried again.
+    #[cfg(feature = "jsonapi")]
+    fn try_jsonapi<T>(&self, request: impl FnOnce(&crate::jsonapi::JsonApi) -> anyhow::Result<T>) -> anyhow::Result<Option<T>> {
+        let jsonapi = self.jsonapi();
+        if jsonapi.is_available() == Some(false) {
+            return Ok(None);
+        }
+        match request(&jsonapi) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if matches!(e.downcast_ref::<StudIpError>(), Some(StudIpError::FeatureDisabled { .. })) => Ok(None),
+            Err(e) => Err(e),
+        }
     }
 
-    /// Returns the courses root [`FolderContents`].
+    /// Discards the cached pages of the course, so that they are fetched again \
+    /// Pages are reused by consecutive reads, see [Course::set_cache_freshness()](crate::course::Course::set_cache_freshness()).
+    pub fn refresh(&self) {
+        self.module_data.refresh();
+    }
+
+    /// Returns the courses root [`FolderContents`]. \
+    /// With the `jsonapi` feature, the JSON:API is used, if it is available on the installation.
     pub fn get_root(&self) -> anyhow::Result<FolderContents> {
-        let response = self.module_data.client.get(FILE_MODULE_URL)
-            .query(&[("cid", &self.module_data.course_id)])
-            .send()?;
-        self.parse_into_folder_contents(&response.text()?)
+        Ok(self.get_root_with_warnings()?.value)
+    }
+
+    /// Returns the courses root [`FolderContents`], together with a warning for each file or folder, that could not be parsed
+    pub fn get_root_with_warnings(&self) -> anyhow::Result<Parsed<FolderContents>> {
+        #[cfg(feature = "jsonapi")]
+        if let Some(contents) = self.try_jsonapi(|jsonapi| jsonapi.course_root_folder(&self.module_data.course_id)
+            .and_then(|root| jsonapi.folder_files(&root.object.id)))? {
+            return Ok(ParseWarnings::default().into_parsed(contents));
+        }
+        let body = self.module_data.get_page(&self.module_url(), &[("cid", &self.module_data.course_id)])?;
+        parse_folder_page(&body, &self.module_data.client, &self.module_data.course_id)
     }
 
     /// Returns the [`FolderContents`] of a specific folder. \
     /// The `folder_id` parameter specifies the ID of the folder.
-    pub fn get_folder(&self, folder_id: &str) -> anyhow::Result<FolderContents> {
-        let response = self.module_data.client.get(format!("{}/index/{}", FILE_MODULE_URL, folder_id))
-            .query(&[("cid", &self.module_data.course_id)])
-            .send()?;
-        self.parse_into_folder_contents(&response.text()?)
+    pub fn get_folder(&self, folder_id: &FolderId) -> anyhow::Result<FolderContents> {
+        Ok(self.get_folder_with_warnings(folder_id)?.value)
+    }
+
+    /// Returns the [`FolderContents`] of a specific folder, together with a warning for each file or folder, that could not be parsed
+    pub fn get_folder_with_warnings(&self, folder_id: &FolderId) -> anyhow::Result<Parsed<FolderContents>> {
+        #[cfg(feature = "jsonapi")]
+        if let Some(contents) = self.try_jsonapi(|jsonapi| jsonapi.folder_files(folder_id))? {
+            return Ok(ParseWarnings::default().into_parsed(contents));
+        }
+        let body = self.module_data.get_page(&format!("{}/index/{}", self.module_url(), folder_id), &[("cid", &self.module_data.course_id)])?;
+        let mut contents = parse_folder_page(&body, &self.module_data.client, &self.module_data.course_id)?;
+        for file in &mut contents.value.files {
+            file.folder_id = Some(folder_id.clone());
+        }
+        Ok(contents)
+    }
+
+    // The trash is a special top folder, which is missing if the trash is disabled
+    fn get_trash_folder(&self) -> anyhow::Result<Folder> {
+        self.get_root()?.folders.into_iter()
+            .find(|folder| folder.object.icon.contains("trash") || TRASH_FOLDER_NAMES.contains(&folder.object.name.trim().to_lowercase().as_str()))
+            .ok_or_else(|| StudIpError::FeatureDisabled { feature: "The trash".to_string() }.into())
+    }
+
+    /// Returns the [`FolderContents`] of the trash, which holds the deleted files and folders of the course \
+    /// Fails with [`StudIpError::FeatureDisabled`], if the installation does not keep deleted files.
+    pub fn get_trash(&self) -> anyhow::Result<FolderContents> {
+        let trash = self.get_trash_folder()?;
+        self.get_folder(&trash.id())
+    }
+
+    /// Restores a file or folder from the trash, into the folder it was deleted from or into the `target_folder_id`
+    pub fn restore(&self, object_id: &str, target_folder_id: Option<&FolderId>) -> anyhow::Result<()> {
+        let client = &self.module_data.client;
+        let security_token = client.security_token()?;
+        let mut params = vec![("security_token", security_token.as_str())];
+        if let Some(target_folder_id) = target_folder_id {
+            params.push(("to_folder_id", target_folder_id.as_str()));
+        }
+        let response = client.post(format!("{}/{}", FILE_RESTORE_URL, object_id))
+            .query(&[("cid", self.module_data.course_id.as_str())])
+            .form(&params)
+            .send_through(client)?;
+        check_status(response.status())?;
+        if !response.status().is_success() {
+            bail!("Could not restore {}. Status Code: {}", object_id, response.status());
+        }
+        flash::check(&parse_page(&response.text()?)?)?;
+        self.module_data.invalidate(&self.module_url());
+        Ok(())
+    }
+
+    /// Deletes a file or folder in the trash permanently, which can not be undone
+    pub fn purge(&self, object_id: &str) -> anyhow::Result<()> {
+        // Opens the confirmation dialog and then confirms it with the dialog's security token
+        let client = &self.module_data.client;
+        let url = format!("{}/{}", FILE_DELETE_URL, object_id);
+        let query = [("cid", self.module_data.course_id.as_str())];
+        let response = client.get(&url)
+            .query(&query)
+            .header("X-Requested-With", "XMLHttpRequest")
+            .send_through(client)?;
+        check_status(response.status())?;
+        let security_token = parse_security_token(&parse_page(&response.text()?)?)?;
+        let response = client.post(&url)
+            .query(&query)
+            .form(&[("security_token", security_token.as_str()), ("yes", "1")])
+            .send_through(client)?;
+        if !response.status().is_success() {
+            bail!("Could not delete {}. Status Code: {}", object_id, response.status());
+        }
+        flash::check(&parse_page(&response.text()?)?)?;
+        self.module_data.invalidate(&self.module_url());
+        Ok(())
+    }
+
+    /// Returns a single [`File`] by its id, without listing its folder (e.g. for files linked by notifications) \
+    /// Besides the details page, the download is requested once without its body, for the exact size and the mime type of the file. \
+    /// Fails with [`StudIpError::NotFound`](crate::error::StudIpError::NotFound) or [`StudIpError::PermissionDenied`](crate::error::StudIpError::PermissionDenied),
+    /// if the file does not exist or is not accessible.
+    pub fn get_file(&self, file_id: &FileId) -> anyhow::Result<File> {
+        let client = &self.module_data.client;
+        let response = client.get(format!("{}/{}", FILE_DETAILS_URL, file_id))
+            .query(&[("cid", self.module_data.course_id.as_str())])
+            .header("X-Requested-With", "XMLHttpRequest")
+            .send_through(client)?;
+        check_status(response.status())?;
+        let html = parse_page(&response.text()?)?;
+        check_not_found(&html)?;
+        let mut file = self.parse_file_details(file_id, &html)?;
+        let download = match &file.download_url {
+            Some(download_url) => client.head(download_url).send_through(client)?,
+            None => request_file_by_id(client, file_id, &file.object.name, true)?,
+        };
+        file.is_accessible = download.status().is_success();
+        if file.is_accessible {
+            // The body of a HEAD response is empty, so the length is read from the header instead of `content_length()`
+            let header = |name| download.headers().get(name).and_then(|value| value.to_str().ok());
+            if let Some(size) = header(reqwest::header::CONTENT_LENGTH).and_then(|size| size.parse().ok()) {
+                file.size = size;
+            }
+            if let Some(mime_type) = header(reqwest::header::CONTENT_TYPE) {
+                file.object.mime_type = mime_type.split(';').next().unwrap_or_default().trim().to_string();
+            }
+        }
+        Ok(file)
+    }
+
+    // Parses the details page of a file, which only shows a rounded size and no mime type
+    fn parse_file_details(&self, file_id: &FileId, html: &Html) -> anyhow::Result<File> {
+        let client = &self.module_data.client;
+        let aside = html.select(selector!("#file_aside"))
+            .next()
+            .with_context(|| format!("Could not find the details of the file {}", file_id))?;
+        let name = aside.select(selector!("h3"))
+            .next()
+            .map(|heading| heading.text().collect::<String>().trim().to_string())
+            .context("Could not find the name of the file")?;
+        let mut size = 0;
+        let mut downloads = 0;
+        let mut change_date = None;
+        for row in aside.select(selector!("table tr")) {
+            let mut cells = row.select(selector!("td, th"))
+                .map(|cell| cell.text().collect::<String>().trim().to_lowercase());
+            let (Some(label), Some(value)) = (cells.next(), cells.next()) else {
+                continue;
+            };
+            if label.starts_with("größe") || label.starts_with("size") {
+                size = parse_size(&value).unwrap_or(0) as usize;
+            } else if label.starts_with("downloads") {
+                downloads = parse_count(&value).unwrap_or(0);
+            } else if label.starts_with("geändert") || label.starts_with("changed") || label.starts_with("modified") {
+                change_date = parse_localized_date_time(&value).and_then(|date_time| local_to_utc(date_time, client.timezone()));
+            }
+        }
+        let owner = aside.select(selector!("table a[href*='username=']"))
+            .next()
+            .context("Could not find the owner of the file")?;
+        let author = User {
+            display_name: owner.text().collect::<String>().trim().to_string(),
+            username: get_username_from_url(client.absolutize(owner.value().attr("href").unwrap_or_default())?)?,
+            avatar_src: None,
+            source: ReferenceSource::Course(self.module_data.course_id.clone()),
+            user_id: None,
+        };
+        let icon = aside.select(selector!(".FileIcon img"))
+            .next()
+            .and_then(|icon| icon.value().attr("src"))
+            .map(|icon| absolutize_icon(client, icon.to_string()))
+            .transpose()?
+            .unwrap_or_default();
+        // The folder is only referenced by the button, that opens it
+        let folder_id = html.select(selector!("a[href*='/files/index/']"))
+            .filter_map(|link| link.value().attr("href"))
+            .filter_map(|href| href.split("/files/index/").nth(1))
+            .filter_map(|rest| rest.split(['?', '/', '#']).next())
+            .find(|id| !id.is_empty())
+            .map(FolderId::from);
+        Ok(File {
+            object: FilesObject {
+                id: file_id.to_string(),
+                name,
+                change_date: change_date.context("Could not find the change date of the file")?,
+                author,
+                icon,
+                mime_type: String::new(),
+            },
+            size,
+            downloads,
+            restricted_terms_of_use: false,
+            new: false,
+            is_editable: html.select(selector!("a[href*='/file/edit/']")).next().is_some(),
+            is_accessible: true,
+            folder_id,
+            download_url: html.select(selector!("a[href*='sendfile.php']"))
+                .next()
+                .and_then(|link| link.value().attr("href"))
+                .map(|href| client.absolutize(href).map(|url| url.to_string()))
+                .transpose()?,
+        })
+    }
+
+    /// Downloads a file, of which only the id is known, and returns its bytes \
+    /// The `name_hint` is only used for the download url, it does not need to match the actual name of the file.
+    /// Fails with [`StudIpError::NotFound`](crate::error::StudIpError::NotFound) or [`StudIpError::PermissionDenied`](crate::error::StudIpError::PermissionDenied),
+    /// if the file does not exist or is not accessible.
+    pub fn download_by_id(&self, file_id: &FileId, name_hint: &str) -> anyhow::Result<Vec<u8>> {
+        // The content of files is not JSON, so the JSON:API is only used for it, once it is known to be available
+        #[cfg(feature = "jsonapi")]
+        if self.jsonapi().is_available() == Some(true) {
+            return self.jsonapi().download_file_ref(file_id);
+        }
+        let response = request_file_by_id(&self.module_data.client, file_id, name_hint, false)?;
+        check_status(response.status())?;
+        if !response.status().is_success() {
+            bail!("Could not download {}. Status code: {}", file_id, response.status());
+        }
+        Ok(response.bytes()?.to_vec())
+    }
+
+    /// Returns the [`FolderContents`] of a specific folder by its untyped id
+    #[deprecated(note = "Use FileModule::get_folder() with a FolderId instead")]
+    pub fn get_folder_str(&self, folder_id: &str) -> anyhow::Result<FolderContents> {
+        self.get_folder(&folder_id.into())
+    }
+
+    /// Recursively queries all folders of the course and returns them as a [`FolderTree`] \
+    /// *Note: This makes one request per folder*
+    pub fn get_tree(&self) -> anyhow::Result<FolderTree> {
+        let root = self.get_root()?;
+        self.build_tree(None, root)
+    }
+
+    fn build_tree(&self, folder: Option<Folder>, contents: FolderContents) -> anyhow::Result<FolderTree> {
+        let mut children = vec![];
+        for child in contents.folders {
+            let child_contents = self.get_folder(&child.id())?;
+            children.push(self.build_tree(Some(child), child_contents)?);
+        }
+        Ok(FolderTree {
+            folder,
+            files: contents.files,
+            children,
+        })
     }
 
     /// Downloads a [`File`] and returns its bytes
     pub fn download_file(&self, file: &File) -> anyhow::Result<Vec<u8>> {
-        let response = self.module_data.client.get(DOWNLOAD_URL)
-            .query(&[("type", "0")])
-            .query(&[("file_id", &file.object.id)])
-            .query(&[("file_name", &file.object.name)])
-            .send()?;
-        Ok(response.bytes()?.to_vec())
+        let mut bytes = Vec::with_capacity(file.size);
+        self.download_to_writer(file, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Streams the content of a [`File`] into the `writer` (e.g. an archive) without buffering it and returns the number of written bytes \
+    /// Fails, if Stud.IP answered with a page instead of the file, or if the number of bytes does not match the size of the `file`.
+    pub fn download_to_writer(&self, file: &File, writer: &mut dyn Write) -> anyhow::Result<u64> {
+        self.stream_file(file, writer, None)
+    }
+
+    fn stream_file(&self, file: &File, writer: &mut dyn Write, limiter: Option<&BandwidthLimiter>) -> anyhow::Result<u64> {
+        let mut response = self.request_file(file)?;
+        if !response.status().is_success() {
+            bail!("Could not download {}. Status code: {}", file.object.name, response.status());
+        }
+        let content_type = response.headers().get(reqwest::header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .unwrap_or_default();
+        // Error and login pages are served with a success status, but can be told apart by their type
+        if content_type.starts_with("text/html") && !file.object.mime_type.starts_with("text/html") {
+            bail!("Could not download {}, received a page instead of {}", file.object.name, file.object.mime_type);
+        }
+        let written = match limiter {
+            Some(limiter) => limiter.copy(&mut response, writer),
+            None => std::io::copy(&mut response, writer),
+        }.with_context(|| format!("Could not download {}", file.object.name))?;
+        if file.size != 0 && written != file.size as u64 {
+            bail!("Could not download {}, received {} of {} bytes", file.object.name, written, file.size);
+        }
+        Ok(written)
+    }
+
+    // Requests the content of a file, so that its body can be streamed
+    // The url, that Stud.IP provided for the file, is preferred, as some installations route downloads through other controllers.
+    fn request_file(&self, file: &File) -> anyhow::Result<Response> {
+        let client = &self.module_data.client;
+        if let Some(download_url) = &file.download_url {
+            return client.get_with_timeout(download_url, client.transfer_timeout()).send_through(client);
+        }
+        #[cfg(feature = "jsonapi")]
+        if self.jsonapi().is_available() == Some(true) {
+            return self.jsonapi().file_ref_content(&file.object.id);
+        }
+        request_file_by_id(client, &file.object.id, &file.object.name, false)
     }
 
     /// Saves a [`File`] to a specified location. \
     /// The `file` parameter specifies the file to be saved. \
     /// The `to` parameter specifies the location where the file will be saved. \
-    /// Note: The file is not streamed to disk, which could lead to memory issues for very large files.
+    /// The file is streamed to disk (see [`FileModule::download_to_writer()`]), an incomplete file is removed again. \
+    /// Its name is sanitized, so that it can not escape the location and is valid on common file systems (see [`DownloadOptions::raw_names()`]).
     pub fn save_file_to(&self, file: &File, to: impl AsRef<Path>) -> anyhow::Result<()> {
-        let bytes = self.download_file(file)?;
-        let path = to.as_ref().join(&file.object.name);
-        std::fs::write(path, bytes)?;
+        self.save_file_with(file, to, &DownloadOptions::default())
+    }
+
+    /// Saves a [`File`] to a specified location, like [`FileModule::save_file_to()`], using the given [`DownloadOptions`] \
+    /// The name, under which the file was saved, is kept by its id in the [`SAVED_NAMES_FILE`] of the location, so that saving it again replaces it under the same name.
+    /// If another file with the same name already exists in the location, the [`CollisionPolicy`] of the options decides what happens.
+    pub fn save_file_with(&self, file: &File, to: impl AsRef<Path>, options: &DownloadOptions) -> anyhow::Result<()> {
+        let to = to.as_ref();
+        let mut names = SavedNames::load(to);
+        let path = match names.get(&file.object.id) {
+            Some(name) => Some(to.join(name)),
+            None => {
+                let path = to.join(options.file_name(&file.object.name).as_ref());
+                options.collision_policy.resolve(path, |path| path.exists() || SavedNames::is_own_file(path))?
+            },
+        };
+        if let Some(path) = path {
+            self.write_file(file, &path, options)?;
+            names.insert(&file.object.id, to, &path);
+            names.store(to)?;
+        }
+        Ok(())
+    }
+
+    fn write_file(&self, file: &File, path: &Path, options: &DownloadOptions) -> anyhow::Result<()> {
+        let mut handle = std::fs::File::create(path)
+            .with_context(|| format!("Could not create {}", path.display()))?;
+        if let Err(e) = self.stream_file(file, &mut handle, options.limiter.as_deref()) {
+            drop(handle);
+            let _ = std::fs::remove_file(path);
+            return Err(e);
+        }
+        if options.preserve_mtime {
+            set_modified(path, file.object.change_date)?;
+        }
+        Ok(())
+    }
+
+    /// Saves all files of a [`FolderTree`] into the `to` directory, preserving the folder structure \
+    /// File and folder names are sanitized, so that they are valid on common file systems (see [`DownloadOptions::raw_names()`]). \
+    /// Files with the same name in the same folder, which Stud.IP allows, are handled by the [`CollisionPolicy`] of the options.
+    /// Only the files of the tree itself collide and their names are kept in the [`SAVED_NAMES_FILE`] of each folder,
+    /// so that saving it again replaces the files of the last time under the same names, even if files were added on the server.
+    /// Folders with the same name are merged. \
+    /// Failures of single files do not abort saving the tree, instead they are returned together with the path of the file.
+    pub fn save_tree_to(&self, tree: &FolderTree, to: impl AsRef<Path>, options: &DownloadOptions) -> anyhow::Result<Vec<(PathBuf, anyhow::Error)>> {
+        let mut saved = SavedTree::default();
+        self.save_tree(tree, to.as_ref(), options, &mut saved)?;
+        Ok(saved.failures)
+    }
+
+    pub(crate) fn save_tree<'a>(&self, tree: &'a FolderTree, to: &Path, options: &DownloadOptions, saved: &mut SavedTree<'a>) -> anyhow::Result<()> {
+        std::fs::create_dir_all(to).with_context(|| format!("Could not create {}", to.display()))?;
+        let mut names = SavedNames::load(to);
+        // The files, that were saved before, keep their names, so that the new ones can not take them
+        saved.taken.insert(SavedTree::key(&to.join(SAVED_NAMES_FILE)));
+        saved.taken.extend(tree.files.iter()
+            .filter_map(|file| names.get(&file.object.id))
+            .map(|name| SavedTree::key(&to.join(name))));
+        for file in &tree.files {
+            let path = match names.get(&file.object.id) {
+                Some(name) => to.join(name),
+                None => {
+                    let path = to.join(options.file_name(&file.object.name).as_ref());
+                    match options.collision_policy.resolve(path.clone(), |path| saved.taken.contains(&SavedTree::key(path))) {
+                        Ok(Some(path)) => path,
+                        Ok(None) => continue,
+                        Err(e) => {
+                            saved.failures.push((path, e));
+                            continue;
+                        },
+                    }
+                },
+            };
+            saved.taken.insert(SavedTree::key(&path));
+            match self.write_file(file, &path, options) {
+                Ok(()) => {
+                    names.insert(&file.object.id, to, &path);
+                    saved.files.push((path, file));
+                },
+                Err(e) => saved.failures.push((path, e)),
+            }
+        }
+        names.store(to)?;
+        for child in &tree.children {
+            let name = child.folder.as_ref().map(|folder| folder.object.name.as_str()).unwrap_or_default();
+            self.save_tree(child, &to.join(options.file_name(name).as_ref()), options, saved)?;
+        }
+        // The directory is only touched after its contents were written, as writing them changes its modification time
+        if let (Some(folder), true) = (&tree.folder, options.preserve_mtime) {
+            if let Err(e) = set_modified(to, folder.object.change_date) {
+                saved.failures.push((to.to_path_buf(), e));
+            }
+        }
         Ok(())
     }
 
-    // TODO: Add file upload option
+    /// Uploads a single file into the folder, see [`FileModule::upload_files()`]
+    pub fn upload_file(&self, folder_id: &FolderId, path: impl AsRef<Path>, options: &UploadOptions) -> anyhow::Result<File> {
+        let result = self.upload(folder_id, path.as_ref(), options, &mut |_| {});
+        self.module_data.invalidate(&self.module_url());
+        result
+    }
+
+    /// Uploads the files at the `paths` into the folder one after another, reporting their progress to `on_progress` \
+    /// The files are streamed from disk and the security token of the session is only fetched once. \
+    /// Failures of single files do not abort the other uploads, instead they are returned together with the path of the file, in the order of the `paths`.
+    /// Rejections by Stud.IP are returned as [`StudIpError`](crate::error::StudIpError)s, like [`StudIpError::FileTypeBlocked`](crate::error::StudIpError::FileTypeBlocked).
+    pub fn upload_files(&self, folder_id: &FolderId, paths: &[PathBuf], options: UploadOptions, mut on_progress: impl FnMut(UploadEvent)) -> Vec<(PathBuf, anyhow::Result<File>)> {
+        let results = paths.iter()
+            .map(|path| {
+                let result = self.upload(folder_id, path, &options, &mut on_progress);
+                on_progress(UploadEvent::Finished { path: path.clone(), success: result.is_ok() });
+                (path.clone(), result)
+            })
+            .collect();
+        self.module_data.invalidate(&self.module_url());
+        results
+    }
+
+    fn upload(&self, folder_id: &FolderId, path: &Path, options: &UploadOptions, on_progress: &mut impl FnMut(UploadEvent)) -> anyhow::Result<File> {
+        let client = &self.module_data.client;
+        let security_token = client.security_token()?;
+        let file_name = path.file_name()
+            .with_context(|| format!("{} is not a file", path.display()))?
+            .to_string_lossy()
+            .to_string();
+        let handle = std::fs::File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+        let total_bytes = handle.metadata()?.len();
+        let sent_bytes = Arc::new(AtomicU64::new(0));
+        let reader = CountingReader { inner: handle, count: sent_bytes.clone() };
+        let mut form = Form::new()
+            .text("security_token", security_token)
+            .part("file[]", Part::reader_with_length(reader, total_bytes).file_name(file_name.clone()));
+        if let Some(terms_of_use_id) = &options.terms_of_use_id {
+            form = form.text("content_terms_of_use_id", terms_of_use_id.clone());
+        }
+        let request = client.post_with_timeout(format!("{}/{}", FILE_UPLOAD_URL, folder_id), client.transfer_timeout())
+            .query(&[("cid", self.module_data.course_id.as_str())])
+            .header("X-Requested-With", "XMLHttpRequest")
+            .multipart(form);
+        on_progress(UploadEvent::Started { path: path.to_path_buf(), total_bytes });
+        // The request is sent on another thread, so that the progress can be reported from this one
+        let response = std::thread::scope(|scope| {
+            let upload = scope.spawn(|| request.send_through(client));
+            let mut reported_bytes = 0;
+            loop {
+                let finished = upload.is_finished();
+                let sent = sent_bytes.load(Ordering::Relaxed);
+                if sent != reported_bytes {
+                    reported_bytes = sent;
+                    on_progress(UploadEvent::Progress { path: path.to_path_buf(), sent_bytes: sent, total_bytes });
+                }
+                if finished {
+                    break;
+                }
+                std::thread::sleep(UPLOAD_PROGRESS_INTERVAL);
+            }
+            upload.join().expect("Upload thread panicked")
+        })?;
+        self.parse_upload_response(response, &file_name)
+    }
+
+    // Parses the added file from the response, or the reason, why the file was rejected
+    fn parse_upload_response(&self, response: Response, file_name: &str) -> anyhow::Result<File> {
+        let status = response.status();
+        let text = response.text()?;
+        let message = match serde_json::from_str::<TheirUploadResponse>(&text) {
+            Ok(their) => match their.added_files.into_iter().next() {
+                Some(their_file) if status.is_success() => return try_file_from_their(their_file, &self.module_data.client, &self.module_data.course_id),
+                _ => their.error.join(" "),
+            },
+            Err(_) => flash::extract(&parse_page(&text)?)
+                .into_iter()
+                .find(|message| message.severity == Severity::Error)
+                .map(|message| message.text)
+                .unwrap_or_default(),
+        };
+        if let Some(rejection) = upload_rejection(file_name, &message) {
+            return Err(rejection.into());
+        }
+        if message.is_empty() {
+            bail!("Could not upload {}, no file was added. Status code: {}", file_name, status);
+        }
+        bail!("Could not upload {}: {}", file_name, message)
+    }
+
+}
+
+/// Options for uploading files with [`FileModule::upload_files()`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadOptions {
+    /// The id of the license (terms of use), under which the files are published, the default of the installation is used if `None`
+    pub terms_of_use_id: Option<String>,
+}
+
+/// The progress of a single file of [`FileModule::upload_files()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadEvent {
+    /// The file was opened and its upload starts
+    Started { path: PathBuf, total_bytes: u64 },
+    /// A part of the file was sent
+    Progress { path: PathBuf, sent_bytes: u64, total_bytes: u64 },
+    /// The upload of the file ended, the error of a failed upload is returned by [`FileModule::upload_files()`]
+    Finished { path: PathBuf, success: bool },
+}
+
+// Counts the bytes read from the inner reader, so that the progress of a request can be observed while it is sent
+struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// The files, that were saved by [`FileModule::save_tree()`]
+#[derive(Debug, Default)]
+pub(crate) struct SavedTree<'a> {
+    pub files: Vec<(PathBuf, &'a File)>,
+    pub failures: Vec<(PathBuf, anyhow::Error)>,
+    /// The paths of the saved files, compared without their case, as some file systems do
+    taken: HashSet<String>,
+}
+
+impl SavedTree<'_> {
+
+    fn key(path: &Path) -> String {
+        path.to_string_lossy().to_lowercase()
+    }
+
+}
+
+/// The names, under which the files were saved in a directory, by their id, see [`SAVED_NAMES_FILE`]
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SavedNames(BTreeMap<String, String>);
+
+impl SavedNames {
+
+    // A missing or unreadable file is treated as empty, so that the files are saved as if for the first time
+    fn load(dir: &Path) -> Self {
+        let Ok(text) = std::fs::read_to_string(dir.join(SAVED_NAMES_FILE)) else {
+            return Self::default();
+        };
+        let mut names: Self = serde_json::from_str(&text).unwrap_or_default();
+        // Only plain names are used, so that an edited file can not point outside the directory
+        names.0.retain(|_, name| Self::is_plain_name(name));
+        names
+    }
+
+    fn store(&self, dir: &Path) -> anyhow::Result<()> {
+        let path = dir.join(SAVED_NAMES_FILE);
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Could not write {}", path.display()))
+    }
+
+    fn get(&self, id: &str) -> Option<&str> {
+        self.0.get(id).map(String::as_str)
+    }
+
+    // Raw names, which escape the directory, are not kept
+    fn insert(&mut self, id: &str, dir: &Path, path: &Path) {
+        if let Some(name) = path.strip_prefix(dir).ok().and_then(Path::to_str).filter(|name| Self::is_plain_name(name)) {
+            self.0.insert(id.to_string(), name.to_string());
+        }
+    }
+
+    fn is_plain_name(name: &str) -> bool {
+        Path::new(name).file_name() == Some(std::ffi::OsStr::new(name))
+    }
+
+    fn is_own_file(path: &Path) -> bool {
+        path.file_name().is_some_and(|name| name.eq_ignore_ascii_case(SAVED_NAMES_FILE))
+    }
+
+}
+
+/// What happens, when a file is saved under a name, that is already taken (e.g. by another file with the same name in the same folder)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CollisionPolicy {
+    /// Replaces the other file
+    Overwrite,
+    /// Keeps the other file and does not download the new one
+    Skip,
+    /// Appends ` (n)` to the name before its extension (e.g. `Blatt 1 (1).pdf`), with the lowest `n`, that is not taken
+    #[default]
+    RenameWithSuffix,
+    /// Fails to save the new file
+    Error,
+}
+
+impl CollisionPolicy {
+
+    // Decides, under which path the file is saved, `None` if it is skipped
+    fn resolve(self, path: PathBuf, is_taken: impl Fn(&Path) -> bool) -> anyhow::Result<Option<PathBuf>> {
+        if !is_taken(&path) {
+            return Ok(Some(path));
+        }
+        match self {
+            Self::Overwrite => Ok(Some(path)),
+            Self::Skip => Ok(None),
+            Self::Error => bail!("{} already exists", path.display()),
+            Self::RenameWithSuffix => {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+                let extension = path.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+                Ok((1..).map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+                    .find(|path| !is_taken(path)))
+            },
+        }
+    }
+
+}
+
+/// Options for saving files to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadOptions {
+    /// Sets the modification time of saved files and folders to their change date on the server \
+    /// Enabled by default. *Note: Folders keep the time, at which they were written, on Windows*
+    pub preserve_mtime: bool,
+    /// Saves files and folders under their names on the server, instead of sanitizing them \
+    /// Disabled by default. *Warning: Names like `../../.bashrc` can then escape the target directory*
+    #[serde(default)]
+    pub raw_names: bool,
+    /// What happens, when a file is saved under a name, that is already taken, [`CollisionPolicy::RenameWithSuffix`] by default
+    #[serde(default)]
+    pub collision_policy: CollisionPolicy,
+    /// Shared by all clones of the options, so that concurrent downloads share the rate
+    #[serde(skip)]
+    limiter: Option<Arc<BandwidthLimiter>>,
+}
+
+impl DownloadOptions {
+
+    /// Limits the average download rate to `max_bytes_per_sec`, `None` removes the limit (the default) \
+    /// Downloads, which use clones of these options (e.g. in [MyCourses::for_each_course_parallel()](crate::course::MyCourses::for_each_course_parallel())), share the limit. \
+    /// *Note: The limit is not serialized*
+    pub fn max_bytes_per_sec(mut self, max_bytes_per_sec: Option<u64>) -> Self {
+        self.limiter = max_bytes_per_sec.map(|max_bytes_per_sec| Arc::new(BandwidthLimiter::new(max_bytes_per_sec)));
+        self
+    }
+
+    /// Saves files and folders under their names on the server, instead of sanitizing them, which is disabled by default
+    pub fn raw_names(mut self, raw_names: bool) -> Self {
+        self.raw_names = raw_names;
+        self
+    }
+
+    /// Sets what happens, when a file is saved under a name, that is already taken
+    pub fn collision_policy(mut self, collision_policy: CollisionPolicy) -> Self {
+        self.collision_policy = collision_policy;
+        self
+    }
+
+    /// The name, under which a file or folder with the `name` is saved
+    pub fn file_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        if self.raw_names {
+            Cow::Borrowed(name)
+        } else {
+            Cow::Owned(sanitize_file_name(name))
+        }
+    }
+
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self { preserve_mtime: true, raw_names: false, collision_policy: CollisionPolicy::default(), limiter: None }
+    }
+}
+
+impl PartialEq for DownloadOptions {
+    fn eq(&self, other: &Self) -> bool {
+        let max_bytes_per_sec = |options: &Self| options.limiter.as_ref().map(|limiter| limiter.max_bytes_per_sec());
+        self.preserve_mtime == other.preserve_mtime
+            && self.raw_names == other.raw_names
+            && self.collision_policy == other.collision_policy
+            && max_bytes_per_sec(self) == max_bytes_per_sec(other)
+    }
+}
+
+impl Eq for DownloadOptions {}
+
+// Sets the modification time of a file or directory
+// Directories can not be opened as files on Windows, so they keep the time, at which they were written
+fn set_modified(path: &Path, time: DateTime<Utc>) -> anyhow::Result<()> {
+    if cfg!(windows) && path.is_dir() {
+        return Ok(());
+    }
+    let handle = if path.is_dir() {
+        std::fs::File::open(path)
+    } else {
+        std::fs::File::options().write(true).open(path)
+    }.with_context(|| format!("Could not open {}", path.display()))?;
+    handle.set_modified(time.into())
+        .with_context(|| format!("Could not set modification time of {}", path.display()))
+}
+
+/// Downloads a file by its id and returns its bytes
+pub(crate) fn download_file_by_id(client: &StudIpClient, file_id: &str, file_name: &str) -> anyhow::Result<Vec<u8>> {
+    Ok(request_file_by_id(client, file_id, file_name, false)?.bytes()?.to_vec())
+}
+
+// Streams a file, of which only the id is known, into the `writer` and returns the number of written bytes
+pub(crate) fn stream_file_by_id(client: &StudIpClient, file_id: &str, file_name: &str, writer: &mut dyn Write) -> anyhow::Result<u64> {
+    let mut response = request_file_by_id(client, file_id, file_name, false)?;
+    check_status(response.status())?;
+    if !response.status().is_success() {
+        bail!("Could not download {}. Status code: {}", file_name, response.status());
+    }
+    std::io::copy(&mut response, writer).with_context(|| format!("Could not download {}", file_name))
+}
 
+// Requests a file by its id, so that its body can be streamed, or only its headers, if `head_only` is set
+fn request_file_by_id(client: &StudIpClient, file_id: &str, file_name: &str, head_only: bool) -> anyhow::Result<Response> {
+    let request = if head_only {client.head(DOWNLOAD_URL)} else {client.get_with_timeout(DOWNLOAD_URL, client.transfer_timeout())};
+    request
+        .query(&[("type", "0")])
+        .query(&[("file_id", file_id)])
+        .query(&[("file_name", file_name)])
+        .send_through(client)
 }
 
 /// Contains common data for [`Folder`]s and [`File`]s
@@ -110,6 +834,7 @@ pub struct FilesObject {
     pub name: String,
     pub change_date: DateTime<Utc>,
     pub author: User,
+    /// The name of the icon shape (e.g. "file-pdf") or the absolute url of the icon image
     pub icon: String,
     pub mime_type: String,
 }
@@ -129,6 +854,19 @@ pub struct File {
     pub new: bool,
     pub is_editable: bool,
     pub is_accessible: bool,
+    /// The id of the folder, that contains the file, if it is known
+    #[serde(default)]
+    pub folder_id: Option<FolderId>,
+    /// The absolute url, under which Stud.IP offers the download of the file, if it provided one
+    #[serde(default)]
+    pub download_url: Option<String>,
+}
+
+impl File {
+    /// The typed id of the file
+    pub fn id(&self) -> FileId {
+        self.object.id.as_str().into()
+    }
 }
 
 impl PartialEq for File {
@@ -144,12 +882,63 @@ pub struct Folder {
     pub permissions: String
 }
 
+impl Folder {
+    /// The typed id of the folder, as expected by [`FileModule::get_folder()`]
+    pub fn id(&self) -> FolderId {
+        self.object.id.as_str().into()
+    }
+}
+
 impl PartialEq for Folder {
     fn eq(&self, other: &Self) -> bool {
         self.object == other.object
     }
 }
 
+/// The property, by which files are sorted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortKey {
+    Name,
+    Size,
+    ChangeDate,
+}
+
+/// The direction, in which files are sorted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Order {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+fn files_matching<'a>(files: impl Iterator<Item = &'a File>, glob: &str) -> Vec<&'a File> {
+    files.filter(|file| glob_match(glob, &file.object.name)).collect()
+}
+
+fn files_with_mime<'a>(files: impl Iterator<Item = &'a File>, prefix: &str) -> Vec<&'a File> {
+    files.filter(|file| file.object.mime_type.starts_with(prefix)).collect()
+}
+
+fn modified_since<'a>(files: impl Iterator<Item = &'a File>, since: DateTime<Utc>) -> Vec<&'a File> {
+    files.filter(|file| file.object.change_date > since).collect()
+}
+
+fn sorted_by<'a>(files: impl Iterator<Item = &'a File>, key: SortKey, order: Order) -> Vec<&'a File> {
+    let mut files = files.collect::<Vec<_>>();
+    files.sort_by(|a, b| {
+        let ordering = match key {
+            SortKey::Name => a.object.name.to_lowercase().cmp(&b.object.name.to_lowercase()),
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::ChangeDate => a.object.change_date.cmp(&b.object.change_date),
+        };
+        match order {
+            Order::Ascending => ordering,
+            Order::Descending => ordering.reverse(),
+        }
+    });
+    files
+}
+
 /// Combines the [`File`]s and [`Folder`]s inside a Folder
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FolderContents {
@@ -157,14 +946,119 @@ pub struct FolderContents {
     pub files: Vec<File>
 }
 
-fn from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
-    where
-        D: Deserializer<'de>,
-        T: FromStr,
-        T::Err: std::fmt::Display,
-{
-    let s = String::deserialize(deserializer)?;
-    T::from_str(&s).map_err(serde::de::Error::custom)
+impl FolderContents {
+
+    /// Returns the files, which names match the `glob` pattern (e.g. `"*.pdf"`), ignoring the case
+    pub fn files_matching(&self, glob: &str) -> Vec<&File> {
+        files_matching(self.files.iter(), glob)
+    }
+
+    /// Returns the files, which mime type starts with the `prefix` (e.g. `"image/"`)
+    pub fn files_with_mime(&self, prefix: &str) -> Vec<&File> {
+        files_with_mime(self.files.iter(), prefix)
+    }
+
+    /// Returns the files, that were changed after `since`
+    pub fn modified_since(&self, since: DateTime<Utc>) -> Vec<&File> {
+        modified_since(self.files.iter(), since)
+    }
+
+    /// Returns the files sorted by the `key`
+    pub fn sorted_by(&self, key: SortKey, order: Order) -> Vec<&File> {
+        sorted_by(self.files.iter(), key, order)
+    }
+
+    /// The size of all files in bytes, without the contents of sub folders
+    pub fn total_size(&self) -> usize {
+        self.files.iter().map(|file| file.size).sum()
+    }
+
+}
+
+/// A folder with all of its (recursive) sub folders and files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderTree {
+    /// The folder itself, or `None` for the root folder of the course
+    pub folder: Option<Folder>,
+    pub files: Vec<File>,
+    pub children: Vec<FolderTree>,
+}
+
+impl FolderTree {
+
+    /// Returns all files of the tree together with their folder path, relative to this folder
+    pub fn files_with_paths(&self) -> Vec<(PathBuf, &File)> {
+        let mut files = self.files.iter()
+            .map(|file| (PathBuf::new(), file))
+            .collect::<Vec<_>>();
+        for child in &self.children {
+            let name = child.folder.as_ref().map(|folder| folder.object.name.as_str()).unwrap_or_default();
+            files.extend(child.files_with_paths().into_iter().map(|(path, file)| (Path::new(name).join(path), file)));
+        }
+        files
+    }
+
+    /// Returns all files of the tree, including the ones of sub folders
+    pub fn all_files(&self) -> Vec<&File> {
+        let mut files = self.files.iter().collect::<Vec<_>>();
+        for child in &self.children {
+            files.extend(child.all_files());
+        }
+        files
+    }
+
+    /// Returns the files of the tree, which names match the `glob` pattern (e.g. `"*.pdf"`), ignoring the case
+    pub fn files_matching(&self, glob: &str) -> Vec<&File> {
+        files_matching(self.all_files().into_iter(), glob)
+    }
+
+    /// Returns the files of the tree, which mime type starts with the `prefix` (e.g. `"image/"`)
+    pub fn files_with_mime(&self, prefix: &str) -> Vec<&File> {
+        files_with_mime(self.all_files().into_iter(), prefix)
+    }
+
+    /// Returns the files of the tree, that were changed after `since`
+    pub fn modified_since(&self, since: DateTime<Utc>) -> Vec<&File> {
+        modified_since(self.all_files().into_iter(), since)
+    }
+
+    /// Returns all files of the tree sorted by the `key`
+    pub fn sorted_by(&self, key: SortKey, order: Order) -> Vec<&File> {
+        sorted_by(self.all_files().into_iter(), key, order)
+    }
+
+    /// The size of all files of the tree in bytes
+    pub fn total_size(&self) -> usize {
+        self.all_files().into_iter().map(|file| file.size).sum()
+    }
+
+}
+
+// Deserializes a count, which may be a number or a localized string (e.g. "1.234").
+// Malformed counts default to 0, so that a single file can not break the listing of its folder.
+fn lenient_count<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    let count = match &value {
+        serde_json::Value::Number(number) => number.as_u64().and_then(|number| usize::try_from(number).ok()),
+        serde_json::Value::String(text) => parse_count(text),
+        serde_json::Value::Null => Some(0),
+        _ => None,
+    };
+    if count.is_none() {
+        log_parse!(debug, "Could not parse count {}, using 0", value);
+    }
+    Ok(count.unwrap_or(0))
+}
+
+// Parses a count, ignoring thousands separators, where an empty string is 0
+fn parse_count(text: &str) -> Option<usize> {
+    let digits = text.chars()
+        .filter(|c| !matches!(c, '.' | ',' | '\u{a0}' | '\u{202f}') && !c.is_whitespace())
+        .collect::<String>();
+    if digits.is_empty() {
+        return Some(0);
+    }
+    digits.parse().ok()
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -174,12 +1068,12 @@ struct TheirFile {
     pub name: String,
     #[serde(rename = "download_url")]
     pub download_url: Option<String>,
-    #[serde(deserialize_with = "from_str")]
+    #[serde(deserialize_with = "lenient_count")]
     pub downloads: usize,
     #[serde(rename = "mime_type")]
     pub mime_type: String,
     pub icon: String,
-    #[serde(deserialize_with = "from_str")]
+    #[serde(deserialize_with = "lenient_count")]
     pub size: usize,
     #[serde(rename = "author_url")]
     pub author_url: String,
@@ -199,7 +1093,68 @@ struct TheirFile {
     pub is_accessible: bool,
 }
 
-fn try_file_from_their(their: TheirFile, course_id: &str) -> anyhow::Result<File> {
+/// Parses the html of a folder page of the course (e.g. `dispatch.php/course/files`) into its [`FolderContents`], without requesting anything \
+/// Entries, that can not be parsed, are left out. Relative urls are resolved against the placeholder host `studip.example.com`.
+pub fn parse_folder_contents(html: &str, course_id: &str) -> anyhow::Result<FolderContents> {
+    Ok(parse_folder_page(html, StudIpClient::offline(), course_id)?.value)
+}
+
+// Entries, that can not be parsed, are left out with a warning, so that a single file can not break the listing of its folder
+fn parse_folder_page(html: &str, client: &StudIpClient, course_id: &str) -> anyhow::Result<Parsed<FolderContents>> {
+    let html = Html::parse_document(html);
+    let files_form = html.select(selector!("#files_table_form"))
+        .next()
+        .context("Could not find files table form")?;
+    let file_form_element = files_form.value();
+    let data_files = file_form_element.attr("data-files")
+        .context("Could not get files")?;
+    let data_folders = file_form_element.attr("data-folders")
+        .context("Could not get folders")?;
+
+    let their_files: Vec<serde_json::Value> = serde_json::from_str(data_files)?;
+    let their_folders: Vec<serde_json::Value> = serde_json::from_str(data_folders)?;
+    let mut warnings = ParseWarnings::default();
+    let folders = their_folders.into_iter()
+        .enumerate()
+        .filter_map(|(i, their)| warnings.skip_err(
+            format!("folder {}", entry_name(&their, i)),
+            serde_json::from_value(their).map_err(anyhow::Error::from).and_then(|their| try_folder_from_their(their, client, course_id)),
+        ))
+        .collect();
+    let files = their_files.into_iter()
+        .enumerate()
+        .filter_map(|(i, their)| warnings.skip_err(
+            format!("file {}", entry_name(&their, i)),
+            serde_json::from_value(their).map_err(anyhow::Error::from).and_then(|their| try_file_from_their(their, client, course_id)),
+        ))
+        .collect();
+    Ok(warnings.into_parsed(FolderContents { folders, files }))
+}
+
+// The name of a listed file or folder for warnings, falling back to its position
+fn entry_name(their: &serde_json::Value, index: usize) -> String {
+    their.get("name")
+        .and_then(|name| name.as_str())
+        .map_or_else(|| format!("#{}", index + 1), |name| format!("{:?}", name))
+}
+
+// Icons are either the names of icon shapes (e.g. "file-pdf") or paths to their images, which are made absolute
+fn absolutize_icon(client: &StudIpClient, icon: String) -> anyhow::Result<String> {
+    if !icon.contains('/') {
+        return Ok(icon);
+    }
+    Ok(client.absolutize(&icon)?.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+struct TheirUploadResponse {
+    #[serde(default)]
+    added_files: Vec<TheirFile>,
+    #[serde(default)]
+    error: Vec<String>,
+}
+
+fn try_file_from_their(their: TheirFile, client: &StudIpClient, course_id: &str) -> anyhow::Result<File> {
     Ok(File {
         object: FilesObject {
             id: their.id,
@@ -209,9 +1164,10 @@ fn try_file_from_their(their: TheirFile, course_id: &str) -> anyhow::Result<File
                 display_name: their.author_name,
                 username: get_username_from_url(&their.author_url)?,
                 avatar_src: None,
-                source: ReferenceSource::Course(course_id.to_string()),
+                source: ReferenceSource::Course(course_id.into()),
+                user_id: Some(UserId::from(their.author_id)).filter(|user_id| !user_id.is_empty()),
             },
-            icon: their.icon,
+            icon: absolutize_icon(client, their.icon)?,
             mime_type: their.mime_type,
         },
         size: their.size,
@@ -220,6 +1176,11 @@ fn try_file_from_their(their: TheirFile, course_id: &str) -> anyhow::Result<File
         new: their.new,
         is_editable: their.is_editable,
         is_accessible: their.is_accessible,
+        folder_id: None,
+        download_url: their.download_url
+            .filter(|download_url| !download_url.is_empty())
+            .map(|download_url| client.absolutize(&download_url).map(|url| url.to_string()))
+            .transpose()?,
     })
 }
 
@@ -232,7 +1193,7 @@ struct TheirFolder {
     pub url: String,
     #[serde(rename = "user_id")]
     pub user_id: String,
-    #[serde(rename = "object_count")]
+    #[serde(rename = "object_count", deserialize_with = "lenient_count")]
     pub object_count: usize,
     #[serde(rename = "author_name")]
     pub author_name: String,
@@ -247,7 +1208,7 @@ struct TheirFolder {
     pub additional_columns: Vec<serde_json::Value>,
 }
 
-fn try_folder_from_their(their: TheirFolder, course_id: &str) -> anyhow::Result<Folder> {
+fn try_folder_from_their(their: TheirFolder, client: &StudIpClient, course_id: &str) -> anyhow::Result<Folder> {
     Ok(Folder {
         object: FilesObject {
             id: their.id,
@@ -257,12 +1218,537 @@ fn try_folder_from_their(their: TheirFolder, course_id: &str) -> anyhow::Result<
                 display_name: their.author_name,
                 username: get_username_from_url(&their.author_url)?,
                 avatar_src: None,
-                source: ReferenceSource::Course(course_id.to_string()),
+                source: ReferenceSource::Course(course_id.into()),
+                user_id: Some(UserId::from(their.user_id)).filter(|user_id| !user_id.is_empty()),
             },
-            icon: their.icon,
+            icon: absolutize_icon(client, their.icon)?,
             mime_type: their.mime_type,
         },
         object_count: their.object_count,
         permissions: their.permissions,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use super::*;
+    use crate::mock::MockServer;
+    use crate::error::StudIpError;
+
+    fn files_object(id: &str, name: &str, timestamp: i64) -> FilesObject {
+        FilesObject {
+            id: id.to_string(),
+            name: name.to_string(),
+            change_date: Utc.timestamp_opt(timestamp, 0).unwrap(),
+            author: User {
+                display_name: "Max Mustermann".to_string(),
+                username: "mmustermann".to_string(),
+                avatar_src: None,
+                source: ReferenceSource::Unspecified,
+                user_id: None,
+            },
+            icon: String::new(),
+            mime_type: "application/pdf".to_string(),
+        }
+    }
+
+    fn file(id: &str, name: &str, timestamp: i64) -> File {
+        File {
+            object: files_object(id, name, timestamp),
+            size: 4,
+            downloads: 0,
+            restricted_terms_of_use: false,
+            new: false,
+            is_editable: false,
+            is_accessible: true,
+            folder_id: None,
+            download_url: None,
+        }
+    }
+
+    #[test]
+    fn test_filter_and_sort() {
+        let mut image = file("f3", "Diagramm.png", 300);
+        image.object.mime_type = "image/png".to_string();
+        image.size = 10;
+        let contents = FolderContents {
+            folders: vec![],
+            files: vec![file("f1", "blatt_02.pdf", 200), file("f2", "Blatt_01.PDF", 100), image],
+        };
+        let ids = |files: Vec<&File>| files.into_iter().map(|file| file.object.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(contents.files_matching("blatt_*.pdf")), vec!["f1", "f2"]);
+        assert_eq!(ids(contents.files_with_mime("image/")), vec!["f3"]);
+        assert_eq!(ids(contents.modified_since(Utc.timestamp_opt(150, 0).unwrap())), vec!["f1", "f3"]);
+        assert_eq!(ids(contents.sorted_by(SortKey::Name, Order::Ascending)), vec!["f2", "f1", "f3"]);
+        assert_eq!(ids(contents.sorted_by(SortKey::ChangeDate, Order::Descending)), vec!["f3", "f1", "f2"]);
+        assert_eq!(ids(contents.sorted_by(SortKey::Size, Order::Descending))[0], "f3");
+        assert_eq!(contents.total_size(), 18);
+
+        let tree = FolderTree {
+            folder: None,
+            files: vec![file("f4", "notes.txt", 400)],
+            children: vec![FolderTree { folder: None, files: contents.files.clone(), children: vec![] }],
+        };
+        assert_eq!(ids(tree.files_matching("*.pdf")), vec!["f1", "f2"]);
+        assert_eq!(ids(tree.sorted_by(SortKey::ChangeDate, Order::Descending)), vec!["f4", "f3", "f1", "f2"]);
+        assert_eq!(tree.total_size(), 22);
+    }
+
+    #[test]
+    fn test_save_preserves_mtime() {
+        let server = MockServer::start();
+        server.route_typed("GET", "/sendfile.php", 200, "application/pdf", "data");
+        let module = FileModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+        let dest = std::env::temp_dir().join(format!("stud_ip_mtime_{}", std::process::id()));
+        let tree = FolderTree {
+            folder: None,
+            files: vec![file("f1", "a.pdf", 1_600_000_000)],
+            children: vec![FolderTree {
+                folder: Some(Folder { object: files_object("d1", "Slides: Week 1", 1_500_000_000), object_count: 1, permissions: String::new() }),
+                files: vec![file("f2", "b.pdf", 1_550_000_000)],
+                children: vec![],
+            }],
+        };
+        let mtime = |path: PathBuf| DateTime::<Utc>::from(std::fs::metadata(path).unwrap().modified().unwrap()).timestamp();
+
+        assert!(module.save_tree_to(&tree, &dest, &DownloadOptions::default()).unwrap().is_empty());
+        assert_eq!(std::fs::read_to_string(dest.join("a.pdf")).unwrap(), "data");
+        assert_eq!(mtime(dest.join("a.pdf")), 1_600_000_000);
+        assert_eq!(mtime(dest.join("Slides_ Week 1").join("b.pdf")), 1_550_000_000);
+        if !cfg!(windows) {
+            assert_eq!(mtime(dest.join("Slides_ Week 1")), 1_500_000_000);
+        }
+
+        let options = DownloadOptions { preserve_mtime: false, ..Default::default() }.collision_policy(CollisionPolicy::Overwrite);
+        module.save_file_with(&tree.files[0], &dest, &options).unwrap();
+        assert!(mtime(dest.join("a.pdf")) > 1_600_000_000);
+
+        // Both files share the limit, so that 2 * 4 bytes at 10 bytes per second take ~0.8s
+        let start = std::time::Instant::now();
+        let options = DownloadOptions::default().max_bytes_per_sec(Some(10));
+        assert!(module.save_tree_to(&tree, &dest, &options).unwrap().is_empty());
+        assert!(start.elapsed() >= std::time::Duration::from_millis(700), "{:?}", start.elapsed());
+        assert_eq!(std::fs::read_to_string(dest.join("Slides_ Week 1").join("b.pdf")).unwrap(), "data");
+        assert_eq!(mtime(dest.join("a.pdf")), 1_600_000_000);
+        std::fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn test_collision_policies() {
+        let server = MockServer::start();
+        server.route_typed("GET", "/sendfile.php?type=0&file_id=f1", 200, "application/pdf", "eins")
+            .route_typed("GET", "/sendfile.php?type=0&file_id=f2", 200, "application/pdf", "zwei")
+            .route_typed("GET", "/sendfile.php?type=0&file_id=f3", 200, "application/pdf", "drei");
+        let module = FileModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+        let root = std::env::temp_dir().join(format!("stud_ip_collisions_{}", std::process::id()));
+        // Stud.IP allows files with the same name in the same folder
+        let tree = FolderTree {
+            folder: None,
+            files: vec![file("f1", "Blatt 1.pdf", 0), file("f2", "Blatt 1.pdf", 0)],
+            children: vec![],
+        };
+        let save = |policy: CollisionPolicy| {
+            let dest = root.join(format!("{:?}", policy));
+            let failures = module.save_tree_to(&tree, &dest, &DownloadOptions::default().collision_policy(policy)).unwrap();
+            (dest, failures)
+        };
+        let read = |path: PathBuf| std::fs::read_to_string(path).unwrap();
+        let count = |dest: &Path| std::fs::read_dir(dest).unwrap()
+            .filter(|entry| entry.as_ref().unwrap().file_name() != SAVED_NAMES_FILE)
+            .count();
+
+        let (dest, failures) = save(CollisionPolicy::default());
+        assert!(failures.is_empty());
+        assert_eq!(read(dest.join("Blatt 1.pdf")), "eins");
+        assert_eq!(read(dest.join("Blatt 1 (1).pdf")), "zwei");
+        // Saving the tree again keeps the names of the last time
+        assert!(save(CollisionPolicy::default()).1.is_empty());
+        assert_eq!(count(&dest), 2);
+
+        let (dest, failures) = save(CollisionPolicy::Overwrite);
+        assert!(failures.is_empty());
+        assert_eq!((read(dest.join("Blatt 1.pdf")), count(&dest)), ("zwei".to_string(), 1));
+
+        let (dest, failures) = save(CollisionPolicy::Skip);
+        assert!(failures.is_empty());
+        assert_eq!((read(dest.join("Blatt 1.pdf")), count(&dest)), ("eins".to_string(), 1));
+
+        let (dest, failures) = save(CollisionPolicy::Error);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, dest.join("Blatt 1.pdf"));
+        assert_eq!((read(dest.join("Blatt 1.pdf")), count(&dest)), ("eins".to_string(), 1));
+
+        // A file, that was added before the others on the server, does not take their names
+        let reordered = FolderTree { files: vec![file("f3", "Blatt 1.pdf", 0), tree.files[0].clone(), tree.files[1].clone()], ..tree.clone() };
+        let dest = root.join("RenameWithSuffix");
+        assert!(module.save_tree_to(&reordered, &dest, &DownloadOptions::default()).unwrap().is_empty());
+        assert_eq!(read(dest.join("Blatt 1.pdf")), "eins");
+        assert_eq!(read(dest.join("Blatt 1 (1).pdf")), "zwei");
+        assert_eq!(read(dest.join("Blatt 1 (2).pdf")), "drei");
+
+        // Saving a single file again replaces it, instead of colliding with itself
+        let dest = root.join("Single");
+        std::fs::create_dir_all(&dest).unwrap();
+        module.save_file_to(&tree.files[1], &dest).unwrap();
+        module.save_file_to(&tree.files[1], &dest).unwrap();
+        assert_eq!((read(dest.join("Blatt 1.pdf")), count(&dest)), ("zwei".to_string(), 1));
+        // Other files collide with it
+        module.save_file_to(&tree.files[0], &dest).unwrap();
+        assert_eq!(read(dest.join("Blatt 1 (1).pdf")), "eins");
+        module.save_file_with(&tree.files[0], &dest, &DownloadOptions::default().collision_policy(CollisionPolicy::Error)).unwrap();
+        module.save_file_with(&reordered.files[0], &dest, &DownloadOptions::default().collision_policy(CollisionPolicy::Skip)).unwrap();
+        assert_eq!(count(&dest), 2);
+        assert!(module.save_file_with(&reordered.files[0], &dest, &DownloadOptions::default().collision_policy(CollisionPolicy::Error)).is_err());
+        assert_eq!(count(&dest), 2);
+        // As do files, that were not saved by it
+        std::fs::write(dest.join("Blatt 2.pdf"), "eigene").unwrap();
+        module.save_file_to(&file("f3", "Blatt 2.pdf", 0), &dest).unwrap();
+        assert_eq!((read(dest.join("Blatt 2.pdf")), read(dest.join("Blatt 2 (1).pdf"))), ("eigene".to_string(), "drei".to_string()));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_save_sanitizes_names() {
+        let server = MockServer::start();
+        server.route_typed("GET", "/sendfile.php", 200, "application/pdf", "data");
+        let module = FileModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+        let root = std::env::temp_dir().join(format!("stud_ip_sanitize_{}", std::process::id()));
+        let dest = root.join("dest");
+        let tree = FolderTree {
+            folder: None,
+            files: vec![file("f1", "../escaped.pdf", 0), file("f2", "CON.pdf", 0)],
+            children: vec![FolderTree {
+                folder: Some(Folder { object: files_object("d1", "..", 0), object_count: 1, permissions: String::new() }),
+                files: vec![file("f3", "Blatt 1. ", 0)],
+                children: vec![],
+            }],
+        };
+
+        assert!(module.save_tree_to(&tree, &dest, &DownloadOptions::default()).unwrap().is_empty());
+        assert!(dest.join(".._escaped.pdf").exists());
+        assert!(dest.join("_CON.pdf").exists());
+        assert!(dest.join("_").join("Blatt 1").exists());
+        assert!(!root.join("escaped.pdf").exists());
+        module.save_file_to(&tree.files[0], &dest).unwrap();
+        assert!(!root.join("escaped.pdf").exists());
+
+        // Raw names are used as they are
+        let options = DownloadOptions::default().raw_names(true);
+        assert_eq!(options.file_name("../escaped.pdf"), "../escaped.pdf");
+        module.save_file_with(&file("f4", "../escaped.pdf", 0), &dest, &options).unwrap();
+        assert!(root.join("escaped.pdf").exists());
+        assert_ne!(options, DownloadOptions::default());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_download_to_writer() {
+        #[derive(Default)]
+        struct CountingWriter {
+            writes: usize,
+            bytes: usize,
+        }
+        impl Write for CountingWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.writes += 1;
+                self.bytes += buf.len();
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let server = MockServer::start();
+        server.route_typed("GET", "/sendfile.php?type=0&file_id=f1", 200, "application/pdf", "x".repeat(100_000));
+        server.route_typed("GET", "/sendfile.php?type=0&file_id=f2", 200, "application/pdf", "data");
+        server.route("GET", "/sendfile.php?type=0&file_id=f3", 200, "<html><body>Login</body></html>");
+        let module = FileModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+        let mut large = file("f1", "a.pdf", 0);
+        large.size = 100_000;
+        let mut writer = CountingWriter::default();
+        assert_eq!(module.download_to_writer(&large, &mut writer).unwrap(), 100_000);
+        assert_eq!(writer.bytes, 100_000);
+        // The body is streamed in chunks, instead of being buffered as a whole
+        assert!(writer.writes > 1, "{} writes", writer.writes);
+
+        assert_eq!(module.download_file(&file("f2", "b.pdf", 0)).unwrap(), b"data");
+        let mut truncated = file("f2", "b.pdf", 0);
+        truncated.size = 10;
+        assert!(module.download_file(&truncated).is_err());
+        assert!(module.download_to_writer(&file("f3", "c.pdf", 0), &mut std::io::sink()).is_err());
+    }
+
+    #[test]
+    fn test_get_file() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/file/details/f1", 200, include_str!("../../testdata/files/details.html"));
+        server.route("GET", "/dispatch.php/file/details/f2", 200, include_str!("../../testdata/files/details_not_found.html"));
+        server.route("GET", "/dispatch.php/file/details/f3", 403, "");
+        server.route_typed("HEAD", "/sendfile.php?type=0&file_id=f1", 200, "application/pdf", "x".repeat(2_000_123));
+        server.route_typed("GET", "/sendfile.php?type=0&file_id=f1", 200, "application/pdf", "data");
+        let module = FileModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+
+        let file = module.get_file(&"f1".into()).unwrap();
+        assert_eq!(file.object.name, "Skript Woche 1.pdf");
+        assert_eq!(file.object.author.username, "mmustermann");
+        assert_eq!(file.object.author.display_name, "Max Mustermann");
+        assert_eq!(file.object.mime_type, "application/pdf");
+        assert_eq!(file.object.change_date, Utc.with_ymd_and_hms(2024, 11, 14, 15, 42, 0).unwrap());
+        // The exact size is taken from the download, instead of the rounded one of the page
+        assert_eq!(file.size, 2_000_123);
+        assert_eq!(file.downloads, 1234);
+        assert_eq!(file.folder_id, Some("d7".into()));
+        assert!(file.is_accessible);
+
+        let error = |result: anyhow::Result<File>| result.unwrap_err().downcast::<StudIpError>().unwrap();
+        assert!(matches!(error(module.get_file(&"f2".into())), StudIpError::NotFound { message: Some(_) }));
+        assert!(matches!(error(module.get_file(&"f3".into())), StudIpError::PermissionDenied { .. }));
+        assert!(matches!(error(module.get_file(&"f4".into())), StudIpError::NotFound { message: None }));
+
+        assert_eq!(module.download_by_id(&"f1".into(), "file").unwrap(), b"data");
+        let error = module.download_by_id(&"f5".into(), "file").unwrap_err();
+        assert_eq!(error.downcast_ref::<StudIpError>(), Some(&StudIpError::NotFound { message: None }));
+    }
+
+    #[test]
+    fn test_download_url() {
+        let server = MockServer::start();
+        server.route_typed("GET", "/dispatch.php/file/download/f1", 200, "application/pdf", "custom");
+        server.route_typed("GET", "/sendfile.php?type=0&file_id=f1", 200, "application/pdf", "fallback");
+        let client = Arc::new(server.client());
+        let module = FileModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: client.clone(),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+        let their_file = |download_url: serde_json::Value| {
+            let mut their = serde_json::from_str::<TheirUploadResponse>(include_str!("../../testdata/files/upload_added.json")).unwrap()
+                .added_files.remove(0);
+            their.download_url = serde_json::from_value(download_url).unwrap();
+            their.size = 0;
+            their.id = "f1".to_string();
+            try_file_from_their(their, &client, "c1").unwrap()
+        };
+
+        // Relative urls are made absolute
+        let file = their_file(serde_json::json!("/dispatch.php/file/download/f1"));
+        assert_eq!(file.download_url, Some(server.url("/dispatch.php/file/download/f1")));
+        assert_eq!(module.download_file(&file).unwrap(), b"custom");
+        let file = their_file(serde_json::json!(server.url("/dispatch.php/file/download/f1")));
+        assert_eq!(module.download_file(&file).unwrap(), b"custom");
+        // Without a url, the download is requested from sendfile.php
+        let file = their_file(serde_json::Value::Null);
+        assert_eq!(file.download_url, None);
+        assert_eq!(module.download_file(&file).unwrap(), b"fallback");
+    }
+
+    #[test]
+    fn test_transfer_timeout() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/course/files", 200, "")
+            .route_typed("GET", "/sendfile.php?type=0&file_id=f1", 200, "application/pdf", "data")
+            .delay(Duration::from_millis(500));
+        let client = Arc::new(server.client_builder()
+            .timeout(Duration::from_millis(100))
+            .transfer_timeout(Duration::from_secs(5))
+            .build(crate::auth::AuthMethod::Session)
+            .unwrap());
+        assert_eq!(client.transfer_timeout(), Duration::from_secs(5));
+
+        // Pages are given up on quickly
+        let error = client.get(server.url("/dispatch.php/course/files")).send().unwrap_err();
+        assert!(error.is_timeout(), "{:?}", error);
+        // While files may take longer
+        assert_eq!(download_file_by_id(&client, "f1", "file").unwrap(), b"data");
+        let response = client.get_with_timeout(server.url("/dispatch.php/course/files"), Duration::from_secs(5)).send().unwrap();
+        assert!(response.status().is_success());
+    }
+
+    #[test]
+    fn test_localized_counts() {
+        assert_eq!(parse_count("1.234"), Some(1234));
+        assert_eq!(parse_count("1,234"), Some(1234));
+        assert_eq!(parse_count("1\u{a0}234 "), Some(1234));
+        assert_eq!(parse_count(""), Some(0));
+        assert_eq!(parse_count("n/a"), None);
+
+        let contents = parse_folder_page(include_str!("../../testdata/files/localized_counts.html"), StudIpClient::offline(), "c1").unwrap();
+        assert!(contents.warnings.is_empty());
+        let contents = contents.value;
+        let counts = contents.files.iter().map(|file| (file.downloads, file.size)).collect::<Vec<_>>();
+        assert_eq!(counts, vec![(1234, 2097152), (0, 12800), (0, 512)]);
+        assert_eq!(contents.folders[0].object_count, 1024);
+        // Icon images are made absolute, while the names of icon shapes are kept
+        assert_eq!(contents.files[0].object.icon, "file-pdf");
+        assert_eq!(contents.files[2].object.icon, "https://studip.example.com/assets/images/icons/blue/file-text.svg");
+    }
+
+    #[test]
+    fn test_folder_contents_warnings() {
+        let file = |id: &str, name: &str, author_url: &str| serde_json::json!({
+            "id": id, "name": name, "download_url": null, "downloads": 0, "mime_type": "text/plain", "icon": "file-text", "size": 1,
+            "author_url": author_url, "author_name": "Max Mustermann", "author_id": "u1", "chdate": 1700000000, "additionalColumns": [],
+            "details_url": "", "restrictedTermsOfUse": false, "actions": "", "new": false, "isEditable": false, "isAccessible": true,
+        });
+        let author_url = "https://studip.example.com/dispatch.php/profile?username=mmustermann";
+        let files = serde_json::json!([file("f1", "a.txt", author_url), file("f2", "b.txt", "https://studip.example.com/dispatch.php/profile"), file("f3", "c.txt", author_url)]);
+        let html = format!(r#"<form id="files_table_form" data-files='{}' data-folders='[{{"id": "d1"}}]'></form>"#, files);
+
+        let contents = parse_folder_page(&html, StudIpClient::offline(), "c1").unwrap();
+        let ids = contents.value.files.iter().map(|file| file.object.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["f1", "f3"]);
+        assert!(contents.value.folders.is_empty());
+        let contexts = contents.warnings.iter().map(|warning| warning.context.as_str()).collect::<Vec<_>>();
+        assert_eq!(contexts, vec!["folder #1", "file \"b.txt\""]);
+        assert_eq!(contents.value.files[0].object.author.user_id.as_deref(), Some("u1"));
+    }
+
+    #[test]
+    fn test_upload_files() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/start", 200, r#"<form><input type="hidden" name="security_token" value="tok="></form>"#);
+        server.route("POST", "/dispatch.php/file/upload/d1", 200, include_str!("../../testdata/files/upload_added.json"));
+        server.route("POST", "/dispatch.php/file/upload/d2", 200, include_str!("../../testdata/files/upload_rejected.html"));
+        let module = FileModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+        let dir = std::env::temp_dir().join(format!("stud_ip_upload_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Blatt 1.txt"), "hello world").unwrap();
+        std::fs::write(dir.join("setup.exe"), "MZ").unwrap();
+
+        let paths = vec![dir.join("Blatt 1.txt"), dir.join("missing.txt")];
+        let mut events = vec![];
+        let options = UploadOptions { terms_of_use_id: Some("FREE_LICENSE".to_string()) };
+        let results = module.upload_files(&"d1".into(), &paths, options, |event| events.push(event));
+        // A missing file does not abort the batch
+        assert_eq!(results.len(), 2);
+        let file = results[0].1.as_ref().unwrap();
+        assert_eq!(file.object.id, "f9");
+        assert_eq!(file.object.icon, format!("{}/assets/images/icons/blue/file-text.svg", server.url("")));
+        assert!(results[1].1.is_err());
+        assert_eq!(events, vec![
+            UploadEvent::Started { path: paths[0].clone(), total_bytes: 11 },
+            UploadEvent::Progress { path: paths[0].clone(), sent_bytes: 11, total_bytes: 11 },
+            UploadEvent::Finished { path: paths[0].clone(), success: true },
+            UploadEvent::Finished { path: paths[1].clone(), success: false },
+        ]);
+
+        let error = module.upload_file(&"d2".into(), dir.join("setup.exe"), &UploadOptions::default()).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<StudIpError>(),
+            Some(StudIpError::FileTypeBlocked { file_name, .. }) if file_name == "setup.exe"
+        ), "{:?}", error);
+
+        let requests = server.requests();
+        // The security token is only fetched once
+        assert_eq!(requests.iter().filter(|request| request.path.starts_with("/dispatch.php/start")).count(), 1);
+        let upload = requests.iter().find(|request| request.path.starts_with("/dispatch.php/file/upload/d1")).unwrap();
+        assert_eq!(upload.path, "/dispatch.php/file/upload/d1?cid=c1");
+        for part in ["tok=", "filename=\"Blatt 1.txt\"", "hello world", "FREE_LICENSE"] {
+            assert!(upload.body.contains(part), "{} is missing in {}", part, upload.body);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_trash() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/start", 200, r#"<form><input type="hidden" name="security_token" value="tok="></form>"#)
+            .route("GET", "/dispatch.php/course/files", 200, include_str!("../../testdata/files/trash_root.html"))
+            .route("GET", "/dispatch.php/course/files/index/t1", 200, include_str!("../../testdata/files/trash.html"))
+            .route("POST", "/dispatch.php/file/restore/f5", 200, r#"<div class="messagebox messagebox_success">Die Datei wurde wiederhergestellt.</div>"#)
+            .route("GET", "/dispatch.php/file/delete/f6", 200, r#"<form><input type="hidden" name="security_token" value="dialog="></form>"#)
+            .route("POST", "/dispatch.php/file/delete/f6", 200, r#"<div class="messagebox messagebox_success">Die Datei wurde gelöscht.</div>"#);
+        let module = FileModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+        let trash = module.get_trash().unwrap();
+        assert_eq!(trash.files.iter().map(|file| file.object.name.as_str()).collect::<Vec<_>>(), vec!["Blatt_00.pdf"]);
+
+        module.restore("f5", Some(&"d1".into())).unwrap();
+        module.purge("f6").unwrap();
+        let requests = server.requests();
+        let restore = requests.iter().find(|request| request.path.starts_with("/dispatch.php/file/restore/f5")).unwrap();
+        assert_eq!(restore.method, "POST");
+        assert!(restore.path.contains("cid=c1"));
+        assert!(restore.body.contains("security_token=tok%3D") && restore.body.contains("to_folder_id=d1"), "{}", restore.body);
+        let purge = requests.iter().rfind(|request| request.path.starts_with("/dispatch.php/file/delete/f6")).unwrap();
+        assert_eq!(purge.method, "POST");
+        // The token of the confirmation dialog is used
+        assert!(purge.body.contains("security_token=dialog%3D") && purge.body.contains("yes=1"), "{}", purge.body);
+
+        // Installations without a trash
+        server.route("GET", "/dispatch.php/course/files", 200, include_str!("../../testdata/files/localized_counts.html"));
+        module.refresh();
+        let error = module.get_trash().unwrap_err();
+        assert!(matches!(error.downcast_ref::<StudIpError>(), Some(StudIpError::FeatureDisabled { .. })), "{:?}", error);
+    }
+
+    #[cfg(feature = "jsonapi")]
+    #[test]
+    fn test_jsonapi_fallback() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/course/files", 200, include_str!("../../testdata/files/localized_counts.html"));
+        let module = FileModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+        let jsonapi_requests = || server.requests().iter().filter(|request| request.path.starts_with("/jsonapi.php")).count();
+
+        // The JSON:API is only tried once, after it turned out to be unavailable the html pages are used right away
+        assert_eq!(module.get_root().unwrap().files.len(), 3);
+        module.refresh();
+        assert_eq!(module.get_root().unwrap().files.len(), 3);
+        assert_eq!(jsonapi_requests(), 1);
+
+        // Other errors of the JSON:API are returned
+        let server = MockServer::start();
+        server.route_json("GET", "/jsonapi.php/v1/courses/c1/folders", 500, r#"{"errors": [{"status": "500"}]}"#)
+            .route("GET", "/dispatch.php/course/files", 200, include_str!("../../testdata/files/localized_counts.html"));
+        let module = FileModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+        let error = module.get_root().unwrap_err();
+        assert!(error.to_string().contains("500"), "{:?}", error);
+    }
+}
@@ -1,19 +1,36 @@
 use std::any::Any;
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::sync::Arc;
-use anyhow::bail;
+use std::time::Duration;
+use anyhow::{bail, Context};
 use chrono::{DateTime, NaiveDateTime, Utc};
 use chrono::serde::ts_seconds;
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
+use regex::Regex;
 use reqwest::Url;
-use scraper::{Element, ElementRef, Html, Selector};
+use scraper::{Element, ElementRef, Html};
 use serde::{Deserialize, Serialize};
 use crate::course_modules::{CourseModule, CourseModuleData};
-use crate::user::{get_username_from_link_element, User};
+use crate::error::StudIpError;
+use crate::ids::GroupId;
+use crate::user::{get_username_from_link_element, user_id_from_avatar_src, User};
 use crate::ref_source::ReferenceSource;
+use crate::flash;
+use crate::util::{csv_row, local_to_utc, parse_page, selector, trimmed_text};
+use crate::warnings::{ParseWarnings, Parsed};
+use crate::logging::log_parse;
+use crate::{SendThrough, StudIpClient};
 
 const MEMBERS_URL : &str = "https://studip.example.com/dispatch.php/course/members";
 const GROUPS_URL : &str = "https://studip.example.com/dispatch.php/course/statusgroups";
 
+static GROUP_NAME_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?P<name>.+) \((?P<members>\d+)(/(?P<max_members>\d+))?\)").unwrap());
+static ENTRY_DATE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d{2}\.\d{2}\.\d{4} \d{2}:\d{2}").unwrap());
+static WAITLIST_LENGTH_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)(?:warteliste|waiting list)\D{0,3}(?P<length>\d+)").unwrap());
+static WAITLIST_POSITION_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)(?:platz|position)\s*(?P<position>\d+)").unwrap());
+
 /// Module, that enables querying the members of a course and operating on the courses groups
 #[derive(Debug)]
 pub struct MembersModule {
@@ -32,184 +49,518 @@ impl CourseModule for MembersModule {
     fn as_any(&mut self) -> &mut dyn Any {
         self
     }
+
+    fn data(&self) -> Option<&CourseModuleData> {
+        Some(&self.course_module_data)
+    }
 }
 
 impl MembersModule {
 
+    // The url of the members page, as linked by the tab
+    fn members_url(&self) -> String {
+        self.course_module_data.base_url(MEMBERS_URL)
+    }
+
+    // The groups page lives next to the members page
+    fn groups_url(&self) -> String {
+        match self.members_url().strip_suffix("/members") {
+            Some(base) => format!("{}/statusgroups", base),
+            None => GROUPS_URL.to_string(),
+        }
+    }
+
+    /// Discards the cached pages of the course, so that they are fetched again \
+    /// Pages are reused by consecutive reads, see [Course::set_cache_freshness()](crate::course::Course::set_cache_freshness()).
+    pub fn refresh(&self) {
+        self.course_module_data.refresh();
+    }
+
     /// Returns the members of the course. \
-    /// This includes the lecturers, tutors, and students.
+    /// This includes the lecturers, tutors, and students. Rows, that could not be parsed, are left out (see [`MembersModule::get_members_with_warnings()`]).
     pub fn get_members(&self) -> anyhow::Result<CourseMembers> {
-        let response = self.course_module_data.client.get(MEMBERS_URL)
-            .query(&[("cid", &self.course_module_data.course_id)])
-            .send()?;
-        let html = Html::parse_document(&response.text()?);
-        let table_selector = Selector::parse("#content table").unwrap();
-        let mut tables_members : HashMap<_, _> = html.select(&table_selector)
-            .filter_map(|table| parse_member_table(table, ReferenceSource::Course(self.course_module_data.course_id.to_string())).ok())
-            .collect();
-        Ok(CourseMembers {
-            lecturers: tables_members.remove(&Some("dozierende".to_string()))
-                .or_else(|| tables_members.remove(&Some("lecturers".to_string())))
-                .unwrap_or_default(),
-            tutors: tables_members.remove(&Some("tutor*innen".to_string()))
-                .or_else(|| tables_members.remove(&Some("tutors".to_string())))
-                .unwrap_or_default(),
-            students: tables_members.remove(&Some("studierende".to_string()))
-                .or_else(|| tables_members.remove(&Some("students".to_string())))
-                .unwrap_or_default(),
-        })
+        Ok(self.get_members_with_warnings()?.value)
+    }
+
+    /// Returns the members of the course, together with a warning for each row, that could not be parsed
+    pub fn get_members_with_warnings(&self) -> anyhow::Result<Parsed<CourseMembers>> {
+        let body = self.course_module_data.get_page(&self.members_url(), &[("cid", &self.course_module_data.course_id)])?;
+        Ok(parse_members_html(&Html::parse_document(&body), &self.course_module_data.client, ReferenceSource::Course(self.course_module_data.course_id.clone())))
     }
 
-    /// Returns the groups within the course.
+    /// Returns the groups within the course. \
+    /// Groups, that could not be parsed, are left out (see [`MembersModule::get_groups_with_warnings()`]).
     pub fn get_groups(&self) -> anyhow::Result<Vec<Group>> {
-        let response = self.course_module_data.client.get(GROUPS_URL)
-            .query(&[("cid", &self.course_module_data.course_id)])
-            .send()?;
-        let html = Html::parse_document(&response.text()?);
-        let group_selector= Selector::parse("div#content article > header").unwrap();
-        let h1_selector = Selector::parse("h1").unwrap();
-        let disabled_entry_selector = Selector::parse("img.icon-shape-door-enter").unwrap();
-        Ok(html.select(&group_selector).map(|group_ref| {
-            let raw_name = group_ref.select(&h1_selector).next()
-                .unwrap()
-                .text()
-                .collect::<String>()
-                .trim()
-                .to_string();
-
-            let name_captures = regex::Regex::new(r"(?P<name>.+) \((?P<members>\d+)(/(?P<max_members>\d+))?\)").unwrap()
-                .captures(&raw_name)
-                .unwrap();
-
-            let name = name_captures.name("name").unwrap().as_str().to_string();
-            let members = name_captures.name("members")
-                .map(|re_match| re_match.as_str().parse().unwrap())
-                .unwrap_or(0);
-            let max_members = name_captures.name("max_members")
-                .map(|re_match| re_match.as_str().parse().unwrap())
-                .unwrap_or(0);
-
-            let leave_selector = Selector::parse("a > img.icon-shape-door-leave").unwrap();
-            let entered = group_ref.select(&leave_selector).next().is_some();
-
-            let group_info_selector = Selector::parse("a > img.icon-shape-info-circle").unwrap();
-            let id = group_ref.select(&group_info_selector)
-                .next()
-                .map(|elem| elem.parent_element().unwrap().value().attr("href").unwrap())
-                .map(|group_info_link| {
-                    let group_info_url = Url::parse(group_info_link).unwrap();
-                    group_info_url.path_segments().unwrap().last().unwrap().to_string()
-                })
-                .unwrap_or_else(|| "nogroup".to_string());
-
-            let mut group = Group {
-                name,
-                id,
-                entered,
-                enables_entry_at: None,
-                members,
-                max_members,
-            };
+        Ok(self.get_groups_with_warnings()?.value)
+    }
 
-            if let Some(disabled_entry_link) = group_ref.select(&disabled_entry_selector).next() {
-                let title = disabled_entry_link.value().attr("title").unwrap();
-                if let Some(re_match) = regex::Regex::new(r"\d{2}\.\d{2}\.\d{4} \d{2}:\d{2}").unwrap().find(title) {
-                    let date_str = re_match.as_str();
-                    let date = NaiveDateTime::parse_from_str(date_str, "%d.%m.%Y %H:%M")
-                        .expect("Could not parse entry_enabled_at date time");
-                    let enables_entry_at = date.and_local_timezone(chrono::Local)
-                        .earliest()
-                        .map(|local| local.to_utc());
-                    group.enables_entry_at = enables_entry_at;
-                }
-            }
-            group
-        }).collect::<Vec<_>>())
+    /// Returns the groups within the course, together with a warning for each group, that could not be parsed
+    pub fn get_groups_with_warnings(&self) -> anyhow::Result<Parsed<Vec<Group>>> {
+        let body = self.course_module_data.get_page(&self.groups_url(), &[("cid", &self.course_module_data.course_id)])?;
+        Ok(parse_groups_html(&body, self.course_module_data.client.timezone()))
     }
 
     /// Attempts to join a specifies [`Group`] within the course.
     pub fn try_join_group(&self, group: &Group) -> anyhow::Result<()> {
-        let url = format!("{}/join/{}", GROUPS_URL, group.id);
-        let response = self.course_module_data.client.get(url)
-            .query(&[("cid", &self.course_module_data.course_id)])
-            .send()?;
-        let status = response.status();
-        if status.is_success() {
-            Ok(())
-        } else {
-            bail!("Could not join group. Status code: {}", status)
-        }
+        let body = self.course_module_data.fetch_page(&format!("{}/join/{}", self.groups_url(), group.id), &[]);
+        // The member counts of the groups and the members page changed
+        self.course_module_data.invalidate(&self.groups_url());
+        self.course_module_data.invalidate(&self.members_url());
+        let body = body.context("Could not join group")?;
+        // The page is shown with a message box, that tells if it worked
+        flash::check(&parse_page(&body)?)?;
+        Ok(())
     }
 
     /// Attempts to leave a specific [`Group`] within the course.
     pub fn try_leave_group(&self, group: &Group) -> anyhow::Result<()> {
-        let url = format!("{}/leave/{}", GROUPS_URL, group.id);
-        let response = self.course_module_data.client.get(url)
-            .query(&[("cid", &self.course_module_data.course_id)])
-            .send()?;
-        let status = response.status();
-        if status.is_success() {
-            Ok(())
-        } else {
-            bail!("Could not leave group. Status code: {}", status)
+        let body = self.course_module_data.fetch_page(&format!("{}/leave/{}", self.groups_url(), group.id), &[]);
+        // The member counts of the groups and the members page changed
+        self.course_module_data.invalidate(&self.groups_url());
+        self.course_module_data.invalidate(&self.members_url());
+        let body = body.context("Could not leave group")?;
+        // The page is shown with a message box, that tells if it worked
+        flash::check(&parse_page(&body)?)?;
+        Ok(())
+    }
+
+    /// Attempts to join the waiting list of a full [`Group`] within the course \
+    /// Whether a group has a waiting list, is indicated by [`Group::waitlist`].
+    pub fn join_waitlist(&self, group: &Group) -> anyhow::Result<()> {
+        if group.waitlist.as_ref().is_none_or(|waitlist| !waitlist.enabled) {
+            bail!("The group {} has no waiting list, that can be joined", group.name);
+        }
+        let body = self.course_module_data.fetch_page(&format!("{}/join_waitlist/{}", self.groups_url(), group.id), &[]);
+        // The waiting list of the group changed
+        self.course_module_data.invalidate(&self.groups_url());
+        let body = body.context("Could not join waiting list")?;
+        // The page is shown with a message box, that tells if it worked
+        flash::check(&parse_page(&body)?)?;
+        Ok(())
+    }
+
+    /// Joins the first group matching the `criteria`, trying them in the preferred order, and returns the joined group \
+    /// If a matching group has already been joined, it is returned without joining another one.
+    /// Without a `wait` strategy, only a single attempt is made. With one, the groups are polled until one could be joined or the deadline passed,
+    /// sleeping until groups open their entry (see [`Group::enables_entry_at`]). Every poll goes through the rate limiter of the client.
+    /// If no group could be joined, an [`AutoJoinError`] tells why.
+    pub fn auto_join(&self, criteria: JoinCriteria, wait: Option<WaitStrategy>) -> anyhow::Result<Group> {
+        loop {
+            // The member counts have to be current
+            self.course_module_data.invalidate(&self.groups_url());
+            let groups = self.get_groups()?;
+            let candidates = criteria.candidates(&groups);
+            if candidates.is_empty() {
+                return Err(AutoJoinError::NoMatchingGroup.into());
+            }
+            if let Some(entered) = candidates.iter().find(|group| group.entered) {
+                return Ok((*entered).clone());
+            }
+
+            let now = Utc::now();
+            let opens_at = candidates.iter()
+                .filter_map(|group| group.enables_entry_at)
+                .filter(|opens_at| *opens_at > now)
+                .min();
+            let open = candidates.iter()
+                .filter(|group| group.enables_entry_at.is_none_or(|opens_at| opens_at <= now))
+                .filter(|group| !criteria.not_full || !group.is_full());
+            for group in open {
+                match self.try_join_group(group) {
+                    Ok(()) => {
+                        let mut joined = (*group).clone();
+                        joined.entered = true;
+                        joined.members += 1;
+                        return Ok(joined);
+                    },
+                    // Someone else was faster, the next group might still have space
+                    Err(error) if matches!(error.downcast_ref::<StudIpError>(), Some(StudIpError::ActionFailed { .. })) => {
+                        log_parse!(debug, "Could not join group {:?}: {}", group.name, error);
+                    },
+                    Err(error) => return Err(error),
+                }
+            }
+
+            let no_place = || AutoJoinError::AllFull {
+                groups: candidates.iter().map(|group| group.name.clone()).collect(),
+            };
+            let Some(wait) = &wait else {
+                return Err(match opens_at {
+                    Some(opens_at) => AutoJoinError::NotYetOpen { opens_at },
+                    None => no_place(),
+                }.into());
+            };
+            // Groups, that are not open yet, are waited for, full groups are polled, as members might leave them
+            let wake_at = match opens_at {
+                Some(opens_at) if opens_at > wait.deadline => return Err(AutoJoinError::DeadlineExceeded { opens_at }.into()),
+                Some(opens_at) => opens_at,
+                None => now + wait.poll_interval,
+            };
+            if wake_at > wait.deadline {
+                return Err(no_place().into());
+            }
+            std::thread::sleep((wake_at - Utc::now()).to_std().unwrap_or_default());
         }
     }
 
     /// Returns the members of a specific [`Group`] within the course.
     pub fn get_group_members(&self, group: &Group) -> anyhow::Result<Vec<User>> {
-        let url = format!("{}/getgroup/{}", GROUPS_URL, group.id);
+        let url = format!("{}/getgroup/{}", self.groups_url(), group.id);
         let response = self.course_module_data.client.get(url)
             .query(&[("cid", &self.course_module_data.course_id)])
             .header("X-Requested-With", "XMLHttpRequest")
-            .send()?;
+            .send_through(&self.course_module_data.client)?;
         let text = response.text()?;
         let html = Html::parse_fragment(&text);
-        Ok(parse_member_table(html.root_element(), ReferenceSource::Course(self.course_module_data.course_id.to_string()))?.1)
+        let mut warnings = ParseWarnings::default();
+        let source = ReferenceSource::Course(self.course_module_data.course_id.clone());
+        let members = html.select(selector!("table"))
+            .flat_map(|table| parse_member_table(table, &self.course_module_data.client, &source, &mut warnings).1)
+            .collect();
+        // The warnings are not returned, so they are at least logged
+        warnings.log_summary();
+        Ok(members)
+    }
+
+    /// Exports the groups of the course as CSV with the columns `group`, `display_name` and `username` \
+    /// If `include_members` is set, there is one row per member of each group, otherwise (and for empty groups) one row per group with empty member columns. \
+    /// *Note: Including the members makes one request per group*
+    pub fn export_groups_csv(&self, include_members: bool) -> anyhow::Result<String> {
+        let mut csv = csv_row(&["group", "display_name", "username"]);
+        for group in self.get_groups()? {
+            let members = if include_members {
+                self.get_group_members(&group)
+                    .with_context(|| format!("Could not get members of group {}", group.name))?
+            } else {
+                vec![]
+            };
+            if members.is_empty() {
+                csv.push_str(&csv_row(&[group.name.as_str(), "", ""]));
+            }
+            for member in members {
+                csv.push_str(&csv_row(&[group.name.as_str(), member.display_name.as_str(), member.username.as_str()]));
+            }
+        }
+        Ok(csv)
     }
 
 }
 
 /// The members of a course
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CourseMembers {
     pub lecturers: Vec<User>,
     pub tutors: Vec<User>,
     pub students: Vec<User>
 }
 
+impl CourseMembers {
+
+    /// Exports the members as CSV with the columns `role` (lecturer, tutor or student), `display_name` and `username` \
+    /// Lecturers come first, then tutors and students, each in the order of the members page.
+    pub fn to_csv(&self) -> String {
+        let mut csv = csv_row(&["role", "display_name", "username"]);
+        for (role, members) in [("lecturer", &self.lecturers), ("tutor", &self.tutors), ("student", &self.students)] {
+            for member in members {
+                csv.push_str(&csv_row(&[role, member.display_name.as_str(), member.username.as_str()]));
+            }
+        }
+        csv
+    }
+
+    /// Exports the members as pretty printed JSON
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        serde_json::to_string_pretty(self).context("Could not serialize course members")
+    }
+
+}
+
 /// A group of members of a specific course
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Group {
     pub name: String,
-    pub id: String,
+    pub id: GroupId,
     pub entered: bool,
     #[serde(with = "option_ts_seconds")]
     pub enables_entry_at: Option<DateTime<Utc>>,
     pub members: usize,
-    pub max_members: usize
+    pub max_members: usize,
+    /// The waiting list of the group, if it has a visible one
+    #[serde(default)]
+    pub waitlist: Option<WaitlistInfo>,
+}
+
+/// The waiting list of a full [`Group`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WaitlistInfo {
+    /// The number of members on the waiting list
+    pub length: usize,
+    /// The position of the current user on the waiting list, if they are on it
+    pub my_position: Option<usize>,
+    /// If the waiting list can currently be joined
+    pub enabled: bool,
+}
+
+impl Group {
+
+    /// If the group has as many members, as it allows
+    pub fn is_full(&self) -> bool {
+        self.max_members != 0 && self.members >= self.max_members
+    }
+
+}
+
+/// Which groups [`MembersModule::auto_join()`] may join
+#[derive(Debug, Clone, Default)]
+pub struct JoinCriteria {
+    /// Only groups with a name matching this are joined
+    pub name_regex: Option<Regex>,
+    /// Full groups are not attempted to be joined
+    pub not_full: bool,
+    /// The groups, whose names contain these (e.g. "Di 14"), are tried first and in this order, before the other matching groups
+    pub preferred_order: Vec<String>,
+}
+
+impl JoinCriteria {
+
+    /// Returns the matching groups in the order, in which they should be tried
+    pub fn candidates<'a>(&self, groups: &'a [Group]) -> Vec<&'a Group> {
+        let mut candidates = groups.iter()
+            .filter(|group| self.name_regex.as_ref().is_none_or(|regex| regex.is_match(&group.name)))
+            .collect::<Vec<_>>();
+        // The sort is stable, so the groups, that are not preferred, keep the order of the page
+        candidates.sort_by_key(|group| self.preferred_order.iter()
+            .position(|preferred| group.name.contains(preferred.as_str()))
+            .unwrap_or(self.preferred_order.len()));
+        candidates
+    }
+
+}
+
+/// How long [`MembersModule::auto_join()`] keeps trying
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaitStrategy {
+    /// How often full groups are checked for places, that became free
+    pub poll_interval: Duration,
+    /// When to give up
+    pub deadline: DateTime<Utc>,
 }
 
-fn parse_member_table(table_ref: ElementRef, reference_source: ReferenceSource) -> anyhow::Result<(Option<String>, Vec<User>)> {
-    let caption_selector = Selector::parse("caption").unwrap();
-    let caption = table_ref.select(&caption_selector)
+/// Why [`MembersModule::auto_join()`] could not join a group \
+/// Returned inside [`anyhow::Error`]s, other failures are reported as [`StudIpError`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AutoJoinError {
+    /// No group of the course matches the criteria
+    NoMatchingGroup,
+    /// The matching groups can not be joined before `opens_at` and there was no strategy to wait for it
+    NotYetOpen { opens_at: DateTime<Utc> },
+    /// All matching `groups` are full or refused the join (until the deadline, when waiting)
+    AllFull { groups: Vec<String> },
+    /// The matching groups open after the deadline, at `opens_at`
+    DeadlineExceeded { opens_at: DateTime<Utc> },
+}
+
+impl Display for AutoJoinError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AutoJoinError::NoMatchingGroup => f.write_str("No group matches the criteria"),
+            AutoJoinError::NotYetOpen { opens_at } => write!(f, "The matching groups can not be joined before {}", opens_at),
+            AutoJoinError::AllFull { groups } => write!(f, "The matching groups are full: {}", groups.join(", ")),
+            AutoJoinError::DeadlineExceeded { opens_at } => write!(f, "The matching groups open after the deadline, at {}", opens_at),
+        }
+    }
+}
+
+impl std::error::Error for AutoJoinError {}
+
+impl PartialEq for Group {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+// Parses the waiting list from the header of a group, returns None if the group does not show one
+fn parse_waitlist(group_ref: ElementRef) -> Option<WaitlistInfo> {
+    let text = group_ref.text().collect::<String>();
+    let enabled = group_ref.select(selector!("a[href*=\"/join_waitlist/\"]")).next().is_some();
+    let length = WAITLIST_LENGTH_REGEX.captures(&text)
+        .and_then(|captures| captures["length"].parse().ok());
+    if length.is_none() && !enabled {
+        return None;
+    }
+    Some(WaitlistInfo {
+        length: length.unwrap_or(0),
+        my_position: WAITLIST_POSITION_REGEX.captures(&text)
+            .and_then(|captures| captures["position"].parse().ok()),
+        enabled,
+    })
+}
+
+/// Parses the html of the members page of a course into its [`CourseMembers`], without requesting anything \
+/// Rows, that can not be parsed, are left out. Relative avatar urls are resolved against the placeholder host `studip.example.com`.
+pub fn parse_members_page(html: &str, source: ReferenceSource) -> anyhow::Result<CourseMembers> {
+    parse_members_document(&Html::parse_document(html), source)
+}
+
+/// Like [`parse_members_page()`], but for an already parsed page
+pub fn parse_members_document(html: &Html, source: ReferenceSource) -> anyhow::Result<CourseMembers> {
+    Ok(parse_members_html(html, StudIpClient::offline(), source).value)
+}
+
+/// Parses the html of the groups page of a course into its [`Group`]s, without requesting anything \
+/// Groups, that can not be parsed, are left out. The dates, at which groups open, are converted to UTC in the [`DEFAULT_TIMEZONE`](crate::DEFAULT_TIMEZONE).
+pub fn parse_groups_page(html: &str) -> anyhow::Result<Vec<Group>> {
+    Ok(parse_groups_html(html, StudIpClient::offline().timezone()).value)
+}
+
+// The tables are told apart by their localized captions
+fn parse_members_html(html: &Html, client: &StudIpClient, source: ReferenceSource) -> Parsed<CourseMembers> {
+    let mut warnings = ParseWarnings::default();
+    let mut tables_members : HashMap<_, _> = html.select(selector!("#content table"))
+        .map(|table| parse_member_table(table, client, &source, &mut warnings))
+        .collect();
+    let members = CourseMembers {
+        lecturers: tables_members.remove(&Some("dozierende".to_string()))
+            .or_else(|| tables_members.remove(&Some("lecturers".to_string())))
+            .unwrap_or_default(),
+        tutors: tables_members.remove(&Some("tutor*innen".to_string()))
+            .or_else(|| tables_members.remove(&Some("tutors".to_string())))
+            .unwrap_or_default(),
+        students: tables_members.remove(&Some("studierende".to_string()))
+            .or_else(|| tables_members.remove(&Some("students".to_string())))
+            .unwrap_or_default(),
+    };
+    for caption in tables_members.keys() {
+        log_parse!(debug, "Ignored members table with unknown caption {:?}", caption);
+    }
+    warnings.into_parsed(members)
+}
+
+fn parse_groups_html(html: &str, timezone: Tz) -> Parsed<Vec<Group>> {
+    let html = Html::parse_document(html);
+    let mut warnings = ParseWarnings::default();
+    let groups = html.select(selector!("div#content article > header"))
+        .enumerate()
+        .filter_map(|(i, group_ref)| warnings.skip_err(format!("group {}", i + 1), parse_group(group_ref, timezone)))
+        .collect();
+    warnings.into_parsed(groups)
+}
+
+fn parse_group(group_ref: ElementRef, timezone: Tz) -> anyhow::Result<Group> {
+    let raw_name = group_ref.select(selector!("h1")).next()
+        .context("Expected group name")?
+        .text()
+        .collect::<String>()
+        .trim()
+        .to_string();
+
+    let name_captures = GROUP_NAME_REGEX.captures(&raw_name)
+        .with_context(|| format!("Expected member count in group name {:?}", raw_name))?;
+    let name = name_captures["name"].to_string();
+    let members = name_captures["members"].parse()?;
+    let max_members = name_captures.name("max_members")
+        .map(|re_match| re_match.as_str().parse())
+        .transpose()?
+        .unwrap_or(0);
+
+    let leave_selector = selector!("a > img.icon-shape-door-leave");
+    let entered = group_ref.select(leave_selector).next().is_some();
+
+    let group_info_selector = selector!("a > img.icon-shape-info-circle");
+    let id = match group_ref.select(group_info_selector).next() {
+        Some(info_icon) => {
+            let group_info_link = info_icon.parent_element().unwrap().value().attr("href")
+                .context("Expected link to group info")?;
+            Url::parse(group_info_link)?
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .context("Expected group id in link to group info")?
+                .to_string()
+        },
+        None => {
+            log_parse!(debug, "Group {:?} has no info link, using the id nogroup", name);
+            "nogroup".to_string()
+        },
+    };
+
+    let mut group = Group {
+        name,
+        id: id.into(),
+        entered,
+        enables_entry_at: None,
+        members,
+        max_members,
+        waitlist: parse_waitlist(group_ref),
+    };
+
+    let disabled_entry_selector = selector!("img.icon-shape-door-enter");
+    if let Some(disabled_entry_link) = group_ref.select(disabled_entry_selector).next() {
+        let title = disabled_entry_link.value().attr("title").unwrap_or_default();
+        if let Some(re_match) = ENTRY_DATE_REGEX.find(title) {
+            let date = NaiveDateTime::parse_from_str(re_match.as_str(), "%d.%m.%Y %H:%M")
+                .context("Could not parse entry_enabled_at date time")?;
+            group.enables_entry_at = local_to_utc(date, timezone);
+        }
+    }
+    Ok(group)
+}
+
+// Rows with a cell spanning the table are placeholders (e.g. "Keine Studierenden") and not members
+fn parse_member_table(table_ref: ElementRef, client: &StudIpClient, reference_source: &ReferenceSource, warnings: &mut ParseWarnings) -> (Option<String>, Vec<User>) {
+    let caption_selector = selector!("caption");
+    let caption = table_ref.select(caption_selector)
         .next()
         .map(|elem| elem.text().collect::<String>().trim().to_lowercase());
-    let rows_selector = Selector::parse("tbody tr").unwrap();
-    let main_a_selector = Selector::parse("td a").unwrap();
-    let img_selector = Selector::parse("img").unwrap();
-    Ok((caption, table_ref.select(&rows_selector).filter_map(|row| {
-        let main_a_ref = row.select(&main_a_selector).next()?;
-        let username = get_username_from_link_element(main_a_ref).ok()?;
-        let avatar_img = main_a_ref.select(&img_selector).next()?.value();
-        let avatar_src = avatar_img.attr("src")?;
-        let display_name = main_a_ref.text().collect::<String>().trim().to_string();
-        Some(User {
-            display_name,
-            username,
-            avatar_src: Some(avatar_src.to_string()),
-            source: reference_source.clone(),
+    let context = format!("members table {}", caption.as_deref().unwrap_or("without caption"));
+    let members = children(table_ref, "tbody")
+        .flat_map(|tbody| children(tbody, "tr"))
+        .filter(|row| {
+            let is_placeholder = cells(*row).any(|cell| cell.attr("colspan").is_some());
+            if is_placeholder {
+                log_parse!(debug, "Skipped placeholder row of {}", context);
+            }
+            !is_placeholder
         })
-    }).collect::<Vec<_>>()))
+        .filter_map(|row| warnings.skip_err(context.as_str(), parse_member_row(row, client, reference_source)))
+        .collect();
+    (caption, members)
+}
+
+// The rows are walked directly, as matching selectors against every row of large tables is slow
+fn children<'a>(element: ElementRef<'a>, name: &'static str) -> impl Iterator<Item = ElementRef<'a>> {
+    element.children()
+        .filter_map(ElementRef::wrap)
+        .filter(move |child| child.value().name() == name)
+}
+
+fn cells(row: ElementRef) -> impl Iterator<Item = ElementRef> {
+    children(row, "td")
+}
+
+fn first_descendant<'a>(element: ElementRef<'a>, name: &str) -> Option<ElementRef<'a>> {
+    element.descendants()
+        .filter_map(ElementRef::wrap)
+        .find(|descendant| descendant.value().name() == name)
+}
+
+fn parse_member_row(row: ElementRef, client: &StudIpClient, reference_source: &ReferenceSource) -> anyhow::Result<User> {
+    let main_a_ref = cells(row).find_map(|cell| first_descendant(cell, "a")).context("Expected link to member")?;
+    let username = get_username_from_link_element(main_a_ref)?;
+    let avatar_src = first_descendant(main_a_ref, "img")
+        .and_then(|img| img.attr("src"))
+        .with_context(|| format!("Expected avatar of member {}", username))?;
+    let display_name = trimmed_text(main_a_ref);
+    let absolute_avatar_src = client.absolutize_to_string(avatar_src)
+        .map_err(|error| log_parse!(debug, "Could not resolve avatar {:?} of member {}: {}", avatar_src, username, error))
+        .ok();
+    Ok(User {
+        display_name,
+        username,
+        avatar_src: absolute_avatar_src,
+        source: reference_source.clone(),
+        user_id: user_id_from_avatar_src(avatar_src),
+    })
 }
 
 pub mod option_ts_seconds {
@@ -235,3 +586,223 @@ pub mod option_ts_seconds {
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+    use crate::util::parse_csv;
+    use crate::error::StudIpError;
+
+    fn user(username: &str, display_name: &str) -> User {
+        User {
+            display_name: display_name.to_string(),
+            username: username.to_string(),
+            avatar_src: Some(format!("https://studip.example.com/pictures/user/{}.png", username)),
+            source: ReferenceSource::Course("c1".into()),
+            user_id: None,
+        }
+    }
+
+    #[test]
+    fn test_members_export() {
+        let members = CourseMembers {
+            lecturers: vec![user("mmu", "Prof. Max \"Maxi\" Mustermann")],
+            tutors: vec![],
+            students: vec![user("jdoe", "Doe, John"), user("erika", "Erika")],
+        };
+        let csv = members.to_csv();
+        assert_eq!(csv, "role,display_name,username\nlecturer,\"Prof. Max \"\"Maxi\"\" Mustermann\",mmu\nstudent,\"Doe, John\",jdoe\nstudent,Erika,erika\n");
+        let rows = parse_csv(&csv);
+        assert_eq!(rows[1], vec!["lecturer", "Prof. Max \"Maxi\" Mustermann", "mmu"]);
+        assert_eq!(rows[2], vec!["student", "Doe, John", "jdoe"]);
+        let json = members.to_json().unwrap();
+        assert_eq!(serde_json::from_str::<CourseMembers>(&json).unwrap(), members);
+    }
+
+    #[test]
+    fn test_waitlist() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/course/statusgroups", 200, r#"<div id="content">
+            <article><header><h1>Gruppe A (20/20)</h1><span>Warteliste: 4</span>
+                <a href="https://studip.example.com/dispatch.php/course/statusgroups/join_waitlist/g1"><img class="icon-shape-log"></a>
+                <a href="https://studip.example.com/dispatch.php/course/statusgroups/groupinfo/g1"><img class="icon-shape-info-circle"></a></header></article>
+            <article><header><h1>Gruppe B (20/20)</h1><span>Waiting list: 3 (your position 2)</span>
+                <a href="https://studip.example.com/dispatch.php/course/statusgroups/groupinfo/g2"><img class="icon-shape-info-circle"></a></header></article>
+            <article><header><h1>Gruppe C (3/20)</h1>
+                <a href="https://studip.example.com/dispatch.php/course/statusgroups/groupinfo/g3"><img class="icon-shape-info-circle"></a></header></article>
+        </div>"#)
+            .route("GET", "/dispatch.php/course/statusgroups/join_waitlist/g1", 200, "")
+            .route("GET", "/dispatch.php/course/statusgroups/join/g3", 200, r#"<div class="messagebox messagebox_error">Sie sind bereits in einer exklusiven Gruppe eingetragen.</div>"#);
+        let module = MembersModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+
+        let groups = module.get_groups().unwrap();
+        assert_eq!(groups[0].waitlist, Some(WaitlistInfo { length: 4, my_position: None, enabled: true }));
+        assert_eq!(groups[1].waitlist, Some(WaitlistInfo { length: 3, my_position: Some(2), enabled: false }));
+        assert_eq!(groups[2].waitlist, None);
+        module.join_waitlist(&groups[0]).unwrap();
+        assert!(server.requests().iter().any(|request| request.path.starts_with("/dispatch.php/course/statusgroups/join_waitlist/g1?cid=c1")));
+        assert!(module.join_waitlist(&groups[2]).is_err());
+
+        // The group page answers with status 200 and tells the reason in a message box
+        let error = module.try_join_group(&groups[2]).unwrap_err();
+        assert!(matches!(error.downcast_ref::<StudIpError>(), Some(StudIpError::ActionFailed { message }) if message == "Sie sind bereits in einer exklusiven Gruppe eingetragen."), "{:?}", error);
+    }
+
+    fn groups_page(groups: &[(&str, &str, &str)]) -> String {
+        let articles = groups.iter().map(|(id, name, extra)| format!(r#"<article><header><h1>{}</h1>{}
+            <a href="https://studip.example.com/dispatch.php/course/statusgroups/groupinfo/{}"><img class="icon-shape-info-circle"></a></header></article>"#, name, extra, id))
+            .collect::<String>();
+        format!(r#"<div id="content">{}</div>"#, articles)
+    }
+
+    #[test]
+    fn test_auto_join() {
+        let server = MockServer::start();
+        let closed = r#"<a><img class="icon-shape-door-enter" title="Eintragen möglich ab 01.01.2099 10:00"></a>"#;
+        server.route("GET", "/dispatch.php/course/statusgroups", 200, groups_page(&[
+            ("g1", "Mo 10 Gruppe 1 (20/20)", ""),
+            ("g2", "Di 14 Gruppe 2 (5/20)", ""),
+            ("g3", "Di 14 Gruppe 3 (3/20)", ""),
+            ("g4", "Tutorium (1/5)", ""),
+            ("g5", "Sa 8 Gruppe 5 (0/20)", closed),
+        ]))
+            .route("GET", "/dispatch.php/course/statusgroups/join/g2", 200, r#"<div class="messagebox messagebox_error">Die Gruppe ist bereits voll.</div>"#)
+            .route("GET", "/dispatch.php/course/statusgroups/join/g3", 200, "");
+        let module = MembersModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+        let criteria = |regex: &str, preferred_order: &[&str]| JoinCriteria {
+            name_regex: Some(Regex::new(regex).unwrap()),
+            not_full: true,
+            preferred_order: preferred_order.iter().map(|name| name.to_string()).collect(),
+        };
+
+        let groups = module.get_groups().unwrap();
+        let names = |candidates: Vec<&Group>| candidates.iter().map(|group| group.id.to_string()).collect::<Vec<_>>();
+        assert_eq!(names(criteria("^(Mo|Di)", &["Gruppe 3", "Mo 10"]).candidates(&groups)), vec!["g3", "g1", "g2"]);
+        assert_eq!(names(JoinCriteria::default().candidates(&groups)), vec!["g1", "g2", "g3", "g4", "g5"]);
+
+        // The preferred group refuses, so the next one is joined and the full one is not tried
+        let joined = module.auto_join(criteria("^(Mo|Di)", &["Gruppe 2"]), None).unwrap();
+        assert_eq!((joined.id.as_str(), joined.entered, joined.members), ("g3", true, 4));
+        let joins = server.requests().into_iter()
+            .filter(|request| request.path.contains("/join/"))
+            .map(|request| request.path)
+            .collect::<Vec<_>>();
+        assert_eq!(joins, vec!["/dispatch.php/course/statusgroups/join/g2?cid=c1", "/dispatch.php/course/statusgroups/join/g3?cid=c1"]);
+
+        let error = |result: anyhow::Result<Group>| result.unwrap_err().downcast::<AutoJoinError>().unwrap();
+        assert_eq!(error(module.auto_join(criteria("^Fr", &[]), None)), AutoJoinError::NoMatchingGroup);
+        assert_eq!(error(module.auto_join(criteria("^Mo", &[]), None)), AutoJoinError::AllFull { groups: vec!["Mo 10 Gruppe 1".to_string()] });
+        let opens_at = groups[4].enables_entry_at.unwrap();
+        assert_eq!(error(module.auto_join(criteria("^Sa", &[]), None)), AutoJoinError::NotYetOpen { opens_at });
+        let wait = WaitStrategy { poll_interval: Duration::from_millis(50), deadline: Utc::now() + Duration::from_secs(60) };
+        assert_eq!(error(module.auto_join(criteria("^Sa", &[]), Some(wait))), AutoJoinError::DeadlineExceeded { opens_at });
+    }
+
+    #[test]
+    fn test_auto_join_polling() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/course/statusgroups", 200, groups_page(&[("g1", "Di 14 Gruppe 1 (20/20)", "")]))
+            .route("GET", "/dispatch.php/course/statusgroups/join/g1", 200, "");
+        let module = MembersModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+        let criteria = JoinCriteria { not_full: true, ..Default::default() };
+        let wait = |deadline: Duration| Some(WaitStrategy { poll_interval: Duration::from_millis(50), deadline: Utc::now() + deadline });
+
+        let error = module.auto_join(criteria.clone(), wait(Duration::from_millis(200))).unwrap_err();
+        assert!(matches!(error.downcast_ref::<AutoJoinError>(), Some(AutoJoinError::AllFull { .. })), "{:?}", error);
+        // The polls are spaced by the rate limiter as well
+        let polls = server.requests().len();
+        assert!(polls >= 2, "{}", polls);
+
+        // A place becomes free while polling
+        let joined = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(200));
+                server.route("GET", "/dispatch.php/course/statusgroups", 200, groups_page(&[("g1", "Di 14 Gruppe 1 (19/20)", "")]));
+            });
+            module.auto_join(criteria, wait(Duration::from_secs(5))).unwrap()
+        });
+        assert_eq!((joined.id.as_str(), joined.members), ("g1", 20));
+    }
+
+    #[test]
+    fn test_parse_warnings() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/course/members", 200, include_str!("../../testdata/members/malformed_row.html"))
+            .route("GET", "/dispatch.php/course/statusgroups", 200, r#"<div id="content">
+                <article><header><h1>Gruppe A (3/20)</h1></header></article>
+                <article><header><h1>Gruppe B</h1></header></article>
+                <article><header><h1>Gruppe C (0)</h1></header></article>
+            </div>"#);
+        let module = MembersModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+
+        // The row of the deleted user is skipped with a warning, the placeholder of the empty tutors table is not a member
+        let members = module.get_members_with_warnings().unwrap();
+        assert_eq!(members.value.lecturers.len(), 1);
+        assert_eq!(members.value.lecturers[0].user_id.as_deref(), Some("0123456789abcdef0123456789abcdef"));
+        assert!(members.value.tutors.is_empty());
+        let usernames = members.value.students.iter().map(|user| user.username.as_str()).collect::<Vec<_>>();
+        assert_eq!(usernames, vec!["jdoe", "erika", "lmeier"]);
+        assert_eq!(members.warnings.len(), 1);
+        assert_eq!(members.warnings[0].context, "members table studierende");
+        assert_eq!(members.warnings[0].detail, "Expected link to member");
+
+        let groups = module.get_groups_with_warnings().unwrap();
+        let names = groups.value.iter().map(|group| group.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Gruppe A", "Gruppe C"]);
+        assert_eq!(groups.warnings.len(), 1);
+        assert_eq!(groups.warnings[0].context, "group 2");
+        assert_eq!(module.get_groups().unwrap().len(), 2);
+
+        // The same page parsed without a client resolves the avatars against the placeholder host
+        let offline = parse_members_page(include_str!("../../testdata/members/malformed_row.html"), ReferenceSource::Course("c1".into())).unwrap();
+        assert_eq!(offline.students.len(), 3);
+        assert_eq!(offline.lecturers[0].avatar_src.as_deref(), Some("https://studip.example.com/pictures/user/0123456789abcdef0123456789abcdef_small.png"));
+        assert_eq!(parse_groups_page("<div id=\"content\"><article><header><h1>Gruppe A (3/20)</h1></header></article></div>").unwrap()[0].members, 3);
+    }
+
+    #[test]
+    fn test_export_groups_csv() {
+        let server = MockServer::start();
+        let group = |id: &str, name: &str| format!(r#"<article><header><h1>{} (1/2)</h1>
+            <a href="https://studip.example.com/dispatch.php/course/statusgroups/groupinfo/{}"><img class="icon-shape-info-circle"></a></header></article>"#, name, id);
+        server.route("GET", "/dispatch.php/course/statusgroups", 200, format!(r#"<div id="content">{}{}</div>"#, group("g1", "Gruppe A, Montag"), group("g2", "Gruppe B")))
+            .route("GET", "/dispatch.php/course/statusgroups/getgroup/g1", 200, r#"<table><tbody><tr><td>
+                <a href="https://studip.example.com/dispatch.php/profile?username=jdoe"><img src="avatar.png"> Doe, John</a></td></tr></tbody></table>"#)
+            .route("GET", "/dispatch.php/course/statusgroups/getgroup/g2", 200, "<table><tbody></tbody></table>");
+        let module = MembersModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: None,
+        }));
+
+        let csv = module.export_groups_csv(true).unwrap();
+        assert_eq!(parse_csv(&csv), vec![
+            vec!["group", "display_name", "username"],
+            vec!["Gruppe A, Montag", "Doe, John", "jdoe"],
+            vec!["Gruppe B", "", ""],
+        ]);
+        assert_eq!(module.export_groups_csv(false).unwrap(), "group,display_name,username\n\"Gruppe A, Montag\",,\nGruppe B,,\n");
+    }
+}
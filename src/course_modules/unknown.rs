@@ -0,0 +1,60 @@
+use std::any::Any;
+use std::sync::Arc;
+use anyhow::Context;
+use crate::course_modules::{CourseModule, CourseModuleData};
+
+/// A tab of a course, for which no module is registered (e.g. of a plugin like Meetings) \
+/// Only included by [Course::query_modules_with_unknown()](crate::course::Course::query_modules_with_unknown()), as a foothold to scrape its pages.
+#[derive(Debug)]
+pub struct UnknownModule {
+    module_data: Arc<CourseModuleData>,
+    tab_id: String,
+    title: String,
+}
+
+impl CourseModule for UnknownModule {
+    fn new(data: Arc<CourseModuleData>) -> Self {
+        Self::from_tab(data, String::new(), String::new())
+    }
+
+    fn name() -> &'static str {
+        "unknown"
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn data(&self) -> Option<&CourseModuleData> {
+        Some(&self.module_data)
+    }
+}
+
+impl UnknownModule {
+
+    pub(crate) fn from_tab(data: Arc<CourseModuleData>, tab_id: String, title: String) -> Self {
+        Self {
+            module_data: data,
+            tab_id,
+            title,
+        }
+    }
+
+    /// The id of the tab, without the `nav_course_` prefix (e.g. "meetings")
+    pub fn tab_id(&self) -> &str {
+        &self.tab_id
+    }
+
+    /// The displayed title of the tab
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Fetches the html of the page, that the tab links to \
+    /// The page is cached like the pages of other modules.
+    pub fn fetch_raw_html(&self) -> anyhow::Result<String> {
+        let url = self.url().with_context(|| format!("The tab {} does not link to a page", self.tab_id))?;
+        self.module_data.get_page(url.as_str(), &[])
+    }
+
+}
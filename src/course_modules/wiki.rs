@@ -0,0 +1,300 @@
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{ElementRef, Html};
+use serde::{Deserialize, Serialize};
+use crate::archive::write_html;
+use crate::content::html_to_markdown;
+use crate::course_modules::{CourseModule, CourseModuleData};
+use crate::util::{escape_html, parse_page, sanitize_file_name, selector};
+
+const WIKI_URL : &str = "https://studip.example.com/dispatch.php/course/wiki";
+
+/// Matches the href of links to wiki pages, capturing the id of the page
+static WIKI_LINK_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"href="[^"]*/course/wiki/page/(?P<id>[^"/?#]+)[^"]*""#).unwrap());
+
+/// Module, that enables reading and exporting the wiki of a course
+#[derive(Debug)]
+pub struct WikiModule {
+    module_data: Arc<CourseModuleData>,
+}
+
+impl CourseModule for WikiModule {
+    fn new(data: Arc<CourseModuleData>) -> Self {
+        Self { module_data: data }
+    }
+
+    fn name() -> &'static str {
+        "wiki"
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn data(&self) -> Option<&CourseModuleData> {
+        Some(&self.module_data)
+    }
+}
+
+/// A page of the wiki, as listed by [`WikiModule::list_pages()`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WikiPageRef {
+    pub id: String,
+    pub title: String,
+}
+
+/// A page of the wiki, including its content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WikiPage {
+    pub id: String,
+    pub title: String,
+    /// The rendered html content of the page
+    pub html_content: String,
+}
+
+/// The format of the files written by [`WikiModule::export_all()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WikiExportFormat {
+    /// Standalone html pages
+    Html,
+    /// Markdown, converted with [`html_to_markdown()`](crate::content::html_to_markdown())
+    Markdown,
+}
+
+impl WikiExportFormat {
+
+    fn extension(&self) -> &'static str {
+        match self {
+            WikiExportFormat::Html => "html",
+            WikiExportFormat::Markdown => "md",
+        }
+    }
+
+}
+
+/// The outcome of [`WikiModule::export_all()`]
+#[derive(Debug, Default)]
+pub struct ExportReport {
+    /// The written files of the pages, without the table of contents
+    pub written: Vec<PathBuf>,
+    /// The pages, that could not be fetched or written
+    pub failures: Vec<(WikiPageRef, anyhow::Error)>,
+}
+
+impl ExportReport {
+
+    /// Whether all pages were exported
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+}
+
+impl WikiModule {
+
+    // The root of the wiki pages, the tab usually links to the start page below it
+    fn wiki_url(&self) -> String {
+        let url = self.module_data.base_url(WIKI_URL);
+        match url.find("/wiki") {
+            Some(index) => url[..index + "/wiki".len()].to_string(),
+            None => WIKI_URL.to_string(),
+        }
+    }
+
+    /// Discards the cached pages of the course, so that they are fetched again \
+    /// Pages are reused by consecutive reads, see [Course::set_cache_freshness()](crate::course::Course::set_cache_freshness()).
+    pub fn refresh(&self) {
+        self.module_data.refresh();
+    }
+
+    /// Returns all pages of the wiki, in the order of the overview
+    pub fn list_pages(&self) -> anyhow::Result<Vec<WikiPageRef>> {
+        let body = self.module_data.get_page(&format!("{}/allpages", self.wiki_url()), &[("cid", &self.module_data.course_id)])?;
+        parse_page_list(&parse_page(&body)?)
+    }
+
+    /// Returns the page with the given id, including its content
+    pub fn get_page(&self, page_id: &str) -> anyhow::Result<WikiPage> {
+        let body = self.module_data.get_page(&format!("{}/page/{}", self.wiki_url(), page_id), &[("cid", &self.module_data.course_id)])?;
+        parse_wiki_page(&parse_page(&body)?, page_id)
+    }
+
+    /// Writes every page of the wiki into a file in `dest`, together with an `index.html`/`index.md` table of contents \
+    /// Links between the pages are replaced by relative links to their files. File names are sanitized, so that they are valid on common file systems. \
+    /// Pages, that fail to be fetched or written, are recorded in the report and do not abort the export.
+    /// *Note: This makes one request per page*
+    pub fn export_all(&self, dest: &Path, format: WikiExportFormat) -> anyhow::Result<ExportReport> {
+        std::fs::create_dir_all(dest).with_context(|| format!("Could not create {}", dest.display()))?;
+        let pages = self.list_pages()?;
+        let file_names = file_names(&pages, format);
+        let mut report = ExportReport::default();
+        let mut contents = vec![];
+        for page_ref in pages {
+            let file_name = &file_names[&page_ref.id];
+            let path = dest.join(file_name);
+            match self.get_page(&page_ref.id).and_then(|page| write_page(&page, &path, &file_names, format)) {
+                Ok(()) => {
+                    contents.push((page_ref.title, file_name.clone()));
+                    report.written.push(path);
+                },
+                Err(e) => report.failures.push((page_ref, e)),
+            }
+        }
+        write_index(dest, &contents, format)?;
+        Ok(report)
+    }
+
+}
+
+// Assigns a unique file name to every page
+fn file_names(pages: &[WikiPageRef], format: WikiExportFormat) -> HashMap<String, String> {
+    // The table of contents is always named index
+    let mut taken = HashSet::from(["index".to_string()]);
+    pages.iter()
+        .map(|page| {
+            let base = sanitize_file_name(&page.title);
+            let mut name = base.clone();
+            let mut counter = 2;
+            while !taken.insert(name.to_lowercase()) {
+                name = format!("{} ({})", base, counter);
+                counter += 1;
+            }
+            (page.id.clone(), format!("{}.{}", name, format.extension()))
+        })
+        .collect()
+}
+
+// Links are relative to the export directory, so only the spaces of the file name need escaping
+fn link_to(file_name: &str) -> String {
+    file_name.replace(' ', "%20")
+}
+
+fn write_page(page: &WikiPage, path: &Path, file_names: &HashMap<String, String>, format: WikiExportFormat) -> anyhow::Result<()> {
+    let html_content = WIKI_LINK_REGEX.replace_all(&page.html_content, |captures: &regex::Captures| {
+        match file_names.get(&captures["id"]) {
+            Some(file_name) => format!("href=\"{}\"", link_to(file_name)),
+            None => captures[0].to_string(),
+        }
+    });
+    match format {
+        WikiExportFormat::Html => write_html(path.to_path_buf(), &page.title, &format!("<h1>{}</h1>\n{}", escape_html(&page.title), html_content)),
+        WikiExportFormat::Markdown => {
+            let markdown = format!("# {}\n\n{}\n", page.title, html_to_markdown(&html_content));
+            std::fs::write(path, markdown).with_context(|| format!("Could not write {}", path.display()))
+        },
+    }
+}
+
+fn write_index(dest: &Path, contents: &[(String, String)], format: WikiExportFormat) -> anyhow::Result<()> {
+    let path = dest.join(format!("index.{}", format.extension()));
+    match format {
+        WikiExportFormat::Html => {
+            let items = contents.iter()
+                .map(|(title, file_name)| format!("<li><a href=\"{}\">{}</a></li>\n", link_to(file_name), escape_html(title)))
+                .collect::<String>();
+            write_html(path, "Wiki", &format!("<h1>Wiki</h1>\n<ul>\n{}</ul>", items))
+        },
+        WikiExportFormat::Markdown => {
+            let items = contents.iter()
+                .map(|(title, file_name)| format!("- [{}]({})\n", title, link_to(file_name)))
+                .collect::<String>();
+            std::fs::write(&path, format!("# Wiki\n\n{}", items)).with_context(|| format!("Could not write {}", path.display()))
+        },
+    }
+}
+
+// The overview lists every page with a link to it, the id is the last segment of the link
+fn parse_page_list(html: &Html) -> anyhow::Result<Vec<WikiPageRef>> {
+    let mut pages: Vec<WikiPageRef> = vec![];
+    for link in html.select(selector!("#content table a[href*=\"/course/wiki/page/\"]")) {
+        let href = link.attr("href").unwrap();
+        let id = href.split("/course/wiki/page/")
+            .nth(1)
+            .and_then(|rest| rest.split(['?', '/', '#']).next())
+            .filter(|id| !id.is_empty())
+            .with_context(|| format!("Expected page id in {}", href))?;
+        // Pages may be linked more than once (e.g. by their parent page)
+        if pages.iter().any(|page| page.id == id) {
+            continue;
+        }
+        pages.push(WikiPageRef {
+            id: id.to_string(),
+            title: text_of(link),
+        });
+    }
+    Ok(pages)
+}
+
+fn parse_wiki_page(html: &Html, page_id: &str) -> anyhow::Result<WikiPage> {
+    let content = html.select(selector!(".wiki-page-content"))
+        .next()
+        .with_context(|| format!("Expected content of wiki page {}", page_id))?;
+    let title = html.select(selector!(".wiki-page-title, #content h1"))
+        .next()
+        .map(text_of)
+        .unwrap_or_default();
+    Ok(WikiPage {
+        id: page_id.to_string(),
+        title,
+        html_content: content.inner_html().trim().to_string(),
+    })
+}
+
+fn text_of(element: ElementRef) -> String {
+    element.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_export_all() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/course/wiki/allpages", 200, include_str!("../../testdata/wiki/allpages.html"))
+            .route("GET", "/dispatch.php/course/wiki/page/1", 200, include_str!("../../testdata/wiki/page_1.html"))
+            .route("GET", "/dispatch.php/course/wiki/page/2", 200, include_str!("../../testdata/wiki/page_2.html"))
+            .route("GET", "/dispatch.php/course/wiki/page/3", 500, "");
+        let module = WikiModule::new(Arc::new(CourseModuleData {
+            course_id: "c1".into(),
+            client: Arc::new(server.client()),
+            page_cache: Default::default(),
+            tab_url: Some(url::Url::parse("https://studip.example.com/dispatch.php/course/wiki/page?cid=c1").unwrap()),
+        }));
+        let pages = module.list_pages().unwrap();
+        assert_eq!(pages.iter().map(|page| page.title.as_str()).collect::<Vec<_>>(), vec!["Startseite", "Übung 1/2", "Kaputt"]);
+
+        for format in [WikiExportFormat::Html, WikiExportFormat::Markdown] {
+            let dest = std::env::temp_dir().join(format!("stud_ip_wiki_{}_{:?}", std::process::id(), format));
+            let report = module.export_all(&dest, format).unwrap();
+            assert_eq!(report.written.len(), 2);
+            // The failed page does not abort the export
+            assert_eq!(report.failures.len(), 1);
+            assert_eq!(report.failures[0].0.id, "3");
+
+            let start_page = std::fs::read_to_string(dest.join(format!("Startseite.{}", format.extension()))).unwrap();
+            let index = std::fs::read_to_string(dest.join(format!("index.{}", format.extension()))).unwrap();
+            match format {
+                WikiExportFormat::Html => {
+                    assert!(start_page.contains(r#"href="Übung%201_2.html""#), "{}", start_page);
+                    // External links are kept
+                    assert!(start_page.contains(r#"href="https://www.example.com/""#));
+                    assert!(index.contains(r#"<li><a href="Startseite.html">Startseite</a></li>"#), "{}", index);
+                    assert!(!index.contains("Kaputt"));
+                },
+                WikiExportFormat::Markdown => {
+                    assert!(start_page.starts_with("# Startseite\n\n"), "{}", start_page);
+                    assert!(start_page.contains("[Übung 1/2](Übung%201_2.md)"), "{}", start_page);
+                    assert_eq!(index, "# Wiki\n\n- [Startseite](Startseite.md)\n- [Übung 1/2](Übung%201_2.md)\n");
+                },
+            }
+            std::fs::remove_dir_all(&dest).unwrap();
+        }
+    }
+}
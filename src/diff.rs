@@ -0,0 +1,294 @@
+use std::collections::{BTreeMap, BTreeSet};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::course::Course;
+use crate::course_modules::{FileModule, MembersModule};
+use crate::get_module;
+use crate::ids::{CourseId, GroupId};
+
+/// The state of a single file in a [`CourseSnapshot`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileSnapshot {
+    pub id: String,
+    pub name: String,
+    /// The folder path of the file, relative to the root folder of the course
+    pub path: String,
+    pub change_date: DateTime<Utc>,
+}
+
+/// The state of a single announcement in a [`CourseSnapshot`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnnouncementSnapshot {
+    pub id: String,
+    pub title: String,
+}
+
+/// The state of a single date in a [`CourseSnapshot`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DateSnapshot {
+    pub id: String,
+    pub title: String,
+    pub start: Option<DateTime<Utc>>,
+    pub cancelled: bool,
+}
+
+/// The state of a single group in a [`CourseSnapshot`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupSnapshot {
+    pub id: GroupId,
+    pub name: String,
+    pub enables_entry_at: Option<DateTime<Utc>>,
+    /// The usernames of the group members
+    pub members: BTreeSet<String>,
+}
+
+/// The state of a course at a point in time, which can be stored between runs and compared with [`CourseSnapshot::diff()`] \
+/// Sections of modules, that the course does not have, are empty.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CourseSnapshot {
+    pub course_id: CourseId,
+    pub captured_at: DateTime<Utc>,
+    /// The files by their id
+    pub files: BTreeMap<String, FileSnapshot>,
+    /// The announcements by their id
+    pub announcements: BTreeMap<String, AnnouncementSnapshot>,
+    /// The usernames of all members
+    pub members: BTreeSet<String>,
+    /// The groups by their id
+    pub groups: BTreeMap<GroupId, GroupSnapshot>,
+    /// The dates by their id
+    #[serde(default)]
+    pub dates: BTreeMap<String, DateSnapshot>,
+}
+
+/// The changes of a group's members between two [`CourseSnapshot`]s
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupMembershipChange {
+    pub group_id: GroupId,
+    pub group_name: String,
+    pub joined: Vec<String>,
+    pub left: Vec<String>,
+}
+
+/// The changes between two [`CourseSnapshot`]s \
+/// All lists are ordered by id (or username), so that the diff is deterministic.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CourseDiff {
+    pub new_files: Vec<FileSnapshot>,
+    /// Files, that were changed or moved (contains their newer state)
+    pub changed_files: Vec<FileSnapshot>,
+    pub removed_files: Vec<FileSnapshot>,
+    pub new_announcements: Vec<AnnouncementSnapshot>,
+    pub removed_announcements: Vec<AnnouncementSnapshot>,
+    pub new_members: Vec<String>,
+    pub removed_members: Vec<String>,
+    pub new_groups: Vec<GroupSnapshot>,
+    pub removed_groups: Vec<GroupSnapshot>,
+    pub group_membership_changes: Vec<GroupMembershipChange>,
+    /// Groups, which entry was enabled between the two snapshots
+    pub opened_groups: Vec<GroupSnapshot>,
+    /// Dates, that were cancelled between the two snapshots
+    pub cancelled_dates: Vec<DateSnapshot>,
+}
+
+impl CourseDiff {
+    /// Checks if nothing changed
+    pub fn is_empty(&self) -> bool {
+        *self == CourseDiff::default()
+    }
+}
+
+// Splits two maps into the values only in the newer one, the changed values (newer state) and the values only in the older one
+fn diff_maps<K: Ord, V: Clone + PartialEq>(older: &BTreeMap<K, V>, newer: &BTreeMap<K, V>) -> (Vec<V>, Vec<(V, V)>, Vec<V>) {
+    let added = newer.iter()
+        .filter(|(id, _)| !older.contains_key(*id))
+        .map(|(_, value)| value.clone())
+        .collect();
+    let changed = newer.iter()
+        .filter_map(|(id, value)| older.get(id).filter(|old| *old != value).map(|old| (old.clone(), value.clone())))
+        .collect();
+    let removed = older.iter()
+        .filter(|(id, _)| !newer.contains_key(*id))
+        .map(|(_, value)| value.clone())
+        .collect();
+    (added, changed, removed)
+}
+
+impl CourseSnapshot {
+
+    /// Captures the current state of the course's files, announcements, members, groups and dates \
+    /// The modules of the course are queried first, if they have not been yet. \
+    /// *Note: This makes a request for every folder and group*
+    pub fn capture(course: &mut Course) -> anyhow::Result<CourseSnapshot> {
+        if course.modules.is_empty() {
+            course.query_modules()?;
+        }
+        let mut snapshot = CourseSnapshot {
+            course_id: course.id.clone(),
+            captured_at: Utc::now(),
+            files: Default::default(),
+            announcements: Default::default(),
+            members: Default::default(),
+            groups: Default::default(),
+            dates: Default::default(),
+        };
+        snapshot.dates = course.query_dates()?
+            .into_iter()
+            .map(|date| (date.id.clone(), DateSnapshot { id: date.id, title: date.title, start: date.start, cancelled: date.cancelled }))
+            .collect();
+        snapshot.announcements = course.query_news()?
+            .into_iter()
+            .map(|article| (article.id.clone(), AnnouncementSnapshot { id: article.id, title: article.title }))
+            .collect();
+        if let Some(file_module) = get_module!(course, FileModule) {
+            let tree = file_module.get_tree()?;
+            snapshot.files = tree.files_with_paths()
+                .into_iter()
+                .map(|(path, file)| (file.object.id.clone(), FileSnapshot {
+                    id: file.object.id.clone(),
+                    name: file.object.name.clone(),
+                    path: path.to_string_lossy().replace('\\', "/"),
+                    change_date: file.object.change_date,
+                }))
+                .collect();
+        }
+        if let Some(members_module) = get_module!(course, MembersModule) {
+            let members = members_module.get_members()?;
+            snapshot.members = members.lecturers.iter()
+                .chain(&members.tutors)
+                .chain(&members.students)
+                .map(|user| user.username.clone())
+                .collect();
+            for group in members_module.get_groups()? {
+                let group_members = members_module.get_group_members(&group)?;
+                snapshot.groups.insert(group.id.clone(), GroupSnapshot {
+                    id: group.id,
+                    name: group.name,
+                    enables_entry_at: group.enables_entry_at,
+                    members: group_members.into_iter().map(|user| user.username).collect(),
+                });
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Computes the changes from this snapshot to a `newer` one of the same course
+    pub fn diff(&self, newer: &CourseSnapshot) -> CourseDiff {
+        let (new_files, changed_files, removed_files) = diff_maps(&self.files, &newer.files);
+        let (new_announcements, _, removed_announcements) = diff_maps(&self.announcements, &newer.announcements);
+        let (new_groups, changed_groups, removed_groups) = diff_maps(&self.groups, &newer.groups);
+        let group_membership_changes = changed_groups.into_iter()
+            .map(|(old, new)| GroupMembershipChange {
+                group_id: new.id.clone(),
+                group_name: new.name.clone(),
+                joined: new.members.difference(&old.members).cloned().collect(),
+                left: old.members.difference(&new.members).cloned().collect(),
+            })
+            .filter(|change| !change.joined.is_empty() || !change.left.is_empty())
+            .collect();
+        let opened_groups = newer.groups.values()
+            .filter(|group| group.enables_entry_at.is_some_and(|at| at > self.captured_at && at <= newer.captured_at))
+            .cloned()
+            .collect();
+        let cancelled_dates = newer.dates.values()
+            .filter(|date| date.cancelled && self.dates.get(&date.id).is_none_or(|old| !old.cancelled))
+            .cloned()
+            .collect();
+        CourseDiff {
+            new_files,
+            changed_files: changed_files.into_iter().map(|(_, new)| new).collect(),
+            removed_files,
+            new_announcements,
+            removed_announcements,
+            new_members: newer.members.difference(&self.members).cloned().collect(),
+            removed_members: self.members.difference(&newer.members).cloned().collect(),
+            new_groups,
+            removed_groups,
+            group_membership_changes,
+            opened_groups,
+            cancelled_dates,
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn file(id: &str, path: &str, timestamp: i64) -> (String, FileSnapshot) {
+        (id.to_string(), FileSnapshot {
+            id: id.to_string(),
+            name: format!("{}.pdf", id),
+            path: path.to_string(),
+            change_date: Utc.timestamp_opt(timestamp, 0).unwrap(),
+        })
+    }
+
+    fn group(id: &str, members: &[&str]) -> (GroupId, GroupSnapshot) {
+        (id.into(), GroupSnapshot {
+            id: id.into(),
+            name: format!("Group {}", id),
+            enables_entry_at: None,
+            members: members.iter().map(|member| member.to_string()).collect(),
+        })
+    }
+
+    fn snapshot(files: Vec<(String, FileSnapshot)>, members: &[&str], groups: Vec<(GroupId, GroupSnapshot)>) -> CourseSnapshot {
+        CourseSnapshot {
+            course_id: "c1".into(),
+            captured_at: Utc.timestamp_opt(0, 0).unwrap(),
+            files: files.into_iter().collect(),
+            announcements: Default::default(),
+            members: members.iter().map(|member| member.to_string()).collect(),
+            groups: groups.into_iter().collect(),
+            dates: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let older = snapshot(
+            vec![file("f1", "", 10), file("f2", "slides", 10), file("f3", "", 10)],
+            &["alice", "bob"],
+            vec![group("g1", &["alice"]), group("g2", &[])],
+        );
+        let mut newer = snapshot(
+            vec![file("f1", "", 10), file("f2", "slides", 20), file("f4", "exercises", 30)],
+            &["alice", "carol"],
+            vec![group("g1", &["alice", "carol"]), group("g3", &[])],
+        );
+        newer.announcements.insert("n1".to_string(), AnnouncementSnapshot { id: "n1".to_string(), title: "Welcome".to_string() });
+        let diff = older.diff(&newer);
+        assert_eq!(diff.new_files.iter().map(|file| file.id.as_str()).collect::<Vec<_>>(), vec!["f4"]);
+        assert_eq!(diff.changed_files.iter().map(|file| file.change_date.timestamp()).collect::<Vec<_>>(), vec![20]);
+        assert_eq!(diff.removed_files.iter().map(|file| file.id.as_str()).collect::<Vec<_>>(), vec!["f3"]);
+        assert_eq!(diff.new_announcements.len(), 1);
+        assert_eq!(diff.new_members, vec!["carol"]);
+        assert_eq!(diff.removed_members, vec!["bob"]);
+        assert_eq!(diff.new_groups[0].id, "g3");
+        assert_eq!(diff.removed_groups[0].id, "g2");
+        assert_eq!(diff.group_membership_changes, vec![GroupMembershipChange {
+            group_id: "g1".into(),
+            group_name: "Group g1".to_string(),
+            joined: vec!["carol".to_string()],
+            left: vec![],
+        }]);
+        assert!(newer.diff(&newer).is_empty());
+
+        // Opened groups and cancelled dates
+        let mut later = newer.clone();
+        later.captured_at = Utc.timestamp_opt(100, 0).unwrap();
+        later.groups.get_mut("g3").unwrap().enables_entry_at = Some(Utc.timestamp_opt(50, 0).unwrap());
+        later.dates.insert("d1".to_string(), DateSnapshot { id: "d1".to_string(), title: "Lecture".to_string(), start: None, cancelled: true });
+        let later_diff = newer.diff(&later);
+        assert_eq!(later_diff.opened_groups[0].id, "g3");
+        assert_eq!(later_diff.cancelled_dates[0].id, "d1");
+        assert!(later.diff(&later).is_empty());
+
+        // Snapshots survive being stored between runs
+        let stored: CourseSnapshot = serde_json::from_str(&serde_json::to_string(&older).unwrap()).unwrap();
+        assert_eq!(stored.diff(&newer), diff);
+    }
+}
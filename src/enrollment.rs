@@ -0,0 +1,469 @@
+use std::fmt::{Display, Formatter};
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use scraper::{ElementRef, Html};
+use serde::{Deserialize, Serialize};
+use url::Url;
+use crate::error::{check_status, StudIpError};
+use crate::flash;
+use crate::labels::{REGISTRATION_LOST, REGISTRATION_PENDING, REGISTRATION_REGISTERED, REGISTRATION_WON};
+use crate::logging::log_parse;
+use crate::util::{local_to_utc, parse_localized_date_time, parse_page, selector};
+use crate::{SendThrough, StudIpClient};
+
+const ENROLMENT_URL: &str = "https://studip.example.com/dispatch.php/course/enrolment/apply";
+const MY_COURSES_URL: &str = "https://studip.example.com/dispatch.php/my_courses";
+const DECLINE_URL: &str = "https://studip.example.com/dispatch.php/my_courses/decline";
+
+/// Headings of the block, that describes the admission procedure on the details page
+const ADMISSION_HEADINGS: [&str; 5] = ["anmelderegeln", "anmeldeverfahren", "teilnahme", "admission", "registration"];
+/// Texts of the rule, that protects a course with a passcode
+const PASSCODE_MARKERS: [&str; 4] = ["zugangscode", "passwort", "passcode", "password"];
+/// Texts of the rule, that locks the enrollment
+const LOCKED_MARKERS: [&str; 4] = ["gesperrt", "nicht möglich", "locked", "not possible"];
+/// Texts of the rule, that draws the places by lot
+const LOTTERY_MARKERS: [&str; 4] = ["losverfahren", "verlost", "lottery", "drawn by lot"];
+/// Texts of the rule, that gives the places in the order of the registrations
+const FIRST_COME_MARKERS: [&str; 3] = ["reihenfolge der anmeldung", "order of registration", "first come"];
+/// Captions of the table of the admission registrations on the overview of the courses
+const REGISTRATION_CAPTIONS: [&str; 4] = ["wartelisten", "anmeldelisten", "waiting lists", "registration lists"];
+
+/// How the places of a course are given to the users, who want to enroll into it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdmissionProcedure {
+    /// Anybody can enroll
+    #[default]
+    Open,
+    /// Enrolling requires a passcode ("Zugangscode"), see [`StudIp::enroll_with_passcode()`](crate::StudIp::enroll_with_passcode())
+    Password,
+    /// The places are drawn by lot ("Losverfahren") after the registration period
+    Lottery,
+    /// The places are given in the order of the registrations
+    FirstComeFirstServed,
+    /// Enrolling is not possible
+    Locked,
+    /// Another procedure, contains its description
+    Other(String),
+}
+
+impl AdmissionProcedure {
+
+    /// Detects the procedure of an admission block, like the admission rules of the details page or the enrollment dialog \
+    /// A password input marks a passcode, as the dialog does not describe the rule.
+    pub(crate) fn detect(block: ElementRef) -> Self {
+        if block.select(selector!("input[type=password]")).next().is_some() {
+            return Self::Password;
+        }
+        // The rules are listed, if there are several of them, otherwise they are paragraphs next to the heading
+        let rules = block.select(selector!("li")).map(text_of).collect::<Vec<_>>();
+        let rules = match rules.is_empty() {
+            true => block.select(selector!("p")).map(text_of).collect(),
+            false => rules,
+        };
+        let text = match rules.is_empty() {
+            true => text_of(block),
+            false => rules.join("; "),
+        };
+        Self::from_text(text)
+    }
+
+    fn from_text(text: String) -> Self {
+        if contains_any(&text, &PASSCODE_MARKERS) {
+            Self::Password
+        } else if contains_any(&text, &LOCKED_MARKERS) {
+            Self::Locked
+        } else if contains_any(&text, &LOTTERY_MARKERS) {
+            Self::Lottery
+        } else if contains_any(&text, &FIRST_COME_MARKERS) {
+            Self::FirstComeFirstServed
+        } else if text.is_empty() {
+            Self::Open
+        } else {
+            log_parse!(debug, "No admission procedure matched {:?}", text);
+            Self::Other(text)
+        }
+    }
+
+}
+
+/// The state of a registration for a course, whose places are given by an [`AdmissionProcedure`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RegistrationState {
+    /// The user is registered, but the places have not been given yet
+    Registered,
+    /// The user got a place in the course
+    Won,
+    /// The user did not get a place in the course
+    Lost,
+    /// The places have been given, but the user is still waiting for one (e.g. on the waiting list)
+    Pending,
+}
+
+impl RegistrationState {
+
+    /// Detects the state from its localized label, like "Warteliste" or "not admitted"
+    fn from_label(label: &str) -> Option<Self> {
+        let label = label.to_lowercase();
+        // The negated labels contain the positive ones, so they are checked first
+        if REGISTRATION_LOST.is_in(&label) {
+            Some(Self::Lost)
+        } else if REGISTRATION_WON.is_in(&label) {
+            Some(Self::Won)
+        } else if REGISTRATION_PENDING.is_in(&label) {
+            Some(Self::Pending)
+        } else if REGISTRATION_REGISTERED.is_in(&label) {
+            Some(Self::Registered)
+        } else {
+            None
+        }
+    }
+
+}
+
+/// A registration of the current user for a course, which does not admit everybody immediately
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdmissionRegistration {
+    pub course_id: String,
+    pub course_name: String,
+    pub procedure: AdmissionProcedure,
+    pub state: RegistrationState,
+    /// When the places are drawn, if they are given by [`AdmissionProcedure::Lottery`]
+    pub drawing_at: Option<DateTime<Utc>>,
+}
+
+/// Why enrolling into a course failed \
+/// Returned inside [`anyhow::Error`]s, other failures are reported as [`StudIpError`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EnrollmentError {
+    /// The passcode was not accepted
+    WrongPasscode,
+    /// The course does not ask for a passcode, as it admits by the `procedure`
+    NoPasscodeRequired { procedure: AdmissionProcedure },
+    /// The places of the course are not drawn by lot, as it admits by the `procedure`
+    NoLottery { procedure: AdmissionProcedure },
+}
+
+impl Display for EnrollmentError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnrollmentError::WrongPasscode => f.write_str("The passcode of the course is wrong"),
+            EnrollmentError::NoPasscodeRequired { procedure } => write!(f, "The course does not require a passcode, its admission procedure is {:?}", procedure),
+            EnrollmentError::NoLottery { procedure } => write!(f, "The course does not draw its places by lot, its admission procedure is {:?}", procedure),
+        }
+    }
+}
+
+impl std::error::Error for EnrollmentError {}
+
+fn text_of(elem: ElementRef) -> String {
+    elem.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn contains_any(text: &str, markers: &[&str]) -> bool {
+    let lowercase = text.to_lowercase();
+    markers.iter().any(|marker| lowercase.contains(marker))
+}
+
+/// Detects the admission procedure from the admission rules of the details page of a course \
+/// Courses without admission rules are open.
+pub(crate) fn parse_admission(html: &Html) -> AdmissionProcedure {
+    html.select(selector!("#content article, #content section"))
+        .find(|block| block.select(selector!("h1, h2"))
+            .next()
+            .is_some_and(|heading| contains_any(&text_of(heading), &ADMISSION_HEADINGS)))
+        .map_or(AdmissionProcedure::Open, AdmissionProcedure::detect)
+}
+
+// The form of the enrollment dialog
+struct EnrolmentForm {
+    action: Option<String>,
+    fields: Vec<(String, String)>,
+}
+
+// Only the dialog of protected courses asks for a passcode, which is put into its password input
+fn parse_enrolment_form(form: ElementRef, passcode: Option<&str>) -> EnrolmentForm {
+    let mut fields = vec![];
+    for input in form.select(selector!("input[name]")) {
+        let name = input.attr("name").unwrap().to_string();
+        match (input.attr("type").unwrap_or("text"), passcode) {
+            ("password", Some(passcode)) => fields.push((name, passcode.to_string())),
+            ("hidden", _) => fields.push((name, input.attr("value").unwrap_or_default().to_string())),
+            _ => {},
+        }
+    }
+    // The dialog is confirmed by its first button, the other one cancels it
+    if let Some(button) = form.select(selector!("button[type=submit][name], input[type=submit][name]")).next() {
+        fields.push((button.attr("name").unwrap().to_string(), button.attr("value").unwrap_or("1").to_string()));
+    }
+    EnrolmentForm { action: form.attr("action").map(str::to_string), fields }
+}
+
+fn parse_passcode_form(html: &Html, passcode: &str) -> Option<EnrolmentForm> {
+    let form = html.select(selector!("form")).find(|form| form.select(selector!("input[type=password]")).next().is_some())?;
+    Some(parse_enrolment_form(form, Some(passcode)))
+}
+
+// Returns the content of the page, which describes the procedure of the dialog
+fn dialog_content(html: &Html) -> ElementRef<'_> {
+    html.select(selector!("#content")).next().unwrap_or(html.root_element())
+}
+
+fn submit_enrolment_form(client: &StudIpClient, form: EnrolmentForm, url: String) -> anyhow::Result<Html> {
+    let action = match form.action {
+        Some(action) => client.absolutize(&action)?.to_string(),
+        None => url,
+    };
+    let response = client.post(action)
+        .form(&form.fields)
+        .send_through(client)?;
+    check_status(response.status())?;
+    if !response.status().is_success() {
+        bail!("Could not enroll into the course. Status Code: {}", response.status());
+    }
+    parse_page(&response.text()?)
+}
+
+/// Enrolls the user into the course, which is protected by the `passcode`
+pub(crate) fn enroll_with_passcode(client: &StudIpClient, course_id: &str, passcode: &str) -> anyhow::Result<()> {
+    let url = format!("{}/{}", ENROLMENT_URL, course_id);
+    let response = client.get(&url).send_through(client)?;
+    check_status(response.status())?;
+    let html = parse_page(&response.text()?)?;
+    flash::check(&html)?;
+    let Some(form) = parse_passcode_form(&html, passcode) else {
+        let procedure = match AdmissionProcedure::detect(dialog_content(&html)) {
+            // The dialog of open courses only asks for a confirmation
+            AdmissionProcedure::Other(_) => AdmissionProcedure::Open,
+            procedure => procedure,
+        };
+        return Err(EnrollmentError::NoPasscodeRequired { procedure }.into());
+    };
+    let html = submit_enrolment_form(client, form, url)?;
+    match flash::check(&html) {
+        // A wrong passcode shows the dialog again
+        Err(StudIpError::ActionFailed { message }) if contains_any(&message, &PASSCODE_MARKERS) || parse_passcode_form(&html, passcode).is_some() => {
+            Err(EnrollmentError::WrongPasscode.into())
+        },
+        Err(error) => Err(error.into()),
+        Ok(_) if parse_passcode_form(&html, passcode).is_some() => Err(EnrollmentError::WrongPasscode.into()),
+        Ok(_) => Ok(()),
+    }
+}
+
+/// Registers the user for the lottery of the course, whose places are drawn after the registration period
+pub(crate) fn register_for_lottery(client: &StudIpClient, course_id: &str) -> anyhow::Result<()> {
+    let url = format!("{}/{}", ENROLMENT_URL, course_id);
+    let response = client.get(&url).send_through(client)?;
+    check_status(response.status())?;
+    let html = parse_page(&response.text()?)?;
+    flash::check(&html)?;
+    let content = dialog_content(&html);
+    let procedure = AdmissionProcedure::detect(content);
+    if procedure != AdmissionProcedure::Lottery {
+        return Err(EnrollmentError::NoLottery { procedure }.into());
+    }
+    let form = content.select(selector!("form")).next().context("Expected form of the lottery registration")?;
+    let mut form = parse_enrolment_form(form, None);
+    // Some versions render the dialog without a token, then the one of the session is sent
+    if !form.fields.iter().any(|(name, _)| name == "security_token") {
+        form.fields.insert(0, ("security_token".to_string(), client.security_token()?));
+    }
+    flash::check(&submit_enrolment_form(client, form, url)?)?;
+    Ok(())
+}
+
+/// Withdraws the registration of the user for the course, or leaves its waiting list
+pub(crate) fn withdraw(client: &StudIpClient, course_id: &str) -> anyhow::Result<()> {
+    let security_token = client.security_token()?;
+    let response = client.post(format!("{}/{}", DECLINE_URL, course_id))
+        .form(&[("cmd", "kill_admission"), ("security_token", security_token.as_str())])
+        .send_through(client)?;
+    check_status(response.status())?;
+    if !response.status().is_success() {
+        bail!("Could not withdraw the registration. Status Code: {}", response.status());
+    }
+    flash::check(&parse_page(&response.text()?)?)?;
+    Ok(())
+}
+
+/// Returns the admission registrations of the user, which are listed on the overview of the courses
+pub(crate) fn admission_registrations(client: &StudIpClient) -> anyhow::Result<Vec<AdmissionRegistration>> {
+    let response = client.get(MY_COURSES_URL).send_through(client)?;
+    check_status(response.status())?;
+    parse_registrations(&parse_page(&response.text()?)?, client.timezone())
+}
+
+// The course id is a parameter of the link to the course, which differs between versions
+fn parse_course_id(href: &str) -> Option<String> {
+    let url = Url::parse("https://studip.example.com/").ok()?.join(href).ok()?;
+    url.query_pairs()
+        .find(|(key, _)| matches!(key.as_ref(), "sem_id" | "cid" | "auswahl"))
+        .map(|(_, id)| id.into_owned())
+}
+
+fn parse_registrations(html: &Html, timezone: Tz) -> anyhow::Result<Vec<AdmissionRegistration>> {
+    let Some(table) = html.select(selector!("table"))
+        .find(|table| table.select(selector!("caption")).next().is_some_and(|caption| contains_any(&text_of(caption), &REGISTRATION_CAPTIONS))) else {
+        // The table is left out, if there are no registrations
+        return Ok(vec![]);
+    };
+    let mut registrations = vec![];
+    for row in table.select(selector!("tbody tr")) {
+        let cells = row.select(selector!("td")).collect::<Vec<_>>();
+        // Rows without a course, like the notice of an empty list, are skipped
+        let Some((link, course_id)) = cells.first()
+            .and_then(|cell| cell.select(selector!("a[href]")).next())
+            .and_then(|link| Some((link, parse_course_id(link.attr("href")?)?))) else {
+            continue;
+        };
+        let Some(state_index) = cells.iter().skip(1).rposition(|cell| RegistrationState::from_label(&text_of(*cell)).is_some()).map(|index| index + 1) else {
+            bail!("Expected state of the registration for {}", course_id);
+        };
+        let state = RegistrationState::from_label(&text_of(cells[state_index])).unwrap();
+        let description = cells[1..state_index].iter().map(|cell| text_of(*cell)).filter(|text| !text.is_empty()).collect::<Vec<_>>().join("; ");
+        let procedure = AdmissionProcedure::from_text(description.clone());
+        let drawing_at = match procedure {
+            AdmissionProcedure::Lottery => parse_localized_date_time(&description).and_then(|date_time| local_to_utc(date_time, timezone)),
+            _ => None,
+        };
+        registrations.push(AdmissionRegistration {
+            course_id,
+            course_name: text_of(link),
+            procedure,
+            state,
+            drawing_at,
+        });
+    }
+    Ok(registrations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+    use crate::DEFAULT_TIMEZONE;
+
+    #[test]
+    fn test_parse_admission() {
+        let admission = |html| parse_admission(&Html::parse_document(html));
+        assert_eq!(admission(include_str!("../testdata/enrollment/open.html")), AdmissionProcedure::Open);
+        assert_eq!(admission(include_str!("../testdata/enrollment/passcode.html")), AdmissionProcedure::Password);
+        assert_eq!(admission(include_str!("../testdata/enrollment/lottery.html")), AdmissionProcedure::Lottery);
+        assert_eq!(admission(r#"<div id="content"><section><h2>Admission rules</h2><ul>
+            <li>Places are given in the order of registration.</li></ul></section></div>"#), AdmissionProcedure::FirstComeFirstServed);
+        assert_eq!(admission(r#"<div id="content"><article><header><h1>Anmelderegeln</h1></header><p>Nur für Studierende der Informatik.</p></article></div>"#),
+            AdmissionProcedure::Other("Nur für Studierende der Informatik.".to_string()));
+        // The dialog only contains the input of the passcode
+        assert_eq!(AdmissionProcedure::detect(Html::parse_document(include_str!("../testdata/enrollment/apply_passcode.html")).root_element()), AdmissionProcedure::Password);
+    }
+
+    #[test]
+    fn test_enroll_with_passcode() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/course/enrolment/apply/c4", 200, include_str!("../testdata/enrollment/apply_passcode.html"))
+            .route("POST", "/dispatch.php/course/enrolment/apply/c4", 200, r#"<div class="messagebox messagebox_success">Sie wurden in die Veranstaltung eingetragen.</div>"#)
+            .route("GET", "/dispatch.php/course/enrolment/apply/c1", 200, r#"<div id="content"><form method="post">
+                <p>Wollen Sie sich zu der Veranstaltung "Algorithmen und Datenstrukturen" wirklich anmelden?</p>
+                <button type="submit" name="apply" value="1">Ja</button></form></div>"#)
+            .route("GET", "/dispatch.php/course/enrolment/apply/c2", 200, r#"<div class="messagebox messagebox_error">Sie sind bereits in der Veranstaltung eingetragen.</div>"#);
+        let client = server.client();
+
+        enroll_with_passcode(&client, "c4", "geheim").unwrap();
+        let requests = server.requests();
+        let post = requests.last().unwrap();
+        assert_eq!((post.method.as_str(), post.path.as_str()), ("POST", "/dispatch.php/course/enrolment/apply/c4"));
+        assert_eq!(post.body, "security_token=tok%3D&admission_password=geheim&apply=1");
+
+        // A wrong passcode is either reported by a message or the dialog is shown again
+        server.route("POST", "/dispatch.php/course/enrolment/apply/c4", 200, r#"<div class="messagebox messagebox_error">Das eingegebene Passwort ist falsch.</div>"#);
+        let error = enroll_with_passcode(&client, "c4", "falsch").unwrap_err();
+        assert_eq!(error.downcast_ref::<EnrollmentError>(), Some(&EnrollmentError::WrongPasscode));
+        server.route("POST", "/dispatch.php/course/enrolment/apply/c4", 200, include_str!("../testdata/enrollment/apply_passcode.html"));
+        let error = enroll_with_passcode(&client, "c4", "falsch").unwrap_err();
+        assert_eq!(error.downcast_ref::<EnrollmentError>(), Some(&EnrollmentError::WrongPasscode));
+
+        let error = enroll_with_passcode(&client, "c1", "geheim").unwrap_err();
+        assert_eq!(error.downcast_ref::<EnrollmentError>(), Some(&EnrollmentError::NoPasscodeRequired { procedure: AdmissionProcedure::Open }));
+        let error = enroll_with_passcode(&client, "c2", "geheim").unwrap_err();
+        assert!(matches!(error.downcast_ref::<StudIpError>(), Some(StudIpError::ActionFailed { .. })), "{:?}", error);
+    }
+
+    #[test]
+    fn test_parse_registrations() {
+        let registrations = parse_registrations(&Html::parse_document(include_str!("../testdata/enrollment/registrations.html")), DEFAULT_TIMEZONE).unwrap();
+        let drawing_at = |text| local_to_utc(parse_localized_date_time(text).unwrap(), DEFAULT_TIMEZONE);
+        assert_eq!(registrations, vec![
+            AdmissionRegistration {
+                course_id: "c1".to_string(),
+                course_name: "Lineare Algebra".to_string(),
+                procedure: AdmissionProcedure::Lottery,
+                state: RegistrationState::Registered,
+                drawing_at: drawing_at("15.10.2024, 10:00"),
+            },
+            AdmissionRegistration {
+                course_id: "c2".to_string(),
+                course_name: "Analysis I".to_string(),
+                procedure: AdmissionProcedure::Lottery,
+                state: RegistrationState::Lost,
+                drawing_at: drawing_at("01.10.2024, 12:00"),
+            },
+            AdmissionRegistration {
+                course_id: "c3".to_string(),
+                course_name: "Programmierpraktikum".to_string(),
+                procedure: AdmissionProcedure::FirstComeFirstServed,
+                state: RegistrationState::Pending,
+                drawing_at: None,
+            },
+            AdmissionRegistration {
+                course_id: "c5".to_string(),
+                course_name: "Theoretische Informatik".to_string(),
+                procedure: AdmissionProcedure::Lottery,
+                state: RegistrationState::Won,
+                drawing_at: drawing_at("02.10.2024, 09:00"),
+            },
+        ]);
+        assert_eq!(RegistrationState::from_label("Not admitted"), Some(RegistrationState::Lost));
+        assert_eq!(RegistrationState::from_label("Admitted"), Some(RegistrationState::Won));
+        assert_eq!(RegistrationState::from_label("On the waiting list"), Some(RegistrationState::Pending));
+        assert_eq!(RegistrationState::from_label("Tentatively registered"), Some(RegistrationState::Registered));
+        assert_eq!(RegistrationState::from_label("Austragen"), None);
+        // Without registrations the table is left out
+        assert!(parse_registrations(&Html::parse_document(r#"<div id="content"><div id="my-courses"></div></div>"#), DEFAULT_TIMEZONE).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_register_and_withdraw() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/start", 200, r#"<input type="hidden" name="security_token" value="tok">"#)
+            .route("GET", "/dispatch.php/course/enrolment/apply/c1", 200, include_str!("../testdata/enrollment/apply_lottery.html"))
+            .route("POST", "/dispatch.php/course/enrolment/apply/c1", 200, r#"<div class="messagebox messagebox_success">Sie wurden für das Losverfahren eingetragen.</div>"#)
+            .route("GET", "/dispatch.php/course/enrolment/apply/c4", 200, include_str!("../testdata/enrollment/apply_passcode.html"))
+            .route("GET", "/dispatch.php/course/enrolment/apply/c6", 200, r#"<div id="content"><form method="post">
+                <p>Die Plätze werden per Losverfahren vergeben.</p>
+                <button type="submit" name="apply" value="1">Eintragen</button></form></div>"#)
+            .route("POST", "/dispatch.php/course/enrolment/apply/c6", 200, r#"<div class="messagebox messagebox_error">Die Anmeldung ist bereits beendet.</div>"#)
+            .route("POST", "/dispatch.php/my_courses/decline/c1", 200, r#"<div class="messagebox messagebox_success">Sie wurden aus der Anmeldeliste ausgetragen.</div>"#);
+        let client = server.client();
+
+        register_for_lottery(&client, "c1").unwrap();
+        let requests = server.requests();
+        let post = requests.last().unwrap();
+        assert_eq!((post.method.as_str(), post.path.as_str()), ("POST", "/dispatch.php/course/enrolment/apply/c1"));
+        assert_eq!(post.body, "security_token=tok%3D&apply=1");
+
+        let error = register_for_lottery(&client, "c4").unwrap_err();
+        assert_eq!(error.downcast_ref::<EnrollmentError>(), Some(&EnrollmentError::NoLottery { procedure: AdmissionProcedure::Password }));
+        // Dialogs without a token get the one of the session
+        let error = register_for_lottery(&client, "c6").unwrap_err();
+        assert!(matches!(error.downcast_ref::<StudIpError>(), Some(StudIpError::ActionFailed { .. })), "{:?}", error);
+        assert_eq!(server.requests().last().unwrap().body, "security_token=tok&apply=1");
+
+        withdraw(&client, "c1").unwrap();
+        let requests = server.requests();
+        let post = requests.last().unwrap();
+        assert_eq!((post.method.as_str(), post.path.as_str()), ("POST", "/dispatch.php/my_courses/decline/c1"));
+        assert_eq!(post.body, "cmd=kill_admission&security_token=tok");
+    }
+}
@@ -0,0 +1,340 @@
+//! Typed errors for the site-wide pages of Stud.IP, which are served instead of the requested page, for the interstitials, that interrupt a login, and for rejected uploads \
+//! They are returned inside [`anyhow::Error`]s and can be distinguished with [`anyhow::Error::downcast_ref()`].
+
+use std::fmt::{Display, Formatter};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{ElementRef, Html};
+use url::Url;
+use crate::util::selector;
+
+/// Texts in the title or heading of the maintenance page
+const MAINTENANCE_MARKERS: [&str; 3] = ["wartungsarbeiten", "wartungsmodus", "maintenance"];
+/// Texts in the message of the page, that is shown when accessing something without permission
+const PERMISSION_DENIED_MARKERS: [&str; 5] = ["keine berechtigung", "nicht die berechtigung", "zugriff verweigert", "access denied", "no permission"];
+/// Titles of the generic exception page
+const SERVER_ERROR_TITLES: [&str; 2] = ["fehler", "error"];
+/// Texts of the IdP page, that forces a password change
+const PASSWORD_CHANGE_MARKERS: [&str; 5] = ["passwort ist abgelaufen", "passwort abgelaufen", "passwort muss geändert werden", "password has expired", "password expired"];
+/// Texts of the page, that requires accepting the terms of use
+const TERMS_MARKERS: [&str; 3] = ["nutzungsbedingungen", "terms of use", "terms of service"];
+/// Texts of the page, that requires completing the user data
+const PROFILE_COMPLETION_MARKERS: [&str; 2] = ["bitte vervollständigen sie ihre daten", "please complete your data"];
+/// Texts of the error message, when the requested object does not exist
+const NOT_FOUND_MARKERS: [&str; 4] = ["nicht gefunden", "existiert nicht", "not found", "does not exist"];
+/// Texts of the message, when the type of an uploaded file is not allowed
+const FILE_TYPE_BLOCKED_MARKERS: [&str; 4] = ["dateityp", "dateiendung", "file type", "file extension"];
+/// Texts of the message, when an uploaded file exceeds the maximum size
+const FILE_TOO_LARGE_MARKERS: [&str; 4] = ["zu groß", "maximale dateigröße", "too large", "maximum file size"];
+/// Texts of the message, when the storage quota of the user or course is exhausted
+const QUOTA_MARKERS: [&str; 4] = ["kontingent", "speicherplatz", "quota", "storage space"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StudIpError {
+    /// Stud.IP is in maintenance mode, which is usually only temporary
+    Maintenance { message: Option<String> },
+    /// Stud.IP failed to process the request and showed its generic exception page
+    ServerError { message: Option<String> },
+    /// The logged-in user is not allowed to access the requested page
+    PermissionDenied { message: Option<String> },
+    /// The requested object (e.g. a file) does not exist
+    NotFound { message: Option<String> },
+    /// The password of the user expired and has to be changed at the `url`, before logging in again
+    PasswordChangeRequired { url: String },
+    /// The terms of use have to be accepted at the `url`, before logging in again
+    TermsAcceptanceRequired { url: String },
+    /// The user data is incomplete and has to be completed at the `url`, before logging in again
+    ProfileCompletionRequired { url: String },
+    /// The login is stuck in a loop between the Service Provider and the IdP, visiting the urls of the `chain` in order
+    SsoLoop { chain: Vec<String> },
+    /// A request was redirected more often than allowed, visiting the urls of the `chain` in order
+    TooManyRedirects { chain: Vec<String> },
+    /// The type of the uploaded file is not allowed in the folder
+    FileTypeBlocked { file_name: String, message: String },
+    /// The uploaded file exceeds the maximum size of the folder
+    FileTooLarge { file_name: String, message: String },
+    /// The storage quota is exhausted, so that no more files can be uploaded
+    QuotaExceeded { message: String },
+    /// The `feature` (e.g. the trash of the files) is disabled on the installation
+    FeatureDisabled { feature: String },
+    /// Stud.IP answered an action with an error message (see [flash::extract()](crate::flash::extract()))
+    ActionFailed { message: String },
+    /// The `what` (e.g. "option input") could not be found with the `selector`, because the page at the `url` looks different than expected \
+    /// The `snippet` is a truncated part of the html around the expected location, with session cookies and security tokens removed.
+    /// It is only displayed with the `verbose` feature or the alternate format (`{:#}`).
+    Parse { what: String, selector: String, url: Option<String>, snippet: String },
+    /// There is no entry for the `username` under the `service` in the keychain of the OS
+    #[cfg(feature = "keyring")]
+    CredentialsNotFound { service: String, username: String },
+    /// The keychain of the OS is locked or can not be accessed
+    #[cfg(feature = "keyring")]
+    KeyringUnavailable { message: String },
+}
+
+impl Display for StudIpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (description, message) = match self {
+            StudIpError::Maintenance { message } => ("Stud.IP is currently in maintenance", message),
+            StudIpError::ServerError { message } => ("Stud.IP failed to process the request", message),
+            StudIpError::PermissionDenied { message } => ("Missing permission to access this page", message),
+            StudIpError::NotFound { message } => ("The requested object does not exist", message),
+            StudIpError::PasswordChangeRequired { url } => return write!(f, "The password has expired, change it at {} and log in again", url),
+            StudIpError::TermsAcceptanceRequired { url } => return write!(f, "The terms of use have to be accepted at {} before logging in", url),
+            StudIpError::ProfileCompletionRequired { url } => return write!(f, "The user data has to be completed at {} before logging in", url),
+            StudIpError::SsoLoop { chain } => return write!(
+                f,
+                "The login is stuck in a redirect loop ({}). Check, that the entity url of the IdentityProvider is correct, that cookies are accepted and that the system clock is correct",
+                crate::redirect::format_chain(chain)
+            ),
+            StudIpError::TooManyRedirects { chain } => return write!(f, "Too many redirects: {}", crate::redirect::format_chain(chain)),
+            StudIpError::FileTypeBlocked { file_name, message } => return write!(f, "The type of {} is not allowed: {}", file_name, message),
+            StudIpError::FileTooLarge { file_name, message } => return write!(f, "{} is too large: {}", file_name, message),
+            StudIpError::QuotaExceeded { message } => return write!(f, "The storage quota is exceeded: {}", message),
+            StudIpError::FeatureDisabled { feature } => return write!(f, "{} is disabled on this Stud.IP installation", feature),
+            StudIpError::ActionFailed { message } => return write!(f, "Stud.IP reported an error: {}", message),
+            StudIpError::Parse { what, selector, url, snippet } => {
+                write!(f, "Expected {} (selector `{}`)", what, selector)?;
+                if let Some(url) = url {
+                    write!(f, " on {}", url)?;
+                }
+                if cfg!(feature = "verbose") || f.alternate() {
+                    write!(f, "\n{}", snippet)?;
+                }
+                return Ok(());
+            },
+            #[cfg(feature = "keyring")]
+            StudIpError::CredentialsNotFound { service, username } => return write!(f, "No credentials for {} stored under {} in the keyring", username, service),
+            #[cfg(feature = "keyring")]
+            StudIpError::KeyringUnavailable { message } => return write!(f, "The keyring is locked or unavailable: {}", message),
+        };
+        match message {
+            Some(message) => write!(f, "{}: {}", description, message),
+            None => f.write_str(description),
+        }
+    }
+}
+
+impl std::error::Error for StudIpError {}
+
+/// Sets the url of the page, if the error is a [`StudIpError::Parse`], that does not know it yet
+pub(crate) trait WithUrl<T> {
+    fn with_url(self, url: impl Display) -> anyhow::Result<T>;
+}
+
+impl<T> WithUrl<T> for anyhow::Result<T> {
+    fn with_url(self, page_url: impl Display) -> anyhow::Result<T> {
+        self.map_err(|mut error| {
+            if let Some(StudIpError::Parse { url: url @ None, .. }) = error.downcast_mut::<StudIpError>() {
+                *url = Some(page_url.to_string());
+            }
+            error
+        })
+    }
+}
+
+/// The maximum number of characters of the snippet of a [`StudIpError::Parse`]
+const SNIPPET_LENGTH: usize = 500;
+
+static SECRET_INPUT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(?i)<input\b[^>]*(?:security_token|type="password")[^>]*>"#).unwrap());
+static INPUT_VALUE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(?i)\bvalue="[^"]*""#).unwrap());
+static SESSION_COOKIE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"Seminar_Session=[^;&"'\s]*"#).unwrap());
+
+/// Returns the start of the html of the `scope`, without security tokens, passwords and session cookies \
+/// For whole documents only the main content is used, as the head and navigation are the same on every page.
+pub(crate) fn html_snippet(scope: ElementRef) -> String {
+    let scope = match scope.value().name() {
+        "html" => scope.select(selector!("#content")).next()
+            .or_else(|| scope.select(selector!("body")).next())
+            .unwrap_or(scope),
+        _ => scope,
+    };
+    let html = scope.html();
+    let html = SECRET_INPUT_REGEX.replace_all(&html, |captures: &regex::Captures| {
+        INPUT_VALUE_REGEX.replace_all(&captures[0], "value=\"<redacted>\"").into_owned()
+    });
+    let html = SESSION_COOKIE_REGEX.replace_all(&html, "Seminar_Session=<redacted>");
+    match html.char_indices().nth(SNIPPET_LENGTH) {
+        Some((end, _)) => format!("{}…", &html[..end]),
+        None => html.into_owned(),
+    }
+}
+
+fn text_of(elem: ElementRef) -> String {
+    elem.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn contains_any(text: &str, markers: &[&str]) -> bool {
+    let lowercase = text.to_lowercase();
+    markers.iter().any(|marker| lowercase.contains(marker))
+}
+
+/// Checks, whether the page is the maintenance page, the generic exception page or the permission denied page, instead of the requested one
+pub(crate) fn check_page(html: &Html) -> Result<(), StudIpError> {
+    let title = html.select(selector!("title")).next().map(text_of).unwrap_or_default();
+    // Headings of articles are ignored, as announcements of maintenance are not the maintenance page
+    let heading = html.select(selector!("h1"))
+        .find(|heading| !heading.ancestors().filter_map(ElementRef::wrap).any(|ancestor| ancestor.value().name() == "article"))
+        .map(text_of)
+        .unwrap_or_default();
+    let message_box = html.select(selector!(".messagebox_error, .messagebox_exception")).next().map(text_of);
+
+    if contains_any(&title, &MAINTENANCE_MARKERS) || contains_any(&heading, &MAINTENANCE_MARKERS) || html.select(selector!("#maintenance")).next().is_some() {
+        let message = html.select(selector!("#maintenance .messagebox, .messagebox_info, #maintenance p")).next()
+            .map(text_of)
+            .filter(|message| !message.is_empty());
+        return Err(StudIpError::Maintenance { message });
+    }
+    if let Some(message) = message_box.as_deref() {
+        if contains_any(message, &PERMISSION_DENIED_MARKERS) {
+            return Err(StudIpError::PermissionDenied { message: Some(message.to_string()) });
+        }
+    }
+    let is_error_title = SERVER_ERROR_TITLES.iter().any(|error_title| title.to_lowercase().starts_with(error_title));
+    if is_error_title && html.select(selector!(".messagebox_exception")).next().is_some() {
+        return Err(StudIpError::ServerError { message: message_box });
+    }
+    Ok(())
+}
+
+/// Like [`check_page()`], but only parses the `body`, if it might be one of these pages
+pub(crate) fn check_page_text(body: &str) -> Result<(), StudIpError> {
+    let lowercase = body.to_lowercase();
+    if !lowercase.contains("messagebox_") && !MAINTENANCE_MARKERS.iter().any(|marker| lowercase.contains(marker)) {
+        return Ok(());
+    }
+    check_page(&Html::parse_document(body))
+}
+
+/// Maps the status codes 404 and 403 to [`StudIpError::NotFound`] and [`StudIpError::PermissionDenied`]
+pub(crate) fn check_status(status: reqwest::StatusCode) -> Result<(), StudIpError> {
+    match status {
+        reqwest::StatusCode::NOT_FOUND => Err(StudIpError::NotFound { message: None }),
+        reqwest::StatusCode::FORBIDDEN => Err(StudIpError::PermissionDenied { message: None }),
+        _ => Ok(()),
+    }
+}
+
+/// Checks, whether the page only shows an error message, that the requested object does not exist
+pub(crate) fn check_not_found(html: &Html) -> Result<(), StudIpError> {
+    match html.select(selector!(".messagebox_error")).next().map(text_of) {
+        Some(message) if contains_any(&message, &NOT_FOUND_MARKERS) => Err(StudIpError::NotFound { message: Some(message) }),
+        _ => Ok(()),
+    }
+}
+
+/// Maps the error `message` of a rejected upload of the file `file_name` to a [`StudIpError`], if it is a known reason
+pub(crate) fn upload_rejection(file_name: &str, message: &str) -> Option<StudIpError> {
+    let (file_name, message) = (file_name.to_string(), message.to_string());
+    if contains_any(&message, &FILE_TYPE_BLOCKED_MARKERS) {
+        Some(StudIpError::FileTypeBlocked { file_name, message })
+    } else if contains_any(&message, &FILE_TOO_LARGE_MARKERS) {
+        Some(StudIpError::FileTooLarge { file_name, message })
+    } else if contains_any(&message, &QUOTA_MARKERS) {
+        Some(StudIpError::QuotaExceeded { message })
+    } else {
+        None
+    }
+}
+
+/// Checks, whether the page at the `url`, which was reached while logging in, is an interstitial of the IdP or Stud.IP, that requires user interaction \
+/// Can also be used by [`IdentityProvider`](crate::IdentityProvider) implementations, to check the pages of the IdP.
+pub fn check_login_page(url: &Url, html: &Html) -> Result<(), StudIpError> {
+    let title = html.select(selector!("title")).next().map(text_of).unwrap_or_default();
+    let headings = html.select(selector!("h1, h2, .messagebox, form label, form p")).map(text_of).collect::<Vec<_>>().join(" ");
+    let text = format!("{} {}", title, headings);
+    // The url of the form, that has to be submitted manually, or the page itself
+    let form_url = || html.select(selector!("form[action]")).next()
+        .and_then(|form| url.join(form.value().attr("action")?).ok())
+        .unwrap_or_else(|| url.clone())
+        .to_string();
+    if contains_any(&text, &PASSWORD_CHANGE_MARKERS) {
+        return Err(StudIpError::PasswordChangeRequired { url: form_url() });
+    }
+    if contains_any(&text, &PROFILE_COMPLETION_MARKERS) {
+        return Err(StudIpError::ProfileCompletionRequired { url: form_url() });
+    }
+    let has_accept_input = html.select(selector!("form input[type=checkbox], form button, form input[type=submit]")).next().is_some();
+    if contains_any(&text, &TERMS_MARKERS) && has_accept_input {
+        return Err(StudIpError::TermsAcceptanceRequired { url: form_url() });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::expect_one;
+
+    #[test]
+    fn test_check_page() {
+        let check = |html: &str| check_page(&Html::parse_document(html));
+        assert_eq!(
+            check(include_str!("../testdata/pages/maintenance.html")),
+            Err(StudIpError::Maintenance { message: Some("Das System befindet sich im Wartungsmodus. Bitte versuchen Sie es ab 14:00 Uhr erneut.".into()) })
+        );
+        assert_eq!(
+            check(include_str!("../testdata/pages/exception.html")),
+            Err(StudIpError::ServerError { message: Some("Bei der Verarbeitung Ihrer Anfrage ist ein Fehler aufgetreten. Bitte wenden Sie sich an den Support.".into()) })
+        );
+        assert_eq!(
+            check(include_str!("../testdata/pages/permission_denied.html")),
+            Err(StudIpError::PermissionDenied { message: Some("Sie haben keine Berechtigung, diese Seite aufzurufen.".into()) })
+        );
+        // An error message after an action is not an error page
+        assert_eq!(check(r#"<html><head><title>Nachrichten - Stud.IP</title></head><body><div class="messagebox messagebox_error">Die Nachricht konnte nicht verschickt werden.</div></body></html>"#), Ok(()));
+        assert_eq!(check_page_text("<html><head><title>Start - Stud.IP</title></head><body><p>Hallo</p></body></html>"), Ok(()));
+        assert!(check_page_text(include_str!("../testdata/pages/maintenance.html")).is_err());
+        assert_eq!(check_page_text(include_str!("../testdata/news/global_news_1.html")), Ok(()));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let html = Html::parse_document(r#"<html><head><title>Umfrage</title></head><body><div id="content">
+            <form><input type="hidden" name="security_token" value="s3cr3t="><input type="password" name="pw" value="hunter2"></form>
+            <a href="https://studip.example.com/logout?Seminar_Session=abc123">Logout</a>
+            <ul class="clean"><label>Ja</label></ul>
+        </div></body></html>"#);
+        let label = html.select(selector!("label")).next().unwrap();
+        assert!(expect_one(label, selector!("input[value]"), "option input").is_err());
+
+        let error = expect_one(html.root_element(), selector!("article .questionnaire_answer"), "options").unwrap_err();
+        let StudIpError::Parse { what, selector, url, snippet } = &error else { panic!("{:?}", error) };
+        assert_eq!((what.as_str(), selector.as_str(), url), ("options", "article .questionnaire_answer", &None));
+        // The snippet starts at the content and leaves out secrets
+        assert!(snippet.starts_with(r#"<div id="content">"#), "{}", snippet);
+        assert!(snippet.contains("<label>Ja</label>"));
+        for secret in ["s3cr3t=", "hunter2", "abc123"] {
+            assert!(!snippet.contains(secret), "{}", snippet);
+        }
+
+        let error = Err::<(), _>(anyhow::Error::from(error)).with_url("https://studip.example.com/dispatch.php/start").unwrap_err();
+        assert_eq!(error.to_string().lines().next(), Some("Expected options (selector `article .questionnaire_answer`) on https://studip.example.com/dispatch.php/start"));
+        assert!(format!("{:#}", error.downcast_ref::<StudIpError>().unwrap()).contains("<label>Ja</label>"));
+
+        // Long pages are truncated
+        let html = Html::parse_document(&format!("<p>{}</p>", "a".repeat(1000)));
+        let StudIpError::Parse { snippet, .. } = expect_one(html.root_element(), selector!("table"), "table").unwrap_err() else { unreachable!() };
+        assert_eq!(snippet.chars().count(), SNIPPET_LENGTH + 1);
+    }
+
+    #[test]
+    fn test_check_login_page() {
+        let url = Url::parse("https://idp.example.com/idp/profile/SAML2/Redirect/SSO?execution=e1s2").unwrap();
+        let check = |html: &str| check_login_page(&url, &Html::parse_document(html));
+        assert_eq!(
+            check(include_str!("../testdata/pages/password_expired.html")),
+            Err(StudIpError::PasswordChangeRequired { url: "https://idp.example.com/password/change".into() })
+        );
+        assert_eq!(
+            check(include_str!("../testdata/pages/terms.html")),
+            Err(StudIpError::TermsAcceptanceRequired { url: "https://idp.example.com/idp/profile/SAML2/Redirect/SSO?execution=e1s2".into() })
+        );
+        assert_eq!(
+            check(include_str!("../testdata/pages/profile_completion.html")),
+            Err(StudIpError::ProfileCompletionRequired { url: "https://idp.example.com/dispatch.php/settings/details".into() })
+        );
+        // A page, that only links to the terms, does not require accepting them
+        assert_eq!(check(r#"<html><head><title>Start - Stud.IP</title></head><body><footer><p>Nutzungsbedingungen</p></footer></body></html>"#), Ok(()));
+    }
+}
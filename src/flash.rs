@@ -0,0 +1,93 @@
+use scraper::{ElementRef, Html};
+use serde::{Deserialize, Serialize};
+use crate::error::StudIpError;
+use crate::util::selector;
+
+/// The severity of a [`FlashMessage`], as indicated by the color of its box
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Severity {
+    Success,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+
+    // Determines the severity by the class of the box, as its text is localized
+    fn from_class(class: &str) -> Option<Self> {
+        match class {
+            "messagebox_success" => Some(Self::Success),
+            "messagebox_info" => Some(Self::Info),
+            "messagebox_warning" => Some(Self::Warning),
+            "messagebox_error" | "messagebox_exception" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+}
+
+/// A message box ("MessageBox"), with which Stud.IP reports the outcome of an action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlashMessage {
+    pub severity: Severity,
+    /// The text of the message, including its details
+    pub text: String,
+}
+
+/// Extracts all message boxes of a page, in the order they are shown
+pub fn extract(html: &Html) -> Vec<FlashMessage> {
+    html.select(selector!("[class*=\"messagebox_\"]"))
+        .filter_map(|message_box| {
+            let severity = message_box.value().classes().find_map(Severity::from_class)?;
+            // The buttons (e.g. to close the box) are not part of the message
+            let text = message_box.descendants()
+                .filter(|node| !node.ancestors()
+                    .take_while(|ancestor| ancestor.id() != message_box.id())
+                    .filter_map(ElementRef::wrap)
+                    .any(|ancestor| ancestor.value().classes().any(|class| class == "messagebox_buttons")))
+                .filter_map(|node| node.value().as_text().map(|text| text.to_string()))
+                .collect::<String>();
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            Some(FlashMessage { severity, text })
+        })
+        .collect()
+}
+
+/// Decides, whether the action, which was answered with the page, succeeded \
+/// Fails with [`StudIpError::ActionFailed`] and the text of the first error message, otherwise returns the text of the first success message, if there is one.
+pub(crate) fn check(html: &Html) -> Result<Option<String>, StudIpError> {
+    let messages = extract(html);
+    if let Some(error) = messages.iter().find(|message| message.severity == Severity::Error) {
+        return Err(StudIpError::ActionFailed { message: error.text.clone() });
+    }
+    Ok(messages.into_iter()
+        .find(|message| message.severity == Severity::Success)
+        .map(|message| message.text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let html = Html::parse_document(r##"<div id="layout_content">
+            <div class="messagebox messagebox_warning">Die Gruppe ist fast voll.<div class="messagebox_buttons"><a class="close" href="#" title="Nachrichtenbox schließen"><span>Nachrichtenbox schließen</span></a></div></div>
+            <div class="messagebox messagebox_success">  Sie wurden in die Gruppe
+                eingetragen. </div>
+            <div class="messagebox messagebox_custom">Unbekannt</div>
+        </div>"##);
+        assert_eq!(extract(&html), vec![
+            FlashMessage { severity: Severity::Warning, text: "Die Gruppe ist fast voll.".to_string() },
+            FlashMessage { severity: Severity::Success, text: "Sie wurden in die Gruppe eingetragen.".to_string() },
+        ]);
+        assert_eq!(check(&html).unwrap().as_deref(), Some("Sie wurden in die Gruppe eingetragen."));
+
+        // The severity is detected independent of the language
+        let html = Html::parse_document(r#"<div class="messagebox messagebox_error">You are not allowed to join this group.</div>"#);
+        let error = check(&html).unwrap_err();
+        assert!(matches!(&error, StudIpError::ActionFailed { message } if message == "You are not allowed to join this group."), "{:?}", error);
+        assert_eq!(check(&Html::parse_document("<p></p>")).unwrap(), None);
+    }
+}
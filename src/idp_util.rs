@@ -0,0 +1,124 @@
+//! Reusable parts of the login flow of Shibboleth IdPs, to compose [`IdentityProvider`](crate::IdentityProvider) implementations from \
+//! Covers the attribute release (consent) page, that IdPs show the first time or after the released attributes changed,
+//! and the auto-submitting page, that posts the SAML assertion back to Stud.IP.
+
+use anyhow::Context;
+use reqwest::blocking::{Client, Response};
+use scraper::Html;
+use url::Url;
+use crate::SAMLAssertionData;
+use crate::util::selector;
+
+const CONSENT_IDS_FIELD: &str = "_shib_idp_consentIds";
+const CONSENT_OPTIONS_FIELD: &str = "_shib_idp_consentOptions";
+const REMEMBER_CONSENT: &str = "_shib_idp_rememberConsent";
+const DO_NOT_REMEMBER_CONSENT: &str = "_shib_idp_doNotRememberConsent";
+const PROCEED_FIELD: &str = "_eventId_proceed";
+
+/// The form of an attribute release page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsentForm {
+    /// The url, to which the form is posted, which may be relative (see [`ConsentForm::resolve()`])
+    pub action: String,
+    /// The hidden inputs, like the `csrf_token`
+    pub hidden_fields: Vec<(String, String)>,
+    /// The ids of the attributes, that are released to Stud.IP
+    pub consent_ids: Vec<String>,
+}
+
+impl ConsentForm {
+
+    /// Resolves a relative `action` against the url of the page, on which the form was found
+    pub fn resolve(mut self, page_url: &Url) -> anyhow::Result<Self> {
+        self.action = page_url.join(&self.action)?.to_string();
+        Ok(self)
+    }
+
+}
+
+/// Detects the attribute release page of the Shibboleth consent flow and returns its form
+pub fn detect_consent_page(html: &Html) -> Option<ConsentForm> {
+    let form = html.select(selector!("form")).find(|form| {
+        form.select(selector!(r#"input[name="_shib_idp_consentIds"], input[name="_shib_idp_consentOptions"]"#)).next().is_some()
+    })?;
+    let action = form.value().attr("action").unwrap_or_default().to_string();
+    let hidden_fields = form.select(selector!(r#"input[type="hidden"]"#))
+        .filter_map(|input| Some((input.value().attr("name")?.to_string(), input.value().attr("value").unwrap_or_default().to_string())))
+        .collect();
+    let consent_ids = form.select(selector!(r#"input[name="_shib_idp_consentIds"]"#))
+        .filter_map(|input| input.value().attr("value"))
+        .map(|id| id.to_string())
+        .collect();
+    Some(ConsentForm {
+        action,
+        hidden_fields,
+        consent_ids,
+    })
+}
+
+/// Accepts the release of all attributes of the `form` and returns the response, which is usually the auto-submitting page (see [`extract_saml_autosubmit()`]) \
+/// If `remember` is true, the IdP only asks again, once the released attributes change.
+pub fn submit_consent(client: &Client, form: ConsentForm, remember: bool) -> anyhow::Result<Response> {
+    let action = Url::parse(&form.action)
+        .with_context(|| format!("Consent form action {} is not absolute, resolve it first", form.action))?;
+    let mut fields = form.hidden_fields;
+    fields.extend(form.consent_ids.into_iter().map(|id| (CONSENT_IDS_FIELD.to_string(), id)));
+    let option = if remember {REMEMBER_CONSENT} else {DO_NOT_REMEMBER_CONSENT};
+    fields.push((CONSENT_OPTIONS_FIELD.to_string(), option.to_string()));
+    fields.push((PROCEED_FIELD.to_string(), "Accept".to_string()));
+    let response = client.post(action)
+        .form(&fields)
+        .send()?;
+    if !response.status().is_success() {
+        anyhow::bail!("Submitting the consent returned {}", response.status());
+    }
+    Ok(response)
+}
+
+/// Extracts the SAML assertion from the page, that auto-submits it to the Service Provider (the SAML2 POST binding)
+pub fn extract_saml_autosubmit(html: &Html) -> Option<SAMLAssertionData> {
+    let value_of = |selector| html.select(selector).next()
+        .and_then(|input: scraper::ElementRef| input.value().attr("value"))
+        .map(|value| value.to_string());
+    Some(SAMLAssertionData {
+        relay_state: value_of(selector!(r#"form input[name="RelayState"]"#)).unwrap_or_default(),
+        saml_response: value_of(selector!(r#"form input[name="SAMLResponse"]"#))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_consent() {
+        let html = Html::parse_document(include_str!("../testdata/idp/consent.html"));
+        let form = detect_consent_page(&html).unwrap();
+        assert_eq!(form.action, "/idp/profile/SAML2/Redirect/SSO?execution=e1s2");
+        assert_eq!(form.hidden_fields, vec![("csrf_token".to_string(), "_5f0a4ee1c7e2c5d93b0b3b6e3f1f8f2a".to_string())]);
+        assert_eq!(form.consent_ids, vec!["mail", "displayName", "eduPersonScopedAffiliation"]);
+        assert!(detect_consent_page(&Html::parse_document(include_str!("../testdata/idp/autosubmit.html"))).is_none());
+
+        let server = MockServer::start();
+        server.route("POST", "/idp/profile/SAML2/Redirect/SSO", 200, include_str!("../testdata/idp/autosubmit.html"));
+        let form = form.resolve(&Url::parse(&server.url("/idp/profile/SAML2/Redirect/SSO?execution=e1s1")).unwrap()).unwrap();
+        let response = submit_consent(&server.client().client, form, true).unwrap();
+        let assertion = extract_saml_autosubmit(&Html::parse_document(&response.text().unwrap())).unwrap();
+        assert_eq!(assertion.relay_state, "cookie:1710230400_a1b2");
+        assert_eq!(assertion.saml_response, "PHNhbWxwOlJlc3BvbnNlPjwvc2FtbHA6UmVzcG9uc2U+");
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/idp/profile/SAML2/Redirect/SSO?execution=e1s2");
+        assert_eq!(
+            requests[0].body,
+            "csrf_token=_5f0a4ee1c7e2c5d93b0b3b6e3f1f8f2a&_shib_idp_consentIds=mail&_shib_idp_consentIds=displayName&_shib_idp_consentIds=eduPersonScopedAffiliation\
+             &_shib_idp_consentOptions=_shib_idp_rememberConsent&_eventId_proceed=Accept"
+        );
+    }
+
+    #[test]
+    fn test_extract_saml_autosubmit() {
+        assert!(extract_saml_autosubmit(&Html::parse_document(include_str!("../testdata/idp/consent.html"))).is_none());
+    }
+}
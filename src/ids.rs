@@ -0,0 +1,110 @@
+//! Typed ids, so that e.g. a folder id can not be passed where a course id is expected \
+//! All ids are cheap wrappers around a [`String`] and are serialized as a plain string.
+
+use std::borrow::Borrow;
+use std::fmt::{Display, Formatter};
+use std::ops::Deref;
+use serde::{Deserialize, Serialize};
+
+macro_rules! id_type {
+    ($($(#[$meta:meta])* $name:ident),+ $(,)?) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+            #[serde(transparent)]
+            pub struct $name(String);
+
+            impl $name {
+
+                pub fn as_str(&self) -> &str {
+                    &self.0
+                }
+
+                pub fn into_inner(self) -> String {
+                    self.0
+                }
+
+            }
+
+            impl Display for $name {
+                fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                    f.write_str(&self.0)
+                }
+            }
+
+            impl Deref for $name {
+                type Target = str;
+
+                fn deref(&self) -> &str {
+                    &self.0
+                }
+            }
+
+            impl Borrow<str> for $name {
+                fn borrow(&self) -> &str {
+                    &self.0
+                }
+            }
+
+            impl AsRef<str> for $name {
+                fn as_ref(&self) -> &str {
+                    &self.0
+                }
+            }
+
+            impl From<String> for $name {
+                fn from(id: String) -> Self {
+                    Self(id)
+                }
+            }
+
+            impl From<&str> for $name {
+                fn from(id: &str) -> Self {
+                    Self(id.to_string())
+                }
+            }
+
+            impl PartialEq<str> for $name {
+                fn eq(&self, other: &str) -> bool {
+                    self.0 == other
+                }
+            }
+
+            impl PartialEq<&str> for $name {
+                fn eq(&self, other: &&str) -> bool {
+                    self.0 == *other
+                }
+            }
+        )+
+    };
+}
+
+id_type!(
+    /// The id of a [Course](crate::course::Course), also called `cid` in urls
+    CourseId,
+    /// The internal id of a user, which is different from their username
+    UserId,
+    /// The id of a [File](crate::course_modules::file::File)
+    FileId,
+    /// The id of a [Folder](crate::course_modules::file::Folder)
+    FolderId,
+    /// The id of a [Group](crate::course_modules::members::Group)
+    GroupId,
+    /// The id of an [Institute](crate::institute::Institute), also called `cid` in urls
+    InstituteId,
+    /// The id of a [Room](crate::resources::Room) or another resource
+    RoomId,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialized_as_string() {
+        let id = CourseId::from("c1");
+        assert_eq!(serde_json::to_string(&id).unwrap(), r#""c1""#);
+        assert_eq!(serde_json::from_str::<CourseId>(r#""c1""#).unwrap(), id);
+        assert_eq!(id.to_string(), "c1");
+    }
+}
@@ -0,0 +1,192 @@
+//! Acting as another user ("Als Nutzer agieren"), like admins do for support \
+//! Started with [`StudIp::impersonate()`](crate::StudIp::impersonate()), which switches the session of the client to the user,
+//! until the returned [`ImpersonationGuard`] is ended or dropped.
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use anyhow::bail;
+use crate::error::{check_status, StudIpError};
+use crate::flash;
+use crate::session::{self, Session};
+use crate::user::resolve_user_id;
+use crate::util::parse_page;
+use crate::{SendThrough, StudIpClient};
+
+const SWITCH_USER_URL: &str = "https://studip.example.com/dispatch.php/admin/user/switch_user";
+const RESTORE_USER_URL: &str = "https://studip.example.com/dispatch.php/admin/user/restore_user";
+
+/// The global permissions, that are allowed to act as another user
+const IMPERSONATING_PERMS: [&str; 2] = ["root", "admin"];
+
+/// Restores the original session of the client, when it is ended or dropped \
+/// While it exists, every request of the client (and of all handles sharing it) is sent as the impersonated user.
+#[derive(Debug)]
+pub struct ImpersonationGuard {
+    client: Arc<StudIpClient>,
+    user: Session,
+    ended: bool,
+}
+
+impl ImpersonationGuard {
+
+    /// The impersonated user
+    pub fn user(&self) -> &Session {
+        &self.user
+    }
+
+    /// Restores the original session, returning the error, which dropping the guard would ignore
+    pub fn end(mut self) -> anyhow::Result<()> {
+        self.ended = true;
+        restore(&self.client)
+    }
+
+}
+
+impl Drop for ImpersonationGuard {
+    fn drop(&mut self) {
+        if !self.ended {
+            let _ = restore(&self.client);
+        }
+    }
+}
+
+/// Switches the session of the client to the user with the `username` \
+/// The permission of the `current` user is checked first, so that nothing changes for users, that are not allowed to.
+pub(crate) fn impersonate(client: &Arc<StudIpClient>, current: &Session, username: &str) -> anyhow::Result<ImpersonationGuard> {
+    if !IMPERSONATING_PERMS.contains(&current.perm.as_str()) {
+        return Err(StudIpError::PermissionDenied {
+            message: Some(format!("Only admins can act as another user, {} has the permission {}", current.username, current.perm)),
+        }.into());
+    }
+    // Held until the switch is done, so that two switches at the same time can not both start
+    let mut impersonated = client.impersonated.lock().unwrap();
+    if impersonated.is_some() {
+        bail!("Already acting as another user, end that first");
+    }
+    let user_id = resolve_user_id(client, username)?;
+    let security_token = client.security_token()?;
+    let response = client.post(format!("{}/{}", SWITCH_USER_URL, user_id))
+        .form(&[("security_token", security_token.as_str())])
+        .send_through(client)?;
+    check_status(response.status())?;
+    if !response.status().is_success() {
+        bail!("Could not act as {}. Status Code: {}", username, response.status());
+    }
+    flash::check(&parse_page(&response.text()?)?)?;
+    // The token and the cached pages belong to the original session
+    *client.security_token.lock().unwrap() = None;
+    client.identity_switches.fetch_add(1, Ordering::SeqCst);
+    let user = session::query_session(client, None).unwrap_or_else(|_| Session {
+        user_id,
+        username: username.to_string(),
+        display_name: username.to_string(),
+        perm: String::new(),
+        idp_session_expires: None,
+    });
+    *impersonated = Some(user.clone());
+    Ok(ImpersonationGuard { client: client.clone(), user, ended: false })
+}
+
+fn restore(client: &StudIpClient) -> anyhow::Result<()> {
+    let mut impersonated = client.impersonated.lock().unwrap();
+    let security_token = client.security_token()?;
+    let response = client.post(RESTORE_USER_URL)
+        .form(&[("security_token", security_token.as_str())])
+        .send_through(client)?;
+    check_status(response.status())?;
+    if !response.status().is_success() {
+        bail!("Could not restore the original session. Status Code: {}", response.status());
+    }
+    flash::check(&parse_page(&response.text()?)?)?;
+    *client.security_token.lock().unwrap() = None;
+    client.identity_switches.fetch_add(1, Ordering::SeqCst);
+    *impersonated = None;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+    use crate::page_cache::PageCache;
+    use crate::StudIp;
+
+    fn session(username: &str, perm: &str) -> Session {
+        Session {
+            user_id: format!("id_{}", username).into(),
+            username: username.to_string(),
+            display_name: username.to_string(),
+            perm: perm.to_string(),
+            idp_session_expires: None,
+        }
+    }
+
+    #[test]
+    fn test_impersonate() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/start", 200, r#"<input type="hidden" name="security_token" value="tok">"#)
+            .route("GET", "/dispatch.php/profile?username=jdoe", 200, r#"<div id="sidebar"><div data-user-id="u_jdoe"></div></div>"#)
+            .route("POST", "/dispatch.php/admin/user/switch_user/u_jdoe", 200, r#"<div class="messagebox messagebox_success">Sie agieren jetzt als John Doe.</div>"#)
+            .route("POST", "/dispatch.php/admin/user/restore_user", 200, r#"<div class="messagebox messagebox_success">Sie agieren wieder als Sie selbst.</div>"#)
+            .route_json("GET", "/jsonapi.php/v1/users/me", 200, r#"{"data": {"id": "u_jdoe", "attributes": {"username": "jdoe", "formatted-name": "John Doe", "permission": "autor"}}}"#);
+        let mut stud_ip = StudIp::from_client(server.client());
+        stud_ip.session = Some(session("admin", "root"));
+
+        let guard = stud_ip.impersonate("jdoe").unwrap();
+        assert_eq!((guard.user().username.as_str(), guard.user().display_name.as_str()), ("jdoe", "John Doe"));
+        assert_eq!(stud_ip.whoami().unwrap().username, "jdoe");
+        assert!(stud_ip.impersonate("jdoe").is_err());
+        guard.end().unwrap();
+        assert_eq!(stud_ip.whoami().unwrap().username, "admin");
+        let requests = server.requests();
+        let paths = requests.iter().filter(|request| request.method == "POST").map(|request| request.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["/dispatch.php/admin/user/switch_user/u_jdoe", "/dispatch.php/admin/user/restore_user"]);
+
+        // Dropping the guard restores the session too
+        {
+            let _guard = stud_ip.impersonate("jdoe").unwrap();
+            assert_eq!(stud_ip.whoami().unwrap().username, "jdoe");
+        }
+        assert_eq!(stud_ip.whoami().unwrap().username, "admin");
+        assert_eq!(server.requests().last().unwrap().path, "/dispatch.php/admin/user/restore_user");
+    }
+
+    #[test]
+    fn test_impersonate_refetches_cached_pages() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/start", 200, r#"<input type="hidden" name="security_token" value="tok">"#)
+            .route("GET", "/dispatch.php/profile?username=jdoe", 200, r#"<div id="sidebar"><div data-user-id="u_jdoe"></div></div>"#)
+            .route("POST", "/dispatch.php/admin/user/switch_user/u_jdoe", 200, r#"<div class="messagebox messagebox_success">Sie agieren jetzt als John Doe.</div>"#)
+            .route("POST", "/dispatch.php/admin/user/restore_user", 200, r#"<div class="messagebox messagebox_success">Sie agieren wieder als Sie selbst.</div>"#)
+            .route("GET", "/dispatch.php/course/details", 200, r#"<div id="content"></div>"#);
+        let mut stud_ip = StudIp::from_client(server.client());
+        stud_ip.session = Some(session("admin", "root"));
+        let page_cache = PageCache::default();
+        let get_details = || page_cache.get(&stud_ip.client, "https://studip.example.com/dispatch.php/course/details", &[("cid", "c1")]).unwrap();
+        let count = || server.requests().iter().filter(|request| request.path.starts_with("/dispatch.php/course/details")).count();
+
+        get_details();
+        get_details();
+        assert_eq!(count(), 1);
+        // The page of the original user is not reused while acting as another one, nor the other way around
+        let guard = stud_ip.impersonate("jdoe").unwrap();
+        get_details();
+        get_details();
+        assert_eq!(count(), 2);
+        guard.end().unwrap();
+        get_details();
+        assert_eq!(count(), 3);
+    }
+
+    #[test]
+    fn test_impersonate_without_permission() {
+        let server = MockServer::start();
+        let mut stud_ip = StudIp::from_client(server.client());
+        stud_ip.session = Some(session("student", "autor"));
+        let error = stud_ip.impersonate("jdoe").unwrap_err();
+        assert!(matches!(error.downcast_ref::<StudIpError>(), Some(StudIpError::PermissionDenied { .. })), "{:?}", error);
+        // Nothing was requested
+        assert!(server.requests().is_empty());
+        assert_eq!(stud_ip.whoami().unwrap().username, "student");
+    }
+}
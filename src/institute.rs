@@ -1,9 +1,24 @@
+use anyhow::bail;
+use itertools::Itertools;
+use scraper::{ElementRef, Html};
 use serde::{Deserialize, Serialize};
+use crate::error::{check_status, StudIpError};
+use crate::ids::InstituteId;
+use crate::news::{parse_news_box, NewsArticle};
+use crate::ref_source::ReferenceSource;
+use crate::{SendThrough, StudIpClient};
+use crate::user::{parse_simple_user, User};
+use crate::util::{normalize_text, parse_last_page, parse_page, selector};
+
+pub(crate) const INSTITUTE_URL: &str = "https://studip.example.com/dispatch.php/institute/overview";
+const INSTITUTE_MEMBERS_URL: &str = "https://studip.example.com/dispatch.php/institute/members";
+const INSTITUTE_COURSES_URL: &str = "https://studip.example.com/dispatch.php/institute/courses";
+const GLOBAL_SEARCH_PAGE_URL: &str = "https://studip.example.com/dispatch.php/search/globalsearch";
 
 /// Represents basic information about an institute
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Institute {
-    pub id: String,
+    pub id: InstituteId,
     pub name: String,
 }
 
@@ -13,4 +28,418 @@ impl PartialEq for Institute {
         self.id == other.id
     }
 
-}
\ No newline at end of file
+}
+
+/// A course, as listed on the course listing of an [`Institute`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstituteCourse {
+    pub id: String,
+    pub name: String,
+    /// The first listed lecturer of the course
+    pub lecturer: Option<User>,
+}
+
+impl PartialEq for InstituteCourse {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+/// The role of a member of an [`Institute`], as shown in the function column or the group heading of the members page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InstituteRole {
+    Professor,
+    /// Wissenschaftliche*r Mitarbeiter*in
+    ResearchAssistant,
+    /// Lehrbeauftragte*r
+    Lecturer,
+    /// Studentische Hilfskraft or tutor
+    StudentAssistant,
+    /// Technische*r Mitarbeiter*in
+    Technician,
+    Secretariat,
+    /// Any other role, with its label
+    Other(String),
+}
+
+impl InstituteRole {
+
+    /// Maps a (localized) label like "Professor/in" or "wissenschaftliche*r Mitarbeiter*in" to its role
+    pub fn from_label(label: &str) -> Self {
+        let label = label.split_whitespace().join(" ");
+        let lowercase = label.to_lowercase();
+        let contains_any = |words: &[&str]| words.iter().any(|word| lowercase.contains(word));
+        if contains_any(&["professor"]) {
+            Self::Professor
+        } else if contains_any(&["sekretariat", "secretar"]) {
+            Self::Secretariat
+        } else if contains_any(&["studentische", "hilfskraft", "tutor", "student assistant"]) {
+            Self::StudentAssistant
+        } else if contains_any(&["wissenschaftl", "research"]) {
+            Self::ResearchAssistant
+        } else if contains_any(&["lehrbeauftragt", "lecturer"]) {
+            Self::Lecturer
+        } else if contains_any(&["technisch", "technician", "technical"]) {
+            Self::Technician
+        } else {
+            Self::Other(label)
+        }
+    }
+
+}
+
+/// The content of the pages of an [`Institute`] \
+/// Parts, that are restricted to members of the institute, are left empty
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InstituteDetails {
+    pub address: Option<String>,
+    pub phone: Option<String>,
+    pub homepage: Option<String>,
+    pub staff: Vec<User>,
+    pub news: Vec<NewsArticle>,
+    pub courses: Vec<InstituteCourse>,
+}
+
+impl Institute {
+
+    // Gets a page of the institute, returning None if it is not accessible
+    fn get_page(&self, client: &StudIpClient, url: &str, page: Option<usize>) -> anyhow::Result<Option<Html>> {
+        let mut request = client.get(url)
+            .query(&[("cid", self.id.as_str())]);
+        if let Some(page) = page {
+            request = request.query(&[("page", page)]);
+        }
+        let response = request.send_through(client)?;
+        if response.status() == reqwest::StatusCode::FORBIDDEN || response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            bail!("Could not get institute page. Status Code: {}", response.status());
+        }
+        Ok(Some(parse_page(&response.text()?)?))
+    }
+
+    /// Returns all members of the institute from its members page, optionally only those with the `role` \
+    /// Fails with [`StudIpError::PermissionDenied`], if the institute hides its members from the current user.
+    pub fn members(&self, client: &StudIpClient, role: Option<InstituteRole>) -> anyhow::Result<Vec<User>> {
+        let source = ReferenceSource::Institute(self.id.clone());
+        let mut members: Vec<(User, InstituteRole)> = vec![];
+        let mut page = 1;
+        loop {
+            let response = client.get(INSTITUTE_MEMBERS_URL)
+                .query(&[("cid", self.id.as_str())])
+                .query(&[("page", page)])
+                .send_through(client)?;
+            check_status(response.status())?;
+            if !response.status().is_success() {
+                bail!("Could not get institute members. Status Code: {}", response.status());
+            }
+            let html = parse_page(&response.text()?)?;
+            let Some(page_members) = parse_members(&html, &source) else {
+                return Err(StudIpError::PermissionDenied { message: Some("The members of the institute are hidden".to_string()) }.into());
+            };
+            let n_members = members.len();
+            for (user, member_role) in page_members {
+                if !members.iter().any(|(member, known_role)| member.username == user.username && *known_role == member_role) {
+                    members.push((user, member_role));
+                }
+            }
+            if members.len() == n_members || page >= parse_last_page(&html) {
+                break;
+            }
+            page += 1;
+        }
+        // Members with several roles are listed once
+        Ok(members.into_iter()
+            .filter(|(_, member_role)| role.as_ref().is_none_or(|role| role == member_role))
+            .map(|(user, _)| user)
+            .unique_by(|user| user.username.clone())
+            .collect())
+    }
+
+    /// Queries the [`InstituteDetails`] by scraping the overview, the staff and the course listing of the institute. \
+    /// Parts, that are restricted to members, are left empty instead of failing.
+    pub fn query_details(&self, client: &StudIpClient) -> anyhow::Result<InstituteDetails> {
+        let source = ReferenceSource::Institute(self.id.clone());
+        let mut details = InstituteDetails::default();
+        if let Some(html) = self.get_page(client, INSTITUTE_URL, None)? {
+            parse_overview(&html, &source, &mut details)?;
+        }
+        if let Some(html) = self.get_page(client, INSTITUTE_MEMBERS_URL, None)? {
+            details.staff = parse_staff(&html, &source)?;
+        }
+        // The course listing is paginated
+        let mut page = 1;
+        while let Some(html) = self.get_page(client, INSTITUTE_COURSES_URL, Some(page))? {
+            let courses = parse_courses(&html, &source)?;
+            let new_courses = courses.into_iter()
+                .filter(|course| !details.courses.contains(course))
+                .collect::<Vec<_>>();
+            if new_courses.is_empty() || page >= parse_last_page(&html) {
+                details.courses.extend(new_courses);
+                break;
+            }
+            details.courses.extend(new_courses);
+            page += 1;
+        }
+        Ok(details)
+    }
+
+}
+
+/// A faculty and its institutes, as returned by [`get_hierarchy()`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Faculty {
+    pub institute: Institute,
+    pub children: Vec<Institute>,
+}
+
+/// Queries all faculties with their nested institutes, from the institute filter of the global search
+pub fn get_hierarchy(client: &StudIpClient) -> anyhow::Result<Vec<Faculty>> {
+    let response = client.get(GLOBAL_SEARCH_PAGE_URL).send_through(client)?;
+    if !response.status().is_success() {
+        bail!("Could not get institute hierarchy. Status Code: {}", response.status());
+    }
+    parse_hierarchy(&parse_page(&response.text()?)?)
+}
+
+/// Finds an institute (or faculty) by its name in the hierarchy. \
+/// The name is matched regardless of case, diacritics and whitespace, preferring exact matches over partial ones.
+pub fn find_institute_by_name<'a>(hierarchy: &'a [Faculty], name: &str) -> Option<&'a Institute> {
+    let name = normalize_text(name);
+    let institutes = || hierarchy.iter()
+        .flat_map(|faculty| std::iter::once(&faculty.institute).chain(&faculty.children));
+    institutes().find(|institute| normalize_text(&institute.name) == name)
+        .or_else(|| institutes().find(|institute| normalize_text(&institute.name).contains(&name)))
+}
+
+// Faculties are headers of the select, followed by their (indented) institutes
+fn parse_hierarchy(html: &Html) -> anyhow::Result<Vec<Faculty>> {
+    let option_selector = selector!("select[name=\"institute\"] option, select#institute option");
+    let mut faculties: Vec<Faculty> = vec![];
+    for option in html.select(option_selector) {
+        let Some(id) = option.attr("value").filter(|id| !id.is_empty() && *id != "0") else {continue};
+        let raw_name = option.text().collect::<String>();
+        let institute = Institute {
+            id: id.into(),
+            name: raw_name.split_whitespace().join(" "),
+        };
+        let is_child = option.value().classes().any(|class| class == "nested-item")
+            || raw_name.starts_with(|c: char| c.is_whitespace());
+        if faculties.iter().any(|faculty| faculty.institute == institute || faculty.children.contains(&institute)) {
+            continue;
+        }
+        match faculties.last_mut() {
+            Some(faculty) if is_child => faculty.children.push(institute),
+            _ => faculties.push(Faculty { institute, children: vec![] }),
+        }
+    }
+    Ok(faculties)
+}
+
+fn cell_text(element: ElementRef) -> String {
+    element.text().collect::<String>().split_whitespace().join(" ")
+}
+
+fn parse_overview(html: &Html, source: &ReferenceSource, details: &mut InstituteDetails) -> anyhow::Result<()> {
+    let row_selector = selector!("#content tr, #content dl");
+    let key_value_selector = selector!("th, td, dt, dd");
+    let link_selector = selector!("a[href]");
+    let article_selector = selector!("#content article.studip:not([id])");
+    let news_header_selector = selector!("header .icon-shape-news");
+    // The contact data is a list of localized keys and values
+    for row in html.select(row_selector) {
+        let Some((key_elem, value_elem)) = row.select(key_value_selector).next_tuple() else {continue};
+        let key = cell_text(key_elem).to_lowercase();
+        let value = cell_text(value_elem);
+        if value.is_empty() {
+            continue;
+        }
+        if key.starts_with("straße") || key.starts_with("adresse") || key.starts_with("address") || key.starts_with("street") {
+            details.address = Some(value);
+        } else if key.starts_with("telefon") || key.starts_with("phone") {
+            details.phone = Some(value);
+        } else if key.starts_with("homepage") || key.starts_with("website") {
+            details.homepage = value_elem.select(link_selector)
+                .next()
+                .and_then(|link| link.attr("href"))
+                .map(|href| href.to_string())
+                .or(Some(value));
+        }
+    }
+    let news_elem = html.select(article_selector)
+        .find(|elem| elem.select(news_header_selector).next().is_some());
+    if let Some(news_elem) = news_elem {
+        details.news = parse_news_box(news_elem, source)?;
+    }
+    Ok(())
+}
+
+fn parse_staff(html: &Html, source: &ReferenceSource) -> anyhow::Result<Vec<User>> {
+    let user_selector = selector!("#content table tbody a[href*=\"username=\"]");
+    let mut staff: Vec<User> = vec![];
+    for user_elem in html.select(user_selector) {
+        let mut user = parse_simple_user(user_elem)?;
+        // Avatars are linked separately, without any text
+        if user.display_name.is_empty() || staff.iter().any(|member| member.username == user.username) {
+            continue;
+        }
+        user.source = source.clone();
+        staff.push(user);
+    }
+    Ok(staff)
+}
+
+// The role is either shown in a column of its own, or the members are grouped by it (in tables with captions or heading rows)
+// Returns None, if the page does not list members at all
+fn parse_members(html: &Html, source: &ReferenceSource) -> Option<Vec<(User, InstituteRole)>> {
+    let tables = html.select(selector!("#content table")).collect::<Vec<_>>();
+    if tables.is_empty() {
+        // An empty institute shows a notice instead
+        return html.select(selector!(".messagebox_info")).next().map(|_| vec![]);
+    }
+    let mut members = vec![];
+    for table in tables {
+        let role_column = table.select(selector!("thead th"))
+            .position(|header| ["funktion", "function", "rolle", "role", "status"].contains(&cell_text(header).to_lowercase().as_str()));
+        let mut group = table.select(selector!("caption")).next().map(cell_text);
+        for row in table.select(selector!("tbody tr")) {
+            let cells = row.select(selector!("td")).collect::<Vec<_>>();
+            if cells.is_empty() || cells.len() == 1 && cells[0].attr("colspan").is_some() {
+                group = row.select(selector!("th, td")).next().map(cell_text).filter(|text| !text.is_empty()).or(group);
+                continue;
+            }
+            let Some(user_elem) = row.select(selector!("a[href*=\"username=\"]")).find(|link| !cell_text(*link).is_empty()) else {continue};
+            let Ok(mut user) = parse_simple_user(user_elem) else {continue};
+            user.source = source.clone();
+            let label = role_column.and_then(|column| cells.get(column)).map(|cell| cell_text(*cell))
+                .filter(|label| !label.is_empty())
+                .or(group.clone())
+                .unwrap_or_default();
+            members.push((user, InstituteRole::from_label(&label)));
+        }
+    }
+    Some(members)
+}
+
+fn parse_courses(html: &Html, source: &ReferenceSource) -> anyhow::Result<Vec<InstituteCourse>> {
+    let row_selector = selector!("#content table tbody tr");
+    let course_link_selector = selector!("a[href*=\"cid=\"], a[href*=\"sem_id=\"]");
+    let user_selector = selector!("a[href*=\"username=\"]");
+    let mut courses = vec![];
+    for row in html.select(row_selector) {
+        let Some(course_link) = row.select(course_link_selector).find(|link| !cell_text(*link).is_empty()) else {continue};
+        let url = url::Url::parse(course_link.attr("href").unwrap())?;
+        let Some(id) = url.query_pairs().find_map(|(key, value)| (key == "cid" || key == "sem_id").then(|| value.to_string())) else {continue};
+        let lecturer = row.select(user_selector)
+            .next()
+            .map(parse_simple_user)
+            .transpose()?
+            .map(|user| User { source: source.clone(), ..user });
+        courses.push(InstituteCourse {
+            id,
+            name: cell_text(course_link),
+            lecturer,
+        });
+    }
+    Ok(courses)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_parse_hierarchy() {
+        let html = Html::parse_document(r#"
+            <select name="institute">
+                <option value="">Alle</option>
+                <option value="f1" class="nested-item-header">Fakultät für Informatik</option>
+                <option value="i1" class="nested-item">Institut für Algorithmen</option>
+                <option value="i2" class="nested-item">Institut für Datenbanken</option>
+                <option value="f2" class="nested-item-header">Medizinische Fakultät</option>
+                <option value="i3">&nbsp;&nbsp;Institut für Anatomie</option>
+            </select>
+        "#);
+        let hierarchy = parse_hierarchy(&html).unwrap();
+        assert_eq!(hierarchy.len(), 2);
+        assert_eq!(hierarchy[0].institute.name, "Fakultät für Informatik");
+        assert_eq!(hierarchy[0].children.len(), 2);
+        assert_eq!(hierarchy[1].children[0].name, "Institut für Anatomie");
+        assert_eq!(find_institute_by_name(&hierarchy, "institut fur  datenbanken").unwrap().id, "i2");
+        assert_eq!(find_institute_by_name(&hierarchy, "Medizin").unwrap().id, "f2");
+        assert!(find_institute_by_name(&hierarchy, "Physik").is_none());
+    }
+
+    #[test]
+    fn test_parse_institute_pages() {
+        let html = Html::parse_document(r#"
+            <div id="content">
+                <table>
+                    <tr><td>Straße:</td><td>Example Street 1, 12345 Example City</td></tr>
+                    <tr><td>Telefon:</td><td>+49 123 456</td></tr>
+                    <tr><td>Homepage:</td><td><a href="https://cs.example.com">cs.example.com</a></td></tr>
+                    <tr><td>Fax:</td><td></td></tr>
+                </table>
+            </div>
+        "#);
+        let source = ReferenceSource::Institute("i1".into());
+        let mut details = InstituteDetails::default();
+        parse_overview(&html, &source, &mut details).unwrap();
+        assert_eq!(details.address.as_deref(), Some("Example Street 1, 12345 Example City"));
+        assert_eq!(details.phone.as_deref(), Some("+49 123 456"));
+        assert_eq!(details.homepage.as_deref(), Some("https://cs.example.com"));
+        assert!(details.news.is_empty());
+
+        let html = Html::parse_document(r#"
+            <div id="content">
+                <table><tbody>
+                    <tr>
+                        <td><a href="https://studip.example.com/dispatch.php/course/details?sem_id=c1">Algorithms</a></td>
+                        <td><a href="https://studip.example.com/dispatch.php/profile?username=jdoe">John Doe</a></td>
+                    </tr>
+                    <tr><td><a href="https://studip.example.com/dispatch.php/course/details?sem_id=c2">Databases</a></td><td></td></tr>
+                </tbody></table>
+                <div class="pagination"><a href="?page=1">1</a><a href="?page=2">2</a><a href="?page=2">»</a></div>
+            </div>
+        "#);
+        let courses = parse_courses(&html, &source).unwrap();
+        assert_eq!(courses.len(), 2);
+        assert_eq!(courses[0].name, "Algorithms");
+        assert_eq!(courses[0].lecturer.as_ref().unwrap().username, "jdoe");
+        assert_eq!(courses[0].lecturer.as_ref().unwrap().source, source);
+        assert!(courses[1].lecturer.is_none());
+        assert_eq!(parse_last_page(&html), 2);
+        assert_eq!(parse_staff(&html, &source).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_institute_members() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/institute/members?cid=i1&page=1", 200, include_str!("../testdata/institute/members_1.html"))
+            .route("GET", "/dispatch.php/institute/members?cid=i1&page=2", 200, include_str!("../testdata/institute/members_2.html"))
+            .route("GET", "/dispatch.php/institute/members?cid=i2", 200, r#"<div id="content"><p>Diese Einrichtung zeigt ihre Mitarbeitenden nicht an.</p></div>"#)
+            .route("GET", "/dispatch.php/institute/members?cid=i3", 403, "");
+        let client = server.client();
+        let institute = |id: &str| Institute { id: id.into(), name: String::new() };
+
+        let members = institute("i1").members(&client, None).unwrap();
+        assert_eq!(members.iter().map(|user| user.username.as_str()).collect::<Vec<_>>(), vec!["jdoe", "mmustermann", "emusterfrau", "lschmidt"]);
+        assert_eq!(members[0].display_name, "Prof. Dr. John Doe");
+        assert_eq!(members[0].source, ReferenceSource::Institute("i1".into()));
+        let usernames = |role| institute("i1").members(&client, Some(role)).unwrap().into_iter().map(|user| user.username).collect::<Vec<_>>();
+        assert_eq!(usernames(InstituteRole::Professor), vec!["jdoe"]);
+        assert_eq!(usernames(InstituteRole::Secretariat), vec!["emusterfrau"]);
+        assert_eq!(usernames(InstituteRole::StudentAssistant), vec!["lschmidt"]);
+        // Members can have several roles
+        assert_eq!(usernames(InstituteRole::Other("Systemadministration".into())), vec!["mmustermann"]);
+        assert_eq!(usernames(InstituteRole::ResearchAssistant), vec!["mmustermann"]);
+
+        for id in ["i2", "i3"] {
+            let error = institute(id).members(&client, None).unwrap_err();
+            assert!(matches!(error.downcast_ref::<StudIpError>(), Some(StudIpError::PermissionDenied { .. })), "{:?}", error);
+        }
+    }
+}
@@ -0,0 +1,495 @@
+//! Access to the JSON:API of Stud.IP (version 4.5 and newer), which is more robust than scraping the html pages \
+//! The session or token of the [`StudIpClient`] is used for authentication (see [`AuthMethod`](crate::auth::AuthMethod)).
+
+use std::sync::Arc;
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::course::Course;
+use crate::error::{check_status, StudIpError};
+use crate::course_modules::file::{File, FilesObject, Folder, FolderContents};
+use crate::ids::FolderId;
+use crate::ref_source::ReferenceSource;
+use crate::user::User;
+use crate::{SendThrough, StudIpClient};
+
+const JSONAPI_URL: &str = "https://studip.example.com/jsonapi.php/v1";
+/// The number of resources requested per page
+const PAGE_LIMIT: usize = 100;
+
+/// A single resource of a JSON:API document
+#[derive(Debug, Clone, Deserialize)]
+struct Resource<A> {
+    id: String,
+    attributes: A,
+    #[serde(default)]
+    relationships: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct Page {
+    total: usize,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Meta {
+    page: Option<Page>,
+}
+
+/// The envelope of every JSON:API response
+#[derive(Debug, Deserialize)]
+struct Document<D> {
+    data: D,
+    #[serde(default)]
+    included: Vec<Resource<serde_json::Value>>,
+    #[serde(default)]
+    meta: Option<Meta>,
+}
+
+/// All pages of a collection, together with the resources included by them
+struct Collection<A> {
+    data: Vec<Resource<A>>,
+    included: Vec<Resource<serde_json::Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CourseAttributes {
+    title: String,
+    #[serde(default)]
+    course_number: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct UserAttributes {
+    username: String,
+    formatted_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct FolderAttributes {
+    name: String,
+    chdate: DateTime<Utc>,
+    #[serde(default)]
+    is_readable: bool,
+    #[serde(default)]
+    is_writable: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct FileRefAttributes {
+    name: String,
+    chdate: DateTime<Utc>,
+    #[serde(default)]
+    downloads: usize,
+    #[serde(default)]
+    filesize: usize,
+    #[serde(default)]
+    mime_type: String,
+    #[serde(default)]
+    is_downloadable: bool,
+    #[serde(default)]
+    is_editable: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct MembershipAttributes {
+    permission: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct SemesterAttributes {
+    title: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    #[serde(default)]
+    start_of_lectures: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_of_lectures: Option<DateTime<Utc>>,
+}
+
+/// A semester, as returned by [`JsonApi::semesters()`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Semester {
+    pub id: String,
+    /// Example: "WiSe 2024/25"
+    pub title: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub lectures_start: Option<DateTime<Utc>>,
+    pub lectures_end: Option<DateTime<Utc>>,
+}
+
+/// The permission of a member in a course
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CoursePermission {
+    Lecturer,
+    Tutor,
+    Student,
+    /// A member with read access only
+    Reader,
+    Other(String),
+}
+
+impl From<&str> for CoursePermission {
+    fn from(permission: &str) -> Self {
+        match permission {
+            "dozent" => CoursePermission::Lecturer,
+            "tutor" => CoursePermission::Tutor,
+            "autor" => CoursePermission::Student,
+            "user" => CoursePermission::Reader,
+            other => CoursePermission::Other(other.to_string()),
+        }
+    }
+}
+
+/// A member of a course, as returned by [`JsonApi::course_memberships()`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Membership {
+    pub id: String,
+    pub user: User,
+    pub permission: CoursePermission,
+}
+
+/// Typed access to the JSON:API endpoints, can be created with [`JsonApi::new()`] \
+/// Fails with [`StudIpError::FeatureDisabled`], if the JSON:API is disabled on the installation.
+pub struct JsonApi<'a> {
+    client: &'a Arc<StudIpClient>,
+}
+
+impl<'a> JsonApi<'a> {
+
+    pub fn new(client: &'a Arc<StudIpClient>) -> Self {
+        Self { client }
+    }
+
+    /// Whether the JSON:API is available on the installation, `None` until the first request to it \
+    /// The answer is kept by the client, so that callers, which fall back to the html pages, only try the JSON:API once.
+    pub fn is_available(&self) -> Option<bool> {
+        self.client.jsonapi_available.get().copied()
+    }
+
+    fn get<D: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> anyhow::Result<Document<D>> {
+        let response = self.client.get(format!("{}{}", JSONAPI_URL, path))
+            .query(query)
+            .header("Accept", "application/vnd.api+json")
+            .send_through(self.client)?;
+        let status = response.status();
+        let is_json = response.headers().get("Content-Type")
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| content_type.contains("json"));
+        let _ = self.client.jsonapi_available.set(is_json);
+        if !is_json {
+            return Err(StudIpError::FeatureDisabled { feature: "The JSON:API (Stud.IP 4.5 or newer)".to_string() }.into());
+        }
+        if !status.is_success() {
+            bail!("JSON:API request to {} failed with status {}", path, status);
+        }
+        serde_json::from_str(&response.text()?).with_context(|| format!("Could not parse JSON:API response of {}", path))
+    }
+
+    // Fetches all pages of a collection
+    fn get_all<A: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> anyhow::Result<Collection<A>> {
+        let mut data = vec![];
+        let mut included = vec![];
+        loop {
+            let mut page_query = query.to_vec();
+            page_query.push(("page[offset]", data.len().to_string()));
+            page_query.push(("page[limit]", PAGE_LIMIT.to_string()));
+            let document: Document<Vec<Resource<A>>> = self.get(path, &page_query)?;
+            let n_resources = document.data.len();
+            data.extend(document.data);
+            included.extend(document.included);
+            let total = document.meta.and_then(|meta| meta.page).map(|page| page.total);
+            if n_resources == 0 || total.is_none_or(|total| data.len() >= total) {
+                break;
+            }
+        }
+        Ok(Collection { data, included })
+    }
+
+    fn to_course(&self, resource: Resource<CourseAttributes>) -> Course {
+        Course::from_parts(
+            resource.id.into(),
+            resource.attributes.title,
+            resource.attributes.course_number.unwrap_or_default(),
+            self.client.clone(),
+        )
+    }
+
+    // Finds the user, that a relationship of the resource points to, in the included resources
+    fn included_user(relationships: &serde_json::Value, pointer: &str, included: &[Resource<serde_json::Value>], source: ReferenceSource) -> anyhow::Result<User> {
+        let user_id = relationships.pointer(pointer)
+            .and_then(|id| id.as_str())
+            .context("Expected user relationship")?;
+        let user = included.iter()
+            .find(|resource| resource.id == user_id)
+            .context("Expected included user")?;
+        let attributes: UserAttributes = serde_json::from_value(user.attributes.clone())
+            .context("Could not parse included user")?;
+        Ok(User {
+            display_name: attributes.formatted_name,
+            username: attributes.username,
+            avatar_src: None,
+            source,
+            user_id: Some(user_id.into()),
+        })
+    }
+
+    // The owner of files and folders is optional, as it is missing for deleted users
+    fn owner(relationships: &serde_json::Value, included: &[Resource<serde_json::Value>], course_id: &str) -> User {
+        let source = ReferenceSource::Course(course_id.into());
+        Self::included_user(relationships, "/owner/data/id", included, source.clone()).unwrap_or(User {
+            display_name: String::new(),
+            username: String::new(),
+            avatar_src: None,
+            source,
+            user_id: None,
+        })
+    }
+
+    fn to_folder(resource: Resource<FolderAttributes>, included: &[Resource<serde_json::Value>]) -> Folder {
+        let course_id = resource.relationships.pointer("/range/data/id").and_then(|id| id.as_str()).unwrap_or_default();
+        let count = |relationship: &str| resource.relationships.pointer(&format!("/{}/meta/count", relationship))
+            .and_then(|count| count.as_u64())
+            .unwrap_or(0) as usize;
+        let mut permissions = String::new();
+        if resource.attributes.is_readable {
+            permissions.push('r');
+        }
+        if resource.attributes.is_writable {
+            permissions.push('w');
+        }
+        Folder {
+            object: FilesObject {
+                author: Self::owner(&resource.relationships, included, course_id),
+                id: resource.id,
+                name: resource.attributes.name,
+                change_date: resource.attributes.chdate,
+                icon: "folder".to_string(),
+                mime_type: String::new(),
+            },
+            object_count: count("folders") + count("file-refs"),
+            permissions,
+        }
+    }
+
+    fn to_file(resource: Resource<FileRefAttributes>, included: &[Resource<serde_json::Value>], course_id: &str) -> File {
+        File {
+            object: FilesObject {
+                author: Self::owner(&resource.relationships, included, course_id),
+                id: resource.id,
+                name: resource.attributes.name,
+                change_date: resource.attributes.chdate,
+                icon: String::new(),
+                mime_type: resource.attributes.mime_type,
+            },
+            size: resource.attributes.filesize,
+            downloads: resource.attributes.downloads,
+            // The license conditions are not exposed by the JSON:API
+            restricted_terms_of_use: false,
+            new: false,
+            is_editable: resource.attributes.is_editable,
+            is_accessible: resource.attributes.is_downloadable,
+            folder_id: resource.relationships.pointer("/parent/data/id").and_then(|id| id.as_str()).map(FolderId::from),
+            download_url: None,
+        }
+    }
+
+    /// Returns the id of the current user
+    pub fn my_user_id(&self) -> anyhow::Result<String> {
+        let document: Document<Resource<serde_json::Value>> = self.get("/users/me", &[])?;
+        Ok(document.data.id)
+    }
+
+    /// Returns the courses of the current user, with the client attached
+    pub fn my_courses(&self) -> anyhow::Result<Vec<Course>> {
+        let user_id = self.my_user_id()?;
+        let courses = self.get_all::<CourseAttributes>(&format!("/users/{}/courses", user_id), &[])?;
+        Ok(courses.data.into_iter().map(|course| self.to_course(course)).collect())
+    }
+
+    /// Returns a single course by its id, with the client attached
+    pub fn course(&self, id: &str) -> anyhow::Result<Course> {
+        let document: Document<Resource<CourseAttributes>> = self.get(&format!("/courses/{}", id), &[])?;
+        Ok(self.to_course(document.data))
+    }
+
+    /// Returns all members of a course together with their permission
+    pub fn course_memberships(&self, course_id: &str) -> anyhow::Result<Vec<Membership>> {
+        let Collection { data: memberships, included } = self.get_all::<MembershipAttributes>(
+            &format!("/courses/{}/memberships", course_id),
+            &[("include", "user".to_string())],
+        )?;
+        memberships.into_iter().map(|membership| {
+            let source = ReferenceSource::Course(course_id.into());
+            Ok(Membership {
+                user: Self::included_user(&membership.relationships, "/user/data/id", &included, source)
+                    .context("Could not get user of membership")?,
+                id: membership.id,
+                permission: CoursePermission::from(membership.attributes.permission.as_str()),
+            })
+        }).collect()
+    }
+
+    /// Returns all folders of a course (including sub folders)
+    pub fn course_folders(&self, course_id: &str) -> anyhow::Result<Vec<Folder>> {
+        let folders = self.get_all::<FolderAttributes>(&format!("/courses/{}/folders", course_id), &[("include", "owner".to_string())])?;
+        Ok(folders.data.into_iter().map(|folder| Self::to_folder(folder, &folders.included)).collect())
+    }
+
+    /// Returns the top folder of a course, which contains all other folders
+    pub fn course_root_folder(&self, course_id: &str) -> anyhow::Result<Folder> {
+        let folders = self.get_all::<FolderAttributes>(&format!("/courses/{}/folders", course_id), &[("include", "owner".to_string())])?;
+        let root = folders.data.into_iter()
+            .find(|folder| folder.relationships.pointer("/parent/data").is_none_or(|parent| parent.is_null()))
+            .context("Expected root folder of course")?;
+        Ok(Self::to_folder(root, &folders.included))
+    }
+
+    /// Returns a single folder by its id
+    pub fn folder(&self, id: &str) -> anyhow::Result<Folder> {
+        let document: Document<Resource<FolderAttributes>> = self.get(&format!("/folders/{}", id), &[("include", "owner".to_string())])?;
+        Ok(Self::to_folder(document.data, &document.included))
+    }
+
+    /// Returns the [`File`]s of a folder, all pages are followed for folders with many files
+    pub fn file_refs(&self, folder_id: &str) -> anyhow::Result<Vec<File>> {
+        // The files only reference their folder, so the course is taken from it
+        let folder: Document<Resource<FolderAttributes>> = self.get(&format!("/folders/{}", folder_id), &[])?;
+        let course_id = folder.data.relationships.pointer("/range/data/id").and_then(|id| id.as_str()).unwrap_or_default();
+        let file_refs = self.get_all::<FileRefAttributes>(&format!("/folders/{}/file-refs", folder_id), &[("include", "owner".to_string())])?;
+        Ok(file_refs.data.into_iter().map(|file_ref| Self::to_file(file_ref, &file_refs.included, course_id)).collect())
+    }
+
+    /// Returns the sub folders and files of a folder
+    pub fn folder_files(&self, folder_id: &str) -> anyhow::Result<FolderContents> {
+        let folders = self.get_all::<FolderAttributes>(&format!("/folders/{}/folders", folder_id), &[("include", "owner".to_string())])?;
+        Ok(FolderContents {
+            folders: folders.data.into_iter().map(|folder| Self::to_folder(folder, &folders.included)).collect(),
+            files: self.file_refs(folder_id)?,
+        })
+    }
+
+    /// Downloads the content of a file by the id of its file ref
+    pub fn download_file_ref(&self, id: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(self.file_ref_content(id)?.bytes()?.to_vec())
+    }
+
+    // Requests the content of a file by the id of its file ref, so that the body can be streamed
+    pub(crate) fn file_ref_content(&self, id: &str) -> anyhow::Result<reqwest::blocking::Response> {
+        let response = self.client.get_with_timeout(format!("{}/file-refs/{}/content", JSONAPI_URL, id), self.client.transfer_timeout())
+            .send_through(self.client)?;
+        check_status(response.status())?;
+        if !response.status().is_success() {
+            bail!("Could not download file ref {}. Status code: {}", id, response.status());
+        }
+        Ok(response)
+    }
+
+    /// Returns all semesters of the installation
+    pub fn semesters(&self) -> anyhow::Result<Vec<Semester>> {
+        let semesters = self.get_all::<SemesterAttributes>("/semesters", &[])?;
+        Ok(semesters.data.into_iter().map(|semester| Semester {
+            id: semester.id,
+            title: semester.attributes.title,
+            start: semester.attributes.start,
+            end: semester.attributes.end,
+            lectures_start: semester.attributes.start_of_lectures,
+            lectures_end: semester.attributes.end_of_lectures,
+        }).collect())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_jsonapi() {
+        let server = MockServer::start();
+        server.route_json("GET", "/jsonapi.php/v1/users/me", 200, r#"{"data": {"type": "users", "id": "u1", "attributes": {}}}"#)
+            .route_json("GET", "/jsonapi.php/v1/users/u1/courses?page%5Boffset%5D=0", 200, r#"{
+                "meta": {"page": {"offset": 0, "limit": 1, "total": 2}},
+                "data": [{"type": "courses", "id": "c1", "attributes": {"title": "Algorithmen", "course-number": "101"}}]
+            }"#)
+            .route_json("GET", "/jsonapi.php/v1/users/u1/courses?page%5Boffset%5D=1", 200, r#"{
+                "meta": {"page": {"offset": 1, "limit": 1, "total": 2}},
+                "data": [{"type": "courses", "id": "c2", "attributes": {"title": "Lineare Algebra", "course-number": null}}]
+            }"#)
+            .route_json("GET", "/jsonapi.php/v1/courses/c1/memberships", 200, r#"{
+                "data": [{"type": "course-memberships", "id": "c1_u2", "attributes": {"permission": "dozent"},
+                    "relationships": {"user": {"data": {"type": "users", "id": "u2"}}}}],
+                "included": [{"type": "users", "id": "u2", "attributes": {"username": "mmustermann", "formatted-name": "Max Mustermann"}}]
+            }"#)
+            .route_json("GET", "/jsonapi.php/v1/semesters", 200, r#"{"data": [{"type": "semesters", "id": "s1", "attributes": {
+                "title": "WiSe 2024/25", "start": "2024-10-01T00:00:00+02:00", "end": "2025-03-31T23:59:59+02:00"
+            }}]}"#);
+        let client = Arc::new(server.client());
+        let api = JsonApi::new(&client);
+
+        let courses = api.my_courses().unwrap();
+        assert_eq!(courses.iter().map(|course| course.name.as_str()).collect::<Vec<_>>(), vec!["Algorithmen", "Lineare Algebra"]);
+        let memberships = api.course_memberships("c1").unwrap();
+        assert_eq!(memberships[0].user.username, "mmustermann");
+        assert_eq!(memberships[0].permission, CoursePermission::Lecturer);
+        let semesters = api.semesters().unwrap();
+        assert_eq!(semesters[0].title, "WiSe 2024/25");
+        assert_eq!(semesters[0].lectures_start, None);
+
+        assert_eq!(api.is_available(), Some(true));
+
+        // Unknown routes are answered with html, like on an installation without the JSON:API
+        let server = MockServer::start();
+        let client = Arc::new(server.client());
+        let api = JsonApi::new(&client);
+        assert_eq!(api.is_available(), None);
+        let error = api.course("c3").unwrap_err();
+        assert!(matches!(error.downcast_ref::<StudIpError>(), Some(StudIpError::FeatureDisabled { .. })), "{:?}", error);
+        assert_eq!(api.is_available(), Some(false));
+    }
+
+    #[test]
+    fn test_jsonapi_files() {
+        let server = MockServer::start();
+        let root = r#"{"type": "folders", "id": "f1", "attributes": {"name": "", "chdate": "2024-10-01T12:00:00+02:00", "is-readable": true},
+            "relationships": {"owner": {"data": {"type": "users", "id": "u2"}}, "range": {"data": {"type": "courses", "id": "c1"}},
+                "folders": {"meta": {"count": 1}}, "file-refs": {"meta": {"count": 1}}}}"#;
+        let included = r#"[{"type": "users", "id": "u2", "attributes": {"username": "mmustermann", "formatted-name": "Max Mustermann"}}]"#;
+        server.route_json("GET", "/jsonapi.php/v1/courses/c1/folders", 200, format!(r#"{{"data": [
+                {{"type": "folders", "id": "f2", "attributes": {{"name": "Folien", "chdate": "2024-10-02T12:00:00+02:00"}},
+                    "relationships": {{"parent": {{"data": {{"type": "folders", "id": "f1"}}}}}}}},
+                {}
+            ], "included": {}}}"#, root, included))
+            .route_json("GET", "/jsonapi.php/v1/folders/f1", 200, format!(r#"{{"data": {}, "included": {}}}"#, root, included))
+            .route_json("GET", "/jsonapi.php/v1/folders/f1/folders", 200, r#"{"data": [
+                {"type": "folders", "id": "f2", "attributes": {"name": "Folien", "chdate": "2024-10-02T12:00:00+02:00"}}
+            ]}"#)
+            .route_json("GET", "/jsonapi.php/v1/folders/f1/file-refs", 200, format!(r#"{{"data": [
+                {{"type": "file-refs", "id": "r1", "attributes": {{"name": "Skript.pdf", "chdate": "2024-10-03T12:00:00+02:00",
+                    "filesize": 1024, "mime-type": "application/pdf", "is-downloadable": true}},
+                    "relationships": {{"owner": {{"data": {{"type": "users", "id": "u2"}}}}}}}}
+            ], "included": {}}}"#, included))
+            .route("GET", "/jsonapi.php/v1/file-refs/r1/content", 200, "%PDF");
+        let client = Arc::new(server.client());
+        let api = JsonApi::new(&client);
+
+        let root = api.course_root_folder("c1").unwrap();
+        assert_eq!(root.object.id, "f1");
+        assert_eq!(root.object_count, 2);
+        assert_eq!(root.object.author.username, "mmustermann");
+        let contents = api.folder_files(&root.object.id).unwrap();
+        assert_eq!(contents.folders[0].object.name, "Folien");
+        assert_eq!(contents.files[0].object.name, "Skript.pdf");
+        assert_eq!(contents.files[0].size, 1024);
+        assert_eq!(contents.files[0].object.author.source, ReferenceSource::Course("c1".into()));
+        assert_eq!(api.download_file_ref("r1").unwrap(), b"%PDF");
+    }
+}
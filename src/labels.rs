@@ -0,0 +1,194 @@
+//! The German and English labels of the pages, that are parsed by their text \
+//! Kept in one table, so that supporting another language (or another wording of an installation) only needs changes here.
+//! The keys of scraped data can also be translated back, to show them to users (see [`key_to_local()`]).
+
+/// A language of the user interface, in which the labels are known
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    German,
+    English,
+}
+
+impl Language {
+
+    pub const ALL: [Language; 2] = [Language::German, Language::English];
+
+}
+
+/// The lowercase translations of a label, by language
+#[derive(Debug)]
+pub(crate) struct Label {
+    german: &'static [&'static str],
+    english: &'static [&'static str],
+}
+
+impl Label {
+
+    const fn new(german: &'static [&'static str], english: &'static [&'static str]) -> Self {
+        Self { german, english }
+    }
+
+    /// The translations of the label in the `language`
+    pub fn in_language(&self, language: Language) -> &'static [&'static str] {
+        match language {
+            Language::German => self.german,
+            Language::English => self.english,
+        }
+    }
+
+    /// The translations of the label in all languages
+    pub fn all(&self) -> impl Iterator<Item = &'static str> {
+        self.german.iter().chain(self.english).copied()
+    }
+
+    /// Whether the lowercase `text` contains any translation of the label
+    pub fn is_in(&self, text: &str) -> bool {
+        self.all().any(|label| text.contains(label))
+    }
+
+}
+
+/// Finds the language of a text, which is the first one, in which any translation of the `labels` is found by `is_found`
+pub(crate) fn detect_language(labels: &[&Label], is_found: impl Fn(&str) -> bool) -> Option<Language> {
+    Language::ALL.into_iter()
+        .find(|language| labels.iter().flat_map(|label| label.in_language(*language)).any(|label| is_found(label)))
+}
+
+/// The state of an admission registration, in which no place was given \
+/// Contains the labels of [`REGISTRATION_WON`] (e.g. "nicht zugelassen"), so it needs to be checked first.
+pub(crate) const REGISTRATION_LOST: Label = Label::new(&["nicht zugelassen", "kein platz", "abgelehnt"], &["not admitted", "no place", "rejected"]);
+/// The state of an admission registration, in which a place was given
+pub(crate) const REGISTRATION_WON: Label = Label::new(&["zugelassen", "platz erhalten"], &["admitted", "accepted"]);
+/// The state of an admission registration on a waiting list
+pub(crate) const REGISTRATION_PENDING: Label = Label::new(&["warteliste", "ausstehend"], &["waiting list", "pending"]);
+/// The state of an admission registration before the places are given
+pub(crate) const REGISTRATION_REGISTERED: Label = Label::new(&["angemeldet", "vorläufig"], &["registered", "tentative"]);
+
+/// The translations of a key of scraped data for showing it to users, a missing one falls back to English (see [`display_name()`])
+#[derive(Debug)]
+struct Translation {
+    key: &'static str,
+    german: Option<&'static str>,
+    english: Option<&'static str>,
+}
+
+impl Translation {
+
+    const fn new(key: &'static str, german: Option<&'static str>, english: Option<&'static str>) -> Self {
+        Self { key, german, english }
+    }
+
+    fn in_language(&self, language: Language) -> Option<&'static str> {
+        match language {
+            Language::German => self.german,
+            Language::English => self.english,
+        }
+    }
+
+}
+
+/// The key of [`CourseDetails::participants`](crate::course::CourseDetails::participants)
+pub const NUMBER_OF_PARTICIPANTS: &str = "NUMBER_OF_PARTICIPANTS";
+/// The key of [`CourseDetails::max_participants`](crate::course::CourseDetails::max_participants)
+pub const MAX_PARTICIPANTS: &str = "MAX_PARTICIPANTS";
+/// The key of [`CourseDetails::waitlist`](crate::course::CourseDetails::waitlist)
+pub const WAITLIST: &str = "WAITLIST";
+/// The key of [`CourseDetails::guests`](crate::course::CourseDetails::guests)
+pub const GUESTS: &str = "GUESTS";
+/// The key of [`CourseDetails::location`](crate::course::CourseDetails::location)
+pub const LOCATION: &str = "LOCATION";
+
+const TRANSLATIONS: &[Translation] = &[
+    Translation::new(NUMBER_OF_PARTICIPANTS, Some("Teilnehmende"), Some("Participants")),
+    Translation::new(MAX_PARTICIPANTS, Some("Maximale Teilnehmendenanzahl"), Some("Maximum number of participants")),
+    Translation::new(WAITLIST, Some("Warteliste"), Some("Waiting list")),
+    Translation::new(GUESTS, Some("Lesende Gäste"), Some("Guests")),
+    Translation::new(LOCATION, Some("Ort"), Some("Location")),
+];
+
+/// The languages, in which the keys of scraped data can be translated
+pub fn available_languages() -> &'static [Language] {
+    &Language::ALL
+}
+
+/// Translates a key of scraped data (e.g. [`NUMBER_OF_PARTICIPANTS`]) into the `language` ("Teilnehmende" or "Participants") \
+/// Returns None, if the key is unknown or has no translation in the `language`.
+pub fn key_to_local(key: &str, language: Language) -> Option<&'static str> {
+    translate(TRANSLATIONS, key, language)
+}
+
+fn translate(translations: &[Translation], key: &str, language: Language) -> Option<&'static str> {
+    translations.iter()
+        .find(|translation| translation.key == key)
+        .and_then(|translation| translation.in_language(language))
+}
+
+/// Finds the key of scraped data, that the translated `text` in any language belongs to, ignoring its case
+pub fn local_to_key(text: &str) -> Option<&'static str> {
+    let text = text.trim().to_lowercase();
+    TRANSLATIONS.iter()
+        .find(|translation| Language::ALL.into_iter()
+            .filter_map(|language| translation.in_language(language))
+            .any(|local| local.to_lowercase() == text))
+        .map(|translation| translation.key)
+}
+
+/// Like [`key_to_local()`], but falls back to English and then to the key itself, so that there is always something to show
+pub fn display_name(key: &str, language: Language) -> &str {
+    display_name_in(TRANSLATIONS, key, language)
+}
+
+fn display_name_in<'a>(translations: &[Translation], key: &'a str, language: Language) -> &'a str {
+    translate(translations, key, language)
+        .or_else(|| translate(translations, key, Language::English))
+        .unwrap_or(key)
+}
+
+/// Separates the used from the total size in the quota indicator of the personal files \
+/// German renders "1,2 GB von 5 GB belegt", English "1.2 GB of 5 GB used" or "Used: 1.2 GB of 5 GB".
+pub(crate) const QUOTA_OF: Label = Label::new(&["von"], &["of"]);
+/// Marks the used size in the quota indicator, if no total is shown
+pub(crate) const QUOTA_USED: Label = Label::new(&["belegt"], &["used"]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        assert!(REGISTRATION_PENDING.is_in("auf der warteliste (platz 3)"));
+        assert!(REGISTRATION_PENDING.is_in("on the waiting list"));
+        assert!(!REGISTRATION_PENDING.is_in("zugelassen"));
+        let text = "1,2 gb von 5 gb belegt";
+        assert_eq!(detect_language(&[&QUOTA_OF, &QUOTA_USED], |label| text.contains(label)), Some(Language::German));
+        assert_eq!(detect_language(&[&QUOTA_USED], |label| "used: 1 gb".contains(label)), Some(Language::English));
+        assert_eq!(detect_language(&[&QUOTA_OF, &QUOTA_USED], |label| "dateien: 12".contains(label)), None);
+    }
+
+    #[test]
+    fn test_translations() {
+        assert_eq!(key_to_local(NUMBER_OF_PARTICIPANTS, Language::German), Some("Teilnehmende"));
+        assert_eq!(key_to_local(NUMBER_OF_PARTICIPANTS, Language::English), Some("Participants"));
+        assert_eq!(key_to_local("UNKNOWN", Language::German), None);
+        assert_eq!(local_to_key("teilnehmende"), Some(NUMBER_OF_PARTICIPANTS));
+        assert_eq!(local_to_key(" Lesende Gäste "), Some(GUESTS));
+        assert_eq!(local_to_key("Waiting list"), Some(WAITLIST));
+        assert_eq!(local_to_key("Dozent"), None);
+        // Every key can be translated back into itself in every language
+        for language in available_languages() {
+            for translation in TRANSLATIONS {
+                assert_eq!(local_to_key(key_to_local(translation.key, *language).unwrap()), Some(translation.key));
+            }
+        }
+    }
+
+    #[test]
+    fn test_display_name_fallback() {
+        let translations = [Translation::new("PARTIAL", None, Some("Partial")), Translation::new("EMPTY", None, None)];
+        assert_eq!(display_name(WAITLIST, Language::German), "Warteliste");
+        // Missing translations fall back to English and then to the key
+        assert_eq!(display_name_in(&translations, "PARTIAL", Language::German), "Partial");
+        assert_eq!(display_name_in(&translations, "EMPTY", Language::German), "EMPTY");
+        assert_eq!(display_name("UNKNOWN", Language::German), "UNKNOWN");
+    }
+}
@@ -6,42 +6,90 @@ pub mod questionnaire;
 pub mod ref_source;
 pub mod institute;
 pub mod search;
+pub mod messages;
+pub mod my_files;
+pub mod calendar;
+pub mod resources;
+pub mod start_page;
+pub mod notifications;
+pub mod planner;
+pub mod diff;
+pub mod archive;
+pub mod content;
+pub mod auth;
+pub mod ids;
+pub mod error;
+pub mod flash;
+pub mod enrollment;
+pub mod session;
+pub mod impersonation;
+pub mod idp_util;
+pub mod conditional;
+pub mod cookies;
+pub mod warnings;
+pub mod logging;
+pub mod labels;
+#[cfg(feature = "watch")]
+pub mod watch;
+#[cfg(feature = "jsonapi")]
+pub mod jsonapi;
+#[cfg(feature = "bulletin_board")]
+pub mod bulletin_board;
+#[cfg(feature = "rate_limiting")]
+pub mod rate_limit;
+mod util;
+mod page_cache;
+mod throttle;
+mod redirect;
+#[cfg(feature = "record")]
+mod record;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 
-use std::cell::RefCell;
 use std::fmt::Debug;
-use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use anyhow::{bail, Context};
-use reqwest::blocking::{Client, ClientBuilder};
+pub use chrono_tz::Tz;
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
 use reqwest::header::{HeaderMap, HeaderValue};
 use serde::{Deserialize, Serialize};
 use url::Url;
+use crate::auth::{AuthMethod, Credentials};
+use crate::calendar::{Calendar, Timetable};
+use crate::cookies::{CookieJar, CookieRecord, SessionCookie, SESSION_COOKIE_NAME};
 use crate::course::MyCourses;
-use crate::search::{SearchFilter, SearchResult};
+use crate::course_modules::{CourseModule, ModuleRegistry};
+use crate::messages::Messages;
+use crate::my_files::MyFiles;
+use crate::news::NewsArticle;
+use crate::notifications::Notification;
+use crate::planner::Upcoming;
+use crate::search::{FilterSemester, SearchEntryCourse, SearchFilter, SearchResult};
+use crate::session::{SAMLAttributes, Session};
+use crate::start_page::StartPage;
 
 const LOGIN_URL : &str = "https://studip.example.com/Shibboleth.sso/Login";
 const SAML_RESPONSE_URL: &str = "https://studip.example.com/Shibboleth.sso/SAML2/POST";
-
-const REQUEST_MAX_SPEED: Duration = Duration::from_millis(150);
+/// The path prefix of the JSON:API, the only endpoints, that can be accessed with a token instead of a session
+const JSONAPI_PATH: &str = "/jsonapi.php/";
 
 /// The entry point into interacting with StudIp
 pub struct StudIp {
     pub client: Arc<StudIpClient>,
-    pub my_courses: MyCourses
+    pub my_courses: MyCourses,
+    /// The logged-in user, set by [`StudIp::login()`] \
+    /// Stays `None`, if the instance does not expose the JSON:API, through which it is queried.
+    pub session: Option<Session>,
 }
 
 impl StudIp {
 
-    fn login_client<IdP: IdentityProvider>(&self, creds_path: &str) -> anyhow::Result<()> {
+    // Logs in and returns the attributes of the SAML assertion
+    fn login_client<IdP: IdentityProvider>(&self, creds: &Credentials) -> anyhow::Result<SAMLAttributes> {
         // Sets some cookies
-        let _ = self.client.get("https://studip.example.com/index.php?logout=true&set_language=de_DE&set_contrast=").send();
-        // Read and parse credentials
-        let creds = std::fs::read_to_string(creds_path)
-            .context("Could not read from creds.txt")?;
-        let (username, password) = creds.split_once('\n')
-            .context("creds.txt did not have newline seperated username and password")?;
-        let username = username.trim();
-        let password = password.trim();
+        let _ = self.client.get("https://studip.example.com/index.php?logout=true&set_language=de_DE&set_contrast=").send_through(&self.client);
 
         let mut target_url = Url::parse(&format!("https://{}", self.client.host))?;
         target_url.query_pairs_mut()
@@ -49,66 +97,87 @@ impl StudIp {
             .append_pair("again", "yes")
             .append_pair("cancel_login", "1");
         // Get LOGIN_URL to obtain redirected url (The url to the IdP)
-        let redirected_url = self.client.get(LOGIN_URL)
-            .query(&[
-                ("target", target_url.as_str()),
-                ("entityID", IdP::entity_url())
-            ])
-            .send()?
-            .url()
-            .clone();
+        let redirected_url = self.client.send_tracked(
+            self.client.get(LOGIN_URL)
+                .query(&[
+                    ("target", target_url.as_str()),
+                    ("entityID", IdP::entity_url())
+                ])
+        )?.url().clone();
         // Login with Identity Provider
-        let saml_assertion = IdP::login(&self.client.client, redirected_url, username, password)?;
+        logging::log_auth!(debug, "Logging in through the identity provider at {}", redirected_url);
+        let saml_assertion = IdP::login(&self.client.client, redirected_url, &creds.username, &creds.password)?;
+        let attributes = IdP::saml_attributes(&saml_assertion);
         // Send IdP's SAML response back to service provider (Stud Ip)
-        let response = self.client.post(SAML_RESPONSE_URL)
-            .form(&[("RelayState", saml_assertion.relay_state), ("SAMLResponse", saml_assertion.saml_response)])
-            .send()
-            .context("Could not send second login request. Are the credentials incorrect?")?;
-        if !response.status().is_success() {
-            bail!("Second login request had status code: {}", response.status());
-        }
-        Ok(())
+        let response = self.client.send_tracked(
+            self.client.post(SAML_RESPONSE_URL)
+                .form(&[("RelayState", saml_assertion.relay_state), ("SAMLResponse", saml_assertion.saml_response)])
+        ).context("Could not send second login request. Are the credentials incorrect?")?;
+        verify_login(response)?;
+        Ok(attributes)
     }
 
-    fn make_client() -> anyhow::Result<Client> {
-        // Setup client with headers
-        let mut default_headers = HeaderMap::new();
-        default_headers.insert("User-Agent", HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:133.0) Gecko/20100101 Firefox/133.0"));
-        default_headers.insert("Accept", HeaderValue::from_static("text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8"));
-        default_headers.insert("Accept-Language", HeaderValue::from_static("en-US,en;q=0.5"));
-        default_headers.insert("Upgrade-Insecure-Requests", HeaderValue::from_static("1"));
-        default_headers.insert("DNT", HeaderValue::from_static("1"));
-        default_headers.insert("Sec-Fetch-Dest", HeaderValue::from_static("document"));
-        default_headers.insert("Sec-Fetch-Mode", HeaderValue::from_static("navigate"));
-        default_headers.insert("Sec-Fetch-Site", HeaderValue::from_static("cross-site"));
-        ClientBuilder::new()
-            .https_only(true)
-            .cookie_store(true)
-            .timeout(Duration::from_secs(8))
-            .use_rustls_tls()
-            .default_headers(default_headers)
-            .gzip(true)
-            .build()
-            .context("Could not build reqwest client")
+    /// Attempts to log in into a  `[StudIp]` instance, specified by `host` (e.g. studip.example.com) \
+    /// Uses the credentials in the file at `creds_path` (see [`Credentials::from_file()`]) and an [`IdentityProvider`], through which the user is authorized.
+    pub fn login<IdP: IdentityProvider>(creds_path: &str, host: &str) -> anyhow::Result<Self> {
+        Self::login_with::<IdP>(&Credentials::from_file(creds_path)?, host)
     }
 
-    /// Attempts to log in into a  `[StudIp]` instance, specified by `host` (e.g. studip.example.com) \
-    /// Uses the provided credentials and an [`IdentityProvider`], through which the user is authorized.
-    pub fn login<IdP: IdentityProvider>(creds_path: &str, host: &'static str) -> anyhow::Result<Self> {
-        let client = Arc::new(
-            StudIpClient {
-                client: Self::make_client()?,
-                host,
-                #[cfg(feature = "rate_limiting")]
-                last_request_time: RefCell::new(SystemTime::UNIX_EPOCH),
-            }
-        );
-        let stud_ip = Self {
+    /// Like [`StudIp::login()`], but with already loaded [`Credentials`] (e.g. from the keyring)
+    /// Use [`StudIpClientBuilder::login()`] to configure the client (e.g. its redirect limit).
+    pub fn login_with<IdP: IdentityProvider>(creds: &Credentials, host: &str) -> anyhow::Result<Self> {
+        StudIpClientBuilder::new(host).login::<IdP>(creds)
+    }
+
+    /// Creates a `[StudIp]` instance for the `host`, that authenticates every request with the given `auth` (e.g. a personal API token), without logging in through an [`IdentityProvider`] \
+    /// *Note: Stud.IP only accepts tokens on its JSON:API, so only the features backed by it work (see the `jsonapi` feature). Querying html pages fails.*
+    pub fn with_token(auth: AuthMethod, host: &str) -> anyhow::Result<Self> {
+        StudIpClientBuilder::new(host).with_token(auth)
+    }
+
+    fn from_client(client: StudIpClient) -> Self {
+        let client = Arc::new(client);
+        Self {
             client: client.clone(),
             my_courses: MyCourses::from_client(client),
+            session: None,
+        }
+    }
+
+    /// Registers a custom course module for the courses of this instance only (see [`ModuleRegistry`])
+    pub fn register_course_module<M: CourseModule + 'static>(&self) {
+        self.client.module_registry().register::<M>();
+    }
+
+    /// Queries the logged-in user (again), keeping the expiry of the IdP session \
+    /// Also works with tokens, as the user is queried through the JSON:API.
+    pub fn query_session(&mut self) -> anyhow::Result<&Session> {
+        let idp_session_expires = self.session.as_ref().and_then(|session| session.idp_session_expires);
+        Ok(self.session.insert(session::query_session(&self.client, idp_session_expires)?))
+    }
+
+    /// Returns the user, as which the requests are sent \
+    /// This is the impersonated user while an [`ImpersonationGuard`](impersonation::ImpersonationGuard) exists, otherwise the logged-in user,
+    /// which is queried, if it is not known yet.
+    pub fn whoami(&self) -> anyhow::Result<Session> {
+        if let Some(user) = self.client.impersonated.lock().unwrap().clone() {
+            return Ok(user);
+        }
+        match &self.session {
+            Some(session) => Ok(session.clone()),
+            None => session::query_session(&self.client, None),
+        }
+    }
+
+    /// Acts as the user with the `username` ("Als Nutzer agieren"), until the returned guard is ended or dropped \
+    /// Fails with [`StudIpError::PermissionDenied`](error::StudIpError::PermissionDenied) before changing anything, if the logged-in user is not an admin.
+    /// Requests of all handles sharing the client are sent as the user meanwhile, see [`StudIp::whoami()`].
+    pub fn impersonate(&self, username: &str) -> anyhow::Result<impersonation::ImpersonationGuard> {
+        let current = match &self.session {
+            Some(session) => session.clone(),
+            None => session::query_session(&self.client, None)?,
         };
-        stud_ip.login_client::<IdP>(creds_path)?;
-        Ok(stud_ip)
+        impersonation::impersonate(&self.client, &current, username)
     }
 
     /// Does a global search for the given `text`, providing at most `max_results` results per category using the given [`SearchFilter`].
@@ -116,6 +185,103 @@ impl StudIp {
         search::global_search(&self.client, text, max_results, filter)
     }
 
+    /// Searches the courses of the `semester` for the same course as the one with the given `name` (see [`search::find_course_in_semester()`]) \
+    /// For courses, the user is enrolled in, see [`MyCourses::find_predecessors()`].
+    pub fn find_course_in_semester(&self, name: &str, semester: FilterSemester) -> anyhow::Result<Vec<SearchEntryCourse>> {
+        search::find_course_in_semester(&self.client, name, semester)
+    }
+
+    /// Queries the widgets of the [`StartPage`], like the global announcements and upcoming dates
+    pub fn start_page(&self) -> anyhow::Result<StartPage> {
+        start_page::get_start_page(&self.client)
+    }
+
+    /// Queries the latest forum posts of all courses of the user, from the widget of the [`StartPage`] \
+    /// Empty, if the user removed the widget.
+    pub fn recent_forum_posts(&self) -> anyhow::Result<Vec<start_page::RecentForumPost>> {
+        Ok(self.start_page()?.forum_posts)
+    }
+
+    /// Queries the system-wide announcements, optionally including expired ones (see [`news::global_news()`])
+    pub fn global_news(&self, include_expired: bool) -> anyhow::Result<Vec<NewsArticle>> {
+        news::global_news(&self.client, include_expired)
+    }
+
+    /// Queries the [`Notification`]s of the bell icon in the header
+    pub fn notifications(&self) -> anyhow::Result<Vec<Notification>> {
+        notifications::get_notifications(&self.client)
+    }
+
+    /// Marks all [`Notification`]s as seen
+    pub fn mark_notifications_seen(&self) -> anyhow::Result<()> {
+        notifications::mark_notifications_seen(&self.client)
+    }
+
+    /// Gathers the [`Upcoming`] course dates, closing questionnaires and group sign-ups of all courses within the `horizon`. \
+    /// The courses are queried first, if they have not been yet. Failures of individual courses are collected as warnings.
+    /// Deadlines of assignments are not included, as no supported course module provides them yet.
+    pub fn upcoming(&mut self, horizon: Duration) -> anyhow::Result<Upcoming> {
+        planner::gather_upcoming(&self.client, &mut self.my_courses, horizon)
+    }
+
+    /// Enrolls the current user into the course with the `course_id`, which is protected by the `passcode` ("Zugangscode") \
+    /// Fails with [`EnrollmentError::WrongPasscode`](enrollment::EnrollmentError::WrongPasscode), if the passcode is not accepted,
+    /// and with [`EnrollmentError::NoPasscodeRequired`](enrollment::EnrollmentError::NoPasscodeRequired), if the course does not ask for one.
+    /// The course is only part of [`StudIp::my_courses`] after querying them again.
+    pub fn enroll_with_passcode(&self, course_id: &str, passcode: &str) -> anyhow::Result<()> {
+        enrollment::enroll_with_passcode(&self.client, course_id, passcode)
+    }
+
+    /// Returns the registrations of the current user for courses, whose places are given by an admission procedure (e.g. a lottery) \
+    /// These are listed on the overview of the courses, next to the courses the user is already enrolled into.
+    pub fn admission_registrations(&self) -> anyhow::Result<Vec<enrollment::AdmissionRegistration>> {
+        enrollment::admission_registrations(&self.client)
+    }
+
+    /// Registers the current user for the lottery of the course with the `course_id` \
+    /// Fails with [`EnrollmentError::NoLottery`](enrollment::EnrollmentError::NoLottery), if the places of the course are not drawn by lot.
+    pub fn register_for_lottery(&self, course_id: &str) -> anyhow::Result<()> {
+        enrollment::register_for_lottery(&self.client, course_id)
+    }
+
+    /// Withdraws the registration of the current user for the course with the `course_id`, which also leaves its waiting list
+    pub fn withdraw(&self, course_id: &str) -> anyhow::Result<()> {
+        enrollment::withdraw(&self.client, course_id)
+    }
+
+    /// Returns a handle to the internal [`Messages`] of the current user
+    pub fn messages(&self) -> Messages {
+        Messages::from_client(self.client.clone())
+    }
+
+    /// Returns a handle to the personal files ("Meine Dateien") of the current user
+    pub fn my_files(&self) -> MyFiles {
+        MyFiles::from_client(self.client.clone())
+    }
+
+    /// Returns a handle to the personal [`Calendar`] of the current user
+    pub fn calendar(&self) -> Calendar {
+        Calendar::from_client(self.client.clone())
+    }
+
+    /// Queries the regular weekly [`Timetable`] ("Stundenplan") of the given `semester` (its id), or of the current semester if `None`
+    pub fn timetable(&self, semester: Option<&str>) -> anyhow::Result<Timetable> {
+        calendar::get_timetable(&self.client, semester)
+    }
+
+    /// Returns a handle to the JSON:API of the installation, which uses the session of this client
+    #[cfg(feature = "jsonapi")]
+    pub fn jsonapi(&self) -> jsonapi::JsonApi<'_> {
+        jsonapi::JsonApi::new(&self.client)
+    }
+
+    /// Returns a handle to the bulletin board ("Schwarzes Brett") plugin, which is installed at the `base_path` \
+    /// The path usually is [`BulletinBoard::DEFAULT_BASE_PATH`](bulletin_board::BulletinBoard::DEFAULT_BASE_PATH).
+    #[cfg(feature = "bulletin_board")]
+    pub fn bulletin_board(&self, base_path: &str) -> bulletin_board::BulletinBoard {
+        bulletin_board::BulletinBoard::new(self.client.clone(), base_path)
+    }
+
 }
 
 /// The necessary data, that is sent back from the [`IdentityProvider`] to the Service Provider, to complete the authentication
@@ -176,24 +342,276 @@ pub trait IdentityProvider {
     /// The entity url of the Identify Provider, also sometimes called `entityID`
     fn entity_url() -> &'static str;
 
+    /// Parses the attributes of the assertion, that was returned by [`IdentityProvider::login()`], like the expiry of the IdP session \
+    /// By default, these are only found in unencrypted assertions. Implementations, that have access to more data, can override this.
+    fn saml_attributes(saml_assertion: &SAMLAssertionData) -> SAMLAttributes {
+        SAMLAttributes::from_saml_response(saml_assertion)
+    }
+
+}
+
+/// The maximum number of characters of the page title, that is included in login errors
+const LOGIN_TITLE_SNIPPET_LENGTH: usize = 80;
+
+// Verifies, that the response to the SAML assertion is a page of the logged-in Stud.IP, not an interstitial or the login page
+fn verify_login(response: Response) -> anyhow::Result<()> {
+    if !response.status().is_success() {
+        bail!("Second login request had status code: {}", response.status());
+    }
+    let url = response.url().clone();
+    let html = scraper::Html::parse_document(&response.text()?);
+    error::check_login_page(&url, &html)?;
+    if html.select(util::selector!("input[type=password]")).next().is_some() {
+        let title = html.select(util::selector!("title")).next()
+            .map(|title| title.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" "))
+            .unwrap_or_default();
+        let snippet = title.chars().take(LOGIN_TITLE_SNIPPET_LENGTH).collect::<String>();
+        logging::log_auth!(debug, "The response to the SAML assertion still asks for a password at {}", url);
+        bail!("Failed to login, still on login page \"{}\" ({})", snippet, url);
+    }
+    Ok(())
+}
+
+/// How long a request for a page may take by default
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(8);
+/// How long a transfer of a file (a download or an upload) may take by default
+const DEFAULT_TRANSFER_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// The timezone, in which Stud.IP installations render their dates by default (all of them in Germany, Austria and Switzerland)
+pub const DEFAULT_TIMEZONE: Tz = chrono_tz::Europe::Berlin;
+
+/// Configures the [`StudIpClient`] of a [`StudIp`] instance
+#[derive(Debug, Clone)]
+pub struct StudIpClientBuilder {
+    host: String,
+    origin: Option<Url>,
+    max_redirects: usize,
+    conditional_urls: usize,
+    timeout: Duration,
+    transfer_timeout: Duration,
+    timezone: Tz,
+    #[cfg(feature = "rate_limiting")]
+    rate_limiter: Option<Arc<rate_limit::RateLimiter>>,
+    #[cfg(feature = "rate_limiting")]
+    request_max_speed: Duration,
+    #[cfg(feature = "rate_limiting")]
+    request_max_speed_jitter: Duration,
+    #[cfg(feature = "record")]
+    record_dir: Option<std::path::PathBuf>,
+}
+
+impl StudIpClientBuilder {
+
+    /// Starts configuring a client for the `host` (e.g. studip.example.com) \
+    /// Clients for different hosts do not share any state, so they can be used side by side in the same process.
+    pub fn new(host: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            origin: None,
+            max_redirects: redirect::DEFAULT_MAX_REDIRECTS,
+            conditional_urls: 0,
+            timeout: DEFAULT_TIMEOUT,
+            transfer_timeout: DEFAULT_TRANSFER_TIMEOUT,
+            timezone: DEFAULT_TIMEZONE,
+            #[cfg(feature = "rate_limiting")]
+            rate_limiter: None,
+            #[cfg(feature = "rate_limiting")]
+            request_max_speed: rate_limit::RateLimiter::DEFAULT_INTERVAL,
+            #[cfg(feature = "rate_limiting")]
+            request_max_speed_jitter: Duration::ZERO,
+            #[cfg(feature = "record")]
+            record_dir: None,
+        }
+    }
+
+    /// Sets the maximum number of redirects per request (10 by default) \
+    /// Redirect loops between the Service Provider and the IdP are detected before reaching it.
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Remembers the `ETag` and `Last-Modified` headers of up to `max_urls` pages, to request them again conditionally (see [`conditional`]) \
+    /// Disabled (0) by default.
+    pub fn conditional_requests(mut self, max_urls: usize) -> Self {
+        self.conditional_urls = max_urls;
+        self
+    }
+
+    /// Sets how long a request may take (8 seconds by default), which detects hanging pages quickly \
+    /// Transfers of files use the [`StudIpClientBuilder::transfer_timeout()`] instead.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how long a transfer of a file (a download or an upload) may take (10 minutes by default)
+    pub fn transfer_timeout(mut self, transfer_timeout: Duration) -> Self {
+        self.transfer_timeout = transfer_timeout;
+        self
+    }
+
+    /// Sets the timezone, in which the installation renders its dates ([`DEFAULT_TIMEZONE`] by default) \
+    /// The dates on the pages have no offset, so they are converted to UTC in this timezone, regardless of the timezone of the machine running this crate.
+    pub fn timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Sets the minimum interval between two requests (150ms by default)
+    #[cfg(feature = "rate_limiting")]
+    pub fn request_max_speed(mut self, interval: Duration) -> Self {
+        self.request_max_speed = interval;
+        self
+    }
+
+    /// Lengthens every interval between two requests by a random duration up to the `jitter` (none by default)
+    #[cfg(feature = "rate_limiting")]
+    pub fn request_max_speed_jitter(mut self, jitter: Duration) -> Self {
+        self.request_max_speed_jitter = jitter;
+        self
+    }
+
+    /// Shares the `rate_limiter` of another client (see [`StudIpClient::rate_limiter()`]), so that both draw from the same budget \
+    /// Replaces the intervals set with [`StudIpClientBuilder::request_max_speed()`] and [`StudIpClientBuilder::request_max_speed_jitter()`].
+    #[cfg(feature = "rate_limiting")]
+    pub fn rate_limiter(mut self, rate_limiter: Arc<rate_limit::RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Records every request and its response into the `dir` (see [`StudIpClient::record_to()`])
+    #[cfg(feature = "record")]
+    pub fn record_to(mut self, dir: std::path::PathBuf) -> Self {
+        self.record_dir = Some(dir);
+        self
+    }
+
+    /// Overrides the scheme and port of every request, e.g. for a local test server
+    #[cfg(any(test, feature = "mock"))]
+    pub(crate) fn origin(mut self, origin: Url) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Builds the client, which authenticates its requests with the `auth`
+    pub fn build(self, auth: AuthMethod) -> anyhow::Result<StudIpClient> {
+        let redirect_chain: redirect::RedirectChain = Default::default();
+        // Setup client with headers
+        let mut default_headers = HeaderMap::new();
+        default_headers.insert("User-Agent", HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:133.0) Gecko/20100101 Firefox/133.0"));
+        default_headers.insert("Accept", HeaderValue::from_static("text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8"));
+        default_headers.insert("Accept-Language", HeaderValue::from_static("en-US,en;q=0.5"));
+        default_headers.insert("Upgrade-Insecure-Requests", HeaderValue::from_static("1"));
+        default_headers.insert("DNT", HeaderValue::from_static("1"));
+        default_headers.insert("Sec-Fetch-Dest", HeaderValue::from_static("document"));
+        default_headers.insert("Sec-Fetch-Mode", HeaderValue::from_static("navigate"));
+        default_headers.insert("Sec-Fetch-Site", HeaderValue::from_static("cross-site"));
+        let cookies: Arc<CookieJar> = Default::default();
+        let client = ClientBuilder::new()
+            // A local test server is only reachable through http
+            .https_only(self.origin.is_none())
+            .cookie_provider(cookies.clone())
+            .timeout(self.timeout)
+            .use_rustls_tls()
+            .default_headers(default_headers)
+            .gzip(true)
+            .redirect(redirect::policy(self.max_redirects, redirect_chain.clone()))
+            .build()
+            .context("Could not build reqwest client")?;
+        let client = StudIpClient {
+            client,
+            host: self.host,
+            origin: self.origin,
+            redirect_chain,
+            validators: conditional::ValidatorCache::new(self.conditional_urls),
+            auth: Mutex::new(auth),
+            cookies,
+            transfer_timeout: self.transfer_timeout,
+            timezone: self.timezone,
+            #[cfg(feature = "rate_limiting")]
+            rate_limiter: self.rate_limiter.unwrap_or_else(|| Arc::new(
+                rate_limit::RateLimiter::new(self.request_max_speed).with_jitter(self.request_max_speed_jitter)
+            )),
+            ..Default::default()
+        };
+        #[cfg(feature = "record")]
+        if let Some(dir) = self.record_dir {
+            client.record_to(dir)?;
+        }
+        Ok(client)
+    }
+
+    /// Logs in through the [`IdentityProvider`] with the [`Credentials`] (see [`StudIp::login()`])
+    pub fn login<IdP: IdentityProvider>(self, creds: &Credentials) -> anyhow::Result<StudIp> {
+        let mut stud_ip = StudIp::from_client(self.build(AuthMethod::Session)?);
+        let attributes = stud_ip.login_client::<IdP>(creds)?;
+        stud_ip.session = session::query_session(&stud_ip.client, attributes.session_not_on_or_after).ok();
+        Ok(stud_ip)
+    }
+
+    /// Creates a [`StudIp`] instance, that authenticates every request with the `auth` (see [`StudIp::with_token()`])
+    pub fn with_token(self, auth: AuthMethod) -> anyhow::Result<StudIp> {
+        Ok(StudIp::from_client(self.build(auth)?))
+    }
+
 }
 
 /// A wrapped reqwest [`Client`], that automatically replaces the host of every request
 #[derive(Debug)]
 pub struct StudIpClient {
     pub client: Client,
-    pub host: &'static str,
+    pub host: String,
+    /// Overrides the scheme and port of every request, if set (e.g. for a local test server)
+    origin: Option<Url>,
     #[cfg(feature = "rate_limiting")]
-    last_request_time: RefCell<SystemTime>,
+    rate_limiter: Arc<rate_limit::RateLimiter>,
+    security_token: Mutex<Option<String>>,
+    /// The user, as which the requests are sent, while acting as another user
+    impersonated: Mutex<Option<Session>>,
+    /// Counts the switches to and from another user, so that cached pages of the other user are not reused
+    identity_switches: AtomicU64,
+    seminar_types: Mutex<Option<Vec<search::SeminarType>>>,
+    user_ids: user::UserIdCache,
+    validators: conditional::ValidatorCache,
+    /// Whether the JSON:API is available on the installation, known after the first request to it
+    #[cfg(feature = "jsonapi")]
+    jsonapi_available: once_cell::sync::OnceCell<bool>,
+    auth: Mutex<AuthMethod>,
+    redirect_chain: redirect::RedirectChain,
+    module_registry: ModuleRegistry,
+    /// The cookie store of the `client`
+    cookies: Arc<CookieJar>,
+    transfer_timeout: Duration,
+    /// The timezone, in which the installation renders its dates
+    timezone: Tz,
+    #[cfg(feature = "record")]
+    recorder: Mutex<Option<record::Recorder>>,
 }
 
 impl Default for StudIpClient {
     fn default() -> Self {
         Self {
             client: Default::default(),
-            host: "",
+            host: String::new(),
+            origin: None,
             #[cfg(feature = "rate_limiting")]
-            last_request_time: RefCell::new(SystemTime::UNIX_EPOCH),
+            rate_limiter: Default::default(),
+            security_token: Default::default(),
+            impersonated: Default::default(),
+            identity_switches: Default::default(),
+            seminar_types: Default::default(),
+            user_ids: Default::default(),
+            validators: Default::default(),
+            #[cfg(feature = "jsonapi")]
+            jsonapi_available: Default::default(),
+            auth: Mutex::new(AuthMethod::Session),
+            redirect_chain: Default::default(),
+            module_registry: Default::default(),
+            cookies: Default::default(),
+            transfer_timeout: DEFAULT_TRANSFER_TIMEOUT,
+            timezone: DEFAULT_TIMEZONE,
+            #[cfg(feature = "record")]
+            recorder: Default::default(),
         }
     }
 }
@@ -204,19 +622,249 @@ impl StudIpClient {
     fn before_request(&self) {
         // Rate limits on request creation
         // Any requests that are created, but not sent, will still be rate limited
-        let mut last_request_time = self.last_request_time.borrow_mut();
-        let elapsed = last_request_time.elapsed().unwrap_or(Duration::from_secs(0));
-        if elapsed > REQUEST_MAX_SPEED {
-            *last_request_time = SystemTime::now();
-            return;
-        }
-        let wait_time = REQUEST_MAX_SPEED - elapsed;
-        std::thread::sleep(wait_time);
-        *last_request_time = SystemTime::now();
+        self.rate_limiter.acquire();
+    }
+
+    /// Returns the [`RateLimiter`](rate_limit::RateLimiter), which spaces the requests of this client \
+    /// Requests, that are not created through this client, can wait for it with [`RateLimiter::acquire()`](rate_limit::RateLimiter::acquire()).
+    #[cfg(feature = "rate_limiting")]
+    pub fn rate_limiter(&self) -> Arc<rate_limit::RateLimiter> {
+        self.rate_limiter.clone()
     }
 
     #[cfg(not(feature = "rate_limiting"))]
     fn before_request(&self) {}
+
+    /// Returns how long a transfer of a file may take (see [`StudIpClientBuilder::transfer_timeout()`])
+    pub fn transfer_timeout(&self) -> Duration {
+        self.transfer_timeout
+    }
+
+    /// Returns the timezone, in which the installation renders its dates (see [`StudIpClientBuilder::timezone()`])
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+    }
+
+    /// Returns how often the user, as which the requests are sent, was switched (see [`impersonation`])
+    pub(crate) fn identity_switches(&self) -> u64 {
+        self.identity_switches.load(Ordering::SeqCst)
+    }
+
+    /// Like [`StudIpClient::get()`], but the request may take as long as the `timeout`, instead of the timeout of the client
+    pub fn get_with_timeout(&self, url: impl reqwest::IntoUrl, timeout: Duration) -> RequestBuilder {
+        self.get(url).timeout(timeout)
+    }
+
+    /// Like [`StudIpClient::post()`], but the request may take as long as the `timeout`, instead of the timeout of the client
+    pub fn post_with_timeout(&self, url: impl reqwest::IntoUrl, timeout: Duration) -> RequestBuilder {
+        self.post(url).timeout(timeout)
+    }
+
+    /// Returns the [`ModuleRegistry`], which modules are detected on the courses of this client
+    pub fn module_registry(&self) -> &ModuleRegistry {
+        &self.module_registry
+    }
+
+    /// Returns the session cookie (`Seminar_Session`) of Stud.IP, e.g. to reuse the session in other tools
+    pub fn session_cookie(&self) -> Option<SessionCookie> {
+        self.export_cookies().into_iter()
+            .find(|record| record.name == SESSION_COOKIE_NAME)
+            .map(|record| SessionCookie {
+                name: record.name,
+                value: record.value,
+                expires: record.expires,
+            })
+    }
+
+    /// Returns all cookies, that are sent to the host of this client, including their attributes \
+    /// *Note: The values can be used to take over the session, so they should be stored securely*
+    pub fn export_cookies(&self) -> Vec<CookieRecord> {
+        self.cookies.export(&self.host)
+    }
+
+    /// Adds the cookies (e.g. exported by [`StudIpClient::export_cookies()`]) to this client, replacing cookies with the same name, domain and path
+    pub fn import_cookies(&self, records: &[CookieRecord]) -> anyhow::Result<()> {
+        self.cookies.import(records)
+    }
+
+    /// Returns the [`AuthMethod`], that is used for every request
+    pub fn auth(&self) -> AuthMethod {
+        self.auth.lock().unwrap().clone()
+    }
+
+    /// Replaces the [`AuthMethod`], that is used for every request (e.g. to provide a new token)
+    pub fn set_auth(&self, auth: AuthMethod) {
+        *self.auth.lock().unwrap() = auth;
+    }
+
+    /// Fails with a clear error, if html pages can not be accessed with the [`AuthMethod`] of this client \
+    /// Checked before every request, that is not one of the JSON:API.
+    pub fn require_session(&self) -> anyhow::Result<()> {
+        if !self.auth.lock().unwrap().allows_html() {
+            bail!("Html pages can not be accessed with a token, only the JSON:API can. Log in with an IdentityProvider instead");
+        }
+        Ok(())
+    }
+
+    /// Records every following request and its response into the `dir`, to be able to reproduce parser failures \
+    /// Only allowlisted headers are recorded, cookies are left out and credentials and session tokens are redacted.
+    #[cfg(feature = "record")]
+    pub fn record_to(&self, dir: std::path::PathBuf) -> anyhow::Result<()> {
+        *self.recorder.lock().unwrap() = Some(record::Recorder::new(dir)?);
+        Ok(())
+    }
+
+    // Sends the request, reading the whole body and rebuilding the response from it, so that it can be recorded
+    #[cfg(feature = "record")]
+    fn send_recorded(&self, client: &Client, request: reqwest::blocking::Request) -> reqwest::Result<Response> {
+        use reqwest::ResponseBuilderExt;
+        let (method, url) = (request.method().clone(), request.url().clone());
+        let response = client.execute(request)?;
+        let (status, final_url, headers) = (response.status(), response.url().clone(), response.headers().clone());
+        let body = response.bytes()?;
+        if let Some(recorder) = self.recorder.lock().unwrap().as_mut() {
+            // A failed recording does not fail the request
+            let _ = recorder.record(&method, &url, status, &headers, &body);
+        }
+        let mut builder = http::Response::builder()
+            .status(status)
+            .url(final_url);
+        if let Some(builder_headers) = builder.headers_mut() {
+            *builder_headers = headers;
+        }
+        Ok(builder.body(body).expect("Response parts are valid").into())
+    }
+
+    // Sends the request, adding the urls it was redirected through to its error, if it fails
+    fn send_tracked(&self, request: RequestBuilder) -> anyhow::Result<Response> {
+        self.redirect_chain.lock().unwrap().clear();
+        request.send_through(self).map_err(|e| {
+            // Errors of the redirect policy already contain the chain
+            if let Some(error) = e.chain().find_map(|error| error.downcast_ref::<error::StudIpError>()) {
+                return error.clone().into();
+            }
+            let chain = self.redirect_chain.lock().unwrap();
+            match chain.is_empty() {
+                true => e,
+                false => e.context(format!("Redirected through {}", redirect::format_chain(&chain))),
+            }
+        })
+    }
+
+    /// Refreshes the OAuth2 access token through its refresh token \
+    /// This happens automatically before requests, if the expiry of the token is known.
+    pub fn refresh_token(&self) -> anyhow::Result<()> {
+        let mut auth = self.auth.lock().unwrap();
+        let AuthMethod::OAuth2(token) = &mut *auth else {
+            bail!("Only OAuth2 tokens can be refreshed");
+        };
+        token.refresh(&self.client, self.resolve_url(Url::parse(auth::OAUTH2_TOKEN_URL)?))
+    }
+
+    // Attaches the authentication to the request, refreshing an expired OAuth2 token first
+    fn authorize(&self, request: reqwest::blocking::RequestBuilder) -> reqwest::blocking::RequestBuilder {
+        let mut auth = self.auth.lock().unwrap();
+        if let AuthMethod::OAuth2(token) = &mut *auth {
+            if token.is_expired() && token.refresh_token.is_some() {
+                // If the refresh fails, the expired token is sent anyway and the request fails with its status code
+                let _ = token.refresh(&self.client, self.resolve_url(Url::parse(auth::OAUTH2_TOKEN_URL).unwrap()));
+            }
+        }
+        auth.authorize(request)
+    }
+
+    // Replaces the host (and the origin, if set) of the url
+    fn resolve_url(&self, mut url: Url) -> Url {
+        url.set_host(Some(&self.host)).unwrap();
+        if let Some(origin) = &self.origin {
+            url.set_scheme(origin.scheme()).unwrap();
+            url.set_port(origin.port()).unwrap();
+        }
+        url
+    }
+
+    /// Resolves a url, that was found on a page, against the host of this client \
+    /// Paths (e.g. `/assets/images/icons/file.svg`) and protocol relative urls (e.g. `//cdn.example.com/avatar.png`) are made absolute, absolute urls are returned unchanged.
+    pub fn absolutize(&self, url_or_path: &str) -> anyhow::Result<Url> {
+        let url_or_path = url_or_path.trim();
+        if let Ok(url) = Url::parse(url_or_path) {
+            return Ok(url);
+        }
+        let base = Url::parse(&format!("https://{}/", self.host))
+            .with_context(|| format!("Can not resolve {} against the host {:?}", url_or_path, self.host))?;
+        self.resolve_url(base).join(url_or_path)
+            .with_context(|| format!("Invalid url {}", url_or_path))
+    }
+
+    /// Like [`StudIpClient::absolutize()`], but returns the url as a string \
+    /// Plain absolute paths (like most avatars on a large members page) are prefixed with the host, without parsing a url for each of them.
+    pub(crate) fn absolutize_to_string(&self, url_or_path: &str) -> anyhow::Result<String> {
+        let path = url_or_path.trim();
+        let is_plain_path = path.starts_with('/')
+            && !path.starts_with("//")
+            && !path.contains("/.")
+            && path.bytes().all(|byte| byte.is_ascii_graphic() && byte != b'\\');
+        if is_plain_path && self.origin.is_none() && !self.host.is_empty() {
+            return Ok(format!("https://{}{}", self.host, path));
+        }
+        self.absolutize(path).map(String::from)
+    }
+
+    // Resolves the urls of pages, that are parsed without a client, against the placeholder host, which clients replace with their own when requesting them
+    pub(crate) fn offline() -> &'static StudIpClient {
+        static OFFLINE_CLIENT: once_cell::sync::Lazy<StudIpClient> = once_cell::sync::Lazy::new(|| StudIpClient {
+            host: "studip.example.com".to_string(),
+            ..Default::default()
+        });
+        &OFFLINE_CLIENT
+    }
+
+    /// Requests a page with the `If-None-Match` and `If-Modified-Since` headers of its last response, if conditional requests are enabled (see [`StudIpClientBuilder::conditional_requests()`]) \
+    /// Should only be used, if the body of the last response for the same url and `query` is still available, as [`Conditional::NotModified`](conditional::Conditional::NotModified) has none.
+    pub fn get_conditional(&self, url: &str, query: &[(&str, &str)]) -> anyhow::Result<conditional::Conditional> {
+        let key = Url::parse_with_params(url, query)?.to_string();
+        let response = self.validators.apply(&key, self.get(url).query(query)).send_through(self)?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(conditional::Conditional::NotModified);
+        }
+        self.validators.remember(&key, &response);
+        Ok(conditional::Conditional::Modified(response))
+    }
+
+    /// Returns the CSRF security token of the current session \
+    /// The token is fetched from the start page once and then reused, as it stays the same for the whole session.
+    pub fn security_token(&self) -> anyhow::Result<String> {
+        let mut security_token = self.security_token.lock().unwrap();
+        if let Some(token) = security_token.as_ref() {
+            return Ok(token.clone());
+        }
+        let response = self.get(ref_source::START_URL).send_through(self)?;
+        let token = util::parse_security_token(&util::parse_page(&response.text()?)?)?;
+        *security_token = Some(token.clone());
+        Ok(token)
+    }
+}
+
+/// Sends the requests of a [`StudIpClient`] through it, so that they can be recorded (see the `record` feature) \
+/// Requests of html pages fail with a clear error (see [`StudIpClient::require_session()`]), if the client is authenticated by a token.
+pub(crate) trait SendThrough {
+    fn send_through(self, client: &StudIpClient) -> anyhow::Result<Response>;
+}
+
+impl SendThrough for RequestBuilder {
+    fn send_through(self, client: &StudIpClient) -> anyhow::Result<Response> {
+        let (http_client, request) = self.build_split();
+        let request = request?;
+        // Only the JSON:API accepts tokens, everything else is a page of the session
+        if !request.url().path().starts_with(JSONAPI_PATH) {
+            client.require_session()?;
+        }
+        #[cfg(feature = "record")]
+        if client.recorder.lock().unwrap().is_some() {
+            return Ok(client.send_recorded(&http_client, request)?);
+        }
+        Ok(http_client.execute(request)?)
+    }
 }
 
 macro_rules! impl_client_wrap {
@@ -225,17 +873,161 @@ macro_rules! impl_client_wrap {
             $(
                 pub fn $method(&self, url: impl reqwest::IntoUrl) -> reqwest::blocking::RequestBuilder {
                     self.before_request();
-                    let mut url : Url = url.into_url().unwrap();
-                    url.set_host(Some(self.host)).unwrap();
-                    #[cfg(feature = "verbose")]
-                    {
-                        println!("{}: {}", stringify!($method), url.as_str());
-                    }
-                    self.client.$method(url)
+                    let url = self.resolve_url(url.into_url().unwrap());
+                    logging::log_http!(debug, "{}: {}", stringify!($method), url.as_str());
+                    self.authorize(self.client.$method(url))
                 }
             )+
         }
     };
 }
 
-impl_client_wrap!(get, post, put, patch, delete, head);
\ No newline at end of file
+impl_client_wrap!(get, post, put, patch, delete, head);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::StudIpError;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_verify_login() {
+        let server = MockServer::start();
+        server.route("POST", "/Shibboleth.sso/SAML2/POST", 200, include_str!("../testdata/pages/profile_completion.html"))
+            .route("GET", "/login", 200, "<html><head><title>Anmeldung - Stud.IP</title></head><body><form><input type=\"password\" name=\"password\"></form></body></html>")
+            .route("GET", "/dispatch.php/start", 200, "<html><head><title>Start - Stud.IP</title></head><body></body></html>");
+        let client = server.client();
+        let response = client.post(SAML_RESPONSE_URL).send_through(&client).unwrap();
+        let error = verify_login(response).unwrap_err();
+        match error.downcast_ref::<StudIpError>() {
+            Some(StudIpError::ProfileCompletionRequired { url }) => assert!(url.ends_with("/dispatch.php/settings/details"), "{}", url),
+            other => panic!("Unexpected error {:?}", other),
+        }
+        let response = client.get("https://studip.example.com/login").send_through(&client).unwrap();
+        let error = verify_login(response).unwrap_err();
+        assert!(error.to_string().contains("still on login page \"Anmeldung - Stud.IP\""), "{}", error);
+        let response = client.get("https://studip.example.com/dispatch.php/start").send_through(&client).unwrap();
+        verify_login(response).unwrap();
+    }
+
+    #[test]
+    fn test_absolutize() {
+        let client = StudIpClientBuilder::new("studip.example.com").build(AuthMethod::Session).unwrap();
+        let absolutize = |url_or_path| client.absolutize(url_or_path).unwrap().to_string();
+        assert_eq!(absolutize("/assets/images/icons/blue/file-pdf.svg"), "https://studip.example.com/assets/images/icons/blue/file-pdf.svg");
+        assert_eq!(absolutize("pictures/user/nobody_normal.webp"), "https://studip.example.com/pictures/user/nobody_normal.webp");
+        assert_eq!(absolutize("//cdn.example.com/avatar.png"), "https://cdn.example.com/avatar.png");
+        assert_eq!(absolutize(" https://other.example.com/avatar.png?v=2 "), "https://other.example.com/avatar.png?v=2");
+        assert!(StudIpClient::default().absolutize("/avatar.png").is_err());
+        // The shortcut for plain paths yields the same urls
+        for url_or_path in ["/pictures/user/0123_small.png?d=1", "/a/../b.png", "/with space.png", "pictures/x.png", "//cdn.example.com/a.png"] {
+            assert_eq!(client.absolutize_to_string(url_or_path).unwrap(), absolutize(url_or_path), "{}", url_or_path);
+        }
+        assert!(StudIpClient::default().absolutize_to_string("/avatar.png").is_err());
+    }
+
+    struct UnreachableIdP;
+
+    impl IdentityProvider for UnreachableIdP {
+        fn login(_client: &Client, _url: impl reqwest::IntoUrl + Clone, _username: &str, _password: &str) -> anyhow::Result<SAMLAssertionData> {
+            bail!("The IdP should not be reached")
+        }
+
+        fn entity_url() -> &'static str {
+            "https://idp.example.com/idp/shibboleth"
+        }
+    }
+
+    #[test]
+    fn test_redirects() {
+        let creds = Credentials::new("user", "password");
+        let server = MockServer::start();
+        server.route_redirect("GET", "/Shibboleth.sso/Login", 302, "/idp/profile/SAML2/Redirect/SSO?SAMLRequest=1")
+            .route_redirect("GET", "/idp/profile/SAML2/Redirect/SSO", 302, "/Shibboleth.sso/Login?again=yes");
+        let error = server.client_builder().login::<UnreachableIdP>(&creds).err().unwrap();
+        match error.downcast_ref::<StudIpError>() {
+            Some(StudIpError::SsoLoop { chain }) => {
+                let paths = chain.iter().map(|url| Url::parse(url).unwrap().path().to_string()).collect::<Vec<_>>();
+                assert_eq!(paths, [
+                    "/Shibboleth.sso/Login", "/idp/profile/SAML2/Redirect/SSO",
+                    "/Shibboleth.sso/Login", "/idp/profile/SAML2/Redirect/SSO",
+                ]);
+            },
+            other => panic!("Unexpected error {:?}", other),
+        }
+        assert!(error.to_string().contains("entity url"), "{}", error);
+
+        let server = MockServer::start();
+        server.route_redirect("GET", "/Shibboleth.sso/Login", 302, "/one")
+            .route_redirect("GET", "/one", 302, "/two")
+            .route_redirect("GET", "/two", 302, "/three")
+            .route("GET", "/three", 200, "");
+        let error = server.client_builder().max_redirects(1).login::<UnreachableIdP>(&creds).err().unwrap();
+        assert!(matches!(error.downcast_ref::<StudIpError>(), Some(StudIpError::TooManyRedirects { chain }) if chain.len() == 3), "{:?}", error);
+        assert!(error.to_string().contains("/one -> "), "{}", error);
+        // With the default limit, the IdP is reached
+        let error = server.client_builder().login::<UnreachableIdP>(&creds).err().unwrap();
+        assert_eq!(error.to_string(), "The IdP should not be reached");
+    }
+
+    #[derive(Debug)]
+    struct UniversityAMeetings;
+    #[derive(Debug)]
+    struct UniversityBMeetings;
+
+    impl CourseModule for UniversityAMeetings {
+        fn new(_data: Arc<course_modules::CourseModuleData>) -> Self { Self }
+        fn name() -> &'static str { "meetings" }
+        fn as_any(&mut self) -> &mut dyn std::any::Any { self }
+    }
+
+    impl CourseModule for UniversityBMeetings {
+        fn new(_data: Arc<course_modules::CourseModuleData>) -> Self { Self }
+        fn name() -> &'static str { "meetings" }
+        fn as_any(&mut self) -> &mut dyn std::any::Any { self }
+    }
+
+    #[test]
+    fn test_instances_for_different_hosts() {
+        let servers = [MockServer::start(), MockServer::start()];
+        for (server, name) in servers.iter().zip(["a", "b"]) {
+            server.route_with_headers("GET", "/dispatch.php/start", 200, &[("Set-Cookie", &format!("Seminar_Session=session_{}; path=/", name))],
+                    format!(r#"<form><input type="hidden" name="security_token" value="token_{}"></form>"#, name))
+                .route("GET", "/seminar_main.php", 200, r#"<ul id="tabs"><li id="nav_course_meetings"><a href="/plugins.php/meetingplugin/index">Meetings</a></li></ul>"#);
+        }
+        // The hosts are resolved to the same machine, but are different for the clients
+        let hosts = ["127.0.0.1", "localhost"];
+        let instances = servers.iter().zip(hosts).map(|(server, host)| {
+            let client = StudIpClientBuilder::new(host.to_string())
+                .origin(Url::parse(&server.url("")).unwrap())
+                .build(AuthMethod::Session)
+                .unwrap();
+            StudIp::from_client(client)
+        }).collect::<Vec<_>>();
+        // Both register a different module under the same name
+        instances[0].register_course_module::<UniversityAMeetings>();
+        instances[1].register_course_module::<UniversityBMeetings>();
+
+        let results = std::thread::scope(|scope| {
+            let handles = instances.iter().map(|stud_ip| stud_ip.client.clone()).map(|client| scope.spawn(move || {
+                let mut course = course::Course::from_parts("c1".into(), "Course".to_string(), "".to_string(), client.clone());
+                let mut tokens = vec![];
+                for _ in 0..3 {
+                    course.query_modules().unwrap();
+                    tokens.push(client.security_token().unwrap());
+                }
+                let is_a = get_module!(course, UniversityAMeetings).is_some();
+                let is_b = get_module!(course, UniversityBMeetings).is_some();
+                (tokens, is_a, is_b, client.session_cookie().unwrap().value)
+            })).collect::<Vec<_>>();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<_>>()
+        });
+        assert_eq!(results[0], (vec!["token_a".to_string(); 3], true, false, "session_a".to_string()));
+        assert_eq!(results[1], (vec!["token_b".to_string(); 3], false, true, "session_b".to_string()));
+        for (server, host) in servers.iter().zip(hosts) {
+            let requests = server.requests();
+            assert_eq!(requests.len(), 4);
+            assert!(requests.iter().all(|request| request.header("Host").is_some_and(|header| header.starts_with(host))));
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! The targets, under which the `verbose` feature logs through the [`log`](https://docs.rs/log) crate \
+//! A logger can filter them separately, e.g. `RUST_LOG=stud_ip_scraper::parse=debug` with `env_logger`.
+//! Without the feature, nothing is logged.
+
+/// Decisions made while parsing pages, like skipped rows, fallback values and unknown labels
+pub const PARSE: &str = "stud_ip_scraper::parse";
+/// The requests sent by the client
+pub const HTTP: &str = "stud_ip_scraper::http";
+/// Logging in and the authentication of requests
+pub const AUTH: &str = "stud_ip_scraper::auth";
+
+// Logs to the `target` with the `verbose` feature, otherwise the arguments are only type checked
+macro_rules! log_to {
+    ($target:expr, $level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "verbose")]
+        log::$level!(target: $target, $($arg)+);
+        #[cfg(not(feature = "verbose"))]
+        if false {
+            let _ = format!($($arg)+);
+        }
+    }};
+}
+
+macro_rules! log_parse {
+    ($level:ident, $($arg:tt)+) => { $crate::logging::log_to!($crate::logging::PARSE, $level, $($arg)+) };
+}
+
+macro_rules! log_http {
+    ($level:ident, $($arg:tt)+) => { $crate::logging::log_to!($crate::logging::HTTP, $level, $($arg)+) };
+}
+
+macro_rules! log_auth {
+    ($level:ident, $($arg:tt)+) => { $crate::logging::log_to!($crate::logging::AUTH, $level, $($arg)+) };
+}
+
+pub(crate) use {log_auth, log_http, log_parse, log_to};
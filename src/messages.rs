@@ -0,0 +1,828 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::{bail, Context};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use itertools::Itertools;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use scraper::{ElementRef, Html};
+use serde::{Deserialize, Serialize};
+use crate::ref_source::ReferenceSource;
+use crate::{SendThrough, StudIpClient};
+use crate::user::{parse_simple_user, User};
+use crate::course_modules::file::stream_file_by_id;
+use crate::search::quicksearch_by_name;
+use crate::flash;
+use crate::util::{expect_one, local_to_utc, parse_localized_date_time, parse_page, parse_security_token, parse_size, selector};
+
+const MESSAGES_INBOX_URL: &str = "https://studip.example.com/dispatch.php/messages/overview";
+const MESSAGES_OUTBOX_URL: &str = "https://studip.example.com/dispatch.php/messages/sent";
+const MESSAGE_READ_URL: &str = "https://studip.example.com/dispatch.php/messages/read";
+const MESSAGE_WRITE_URL: &str = "https://studip.example.com/dispatch.php/messages/write";
+const MESSAGE_SEND_URL: &str = "https://studip.example.com/dispatch.php/messages/send";
+const MESSAGE_UPLOAD_URL: &str = "https://studip.example.com/dispatch.php/messages/upload_attachment";
+const MESSAGE_TAG_URL: &str = "https://studip.example.com/dispatch.php/messages/tag";
+const MESSAGES_BULK_URL: &str = "https://studip.example.com/dispatch.php/messages/bulk";
+const MESSAGES_TRASH_URL: &str = "https://studip.example.com/dispatch.php/messages/trash";
+const MESSAGES_PURGE_URL: &str = "https://studip.example.com/dispatch.php/messages/purge";
+/// The number of messages, that are displayed on a single page
+const MESSAGES_PER_PAGE: usize = 50;
+
+static QUICKSEARCH_NAME_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"quicksearch/response/(?P<name>[0-9a-zA-Z_]+)").unwrap());
+
+/// A message in the inbox of the current user \
+/// Only contains the data shown in the overview, use the `id` to query the full message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageSummary {
+    pub id: String,
+    pub subject: String,
+    pub sender: User,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub unread: bool,
+    pub has_attachments: bool,
+}
+
+impl PartialEq for MessageSummary {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+/// A message in the outbox of the current user \
+/// Only contains the data shown in the overview, use the `id` to query the full message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentMessageSummary {
+    pub id: String,
+    pub subject: String,
+    pub recipients: Vec<User>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub has_attachments: bool,
+}
+
+impl PartialEq for SentMessageSummary {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+/// A file attached to a [`Message`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageAttachment {
+    pub name: String,
+    /// The size in bytes, if it is displayed
+    pub size: Option<u64>,
+    pub file_id: String,
+}
+
+/// A full message, including its content \
+/// Can be queried with [`Messages::read()`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub id: String,
+    pub subject: String,
+    pub sender: User,
+    pub recipients: Vec<User>,
+    pub sent_at: Option<DateTime<Utc>>,
+    /// The raw html content of the message
+    pub html_body: String,
+    pub attachments: Vec<MessageAttachment>,
+    pub tags: Vec<String>,
+}
+
+impl PartialEq for Message {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+/// Additional options for [`Messages::send()`]
+#[derive(Debug, Clone, Default)]
+pub struct SendOptions {
+    /// Files, that are attached to the message
+    pub attach: Vec<PathBuf>,
+    /// If set, the recipients can not see each other
+    pub blind_copy: bool,
+}
+
+/// Enables operating on the internal messages of the current user \
+/// Can be obtained with [`StudIp::messages()`](crate::StudIp::messages())
+#[derive(Debug, Clone)]
+pub struct Messages {
+    client: Arc<StudIpClient>,
+}
+
+impl Messages {
+
+    pub(crate) fn from_client(client: Arc<StudIpClient>) -> Self {
+        Self { client }
+    }
+
+    fn get_overview_page(&self, url: &str, page: usize) -> anyhow::Result<Html> {
+        let offset = (page * MESSAGES_PER_PAGE).to_string();
+        let limit = MESSAGES_PER_PAGE.to_string();
+        let response = self.client.get(url)
+            .query(&[("offset", offset.as_str()), ("limit", limit.as_str())])
+            .send_through(&self.client)?;
+        parse_page(&response.text()?)
+    }
+
+    /// Returns the messages of the inbox on the given `page`, starting at 0 with the newest messages. \
+    /// An empty vector is returned, if the page does not exist.
+    pub fn inbox(&self, page: usize) -> anyhow::Result<Vec<MessageSummary>> {
+        let html = self.get_overview_page(MESSAGES_INBOX_URL, page)?;
+        parse_inbox(&html, self.client.timezone())
+    }
+
+    /// Returns the messages of the outbox on the given `page`, starting at 0 with the newest messages. \
+    /// An empty vector is returned, if the page does not exist.
+    pub fn outbox(&self, page: usize) -> anyhow::Result<Vec<SentMessageSummary>> {
+        let html = self.get_overview_page(MESSAGES_OUTBOX_URL, page)?;
+        parse_outbox(&html, self.client.timezone())
+    }
+
+    /// Returns the deleted messages of the trash on the given `page`, starting at 0 with the newest messages. \
+    /// An empty vector is returned, if the page does not exist.
+    pub fn trash(&self, page: usize) -> anyhow::Result<Vec<MessageSummary>> {
+        let html = self.get_overview_page(MESSAGES_TRASH_URL, page)?;
+        parse_inbox(&html, self.client.timezone())
+    }
+
+    /// Queries the full [`Message`] with the given id \
+    /// Stud.IP marks a message as read, when it is shown. Unless `mark_read` is set, it is marked as unread again afterwards.
+    pub fn read(&self, id: &str, mark_read: bool) -> anyhow::Result<Message> {
+        let message = self.fetch_message(id)?;
+        if !mark_read {
+            self.mark_unread(id)?;
+        }
+        Ok(message)
+    }
+
+    /// Queries the full [`Message`] of a [`MessageSummary`] \
+    /// If `mark_read` is set, the message is marked as read and so is the summary, otherwise the message keeps its state.
+    pub fn read_summary(&self, summary: &mut MessageSummary, mark_read: bool) -> anyhow::Result<Message> {
+        let message = self.fetch_message(&summary.id)?;
+        if mark_read {
+            summary.unread = false;
+        } else if summary.unread {
+            self.mark_unread(&summary.id)?;
+        }
+        Ok(message)
+    }
+
+    // Shows the message, which marks it as read
+    fn fetch_message(&self, id: &str) -> anyhow::Result<Message> {
+        let response = self.client.get(format!("{}/{}", MESSAGE_READ_URL, id))
+            .header("X-Requested-With", "XMLHttpRequest")
+            .send_through(&self.client)?;
+        let html = Html::parse_fragment(&response.text()?);
+        parse_message(&html, id, self.client.timezone())
+    }
+
+    /// Downloads a [`MessageAttachment`] and returns its bytes \
+    /// Large attachments should be streamed with [`Messages::download_attachment_to_writer()`] instead.
+    pub fn download_attachment(&self, attachment: &MessageAttachment) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        self.download_attachment_to_writer(attachment, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Streams a [`MessageAttachment`] into the `writer` and returns the number of written bytes
+    pub fn download_attachment_to_writer(&self, attachment: &MessageAttachment, writer: &mut dyn Write) -> anyhow::Result<u64> {
+        stream_file_by_id(&self.client, &attachment.file_id, &attachment.name, writer)
+    }
+
+    /// Sends a message to the users with the given usernames and returns the id of the sent message. \
+    /// All recipients are resolved before anything is sent. If any of them can not be found, an error listing all unknown usernames is returned.
+    pub fn send(&self, to: &[&str], subject: &str, body: &str, options: SendOptions) -> anyhow::Result<String> {
+        if to.is_empty() {
+            bail!("Expected at least one recipient");
+        }
+        let response = self.client.get(MESSAGE_WRITE_URL).send_through(&self.client)?;
+        let form = parse_write_form(&parse_page(&response.text()?)?)?;
+
+        let mut user_ids = vec![];
+        let mut unknown = vec![];
+        for username in to {
+            let hits = quicksearch_by_name(&self.client, &form.adressee_search_name, username)?;
+            match match_recipient(hits, username) {
+                Some(user_id) => user_ids.push(user_id),
+                None => unknown.push(*username),
+            }
+        }
+        if !unknown.is_empty() {
+            bail!("Unknown recipients: {}", unknown.join(", "));
+        }
+
+        // Attachments are uploaded into a temporary folder belonging to the message id of the form
+        for path in &options.attach {
+            let file_part = reqwest::blocking::multipart::Part::file(path)
+                .with_context(|| format!("Could not read attachment {}", path.display()))?;
+            let multipart = reqwest::blocking::multipart::Form::new()
+                .text("security_token", form.security_token.clone())
+                .text("message_id", form.message_id.clone())
+                .part("file", file_part);
+            let response = self.client.post(MESSAGE_UPLOAD_URL)
+                .header("X-Requested-With", "XMLHttpRequest")
+                .multipart(multipart)
+                .send_through(&self.client)?;
+            if !response.status().is_success() {
+                bail!("Could not upload attachment {}. Status Code: {}", path.display(), response.status());
+            }
+        }
+
+        let mut params = vec![
+            ("security_token", form.security_token.clone()),
+            ("message_id", form.message_id.clone()),
+            ("message_subject", subject.to_string()),
+            ("message_body", body.to_string()),
+            ("show_adressees", if options.blind_copy { "0" } else { "1" }.to_string()),
+        ];
+        params.extend(user_ids.into_iter().map(|user_id| ("message_to[]", user_id)));
+        let response = self.client.post(MESSAGE_SEND_URL)
+            .form(&params)
+            .send_through(&self.client)?;
+        if !response.status().is_success() {
+            bail!("Could not send message. Status Code: {}", response.status());
+        }
+        flash::check(&parse_page(&response.text()?)?)?
+            .context("Expected message sent confirmation")?;
+        Ok(form.message_id)
+    }
+
+    /// Returns all tags, that are used by messages of the current user
+    pub fn tags(&self) -> anyhow::Result<Vec<String>> {
+        let html = self.get_overview_page(MESSAGES_INBOX_URL, 0)?;
+        Ok(parse_tags(&html))
+    }
+
+    /// Replaces the tags of the message with the given id \
+    /// *Note: The message is read to determine its current tags, which marks it as read on the server*
+    pub fn set_tags(&self, message_id: &str, tags: &[&str]) -> anyhow::Result<()> {
+        let current_tags = self.fetch_message(message_id)?.tags;
+        let security_token = self.client.security_token()?;
+        let url = format!("{}/{}", MESSAGE_TAG_URL, message_id);
+        // Stud.IP stores tags in lowercase
+        let tags = tags.iter().map(|tag| tag.trim().to_lowercase()).unique().collect::<Vec<_>>();
+        let to_remove = current_tags.iter().filter(|tag| !tags.contains(tag));
+        let to_add = tags.iter().filter(|tag| !current_tags.contains(tag));
+        for (action, tag) in to_remove.map(|tag| ("remove_tag", tag)).chain(to_add.map(|tag| ("add_tag", tag))) {
+            let response = self.client.post(&url)
+                .form(&[("security_token", security_token.as_str()), (action, tag.as_str())])
+                .send_through(&self.client)?;
+            if !response.status().is_success() {
+                bail!("Could not {} \"{}\". Status Code: {}", action.replace('_', " "), tag, response.status());
+            }
+        }
+        let new_tags = self.fetch_message(message_id)?.tags;
+        if new_tags.len() != tags.len() || tags.iter().any(|tag| !new_tags.contains(tag)) {
+            bail!("Tags of message {} were not updated. Expected {:?} got {:?}", message_id, tags, new_tags);
+        }
+        Ok(())
+    }
+
+    /// Adds a tag to all messages with the given ids, using a single batch action
+    pub fn add_tag_many(&self, message_ids: &[&str], tag: &str) -> anyhow::Result<()> {
+        self.bulk_action(message_ids, ("add_tag", tag))?;
+        let tag = tag.trim().to_lowercase();
+        if !self.tags()?.contains(&tag) {
+            bail!("Tag \"{}\" was not added", tag);
+        }
+        Ok(())
+    }
+
+    /// Deletes the message with the given id, which moves it to the trash
+    pub fn delete(&self, message_id: &str) -> anyhow::Result<()> {
+        self.delete_many(&[message_id])
+    }
+
+    /// Deletes all messages with the given ids using a single batch action, which moves them to the trash \
+    /// Afterwards every page of the inbox and the outbox is checked, as the messages could have been listed on any of them.
+    pub fn delete_many(&self, message_ids: &[&str]) -> anyhow::Result<()> {
+        if message_ids.is_empty() {
+            return Ok(());
+        }
+        self.bulk_action(message_ids, ("delete", "1"))?;
+        // The deleted messages must no longer be listed
+        let remaining_ids = self.listed_ids(|page| Ok(self.inbox(page)?.into_iter().map(|message| message.id).collect()))?.into_iter()
+            .chain(self.listed_ids(|page| Ok(self.outbox(page)?.into_iter().map(|message| message.id).collect()))?)
+            .filter(|id| message_ids.contains(&id.as_str()))
+            .collect::<Vec<_>>();
+        if !remaining_ids.is_empty() {
+            bail!("Messages were not deleted: {}", remaining_ids.join(", "));
+        }
+        Ok(())
+    }
+
+    // Collects the ids of the messages on all pages of an overview, which ends at the first page without new messages
+    fn listed_ids(&self, page_ids: impl Fn(usize) -> anyhow::Result<Vec<String>>) -> anyhow::Result<Vec<String>> {
+        let mut ids = vec![];
+        let mut seen = HashSet::new();
+        for page in 0.. {
+            let new_ids = page_ids(page)?.into_iter()
+                .filter(|id| seen.insert(id.clone()))
+                .collect::<Vec<_>>();
+            if new_ids.is_empty() {
+                break;
+            }
+            ids.extend(new_ids);
+        }
+        Ok(ids)
+    }
+
+    /// Permanently deletes all messages in the trash
+    pub fn empty_trash(&self) -> anyhow::Result<()> {
+        // Opens the confirmation dialog and then confirms it with the dialog's security token
+        let response = self.client.get(MESSAGES_PURGE_URL)
+            .header("X-Requested-With", "XMLHttpRequest")
+            .send_through(&self.client)?;
+        let security_token = parse_security_token(&parse_page(&response.text()?)?)?;
+        let response = self.client.post(MESSAGES_PURGE_URL)
+            .form(&[("security_token", security_token.as_str()), ("yes", "1")])
+            .send_through(&self.client)?;
+        if !response.status().is_success() {
+            bail!("Could not empty trash. Status Code: {}", response.status());
+        }
+        flash::check(&parse_page(&response.text()?)?)?;
+        if !self.trash(0)?.is_empty() {
+            bail!("Trash was not emptied");
+        }
+        Ok(())
+    }
+
+    // Submits the checkbox form of the overview for the given messages
+    fn bulk_action(&self, message_ids: &[&str], action: (&str, &str)) -> anyhow::Result<()> {
+        if message_ids.is_empty() {
+            return Ok(());
+        }
+        let security_token = self.client.security_token()?;
+        let mut params = vec![("security_token", security_token.as_str()), action];
+        params.extend(message_ids.iter().map(|id| ("bulk[]", *id)));
+        let response = self.client.post(MESSAGES_BULK_URL)
+            .form(&params)
+            .send_through(&self.client)?;
+        if !response.status().is_success() {
+            bail!("Could not perform batch action {}. Status Code: {}", action.0, response.status());
+        }
+        flash::check(&parse_page(&response.text()?)?)?;
+        Ok(())
+    }
+
+    /// Marks the message with the given id as read, without reading it
+    pub fn mark_read(&self, message_id: &str) -> anyhow::Result<()> {
+        self.bulk_action(&[message_id], ("read", "1"))
+    }
+
+    /// Marks the message with the given id as unread
+    pub fn mark_unread(&self, message_id: &str) -> anyhow::Result<()> {
+        self.bulk_action(&[message_id], ("unread", "1"))
+    }
+
+    /// Marks the messages of the summaries as read using a single batch action and updates their `unread` flags
+    pub fn mark_summaries_read(&self, summaries: &mut [MessageSummary]) -> anyhow::Result<()> {
+        let message_ids = summaries.iter()
+            .filter(|summary| summary.unread)
+            .map(|summary| summary.id.as_str())
+            .collect::<Vec<_>>();
+        self.bulk_action(&message_ids, ("read", "1"))?;
+        for summary in summaries {
+            summary.unread = false;
+        }
+        Ok(())
+    }
+
+    /// Marks all unread messages of the inbox as read using a single batch action and returns how many were marked \
+    /// *Note: This requests every page of the inbox, to find the unread messages*
+    pub fn mark_all_read(&self) -> anyhow::Result<usize> {
+        let mut unread = vec![];
+        for page in 0.. {
+            let messages = self.inbox(page)?;
+            let n_messages = messages.len();
+            unread.extend(messages.into_iter().filter(|message| message.unread));
+            if n_messages < MESSAGES_PER_PAGE {
+                break;
+            }
+        }
+        self.mark_summaries_read(&mut unread)?;
+        let remaining = self.unread_count()?;
+        if remaining != 0 {
+            bail!("{} messages are still unread", remaining);
+        }
+        Ok(unread.len())
+    }
+
+    /// Returns the number of unread messages, as displayed in the header
+    pub fn unread_count(&self) -> anyhow::Result<usize> {
+        let response = self.client.get(MESSAGES_INBOX_URL)
+            .query(&[("limit", "1")])
+            .send_through(&self.client)?;
+        parse_unread_count(&parse_page(&response.text()?)?)
+    }
+
+}
+
+// The sender of system messages, which do not link to a profile
+fn system_user() -> User {
+    User {
+        display_name: "Stud.IP".to_string(),
+        username: "".to_string(),
+        avatar_src: None,
+        source: ReferenceSource::Unspecified,
+        user_id: None,
+    }
+}
+
+// Parses the common columns of a message row (id, subject, date and attachment indicator)
+fn parse_message_row(row: ElementRef, timezone: Tz) -> anyhow::Result<(String, String, Option<DateTime<Utc>>, bool)> {
+    let subject_selector = selector!("a[href*=\"messages/read/\"]");
+    let attachment_selector = selector!(".icon-shape-staple, .icon-shape-paperclip");
+    let cell_selector = selector!("td");
+    let id = row.attr("id")
+        .and_then(|id| id.strip_prefix("message_"))
+        .context("Expected message id")?
+        .to_string();
+    let subject = expect_one(row, subject_selector, "message subject")?
+        .text()
+        .collect::<String>()
+        .trim()
+        .to_string();
+    // The date is either in the title of its cell or its text
+    let sent_at = row.select(cell_selector)
+        .flat_map(|cell| [cell.attr("title").map(|title| title.to_string()), Some(cell.text().collect::<String>())])
+        .flatten()
+        .filter(|text| text.contains('.') || text.contains(':'))
+        .find_map(|text| parse_localized_date_time(&text))
+        .and_then(|date_time| local_to_utc(date_time, timezone));
+    let has_attachments = row.select(attachment_selector).next().is_some();
+    Ok((id, subject, sent_at, has_attachments))
+}
+
+fn parse_inbox(html: &Html, timezone: Tz) -> anyhow::Result<Vec<MessageSummary>> {
+    let row_selector = selector!("#messages tbody tr[id^=\"message_\"]");
+    let user_selector = selector!("a[href*=\"username=\"]");
+    let mut messages = vec![];
+    for row in html.select(row_selector) {
+        let (id, subject, sent_at, has_attachments) = parse_message_row(row, timezone)?;
+        // System messages do not have a sender linking to a profile
+        let sender = match row.select(user_selector).next() {
+            Some(user_link) => parse_simple_user(user_link)?,
+            None => system_user(),
+        };
+        messages.push(MessageSummary {
+            id,
+            subject,
+            sender,
+            sent_at,
+            unread: row.value().classes().any(|class| class == "unread"),
+            has_attachments,
+        });
+    }
+    Ok(messages)
+}
+
+fn parse_outbox(html: &Html, timezone: Tz) -> anyhow::Result<Vec<SentMessageSummary>> {
+    let row_selector = selector!("#messages tbody tr[id^=\"message_\"]");
+    let user_selector = selector!("a[href*=\"username=\"]");
+    let mut messages = vec![];
+    for row in html.select(row_selector) {
+        let (id, subject, sent_at, has_attachments) = parse_message_row(row, timezone)?;
+        let recipients = row.select(user_selector)
+            .map(parse_simple_user)
+            .collect::<Result<_, _>>()?;
+        messages.push(SentMessageSummary {
+            id,
+            subject,
+            recipients,
+            sent_at,
+            has_attachments,
+        });
+    }
+    Ok(messages)
+}
+
+fn parse_message(html: &Html, id: &str, timezone: Tz) -> anyhow::Result<Message> {
+    let header_row_selector = selector!("table tr");
+    let cell_selector = selector!("td, th");
+    let user_selector = selector!("a[href*=\"username=\"]");
+    let tag_selector = selector!("a[href*=\"tag=\"]");
+    let body_selector = selector!(".formatted-content, .message_body");
+    let attachment_selector = selector!(".message_attachments li, ul.attachments li");
+    let attachment_link_selector = selector!("a[href*=\"file_id=\"]");
+    let mut subject = None;
+    let mut sender = None;
+    let mut recipients = vec![];
+    let mut sent_at = None;
+    let mut tags = vec![];
+    // The header is a table with a localized key in the first and the value in the second cell
+    for row in html.select(header_row_selector) {
+        let Some((key_cell, value_cell)) = row.select(cell_selector).collect_tuple() else {continue};
+        // The labels are matched exactly, as e.g. "Anhänge" and "Antworten" start like "An"
+        let key = key_cell.text().collect::<String>().trim().trim_end_matches(':').trim_end().to_lowercase();
+        let value = value_cell.text().collect::<String>().trim().to_string();
+        match key.as_str() {
+            "von" | "from" | "absender" | "sender" => {
+                sender = value_cell.select(user_selector).next().map(parse_simple_user).transpose()?;
+            },
+            "an" | "to" | "empfänger" | "recipients" => {
+                recipients = value_cell.select(user_selector).map(parse_simple_user).collect::<Result<_, _>>()?;
+            },
+            "datum" | "date" => sent_at = parse_localized_date_time(&value).and_then(|date_time| local_to_utc(date_time, timezone)),
+            "betreff" | "subject" => subject = Some(value),
+            "schlagworte" | "tags" => {
+                tags = value_cell.select(tag_selector)
+                    .map(|tag| tag.text().collect::<String>().trim().to_string())
+                    .collect();
+            },
+            _ => {},
+        }
+    }
+    let html_body = expect_one(html.root_element(), body_selector, "message body")?
+        .inner_html();
+    let mut attachments = vec![];
+    for attachment_elem in html.select(attachment_selector) {
+        let Some(link) = attachment_elem.select(attachment_link_selector).next() else {continue};
+        let url = url::Url::parse(link.attr("href").unwrap())?;
+        let file_id = url.query_pairs()
+            .find_map(|(key, value)| (key == "file_id").then(|| value.to_string()))
+            .context("Expected attachment file id")?;
+        attachments.push(MessageAttachment {
+            name: link.text().collect::<String>().trim().to_string(),
+            size: parse_size(&attachment_elem.text().collect::<String>()),
+            file_id,
+        });
+    }
+    Ok(Message {
+        id: id.to_string(),
+        subject: subject.context("Expected message subject")?,
+        // System messages do not have a sender linking to a profile
+        sender: sender.unwrap_or_else(system_user),
+        recipients,
+        sent_at,
+        html_body,
+        attachments,
+        tags,
+    })
+}
+
+// The tags are listed as filters in the sidebar
+fn parse_tags(html: &Html) -> Vec<String> {
+    let tag_selector = selector!(".sidebar a[href*=\"tag=\"]");
+    html.select(tag_selector)
+        .map(|tag| tag.text().collect::<String>().trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .unique()
+        .collect()
+}
+
+// The hidden fields and the adressee quicksearch of the write form
+struct WriteForm {
+    security_token: String,
+    message_id: String,
+    adressee_search_name: String,
+}
+
+fn parse_write_form(html: &Html) -> anyhow::Result<WriteForm> {
+    let message_id_selector = selector!("input[name=\"message_id\"]");
+    let script_selector = selector!("script, input[data-qs_name]");
+    let message_id = html.select(message_id_selector)
+        .find_map(|elem| elem.attr("value"))
+        .context("Expected message id")?
+        .to_string();
+    // The quicksearch is registered under a name, that is either an attribute or part of its autocomplete url
+    let adressee_search_name = html.select(script_selector)
+        .find_map(|elem| match elem.attr("data-qs_name") {
+            Some(name) => Some(name.to_string()),
+            None => QUICKSEARCH_NAME_REGEX.captures(&elem.inner_html()).map(|captures| captures["name"].to_string()),
+        })
+        .context("Expected adressee quicksearch")?;
+    Ok(WriteForm {
+        security_token: parse_security_token(html)?,
+        message_id,
+        adressee_search_name,
+    })
+}
+
+// The adressee search returns user ids, with the username in parentheses after the name (e.g. "John Doe (jdoe)")
+fn match_recipient(hits: Vec<(String, String)>, username: &str) -> Option<String> {
+    let suffix = format!("({})", username);
+    hits.into_iter()
+        .find(|(_, name)| name.trim_end().ends_with(&suffix))
+        .map(|(user_id, _)| user_id)
+}
+
+// The badge is not rendered at all, if there are no unread messages
+fn parse_unread_count(html: &Html) -> anyhow::Result<usize> {
+    let badge_selector = selector!("a[href*=\"messages/overview\"][data-badge]");
+    let Some(badge) = html.select(badge_selector).next() else {
+        return Ok(0);
+    };
+    let badge_text = badge.attr("data-badge").unwrap().trim();
+    if badge_text.is_empty() {
+        return Ok(0);
+    }
+    badge_text.parse().context("Could not parse unread messages badge")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::mock::MockServer;
+    use crate::DEFAULT_TIMEZONE;
+
+    const INBOX_HTML: &str = r#"
+        <header>
+            <a href="https://studip.example.com/dispatch.php/messages/overview" data-badge="1">Messages</a>
+        </header>
+        <table id="messages">
+            <tbody>
+                <tr id="message_m1" class="unread">
+                    <td><input type="checkbox" name="bulk[]" value="m1"></td>
+                    <td><a href="https://studip.example.com/dispatch.php/messages/read/m1" data-dialog>Exam dates</a></td>
+                    <td><a href="https://studip.example.com/dispatch.php/profile?username=jdoe">John Doe</a></td>
+                    <td title="12.03.2025 14:05">Mi., 12.03.2025</td>
+                    <td><img class="icon-shape-staple"></td>
+                </tr>
+                <tr id="message_m2">
+                    <td><input type="checkbox" name="bulk[]" value="m2"></td>
+                    <td><a href="https://studip.example.com/dispatch.php/messages/read/m2" data-dialog>Welcome</a></td>
+                    <td>Stud.IP</td>
+                    <td>01.03.2025 09:00</td>
+                    <td></td>
+                </tr>
+            </tbody>
+        </table>
+    "#;
+
+    #[test]
+    fn test_parse_inbox() {
+        let html = Html::parse_document(INBOX_HTML);
+        let messages = parse_inbox(&html, DEFAULT_TIMEZONE).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].id, "m1");
+        assert_eq!(messages[0].subject, "Exam dates");
+        assert_eq!(messages[0].sender.username, "jdoe");
+        assert!(messages[0].unread);
+        assert!(messages[0].has_attachments);
+        assert_eq!(messages[0].sent_at, Some(Utc.with_ymd_and_hms(2025, 3, 12, 13, 5, 0).unwrap()));
+        assert_eq!(messages[1].sender.display_name, "Stud.IP");
+        assert!(!messages[1].unread);
+        assert!(!messages[1].has_attachments);
+        assert_eq!(parse_unread_count(&html).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        let html = Html::parse_document(r#"
+            <div class="sidebar">
+                <a href="https://studip.example.com/dispatch.php/messages/overview?tag=exams">exams</a>
+                <a href="https://studip.example.com/dispatch.php/messages/overview?tag=groups"> groups </a>
+                <a href="https://studip.example.com/dispatch.php/messages/overview?tag=exams">exams</a>
+            </div>
+        "#);
+        assert_eq!(parse_tags(&html), vec!["exams", "groups"]);
+    }
+
+    #[test]
+    fn test_mark_all_read() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/start", 200, r#"<input type="hidden" name="security_token" value="tok">"#)
+            .route("GET", "/dispatch.php/messages/overview", 200, INBOX_HTML)
+            .route("GET", "/dispatch.php/messages/overview?limit=1", 200, r#"<a href="https://studip.example.com/dispatch.php/messages/overview">Messages</a>"#)
+            .route("POST", "/dispatch.php/messages/bulk", 200, r#"<div class="messagebox messagebox_success">1 Nachricht wurde als gelesen markiert.</div>"#);
+        let messages = Messages::from_client(Arc::new(server.client()));
+        assert_eq!(messages.mark_all_read().unwrap(), 1);
+        messages.mark_read("m2").unwrap();
+
+        let bulk_bodies = server.requests().into_iter()
+            .filter(|request| request.method == "POST")
+            .map(|request| request.body)
+            .collect::<Vec<_>>();
+        assert_eq!(bulk_bodies, vec!["security_token=tok&read=1&bulk%5B%5D=m1", "security_token=tok&read=1&bulk%5B%5D=m2"]);
+    }
+
+    #[test]
+    fn test_delete_many() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/start", 200, r#"<input type="hidden" name="security_token" value="tok">"#)
+            .route("POST", "/dispatch.php/messages/bulk", 200, r#"<div class="messagebox messagebox_success">Die Nachrichten wurden gelöscht.</div>"#)
+            .route("GET", "/dispatch.php/messages/overview", 200, "")
+            .route("GET", "/dispatch.php/messages/overview?offset=0", 200, INBOX_HTML.replace("m1", "m3"))
+            .route("GET", "/dispatch.php/messages/overview?offset=50", 200, INBOX_HTML.replace("m1", "m4"))
+            .route("GET", "/dispatch.php/messages/sent", 200, "");
+        let messages = Messages::from_client(Arc::new(server.client()));
+        messages.delete_many(&["m1"]).unwrap();
+        // A message, that is still listed on a later page, was not deleted
+        let error = messages.delete_many(&["m1", "m4"]).unwrap_err();
+        assert_eq!(error.to_string(), "Messages were not deleted: m4");
+        assert!(messages.delete_many(&[]).is_ok());
+
+        let inbox_pages = server.requests().into_iter()
+            .filter(|request| request.path.starts_with("/dispatch.php/messages/overview"))
+            .count();
+        assert_eq!(inbox_pages, 2 * 3);
+    }
+
+    #[test]
+    fn test_parse_unread_count_without_badge() {
+        let html = Html::parse_document(r#"<a href="https://studip.example.com/dispatch.php/messages/overview">Messages</a>"#);
+        assert_eq!(parse_unread_count(&html).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_parse_write_form() {
+        let html = Html::parse_document(r#"
+            <form action="https://studip.example.com/dispatch.php/messages/send" method="post">
+                <input type="hidden" name="security_token" value="token=">
+                <input type="hidden" name="message_id" value="abc123">
+                <input type="text" id="user_id_1" name="user_id_1_parameter">
+                <script>STUDIP.QuickSearch.autocomplete("user_id_1", "https://studip.example.com/dispatch.php/quicksearch/response/5f2b9c", {});</script>
+            </form>
+        "#);
+        let form = parse_write_form(&html).unwrap();
+        assert_eq!(form.security_token, "token=");
+        assert_eq!(form.message_id, "abc123");
+        assert_eq!(form.adressee_search_name, "5f2b9c");
+    }
+
+    #[test]
+    fn test_match_recipient() {
+        let hits = vec![
+            ("u1".to_string(), "John Doe (jdoe2)".to_string()),
+            ("u2".to_string(), "John Doe (jdoe)".to_string()),
+        ];
+        assert_eq!(match_recipient(hits.clone(), "jdoe").as_deref(), Some("u2"));
+        assert_eq!(match_recipient(hits, "doe"), None);
+    }
+
+    #[test]
+    fn test_parse_message() {
+        let html = Html::parse_fragment(r#"
+            <table class="default">
+                <tr><td><strong>Von</strong></td><td><a href="https://studip.example.com/dispatch.php/profile?username=jdoe">John Doe</a></td></tr>
+                <tr><td><strong>An</strong></td><td>
+                    <a href="https://studip.example.com/dispatch.php/profile?username=mmu">Max Muster</a>,
+                    <a href="https://studip.example.com/dispatch.php/profile?username=emu">Erika Muster</a>
+                </td></tr>
+                <tr><td><strong>Datum</strong></td><td>12.03.2025 14:05</td></tr>
+                <tr><td><strong>Betreff</strong></td><td>Exam dates</td></tr>
+                <tr><td><strong>Schlagworte</strong></td><td>
+                    <a href="https://studip.example.com/dispatch.php/messages/overview?tag=exams">exams</a>
+                </td></tr>
+                <tr><td><strong>Anhänge:</strong></td><td>1</td></tr>
+                <tr><td><strong>Antworten</strong></td><td><a href="https://studip.example.com/dispatch.php/profile?username=jdoe">John Doe</a></td></tr>
+            </table>
+            <div class="formatted-content"><p>Hello!</p></div>
+            <ul class="message_attachments">
+                <li>
+                    <a href="https://studip.example.com/sendfile.php?type=0&file_id=f1&file_name=dates.pdf">dates.pdf</a>
+                    (12,5 KB)
+                </li>
+            </ul>
+        "#);
+        let message = parse_message(&html, "m1", DEFAULT_TIMEZONE).unwrap();
+        assert_eq!(message.subject, "Exam dates");
+        assert_eq!(message.sender.username, "jdoe");
+        assert_eq!(message.recipients.iter().map(|r| r.username.as_str()).collect::<Vec<_>>(), vec!["mmu", "emu"]);
+        assert_eq!(message.sent_at, Some(Utc.with_ymd_and_hms(2025, 3, 12, 13, 5, 0).unwrap()));
+        assert_eq!(message.html_body, "<p>Hello!</p>");
+        assert_eq!(message.tags, vec!["exams"]);
+        assert_eq!(message.attachments, vec![MessageAttachment { name: "dates.pdf".to_string(), size: Some(12800), file_id: "f1".to_string() }]);
+    }
+
+    #[test]
+    fn test_read() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/start", 200, r#"<input type="hidden" name="security_token" value="tok">"#)
+            .route("GET", "/dispatch.php/messages/read/m1", 200, r#"
+                <table class="default"><tr><td><strong>Betreff</strong></td><td>Exam dates</td></tr></table>
+                <div class="formatted-content"><p>Hello!</p></div>
+                <ul class="message_attachments"><li><a href="https://studip.example.com/sendfile.php?type=0&file_id=f1&file_name=dates.pdf">dates.pdf</a></li></ul>
+            "#)
+            .route("POST", "/dispatch.php/messages/bulk", 200, r#"<div class="messagebox messagebox_success">Die Nachricht wurde als ungelesen markiert.</div>"#)
+            .route_typed("GET", "/sendfile.php?type=0&file_id=f1", 200, "application/pdf", "%PDF");
+        let messages = Messages::from_client(Arc::new(server.client()));
+        let bulk_bodies = || server.requests().into_iter()
+            .filter(|request| request.method == "POST")
+            .map(|request| request.body)
+            .collect::<Vec<_>>();
+
+        let mut summary = MessageSummary { id: "m1".to_string(), unread: true, ..parse_inbox(&Html::parse_document(INBOX_HTML), DEFAULT_TIMEZONE).unwrap().remove(0) };
+        let message = messages.read_summary(&mut summary, false).unwrap();
+        assert_eq!(message.subject, "Exam dates");
+        // Showing the message marked it as read, so it is marked as unread again
+        assert!(summary.unread);
+        assert_eq!(bulk_bodies(), vec!["security_token=tok&unread=1&bulk%5B%5D=m1"]);
+        messages.read_summary(&mut summary, true).unwrap();
+        assert!(!summary.unread);
+        messages.read("m1", true).unwrap();
+        assert_eq!(bulk_bodies().len(), 1);
+
+        let mut writer = vec![];
+        assert_eq!(messages.download_attachment_to_writer(&message.attachments[0], &mut writer).unwrap(), 4);
+        assert_eq!(writer, b"%PDF");
+        assert_eq!(messages.download_attachment(&message.attachments[0]).unwrap(), b"%PDF");
+    }
+}
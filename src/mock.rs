@@ -0,0 +1,305 @@
+//! A minimal loopback HTTP server, that serves canned responses to a [`StudIpClient`] in tests \
+//! Available outside of the crate with the `mock` feature.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use url::Url;
+use crate::{StudIp, StudIpClient, StudIpClientBuilder};
+
+/// A request, that was received by the [`MockServer`]
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    /// The path including the query
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    pub received_at: Instant,
+}
+
+impl MockRequest {
+
+    /// Returns the value of the header with the given `name`, ignoring its case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+}
+
+#[derive(Debug, Clone)]
+struct MockRoute {
+    method: &'static str,
+    path_prefix: &'static str,
+    status: u16,
+    content_type: &'static str,
+    body: String,
+    /// Additional headers of the response, like `Location`
+    headers: Vec<(String, String)>,
+}
+
+/// The time, after which held responses are given up (see [`MockServer::hold_responses()`])
+const HOLD_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Holds responses, until a number of requests were received
+#[derive(Default)]
+struct Hold {
+    until: Mutex<usize>,
+    arrived: Condvar,
+    timed_out: AtomicBool,
+}
+
+/// Serves canned responses for routes, matched by method and path prefix (the longest prefix wins, the latest of equal ones) \
+/// Unknown routes are answered with 404. Connections are handled concurrently. The server stops, when it is dropped.
+pub struct MockServer {
+    port: u16,
+    stopped: Arc<AtomicBool>,
+    routes: Arc<Mutex<Vec<MockRoute>>>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    delay: Arc<Mutex<Duration>>,
+    hold: Arc<Hold>,
+}
+
+impl MockServer {
+
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let routes: Arc<Mutex<Vec<MockRoute>>> = Default::default();
+        let requests: Arc<Mutex<Vec<MockRequest>>> = Default::default();
+        let stopped: Arc<AtomicBool> = Default::default();
+        let delay: Arc<Mutex<Duration>> = Default::default();
+        let hold: Arc<Hold> = Default::default();
+        let (thread_stopped, thread_routes, thread_requests, thread_delay, thread_hold) = (stopped.clone(), routes.clone(), requests.clone(), delay.clone(), hold.clone());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else {break};
+                let (routes, requests, delay, hold) = (thread_routes.clone(), thread_requests.clone(), thread_delay.clone(), thread_hold.clone());
+                std::thread::spawn(move || handle_connection(stream, &routes, &requests, &delay, &hold));
+            }
+        });
+        Self { port, stopped, routes, requests, delay, hold }
+    }
+
+    /// Delays every response by the given duration, to simulate a slow server
+    pub fn delay(&self, delay: Duration) -> &Self {
+        *self.delay.lock().unwrap() = delay;
+        self
+    }
+
+    /// Holds every response, until `n_requests` requests were received, to check that requests are sent concurrently without relying on timing \
+    /// Requests, that are still held after 5 seconds, are answered with 504 (see [`MockServer::hold_timed_out()`]).
+    pub fn hold_responses(&self, n_requests: usize) -> &Self {
+        *self.hold.until.lock().unwrap() = n_requests;
+        self
+    }
+
+    /// Whether any response was given up, because not enough requests arrived while it was held (see [`MockServer::hold_responses()`])
+    pub fn hold_timed_out(&self) -> bool {
+        self.hold.timed_out.load(Ordering::SeqCst)
+    }
+
+    /// Adds a route, that answers requests with the given `method` and `path_prefix` (e.g. "/dispatch.php/start")
+    pub fn route(&self, method: &'static str, path_prefix: &'static str, status: u16, body: impl Into<String>) -> &Self {
+        self.routes.lock().unwrap().push(MockRoute {
+            method,
+            path_prefix,
+            status,
+            content_type: "text/html; charset=utf-8",
+            body: body.into(),
+            headers: vec![],
+        });
+        self
+    }
+
+    /// Adds a route like [`MockServer::route()`], that answers with the given `content_type` (e.g. for file downloads)
+    pub fn route_typed(&self, method: &'static str, path_prefix: &'static str, status: u16, content_type: &'static str, body: impl Into<String>) -> &Self {
+        self.routes.lock().unwrap().push(MockRoute {
+            method,
+            path_prefix,
+            status,
+            content_type,
+            body: body.into(),
+            headers: vec![],
+        });
+        self
+    }
+
+    /// Adds a route like [`MockServer::route()`], that answers with the additional `headers` (e.g. `ETag`)
+    pub fn route_with_headers(&self, method: &'static str, path_prefix: &'static str, status: u16, headers: &[(&str, &str)], body: impl Into<String>) -> &Self {
+        self.routes.lock().unwrap().push(MockRoute {
+            method,
+            path_prefix,
+            status,
+            content_type: "text/html; charset=utf-8",
+            body: body.into(),
+            headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+        });
+        self
+    }
+
+    /// Adds a route, that redirects to the `location` (e.g. "/idp/sso")
+    pub fn route_redirect(&self, method: &'static str, path_prefix: &'static str, status: u16, location: &str) -> &Self {
+        self.routes.lock().unwrap().push(MockRoute {
+            method,
+            path_prefix,
+            status,
+            content_type: "text/html; charset=utf-8",
+            body: String::new(),
+            headers: vec![("Location".to_string(), location.to_string())],
+        });
+        self
+    }
+
+    /// Adds a route like [`MockServer::route()`], that answers with a JSON:API content type
+    #[cfg_attr(not(feature = "jsonapi"), allow(dead_code))]
+    pub fn route_json(&self, method: &'static str, path_prefix: &'static str, status: u16, body: impl Into<String>) -> &Self {
+        self.routes.lock().unwrap().push(MockRoute {
+            method,
+            path_prefix,
+            status,
+            content_type: "application/vnd.api+json",
+            body: body.into(),
+            headers: vec![],
+        });
+        self
+    }
+
+    /// Adds routes, that answer with the exchanges of a recording (see [`StudIpClient::record_to()`]) \
+    /// Each route matches the recorded path including its (redacted) query.
+    #[cfg(feature = "record")]
+    pub fn replay(&self, dir: &std::path::Path) -> anyhow::Result<&Self> {
+        for (exchange, body) in crate::record::read_recording(dir)? {
+            let url = Url::parse(&exchange.url)?;
+            let path = match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            };
+            let content_type = exchange.headers.iter()
+                .find(|(name, _)| name == "content-type")
+                .map_or("text/html; charset=utf-8".to_string(), |(_, value)| value.clone());
+            // Routes live as long as the test, so leaking is fine here
+            self.routes.lock().unwrap().push(MockRoute {
+                method: Box::leak(exchange.method.into_boxed_str()),
+                path_prefix: Box::leak(path.into_boxed_str()),
+                status: exchange.status,
+                content_type: Box::leak(content_type.into_boxed_str()),
+                body: String::from_utf8_lossy(&body).into_owned(),
+                headers: exchange.headers.iter()
+                    .filter(|(name, _)| name == "location")
+                    .cloned()
+                    .collect(),
+            });
+        }
+        Ok(self)
+    }
+
+    /// The absolute url of the `path` on this server, for clients that do not rewrite urls
+    pub fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, path)
+    }
+
+    /// All requests received so far
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Creates a client builder, which clients send all requests to this server
+    pub fn client_builder(&self) -> StudIpClientBuilder {
+        StudIpClientBuilder::new("127.0.0.1")
+            .origin(Url::parse(&format!("http://127.0.0.1:{}", self.port)).unwrap())
+    }
+
+    /// Creates a client, that sends all requests to this server
+    pub fn client(&self) -> StudIpClient {
+        let cookies: std::sync::Arc<crate::cookies::CookieJar> = Default::default();
+        StudIpClient {
+            client: reqwest::blocking::Client::builder()
+                .cookie_provider(cookies.clone())
+                .build()
+                .unwrap(),
+            host: "127.0.0.1".to_string(),
+            origin: Some(Url::parse(&format!("http://127.0.0.1:{}", self.port)).unwrap()),
+            cookies,
+            ..Default::default()
+        }
+    }
+
+    /// Creates a [`StudIp`] instance, that sends all requests to this server
+    pub fn stud_ip(&self) -> StudIp {
+        StudIp::from_client(self.client())
+    }
+
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        // Wakes up the listener, so that it notices, that it should stop
+        self.stopped.store(true, Ordering::SeqCst);
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+    }
+}
+
+// Handles a single request, returns None if it could not be read or answered
+fn handle_connection(mut stream: TcpStream, routes: &Mutex<Vec<MockRoute>>, requests: &Mutex<Vec<MockRequest>>, delay: &Mutex<Duration>, hold: &Hold) -> Option<()> {
+    let mut reader = BufReader::new(stream.try_clone().ok()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).ok()?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {return Some(())};
+    let mut content_length = 0;
+    let mut headers = vec![];
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            if key.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+            headers.push((key.to_string(), value.trim().to_string()));
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok()?;
+    requests.lock().unwrap().push(MockRequest {
+        method: method.to_string(),
+        path: path.to_string(),
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+        received_at: Instant::now(),
+    });
+    // Notifying while holding the lock ensures, that no waiting request misses the arrival
+    let until = hold.until.lock().unwrap();
+    hold.arrived.notify_all();
+    let held_too_long = hold.arrived.wait_timeout_while(until, HOLD_TIMEOUT, |until| requests.lock().unwrap().len() < *until).unwrap().1.timed_out();
+    if held_too_long {
+        hold.timed_out.store(true, Ordering::SeqCst);
+    }
+    let route = routes.lock().unwrap().iter()
+        .filter(|route| route.method.eq_ignore_ascii_case(method) && path.starts_with(route.path_prefix))
+        .max_by_key(|route| route.path_prefix.len())
+        .cloned();
+    let (status, content_type, body, headers) = match route {
+        _ if held_too_long => (504, "text/html; charset=utf-8", String::new(), vec![]),
+        Some(route) => (route.status, route.content_type, route.body, route.headers),
+        None => (404, "text/html; charset=utf-8", String::new(), vec![]),
+    };
+    let headers = headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, value)).collect::<String>();
+    let delay = *delay.lock().unwrap();
+    std::thread::sleep(delay);
+    let response = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status, content_type, body.len(), headers, body
+    );
+    stream.write_all(response.as_bytes()).ok()?;
+    Some(())
+}
@@ -0,0 +1,113 @@
+use std::sync::Arc;
+use anyhow::bail;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+use crate::util::{parse_page, parse_size, selector};
+use crate::labels::{detect_language, Language, QUOTA_OF, QUOTA_USED};
+use crate::logging::log_parse;
+use crate::{SendThrough, StudIpClient};
+
+const MY_FILES_URL: &str = "https://studip.example.com/dispatch.php/files";
+
+static SIZE_TEXT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\d+(?:[.,]\d+)?\s*(?:[kmgt]i?b|bytes?)\b").unwrap());
+
+/// The storage used by the personal files of the current user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    pub used_bytes: u64,
+    /// The available storage, `None` if the installation does not limit (or show) it
+    pub total_bytes: Option<u64>,
+}
+
+impl Quota {
+
+    /// The storage, that is still available, `None` if it is not limited
+    pub fn remaining_bytes(&self) -> Option<u64> {
+        self.total_bytes.map(|total| total.saturating_sub(self.used_bytes))
+    }
+
+}
+
+/// Enables reading the personal file area ("Meine Dateien") of the current user \
+/// Can be obtained with [`StudIp::my_files()`](crate::StudIp::my_files())
+#[derive(Debug, Clone)]
+pub struct MyFiles {
+    client: Arc<StudIpClient>,
+}
+
+impl MyFiles {
+
+    pub(crate) fn from_client(client: Arc<StudIpClient>) -> Self {
+        Self { client }
+    }
+
+    /// Returns the used and the available storage, as shown by the quota indicator of the personal files page \
+    /// *Note: Installations without a quota indicator yield a used size of 0 and no total*
+    pub fn quota(&self) -> anyhow::Result<Quota> {
+        let response = self.client.get(MY_FILES_URL).send_through(&self.client)?;
+        if !response.status().is_success() {
+            bail!("Could not get the personal files. Status Code: {}", response.status());
+        }
+        Ok(parse_quota(&parse_page(&response.text()?)?).unwrap_or(Quota { used_bytes: 0, total_bytes: None }))
+    }
+
+}
+
+// Parses a text like "1,2 GB von 5 GB belegt", returning None if it does not describe the quota
+fn parse_quota_text(text: &str) -> Option<Quota> {
+    let lowercase = text.to_lowercase();
+    let sizes = SIZE_TEXT_REGEX.find_iter(text).collect::<Vec<_>>();
+    let (first, rest) = sizes.split_first()?;
+    let has_word = |word: &str| lowercase.split(|c: char| !c.is_alphanumeric()).any(|part| part == word);
+    let language = detect_language(&[&QUOTA_OF, &QUOTA_USED], has_word)?;
+    let of_words = QUOTA_OF.in_language(language);
+    let used_bytes = parse_size(first.as_str())?;
+    // The total follows the separating word
+    let total_bytes = rest.first()
+        .filter(|total| of_words.iter().any(|of| has_word(of) && lowercase[first.end()..total.start()].contains(of)))
+        .and_then(|total| parse_size(total.as_str()));
+    Some(Quota { used_bytes, total_bytes })
+}
+
+fn parse_quota(html: &Html) -> Option<Quota> {
+    let text_of = |elem: scraper::ElementRef| elem.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ");
+    let quota = html.select(selector!("[class*=\"quota\"], [id*=\"quota\"]"))
+        .chain(html.select(selector!("#sidebar .sidebar-widget")))
+        .find_map(|elem| parse_quota_text(&text_of(elem)));
+    if quota.is_none() {
+        log_parse!(debug, "No quota text matched any of the {} translations", Language::ALL.len());
+    }
+    quota
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_quota() {
+        assert_eq!(parse_quota_text("1,2 GB von 5 GB belegt"), Some(Quota { used_bytes: 1288490189, total_bytes: Some(5368709120) }));
+        assert_eq!(parse_quota_text("Used: 300 MB of 1 GB"), Some(Quota { used_bytes: 314572800, total_bytes: Some(1073741824) }));
+        assert_eq!(parse_quota_text("512 KB belegt"), Some(Quota { used_bytes: 524288, total_bytes: None }));
+        assert_eq!(parse_quota_text("Dateien: 12"), None);
+
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/files", 200, r#"<div id="sidebar">
+                <div class="sidebar-widget"><div class="sidebar-widget-header">Aktionen</div></div>
+                <div class="sidebar-widget"><div class="sidebar-widget-header">Speicherplatz</div>
+                    <div class="sidebar-widget-content"><progress value="24" max="100"></progress> 1,2 GB von 5 GB belegt</div></div>
+            </div>
+            <div id="content"><table class="default documents"></table></div>"#);
+        let my_files = MyFiles::from_client(Arc::new(server.client()));
+        let quota = my_files.quota().unwrap();
+        assert_eq!(quota.total_bytes, Some(5368709120));
+        assert_eq!(quota.remaining_bytes(), Some(5368709120 - 1288490189));
+
+        // Installations without a quota indicator
+        server.route("GET", "/dispatch.php/files", 200, r#"<div id="content"><table class="default documents"></table></div>"#);
+        assert_eq!(my_files.quota().unwrap(), Quota { used_bytes: 0, total_bytes: None });
+    }
+}
@@ -1,11 +1,16 @@
-use anyhow::{anyhow, Context};
+use anyhow::Context;
 use chrono::NaiveDate;
-use scraper::{Element, ElementRef, Html, Selector};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{Element, ElementRef, Html};
 use serde::{Deserialize, Serialize};
 use url::Url;
-use crate::StudIpClient;
+use crate::{SendThrough, StudIpClient};
+use crate::content::{html_to_markdown, html_to_text};
 use crate::user::{parse_simple_user, User};
-use crate::ref_source::ReferenceSource;
+use crate::ref_source::{ReferenceSource, GLOBAL_NEWS_URL};
+use crate::util::{expect_one, parse_last_page, parse_page, parse_size, selector, trimmed_text};
+use crate::warnings::{ParseWarnings, Parsed};
 
 /// A comment below a news article \
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,12 +25,36 @@ pub struct NewsComment {
     pub time_since_string: String,
 }
 
+impl NewsComment {
+
+    /// The content of the comment as plain text
+    pub fn text_content(&self) -> String {
+        html_to_text(&self.html_content)
+    }
+
+    /// The content of the comment as Markdown
+    pub fn markdown_content(&self) -> String {
+        html_to_markdown(&self.html_content)
+    }
+
+}
+
 impl PartialEq for NewsComment {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
     }
 }
 
+/// A file attached to a [`NewsArticle`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewsAttachment {
+    pub name: String,
+    /// The download url, which is only absolute, if the attachment was queried with [NewsArticle::query_details()](NewsArticle::query_details())
+    pub url: String,
+    /// The size in bytes, if it is displayed
+    pub size: Option<u64>,
+}
+
 /// A news article, found in a news box \
 /// Can be parsed with [parse_news_box()](parse_news_box())
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,47 +77,107 @@ pub struct NewsArticle {
     pub n_comments: usize,
     /// The comments of the article \
     /// Is only filled by calling [NewsArticle::query_comments()](NewsArticle::query_comments())
-    pub comments: Vec<NewsComment>
+    pub comments: Vec<NewsComment>,
+    /// The first day, on which the article is visible \
+    /// Only shown in the footer of the expanded article, see [NewsArticle::query_details()](NewsArticle::query_details())
+    #[serde(default)]
+    pub visible_from: Option<NaiveDate>,
+    /// The last day, on which the article is visible
+    #[serde(default)]
+    pub visible_until: Option<NaiveDate>,
+    /// The files attached to the article, which are also only shown in the expanded article
+    #[serde(default)]
+    pub attachments: Vec<NewsAttachment>,
 }
 
 impl NewsArticle {
 
-    /// Queries the comments of the news article \
-    /// *Note: This is not done automatically*
-    pub fn query_comments(&mut self, stud_ip_client: &StudIpClient) -> anyhow::Result<()> {
-        // Make request to open comment content box
+    /// The content of the article as plain text
+    pub fn text_content(&self) -> String {
+        html_to_text(&self.html_content)
+    }
+
+    /// The content of the article as Markdown
+    pub fn markdown_content(&self) -> String {
+        html_to_markdown(&self.html_content)
+    }
+
+    // Requests the page of the article's source, with the content box of the article opened
+    fn get_expanded(&self, stud_ip_client: &StudIpClient, query: &[(&str, &str)]) -> anyhow::Result<Html> {
         let mut url : Url = (&self.source).try_into()?;
         url.set_fragment(Some(&self.id));
         let response = stud_ip_client.get(url)
-            .query(&[("comments", "1"), ("contentbox_open", &self.id)])
-            .send()?;
-        // Find article by id in html
-        let html = Html::parse_document(&response.text()?);
-        let comment_elements = Selector::parse(&format!("article[id=\"{}\"] .comments .comment", self.id))
-            .map_err(|_| anyhow!("Failed to parse comments selector"))?;
+            .query(query)
+            .query(&[("contentbox_open", &self.id)])
+            .send_through(stud_ip_client)?;
+        parse_page(&response.text()?)
+    }
+
+    /// Queries the visibility range and the attachments of the news article, which are only shown in the expanded article \
+    /// *Note: This is not done automatically*
+    pub fn query_details(&mut self, stud_ip_client: &StudIpClient) -> anyhow::Result<()> {
+        let html = self.get_expanded(stud_ip_client, &[])?;
+        let article = html.select(selector!("article[id]"))
+            .find(|article| article.attr("id") == Some(self.id.as_str()))
+            .context("Expected news article")?;
+        self.parse_footer(article);
+        for attachment in &mut self.attachments {
+            attachment.url = stud_ip_client.absolutize(&attachment.url)?.to_string();
+        }
+        Ok(())
+    }
+
+    // Parses the footer of an expanded article, keeping the fields empty, if it is absent
+    fn parse_footer(&mut self, article_elem: ElementRef) {
+        let Some(footer) = article_elem.select(selector!(":scope > footer, :scope > section > footer")).next() else {
+            return;
+        };
+        let visibility_text = footer.select(selector!(".news_visibility"))
+            .next()
+            .unwrap_or(footer)
+            .text()
+            .collect::<String>();
+        (self.visible_from, self.visible_until) = parse_visibility(&visibility_text);
+        self.attachments = footer.select(selector!(".news_attachments a[href]"))
+            .map(|link| NewsAttachment {
+                name: link.select(selector!(".name")).next().unwrap_or(link).text().collect::<String>().trim().to_string(),
+                url: link.attr("href").unwrap().to_string(),
+                size: parse_size(&link.text().collect::<String>()),
+            })
+            .collect();
+    }
+
+    /// Queries the comments of the news article \
+    /// *Note: This is not done automatically*
+    pub fn query_comments(&mut self, stud_ip_client: &StudIpClient) -> anyhow::Result<()> {
+        // Open the comment content box and find the article by id in html
+        let html = self.get_expanded(stud_ip_client, &[("comments", "1")])?;
+        let comment_elements = html.select(selector!("article[id]"))
+            .filter(|article| article.attr("id") == Some(self.id.as_str()))
+            .flat_map(|article| article.select(selector!(".comments .comment")));
         // Parse comments
-        let time_selector = Selector::parse("time").unwrap();
-        let author_selector = Selector::parse("h1 > a").unwrap();
-        let content_selector = Selector::parse(".formatted-content").unwrap();
-        for comment_element in html.select(&comment_elements) {
+        let time_selector = selector!("time");
+        let author_selector = selector!("h1 > a");
+        let content_selector = selector!(".formatted-content");
+        for comment_element in comment_elements {
             let mut new_comments = vec![];
             let comment_id = comment_element.attr("id")
                 .context("Expected comment id")?
                 .replace("newscomment-", "");
             // We can't easily parse this into a date, because it looks could look like this: "vor einem Monat"
             // This is localized and hard to parse
-            let time_since_string = comment_element.select(&time_selector)
+            let time_since_string = comment_element.select(time_selector)
                 .next()
                 .context("Expected comment time")?
                 .text()
                 .collect::<String>()
                 .trim()
                 .to_string();
-            let author_link = comment_element.select(&author_selector)
+            let author_link = comment_element.select(author_selector)
                 .next()
                 .context("Expected comment author a tag")?;
             let author = parse_simple_user(author_link)?;
-            let content_html = comment_element.select(&content_selector)
+            let content_html = comment_element.select(content_selector)
                 .next()
                 .context("Expected comment content")?
                 .inner_html();
@@ -116,72 +205,172 @@ impl PartialEq for NewsArticle {
     }
 }
 
+/// Queries the system-wide announcements (e.g. exam period notices or maintenance windows) from the listing of all global news \
+/// Expired announcements are only included, if `include_expired` is true. The listing is paginated, so this makes one request per page.
+pub fn global_news(client: &StudIpClient, include_expired: bool) -> anyhow::Result<Vec<NewsArticle>> {
+    let mut articles: Vec<NewsArticle> = vec![];
+    let mut page = 1;
+    loop {
+        let mut request = client.get(GLOBAL_NEWS_URL)
+            .query(&[("page", page)]);
+        if include_expired {
+            request = request.query(&[("show_expired", "1")]);
+        }
+        let response = request.send_through(client)?;
+        if !response.status().is_success() {
+            anyhow::bail!("Could not get global news. Status Code: {}", response.status());
+        }
+        let html = parse_page(&response.text()?)?;
+        let new_articles = parse_news_box(html.root_element(), &ReferenceSource::System)?
+            .into_iter()
+            .filter(|article| !articles.contains(article))
+            .collect::<Vec<_>>();
+        // Stops on pages without new articles too, in case the page parameter is ignored
+        if new_articles.is_empty() || page >= parse_last_page(&html) {
+            articles.extend(new_articles);
+            return Ok(articles);
+        }
+        articles.extend(new_articles);
+        page += 1;
+    }
+}
+
+static VISIBILITY_DATE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)(?P<until>bis|until)?\s*(?P<date>\d{1,2}\.\d{1,2}\.\d{4})").unwrap());
+
+// Parses the visibility range (e.g. "Sichtbar von 01.03.2025 bis 31.03.2025" or "sichtbar bis 31.03.2025")
+fn parse_visibility(text: &str) -> (Option<NaiveDate>, Option<NaiveDate>) {
+    let (mut from, mut until) = (None, None);
+    for captures in VISIBILITY_DATE_REGEX.captures_iter(text) {
+        let date = NaiveDate::parse_from_str(&captures["date"], "%d.%m.%Y").ok();
+        if captures.name("until").is_some() || from.is_some() {
+            until = until.or(date);
+        } else {
+            from = date;
+        }
+    }
+    (from, until)
+}
+
 /// Parse a news box into a list of [news articles](NewsArticle) \
-/// These boxes appear all over the site, including on profile pages, start page and courses pages
+/// These boxes appear all over the site, including on profile pages, start page and courses pages. \
+/// Articles, that could not be parsed, are left out (see [parse_news_box_with_warnings()](parse_news_box_with_warnings())).
 pub fn parse_news_box(element: ElementRef, reference_source: &ReferenceSource) -> anyhow::Result<Vec<NewsArticle>> {
-    let articles_selector = Selector::parse("article[id].studip").unwrap();
-    let title_selector = Selector::parse("header h1").unwrap();
-    let news_author_selector = Selector::parse("header .news_user").unwrap();
-    let news_creation_date_selector = Selector::parse("header .news_date").unwrap();
-    let news_visits_selector = Selector::parse("header .news_visits").unwrap();
-    let news_n_comments_selector = Selector::parse("header .news_comments_indicator").unwrap();
-    let news_content_selector = Selector::parse("section > article .formatted-content").unwrap();
-    let mut news_articles = vec![];
-    for article_elem in element.select(&articles_selector) {
-        // Parse header
-        let article_id = article_elem.attr("id").unwrap().to_string();
-        let title = article_elem.select(&title_selector)
-            .next()
-            .context("Expected news title")?
-            .text()
-            .collect::<String>()
-            .trim()
-            .to_string();
-        let author_elem = article_elem.select(&news_author_selector)
-            .next()
-            .context("Expected news author")?;
-        let author = parse_simple_user(author_elem)?;
-        let news_date_string = article_elem.select(&news_creation_date_selector)
-            .next()
-            .context("Expected news creation date")?
-            .text()
-            .collect::<String>()
-            .trim()
-            .to_string();
-        let news_date = NaiveDate::parse_from_str(&news_date_string, "%d.%m.%Y")?;
-        let visits: usize = article_elem.select(&news_visits_selector)
-            .next()
-            .context("Expected news visits")?
-            .text()
-            .collect::<String>()
-            .trim()
-            .replace('.', "")
-            .parse()?;
-        let n_comments: usize = article_elem.select(&news_n_comments_selector).next().and_then(|e| e.text()
-            .collect::<String>()
-            .trim()
-            .replace('.', "")
-            .parse()
-            .ok()
-        ).unwrap_or(0);
-        // Parse content
-        let content_html = article_elem.select(&news_content_selector)
-            .next()
-            .context("Expected news content")?
-            .first_element_child()
-            .context("Expected content")?
-            .inner_html();
-        news_articles.push(NewsArticle {
-            id: article_id,
-            source: reference_source.clone(),
-            title,
-            html_content: content_html,
-            author,
-            date: news_date,
-            visits,
-            n_comments,
-            comments: vec![],
-        });
+    Ok(parse_news_box_with_warnings(element, reference_source).value)
+}
+
+/// Like [parse_news_box()](parse_news_box()), but also returns a warning for each article, that could not be parsed
+pub fn parse_news_box_with_warnings(element: ElementRef, reference_source: &ReferenceSource) -> Parsed<Vec<NewsArticle>> {
+    let mut warnings = ParseWarnings::default();
+    let news_articles = element.select(selector!("article[id].studip"))
+        .filter_map(|article_elem| warnings.skip_err(
+            format!("news article {}", article_elem.attr("id").unwrap()),
+            parse_news_article(article_elem, reference_source),
+        ))
+        .collect();
+    warnings.into_parsed(news_articles)
+}
+
+fn parse_news_article(article_elem: ElementRef, reference_source: &ReferenceSource) -> anyhow::Result<NewsArticle> {
+    let title_selector = selector!("header h1");
+    let news_author_selector = selector!("header .news_user");
+    let news_creation_date_selector = selector!("header .news_date");
+    let news_visits_selector = selector!("header .news_visits");
+    let news_n_comments_selector = selector!("header .news_comments_indicator");
+    let news_content_selector = selector!("section > article .formatted-content");
+    // Parse header
+    let article_id = article_elem.attr("id").unwrap().to_string();
+    let title = trimmed_text(expect_one(article_elem, title_selector, "news title")?);
+    let author_elem = expect_one(article_elem, news_author_selector, "news author")?;
+    let author = parse_simple_user(author_elem)?;
+    let news_date_string = trimmed_text(expect_one(article_elem, news_creation_date_selector, "news creation date")?);
+    let news_date = NaiveDate::parse_from_str(&news_date_string, "%d.%m.%Y")?;
+    let visits: usize = trimmed_text(expect_one(article_elem, news_visits_selector, "news visits")?)
+        .replace('.', "")
+        .parse()?;
+    let n_comments: usize = article_elem.select(news_n_comments_selector).next()
+        .and_then(|e| trimmed_text(e).replace('.', "").parse().ok())
+        .unwrap_or(0);
+    // Parse content
+    let content_html = expect_one(article_elem, news_content_selector, "news content")?
+        .first_element_child()
+        .context("Expected content")?
+        .inner_html();
+    let mut article = NewsArticle {
+        id: article_id,
+        source: reference_source.clone(),
+        title,
+        html_content: content_html,
+        author,
+        date: news_date,
+        visits,
+        n_comments,
+        comments: vec![],
+        visible_from: None,
+        visible_until: None,
+        attachments: vec![],
+    };
+    article.parse_footer(article_elem);
+    Ok(article)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_global_news() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/news/display/studip?page=2", 200, include_str!("../testdata/news/global_news_2.html"))
+            .route("GET", "/dispatch.php/news/display/studip?page=1", 200, include_str!("../testdata/news/global_news_1.html"))
+            .route("GET", "/dispatch.php/news/display/studip?comments=1", 200, r#"<div id="content"><article class="studip" id="n3">
+                <div class="comments"><div class="comment" id="newscomment-c1">
+                    <h1><a href="https://studip.example.com/dispatch.php/profile?username=jdoe">John Doe</a></h1>
+                    <time>vor 2 Stunden</time>
+                    <div class="formatted-content"><p>Danke!</p></div>
+                </div></div>
+            </article></div>"#);
+        let client = server.client();
+        let mut articles = global_news(&client, true).unwrap();
+        assert_eq!(articles.iter().map(|article| article.id.as_str()).collect::<Vec<_>>(), vec!["n3", "n2", "n1"]);
+        assert_eq!(articles[0].source, ReferenceSource::System);
+        assert_eq!(articles[0].visits, 1204);
+        assert_eq!(articles[0].date, NaiveDate::from_ymd_opt(2025, 3, 10).unwrap());
+
+        articles[0].query_comments(&client).unwrap();
+        assert_eq!(articles[0].comments[0].author.username, "jdoe");
+        let paths = server.requests().into_iter().map(|request| request.path).collect::<Vec<_>>();
+        assert_eq!(paths, vec![
+            "/dispatch.php/news/display/studip?page=1&show_expired=1",
+            "/dispatch.php/news/display/studip?page=2&show_expired=1",
+            "/dispatch.php/news/display/studip?comments=1&contentbox_open=n3",
+        ]);
     }
-    Ok(news_articles)
-}
\ No newline at end of file
+
+    #[test]
+    fn test_news_details() {
+        assert_eq!(parse_visibility("sichtbar bis 31.03.2025"), (None, NaiveDate::from_ymd_opt(2025, 3, 31)));
+        assert_eq!(parse_visibility("Visible from 01.03.2025 until 31.03.2025"), (NaiveDate::from_ymd_opt(2025, 3, 1), NaiveDate::from_ymd_opt(2025, 3, 31)));
+
+        let html = Html::parse_document(include_str!("../testdata/news/global_news_1.html"));
+        let articles = parse_news_box(html.root_element(), &ReferenceSource::System).unwrap();
+        assert!(articles.iter().all(|article| article.visible_from.is_none() && article.visible_until.is_none() && article.attachments.is_empty()));
+
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/news/display/studip?contentbox_open=n3", 200, include_str!("../testdata/news/expanded.html"));
+        let client = server.client();
+        let mut article = articles.into_iter().next().unwrap();
+        article.query_details(&client).unwrap();
+        assert_eq!(article.visible_from, NaiveDate::from_ymd_opt(2025, 3, 10));
+        assert_eq!(article.visible_until, NaiveDate::from_ymd_opt(2025, 3, 16));
+        assert_eq!(article.attachments.len(), 1);
+        assert_eq!(article.attachments[0].name, "Zeitplan.pdf");
+        assert_eq!(article.attachments[0].size, Some(12800));
+        assert_eq!(article.attachments[0].url, server.url("/sendfile.php?type=0&file_id=f1&file_name=Zeitplan.pdf"));
+
+        // Articles without a footer are not affected by the footer of another article
+        let html = Html::parse_document(include_str!("../testdata/news/expanded.html"));
+        let articles = parse_news_box(html.root_element(), &ReferenceSource::System).unwrap();
+        assert_eq!(articles[0].attachments[0].url, "/sendfile.php?type=0&file_id=f1&file_name=Zeitplan.pdf");
+        assert_eq!(articles[1].visible_until, None);
+    }
+}
@@ -0,0 +1,218 @@
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use scraper::Html;
+use serde::{Deserialize, Deserializer, Serialize};
+use url::Url;
+use crate::{SendThrough, StudIpClient};
+
+const JSUPDATER_URL: &str = "https://studip.example.com/dispatch.php/jsupdater/get";
+const MARK_NOTIFICATION_READ_URL: &str = "https://studip.example.com/dispatch.php/jsupdater/mark_notification_read";
+
+/// A notification of the bell icon in the header (e.g. new file, forum post or membership accepted)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: String,
+    pub html_text: String,
+    pub plain_text: String,
+    pub url: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub seen: bool,
+}
+
+impl PartialEq for Notification {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Notification {
+    /// Classifies the url of the notification into a known entity (best-effort)
+    pub fn target(&self) -> NotificationTarget {
+        self.url.as_deref()
+            .map(classify_url)
+            .unwrap_or(NotificationTarget::Unknown)
+    }
+}
+
+/// The entity, that a [`Notification`] refers to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NotificationTarget {
+    /// A file, optionally within a course
+    File { file_id: String, course_id: Option<String> },
+    /// A forum entry of a course
+    ForumPost { course_id: String, entry_id: Option<String> },
+    /// A message of the current user
+    Message { message_id: String },
+    /// The profile of a user
+    Profile { username: String },
+    /// Any other page of a course
+    Course { course_id: String },
+    /// The url could not be classified
+    Unknown,
+}
+
+/// Classifies a url into a [`NotificationTarget`] (best-effort)
+pub fn classify_url(url: &str) -> NotificationTarget {
+    let Ok(url) = Url::parse(url) else {
+        return NotificationTarget::Unknown;
+    };
+    let query_value = |name: &str| url.query_pairs().find_map(|(key, value)| (key == name).then(|| value.to_string()));
+    let path = url.path();
+    // Ids are usually the path segment after the action
+    let segment_after = |action: &str| {
+        let mut segments = path.split('/');
+        segments.find(|segment| *segment == action)?;
+        segments.next().filter(|segment| !segment.is_empty()).map(|segment| segment.to_string())
+    };
+    let course_id = query_value("cid");
+    if let Some(file_id) = query_value("file_id").or_else(|| path.contains("/file/").then(|| segment_after("details")).flatten()) {
+        return NotificationTarget::File { file_id, course_id };
+    }
+    if path.contains("forum") {
+        if let Some(course_id) = course_id.clone() {
+            // Entries are anchored by their id, which is usually also the last path segment
+            let entry_id = url.fragment()
+                .or_else(|| path.rsplit('/').next().filter(|segment| !segment.is_empty() && *segment != "index"))
+                .map(|entry_id| entry_id.to_string());
+            return NotificationTarget::ForumPost { course_id, entry_id };
+        }
+    }
+    if let Some(message_id) = path.contains("messages").then(|| segment_after("read")).flatten() {
+        return NotificationTarget::Message { message_id };
+    }
+    if path.contains("profile") {
+        if let Some(username) = query_value("username") {
+            return NotificationTarget::Profile { username };
+        }
+    }
+    match course_id {
+        Some(course_id) => NotificationTarget::Course { course_id },
+        None => NotificationTarget::Unknown,
+    }
+}
+
+fn from_str_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(s) => s,
+        value => value.to_string(),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct TheirNotification {
+    #[serde(alias = "personal_notification_id", deserialize_with = "from_str_or_number")]
+    id: String,
+    #[serde(default)]
+    html: String,
+    #[serde(default)]
+    text: String,
+    url: Option<String>,
+    mkdate: Option<i64>,
+    #[serde(default)]
+    seen: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct TheirPersonalNotifications {
+    #[serde(default)]
+    notifications: Vec<TheirNotification>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TheirUpdate {
+    #[serde(rename = "PersonalNotifications")]
+    personal_notifications: Option<TheirPersonalNotifications>,
+}
+
+/// Queries the notifications of the current user, using the same endpoint, that the web UI polls
+pub(crate) fn get_notifications(client: &StudIpClient) -> anyhow::Result<Vec<Notification>> {
+    let response = client.get(JSUPDATER_URL)
+        .header("X-Requested-With", "XMLHttpRequest")
+        .send_through(client)?;
+    if !response.status().is_success() {
+        bail!("Could not get notifications. Status Code: {}", response.status());
+    }
+    parse_notifications(&response.text()?)
+}
+
+/// Marks all notifications of the current user as seen
+pub(crate) fn mark_notifications_seen(client: &StudIpClient) -> anyhow::Result<()> {
+    let security_token = client.security_token()?;
+    let response = client.post(format!("{}/all", MARK_NOTIFICATION_READ_URL))
+        .header("X-Requested-With", "XMLHttpRequest")
+        .form(&[("security_token", security_token.as_str())])
+        .send_through(client)?;
+    if !response.status().is_success() {
+        bail!("Could not mark notifications as seen. Status Code: {}", response.status());
+    }
+    Ok(())
+}
+
+fn parse_notifications(text: &str) -> anyhow::Result<Vec<Notification>> {
+    // Nothing to update is sometimes signaled by an empty array
+    if text.trim().is_empty() || text.trim() == "[]" {
+        return Ok(vec![]);
+    }
+    let update: TheirUpdate = serde_json::from_str(text).context("Could not parse notifications json")?;
+    let Some(personal_notifications) = update.personal_notifications else {
+        return Ok(vec![]);
+    };
+    Ok(personal_notifications.notifications.into_iter()
+        .map(|their| {
+            let plain_text = if their.text.trim().is_empty() {
+                Html::parse_fragment(&their.html).root_element().text().collect::<String>()
+            } else {
+                their.text
+            };
+            Notification {
+                id: their.id,
+                plain_text: plain_text.split_whitespace().collect::<Vec<_>>().join(" "),
+                html_text: their.html,
+                url: their.url.filter(|url| !url.is_empty()),
+                created_at: their.mkdate.and_then(|timestamp| DateTime::from_timestamp(timestamp, 0)),
+                seen: their.seen,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_notifications() {
+        let notifications = parse_notifications(r#"{
+            "PersonalNotifications": {"notifications": [
+                {"personal_notification_id": 12, "html": "<p>New file <b>dates.pdf</b></p>", "text": "",
+                 "url": "https://studip.example.com/dispatch.php/file/details/f1?cid=c1", "mkdate": 1741784700},
+                {"id": "n2", "text": "Your membership was accepted", "url": "https://studip.example.com/dispatch.php/course/overview?cid=c2", "seen": true}
+            ]}
+        }"#).unwrap();
+        assert_eq!(notifications.len(), 2);
+        assert_eq!(notifications[0].id, "12");
+        assert_eq!(notifications[0].plain_text, "New file dates.pdf");
+        assert!(notifications[0].created_at.is_some());
+        assert_eq!(notifications[0].target(), NotificationTarget::File { file_id: "f1".to_string(), course_id: Some("c1".to_string()) });
+        assert!(notifications[1].seen);
+        assert_eq!(notifications[1].target(), NotificationTarget::Course { course_id: "c2".to_string() });
+        assert!(parse_notifications("[]").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_classify_url() {
+        assert_eq!(
+            classify_url("https://studip.example.com/plugins.php/coreforum/index/index/e1?cid=c1#e1"),
+            NotificationTarget::ForumPost { course_id: "c1".to_string(), entry_id: Some("e1".to_string()) }
+        );
+        assert_eq!(
+            classify_url("https://studip.example.com/dispatch.php/messages/read/m1"),
+            NotificationTarget::Message { message_id: "m1".to_string() }
+        );
+        assert_eq!(
+            classify_url("https://studip.example.com/dispatch.php/profile?username=jdoe"),
+            NotificationTarget::Profile { username: "jdoe".to_string() }
+        );
+        assert_eq!(classify_url("not a url"), NotificationTarget::Unknown);
+    }
+}
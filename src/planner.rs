@@ -0,0 +1,150 @@
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Context;
+use chrono::{DateTime, Days, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use crate::calendar::{Calendar, CalendarEntry, EntryKind};
+use crate::course::{Course, MyCourses};
+use crate::course_modules::members::Group;
+use crate::course_modules::MembersModule;
+use crate::get_module;
+use crate::ids::CourseId;
+use crate::StudIpClient;
+use crate::util::local_to_utc;
+
+/// A single upcoming item of a course, as returned by [`StudIp::upcoming()`](crate::StudIp::upcoming()) \
+/// *Note: Deadlines of assignments (e.g. of Vips) are not included, as no supported course module provides them yet*
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum UpcomingItem {
+    /// A date of a course (lecture, tutorial, exam, ...)
+    CourseDate { course_id: CourseId, course_name: String, entry: CalendarEntry },
+    /// A questionnaire, that stops accepting answers
+    QuestionnaireClosing { course_id: CourseId, course_name: String, questionnaire_id: String, title: String, closes_at: DateTime<Utc> },
+    /// A group, that can be entered from this point on
+    GroupSignupOpens { course_id: CourseId, course_name: String, group: Group, opens_at: DateTime<Utc> },
+}
+
+impl UpcomingItem {
+
+    /// The point in time, at which the item is due
+    pub fn time(&self) -> DateTime<Utc> {
+        match self {
+            UpcomingItem::CourseDate { entry, .. } => entry.start,
+            UpcomingItem::QuestionnaireClosing { closes_at, .. } => *closes_at,
+            UpcomingItem::GroupSignupOpens { opens_at, .. } => *opens_at,
+        }
+    }
+
+    /// The id of the course, the item belongs to
+    pub fn course_id(&self) -> &str {
+        match self {
+            UpcomingItem::CourseDate { course_id, .. }
+            | UpcomingItem::QuestionnaireClosing { course_id, .. }
+            | UpcomingItem::GroupSignupOpens { course_id, .. } => course_id,
+        }
+    }
+
+}
+
+/// A failure, that occurred while gathering the upcoming items of a single course (or the calendar, if `course_id` is `None`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpcomingWarning {
+    pub course_id: Option<CourseId>,
+    pub course_name: Option<String>,
+    pub error: String,
+}
+
+/// The result of [`StudIp::upcoming()`](crate::StudIp::upcoming())
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Upcoming {
+    /// The upcoming items, ordered by their time
+    pub items: Vec<UpcomingItem>,
+    /// Failures of individual courses, which did not abort the aggregation
+    pub warnings: Vec<UpcomingWarning>,
+}
+
+// Gathers the items of a single course, from the modules it has
+fn gather_course(course: &mut Course, now: DateTime<Utc>, until: DateTime<Utc>, timezone: Tz) -> anyhow::Result<Vec<UpcomingItem>> {
+    let mut items = vec![];
+    for questionnaire in course.query_questionnaires()? {
+        let Some(closes_at) = questionnaire.closes_at().and_then(|closes_at| local_to_utc(closes_at, timezone)) else {continue};
+        if closes_at >= now && closes_at <= until {
+            items.push(UpcomingItem::QuestionnaireClosing {
+                course_id: course.id.clone(),
+                course_name: course.name.clone(),
+                questionnaire_id: questionnaire.id,
+                title: questionnaire.title,
+                closes_at,
+            });
+        }
+    }
+    if course.modules.is_empty() {
+        course.query_modules()?;
+    }
+    let (course_id, course_name) = (course.id.clone(), course.name.clone());
+    if let Some(members_module) = get_module!(course, MembersModule) {
+        for group in members_module.get_groups()? {
+            let Some(opens_at) = group.enables_entry_at else {continue};
+            if opens_at >= now && opens_at <= until {
+                items.push(UpcomingItem::GroupSignupOpens {
+                    course_id: course_id.clone(),
+                    course_name: course_name.clone(),
+                    group,
+                    opens_at,
+                });
+            }
+        }
+    }
+    Ok(items)
+}
+
+/// Gathers the [`Upcoming`] items of all courses within the `horizon`
+pub(crate) fn gather_upcoming(client: &Arc<StudIpClient>, my_courses: &mut MyCourses, horizon: Duration) -> anyhow::Result<Upcoming> {
+    if my_courses.courses.is_empty() {
+        my_courses.query()?;
+    }
+    let now = Utc::now();
+    let until = now + chrono::Duration::from_std(horizon).context("Horizon out of range")?;
+    let mut upcoming = Upcoming::default();
+
+    // The course dates of all courses are aggregated by the personal calendar
+    let timezone = client.timezone();
+    let today = now.with_timezone(&timezone).date_naive();
+    let end_day = until.with_timezone(&timezone).date_naive().checked_add_days(Days::new(1)).context("Horizon out of range")?;
+    match Calendar::from_client(client.clone()).range(today, end_day) {
+        Ok(entries) => {
+            for entry in entries {
+                let EntryKind::CourseDate { course_id } = &entry.kind else {continue};
+                // Only dates of the user's courses, that are within the horizon
+                let Some(course) = my_courses.courses.get(course_id) else {continue};
+                if entry.end < now || entry.start > until {
+                    continue;
+                }
+                upcoming.items.push(UpcomingItem::CourseDate {
+                    course_id: course.id.clone(),
+                    course_name: course.name.clone(),
+                    entry,
+                });
+            }
+        },
+        Err(e) => upcoming.warnings.push(UpcomingWarning {
+            course_id: None,
+            course_name: None,
+            error: format!("{:#}", e),
+        }),
+    }
+
+    for course in my_courses.courses.values_mut() {
+        match gather_course(course, now, until, timezone) {
+            Ok(items) => upcoming.items.extend(items),
+            Err(e) => upcoming.warnings.push(UpcomingWarning {
+                course_id: Some(course.id.clone()),
+                course_name: Some(course.name.clone()),
+                error: format!("{:#}", e),
+            }),
+        }
+    }
+    upcoming.items.sort_by(|a, b| a.time().cmp(&b.time()).then_with(|| a.course_id().cmp(b.course_id())));
+    Ok(upcoming)
+}
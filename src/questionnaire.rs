@@ -1,14 +1,24 @@
-use anyhow::Context;
-use chrono::NaiveDate;
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{bail, Context};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
 use itertools::Itertools;
+use once_cell::sync::Lazy;
 use regex::Regex;
-use scraper::{ElementRef, Html, Selector};
+use scraper::{ElementRef, Html};
 use serde::{Deserialize, Serialize};
 use crate::ref_source::ReferenceSource;
-use crate::StudIpClient;
-use crate::user::{get_username_from_link_element, parse_simple_user, User};
+use crate::{SendThrough, StudIpClient};
+use crate::user::{get_username_from_link_element, parse_simple_user, user_id_from_avatar_src, User};
+use crate::flash;
+use crate::util::{csv_row, expect_one, normalize_text, parse_localized_date_time, parse_page, parse_security_token, selector};
 
 const QUESTIONER_RESULTS_URL: &str = "https://studip.example.com/dispatch.php/questionnaire/evaluate";
+const QUESTIONNAIRE_EDIT_URL: &str = "https://studip.example.com/dispatch.php/questionnaire/edit";
+const QUESTIONNAIRE_STOP_URL: &str = "https://studip.example.com/dispatch.php/questionnaire/stop";
+const QUESTIONNAIRE_DELETE_URL: &str = "https://studip.example.com/dispatch.php/questionnaire/delete";
+
+static TERMS_DATE_TIME_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d{1,2}\.\d{1,2}\.\d{2,4}(?:\D{0,10}\d{1,2}:\d{2})?").unwrap());
 
 /// A single votable option in a questionnaire
 /// Vote results have to be queried separately with [`Questionnaire::query_results`]
@@ -17,11 +27,60 @@ pub struct QuestionnaireOption {
     pub text: String,
     pub value: usize,
     pub n_voters: usize,
+    /// The share of voters in percent, as shown (rounded) by the evaluation
+    #[serde(default)]
+    pub percent: Option<f32>,
     pub voters: Option<Vec<User>>
 }
 
-/// The kind of questionnaire
+impl QuestionnaireOption {
+
+    /// The share of all voters, that voted for this option in percent. \
+    /// Is `0.0`, if nobody voted yet.
+    pub fn percentage(&self, total_voters: usize) -> f64 {
+        if total_voters == 0 {
+            return 0.0;
+        }
+        self.n_voters as f64 / total_voters as f64 * 100.0
+    }
+
+}
+
+/// The result of a single [`QuestionnaireOption`], including the computed percentage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestionnaireOptionResult {
+    pub text: String,
+    pub value: usize,
+    pub n_voters: usize,
+    /// The share of voters, that voted for this option in percent
+    pub percentage: f64,
+    pub voters: Option<Vec<User>>
+}
+
+/// The flattened results of a [`Questionnaire`] \
+/// Can be obtained with [`Questionnaire::results()`]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestionnaireResults {
+    pub id: String,
+    pub title: String,
+    pub total_voters: usize,
+    pub options: Vec<QuestionnaireOptionResult>,
+}
+
+/// The format, in which the results of a [`Questionnaire`] are exported by [`Questionnaire::export_results()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultExport {
+    /// CSV with one row per option (text, value, n_voters, percentage)
+    Csv,
+    /// CSV with one row per known voter (text, value, username, display_name) \
+    /// Anonymous options, or options without voters, do not produce any rows
+    CsvLong,
+    /// The [`QuestionnaireResults`] as JSON
+    Json,
+}
+
+/// The kind of questionnaire
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum QuestionnaireKind {
     SingleChoice,
     MultipleChoice,
@@ -55,6 +114,14 @@ pub struct Questionnaire {
 
 impl Questionnaire {
 
+    /// Returns when the questionnaire closes, if its terms mention a stop date
+    pub fn closes_at(&self) -> Option<NaiveDateTime> {
+        // The stop date is the last date mentioned in the terms (e.g. "Die Umfrage endet am 12.03.2025 um 14:00 Uhr.")
+        TERMS_DATE_TIME_REGEX.find_iter(&self.terms)
+            .last()
+            .and_then(|date_time| parse_localized_date_time(date_time.as_str()))
+    }
+
     /// Query the results of the [`Questionnaire`]'s evaluation, so the votes for each option \
     /// *Note: This is not done automatically*
     pub fn query_results(&mut self, client: &StudIpClient) -> anyhow::Result<()> {
@@ -66,82 +133,72 @@ impl Questionnaire {
         let response = client.get(url)
             .query(&query_params)
             .header("X-Requested-With", "XMLHttpRequest")
-            .send()?;
+            .send_through(client)?;
         // Parse questionnaire results
         let text = response.text()?;
-        let html = Html::parse_document(&text);
+        self.parse_results(client, &Html::parse_document(&text))
+    }
+
+    // Parses the evaluation html into the options, matching each result row to an option by its text
+    fn parse_results(&mut self, client: &StudIpClient, html: &Html) -> anyhow::Result<()> {
         // Parse the options, including the number of voters for each and if not anonymous the actual voters
-        let options_counts_selector = Selector::parse("td:not([width])").unwrap();
-        let options_text_selector = Selector::parse("td[width] > strong").unwrap();
-        let result_options_selector = Selector::parse("table.default tr").unwrap();
-        let voters_selector = Selector::parse("td[width] > a").unwrap();
-        let avatar_selector = Selector::parse("img.avatar-small").unwrap();
-        let n_voters_regex = Regex::new(r"\(\d+% \| (?P<voters>\d+)/(?P<total_voters>\d+)\)").unwrap();
-        for (i, result_option_elem) in html.select(&result_options_selector).enumerate() {
-            // Insert new options
-            if i >= self.options.len() {
-                self.options.push(QuestionnaireOption {
-                    text: "".to_string(),
-                    value: i,
-                    n_voters: 0,
-                    voters: None,
-                });
-            }
-            // Parse option data
-            let option = &mut self.options[i];
-            let options_text = result_option_elem.select(&options_text_selector)
-                .next()
-                .context("Expected option text")?
-                .text()
+        let options_counts_selector = selector!("td:not([width])");
+        let options_text_selector = selector!("td[width] > strong");
+        let result_options_selector = selector!("table.default tr");
+        let voters_selector = selector!("td[width] > a[href*=\"username=\"]");
+        // Long voter lists are truncated, with a link to expand them
+        let expand_selector = selector!("td[width] a[href]:not([href*=\"username=\"])");
+        let n_voters_regex = Regex::new(r"\((?P<percent>\d+(?:[.,]\d+)?)% \| (?P<voters>\d+)/(?P<total_voters>\d+)\)").unwrap();
+        for result_option_elem in html.select(result_options_selector) {
+            // Skip rows, that are not option results (like header or summary rows)
+            let Some(options_text_elem) = result_option_elem.select(options_text_selector).next() else {continue};
+            let Some(option_counts_captures) = result_option_elem.select(options_counts_selector)
+                .find_map(|elem| n_voters_regex.captures(&elem.text().collect::<String>()).map(|c| (
+                    c.name("voters").unwrap().as_str().to_string(),
+                    c.name("total_voters").unwrap().as_str().to_string(),
+                    c.name("percent").unwrap().as_str().replace(',', ".")
+                ))) else {continue};
+            let options_text = options_text_elem.text()
                 .collect::<String>()
                 .trim()
                 .to_string();
-            let option_counts_string = result_option_elem.select(&options_counts_selector)
-                .next()
-                .context("Expected option counts")?
-                .text()
-                .collect::<String>()
-                .trim()
-                .to_string();
-            let n_voters = n_voters_regex
-                .captures(&option_counts_string)
-                .context("Expected voters")?
-                .name("voters")
-                .unwrap()
-                .as_str()
-                .parse()?;
-            let n_total_voters = n_voters_regex
-                .captures(&option_counts_string)
-                .context("Expected total voters")?
-                .name("total_voters")
-                .unwrap()
-                .as_str()
-                .parse()?;
-            option.text = options_text;
+            let n_voters: usize = option_counts_captures.0.parse()?;
+            let n_total_voters: usize = option_counts_captures.1.parse()?;
+            // Find the option by its text and only insert a new option, if the text is not known yet
+            let normalized_text = normalize_text(&options_text);
+            let option_index = match self.options.iter().position(|option| normalize_text(&option.text) == normalized_text) {
+                Some(index) => index,
+                None => {
+                    let value = self.options.iter()
+                        .map(|option| option.value + 1)
+                        .max()
+                        .unwrap_or(0);
+                    self.options.push(QuestionnaireOption {
+                        text: options_text,
+                        value,
+                        n_voters: 0,
+                        percent: None,
+                        voters: None,
+                    });
+                    self.options.len() - 1
+                }
+            };
+            let option = &mut self.options[option_index];
             option.n_voters = n_voters;
+            option.percent = option_counts_captures.2.parse().ok();
             self.total_voters = n_total_voters;
             // If not anonymous, parse the voters too
             if n_voters > 0 {
-                let mut found_voters = vec![];
-                for voter_elem in result_option_elem.select(&voters_selector) {
-                    let username = get_username_from_link_element(voter_elem)?;
-                    let avatar_elem = voter_elem.select(&avatar_selector)
-                        .next()
-                        .context("Expected avatar")?;
-                    let display_name = avatar_elem.attr("title")
-                        .context("Expected avatar display name")?
-                        .to_string();
-                    let avatar_src = avatar_elem
-                        .attr("src")
-                        .context("Expected avatar src")?
-                        .to_string();
-
-                    found_voters.push(User {
-                        username,
-                        display_name,
-                        avatar_src: Some(avatar_src),
-                        source: ReferenceSource::Unspecified,
-                    });
+                let mut found_voters = parse_voters(client, result_option_elem.select(voters_selector), &self.reference_source)?;
+                // Only the first voters are rendered, the expansion lists all of them
+                let expand_elem = result_option_elem.select(expand_selector).next();
+                if let (true, Some(expand_elem)) = (found_voters.len() < n_voters, expand_elem) {
+                    let url = client.absolutize(expand_elem.attr("href").unwrap())?;
+                    let response = client.get(url)
+                        .header("X-Requested-With", "XMLHttpRequest")
+                        .send_through(client)?;
+                    let expanded = parse_page(&response.text()?)?;
+                    found_voters = parse_voters(client, expanded.select(selector!("a[href*=\"username=\"]")), &self.reference_source)?;
                 }
                 if !found_voters.is_empty() {
                     option.voters = Some(found_voters);
@@ -151,6 +208,115 @@ impl Questionnaire {
         Ok(())
     }
 
+    /// Returns the voters of all options, each only once, even if they voted for several options \
+    /// *Note: The votes have to be queried with [`Questionnaire::query_results()`] first and are only known, if the questionnaire is not anonymous*
+    pub fn all_voters(&self) -> Vec<&User> {
+        self.options.iter()
+            .flat_map(|option| option.voters.iter().flatten())
+            .unique_by(|voter| voter.username.as_str())
+            .collect()
+    }
+
+    /// Returns the `members` (e.g. from [MembersModule::get_members()](crate::course_modules::MembersModule::get_members())), that did not vote \
+    /// *Note: Like [`Questionnaire::all_voters()`], this requires the votes to be queried first*
+    pub fn non_voters<'a>(&self, members: &'a [User]) -> Vec<&'a User> {
+        let voters = self.all_voters().into_iter()
+            .map(|voter| voter.username.as_str())
+            .collect::<std::collections::HashSet<_>>();
+        members.iter()
+            .filter(|member| !voters.contains(member.username.as_str()))
+            .collect()
+    }
+
+    /// Flattens the options into [`QuestionnaireResults`], computing the percentages from `total_voters` \
+    /// *Note: The votes have to be queried with [`Questionnaire::query_results()`] first*
+    pub fn results(&self) -> QuestionnaireResults {
+        QuestionnaireResults {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            total_voters: self.total_voters,
+            options: self.options.iter().map(|option| QuestionnaireOptionResult {
+                text: option.text.clone(),
+                value: option.value,
+                n_voters: option.n_voters,
+                percentage: option.percentage(self.total_voters),
+                voters: option.voters.clone(),
+            }).collect(),
+        }
+    }
+
+    /// Exports the results of the questionnaire in the given [`ResultExport`] format
+    pub fn export_results(&self, format: ResultExport) -> anyhow::Result<String> {
+        let results = self.results();
+        match format {
+            ResultExport::Csv => {
+                let mut csv = csv_row(&["text", "value", "n_voters", "percentage"]);
+                for option in &results.options {
+                    csv.push_str(&csv_row(&[
+                        option.text.clone(),
+                        option.value.to_string(),
+                        option.n_voters.to_string(),
+                        format!("{:.2}", option.percentage),
+                    ]));
+                }
+                Ok(csv)
+            }
+            ResultExport::CsvLong => {
+                let mut csv = csv_row(&["text", "value", "username", "display_name"]);
+                for option in &results.options {
+                    for voter in option.voters.iter().flatten() {
+                        csv.push_str(&csv_row(&[
+                            option.text.as_str(),
+                            option.value.to_string().as_str(),
+                            voter.username.as_str(),
+                            voter.display_name.as_str(),
+                        ]));
+                    }
+                }
+                Ok(csv)
+            }
+            ResultExport::Json => serde_json::to_string_pretty(&results)
+                .context("Could not serialize questionnaire results"),
+        }
+    }
+
+}
+
+impl Questionnaire {
+
+    /// Stops the questionnaire, so that no more votes can be cast
+    pub fn stop(&self, client: &StudIpClient) -> anyhow::Result<()> {
+        let url = format!("{}/{}", QUESTIONNAIRE_STOP_URL, self.id);
+        self.confirm_action(client, &url, "stop")
+    }
+
+    /// Deletes the questionnaire, including all of its votes
+    pub fn delete(&self, client: &StudIpClient) -> anyhow::Result<()> {
+        let url = format!("{}/{}", QUESTIONNAIRE_DELETE_URL, self.id);
+        self.confirm_action(client, &url, "delete")
+    }
+
+    // Opens the confirmation dialog of an action and then confirms it with the dialog's security token
+    fn confirm_action(&self, client: &StudIpClient, url: &str, action_name: &str) -> anyhow::Result<()> {
+        let query_params = self.reference_source.get_additional_query_params()
+            .into_iter()
+            .collect_vec();
+        let response = client.get(url)
+            .query(&query_params)
+            .header("X-Requested-With", "XMLHttpRequest")
+            .send_through(client)?;
+        let security_token = parse_security_token(&parse_page(&response.text()?)?)?;
+        let response = client.post(url)
+            .query(&query_params)
+            .form(&[("security_token", security_token.as_str()), ("yes", "1")])
+            .send_through(client)?;
+        if !response.status().is_success() {
+            bail!("Could not {} questionnaire. Status code: {}", action_name, response.status());
+        }
+        flash::check(&parse_page(&response.text()?)?)?;
+        Ok(())
+    }
+
 }
 
 impl PartialEq for Questionnaire {
@@ -159,70 +325,174 @@ impl PartialEq for Questionnaire {
     }
 }
 
+/// The specification of a new [`Questionnaire`], that can be created with [`create()`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewQuestionnaire {
+    pub title: String,
+    pub description: String,
+    /// Either [`QuestionnaireKind::SingleChoice`] or [`QuestionnaireKind::MultipleChoice`]
+    pub kind: QuestionnaireKind,
+    /// The texts of the voting options
+    pub options: Vec<String>,
+    /// If the votes should not be linked to the voters
+    pub anonymous: bool,
+    /// When the questionnaire starts, starts immediately if `None`
+    pub starts_at: Option<NaiveDateTime>,
+    /// When the questionnaire ends, needs to be stopped manually if `None`
+    pub ends_at: Option<NaiveDateTime>,
+}
+
+/// Creates a new [`Questionnaire`] in the given `range`, which is either the current user's profile, a course or the start page \
+/// Returns the created questionnaire, as it is displayed in that range.
+pub fn create(client: &StudIpClient, range: &ReferenceSource, spec: NewQuestionnaire) -> anyhow::Result<Questionnaire> {
+    if matches!(spec.kind, QuestionnaireKind::Unknown) {
+        bail!("Cannot create questionnaire of unknown kind");
+    }
+    // The range of a profile is always the current user, so it does not have to be specified
+    let range_params = match range {
+        ReferenceSource::Unspecified => bail!("Cannot create questionnaire without range"),
+        ReferenceSource::StartPage => vec![("range_type", "static"), ("range_id", "start")],
+        ReferenceSource::System => vec![("range_type", "static"), ("range_id", "studip")],
+        ReferenceSource::Course(id) => vec![("range_type", "course"), ("range_id", id.as_str())],
+        ReferenceSource::Profile(_) => vec![],
+        ReferenceSource::Institute(id) => vec![("range_type", "institute"), ("range_id", id.as_str())],
+    };
+    // The questionnaires, that already exist, so that the created one can be told apart from them, even if their titles are similar
+    let range_url = range.try_get_url().context("Cannot get url of range")?;
+    let response = client.get(range_url.clone()).send_through(client)?;
+    let existing_ids = questionnaire_ids(&parse_page(&response.text()?)?);
+    // Open editor to obtain security token
+    let response = client.get(QUESTIONNAIRE_EDIT_URL)
+        .query(&range_params)
+        .send_through(client)?;
+    let security_token = parse_security_token(&parse_page(&response.text()?)?)?;
+    // Build the editor form with a single vote question
+    let question_id = format!("{:032x}", SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos());
+    let question_data_prefix = format!("questions_data[{}][questiondata]", question_id);
+    let date_format = "%d.%m.%Y %H:%M";
+    let mut form = vec![
+        ("security_token".to_string(), security_token),
+        ("questionnaire[title]".to_string(), spec.title.clone()),
+        ("questionnaire[startdate]".to_string(), spec.starts_at
+            .unwrap_or_else(|| Utc::now().with_timezone(&client.timezone()).naive_local())
+            .format(date_format)
+            .to_string()),
+        ("questionnaire[stopdate]".to_string(), spec.ends_at
+            .map(|date| date.format(date_format).to_string())
+            .unwrap_or_default()),
+        ("questionnaire[anonymous]".to_string(), (spec.anonymous as u8).to_string()),
+        (format!("question_types[{}]", question_id), "Vote".to_string()),
+        (format!("{}[description]", question_data_prefix), spec.description.clone()),
+        (format!("{}[multiplechoice]", question_data_prefix), (matches!(spec.kind, QuestionnaireKind::MultipleChoice) as u8).to_string()),
+    ];
+    form.extend(spec.options.iter().map(|option| (format!("{}[options][]", question_data_prefix), option.clone())));
+    form.extend(range_params.iter().map(|(key, value)| (key.to_string(), value.to_string())));
+    let response = client.post(QUESTIONNAIRE_EDIT_URL)
+        .query(&range_params)
+        .form(&form)
+        .send_through(client)?;
+    if !response.status().is_success() {
+        bail!("Could not create questionnaire. Status code: {}", response.status());
+    }
+    flash::check(&parse_page(&response.text()?)?)?;
+    // The created questionnaire is the one, that is new in its range
+    let response = client.get(range_url).send_through(client)?;
+    let html = parse_page(&response.text()?)?;
+    let questionnaire_elem = find_created_questionnaire(&html, &existing_ids, &spec.title)
+        .context("Expected created questionnaire in range")?;
+    parse_questionnaire(questionnaire_elem, range.clone())
+}
+
+// Parses the voters from their profile links, which contain their avatar
+fn parse_voters<'a>(client: &StudIpClient, voter_elems: impl Iterator<Item = ElementRef<'a>>, reference_source: &ReferenceSource) -> anyhow::Result<Vec<User>> {
+    let avatar_selector = selector!("img.avatar-small");
+    let mut voters = vec![];
+    for voter_elem in voter_elems {
+        let username = get_username_from_link_element(voter_elem)?;
+        let avatar_elem = expect_one(voter_elem, avatar_selector, "avatar")?;
+        let display_name = avatar_elem.attr("title")
+            .context("Expected avatar display name")?
+            .to_string();
+        let avatar_src = avatar_elem
+            .attr("src")
+            .context("Expected avatar src")?;
+        let avatar_src = client.absolutize(avatar_src)?.to_string();
+
+        voters.push(User {
+            username,
+            display_name,
+            user_id: user_id_from_avatar_src(&avatar_src),
+            avatar_src: Some(avatar_src),
+            source: reference_source.clone(),
+        });
+    }
+    Ok(voters)
+}
+
+// The ids of the questionnaires, which are displayed in a range
+fn questionnaire_ids(html: &Html) -> HashSet<String> {
+    html.select(selector!("article[data-questionnaire_id]"))
+        .filter_map(|article| article.attr("data-questionnaire_id"))
+        .map(|id| id.to_string())
+        .collect()
+}
+
+// Finds the questionnaire, which is not one of the `existing_ids` and has exactly the `title`
+fn find_created_questionnaire<'a>(html: &'a Html, existing_ids: &HashSet<String>, title: &str) -> Option<ElementRef<'a>> {
+    let normalized_title = normalize_text(title);
+    html.select(selector!("article[data-questionnaire_id]"))
+        .filter(|article| article.attr("data-questionnaire_id").is_some_and(|id| !existing_ids.contains(id)))
+        .find(|article| article.select(selector!("header > h1 > a")).next()
+            .is_some_and(|title_elem| normalize_text(&title_elem.text().collect::<String>()) == normalized_title))
+}
+
 /// Parses a single [`Questionnaire`] from html, using the given [`ReferenceSource`]
 pub fn parse_questionnaire(element: ElementRef, reference_source: ReferenceSource) -> anyhow::Result<Questionnaire> {
     // Parse header
-    let title_selector = Selector::parse("header > h1 > a").unwrap();
-    let author_selector = Selector::parse("header > nav > a").unwrap();
-    let creation_date_selector = Selector::parse("header > nav > span:not([title])").unwrap();
-    let number_of_answers_selector = Selector::parse("header > nav > span[title*=\"antworten\" i], header > nav span[title*=\"answers\" i]").unwrap();
+    let title_selector = selector!("header > h1 > a");
+    let author_selector = selector!("header > nav > a");
+    let creation_date_selector = selector!("header > nav > span:not([title])");
+    let number_of_answers_selector = selector!("header > nav > span[title*=\"antworten\" i], header > nav span[title*=\"answers\" i]");
     let id = element
         .value()
         .attr("data-questionnaire_id")
         .context("Expected questionnaire id")?
         .to_string();
-    let title = element
-        .select(&title_selector)
-        .next()
-        .context("Expected title")?
+    let title = expect_one(element, title_selector, "title")?
         .text()
         .collect::<String>()
         .trim()
         .to_string();
-    let author = parse_simple_user(
-        element.select(&author_selector)
-            .next()
-            .context("Expected author")?
-    )?;
-    let creation_date_string = element.select(&creation_date_selector)
-        .next()
-        .context("Expected creation date")?
+    let author = parse_simple_user(expect_one(element, author_selector, "author")?)?;
+    let creation_date_string = expect_one(element, creation_date_selector, "creation date")?
         .text()
         .collect::<String>()
         .trim()
         .to_string();
     let creation_date = NaiveDate::parse_from_str(&creation_date_string, "%d.%m.%Y")?;
-    let number_of_answers = element
-        .select(&number_of_answers_selector)
-        .next()
-        .context("Expected number of answers")?
+    let number_of_answers = expect_one(element, number_of_answers_selector, "number of answers")?
         .text()
         .collect::<String>()
         .trim()
         .replace('.', "")
         .parse::<usize>()?;
     // Parse content (description, and options, also the questionnaire kind)
-    let description_selector = Selector::parse("article .description").unwrap();
-    let description = element
-        .select(&description_selector)
-        .next()
-        .context("Expected description")?
+    let description_selector = selector!("article .description");
+    let description = expect_one(element, description_selector, "description")?
         .text()
         .collect::<String>()
         .trim()
         .to_string();
-    let options_selector = Selector::parse("article .questionnaire_answer ul.clean label").unwrap();
-    let options_value_selector = Selector::parse("input[value]").unwrap();
+    let options_selector = selector!("article .questionnaire_answer ul.clean label");
+    let options_value_selector = selector!("input[value]");
     let mut options = vec![];
     let mut kind = QuestionnaireKind::SingleChoice;
-    for option_elem in element.select(&options_selector) {
+    for option_elem in element.select(options_selector) {
         let text = option_elem.text()
             .collect::<String>()
             .trim()
             .to_string();
-        let input_elem = option_elem.select(&options_value_selector)
-            .next()
-            .context("Expected option input")?;
+        let input_elem = expect_one(option_elem, options_value_selector, "option input")?;
         let value : usize = input_elem.attr("value")
             .unwrap() // We can do this because the selector only selects elements with a value
             .to_string()
@@ -235,17 +505,15 @@ pub fn parse_questionnaire(element: ElementRef, reference_source: ReferenceSourc
             text,
             value,
             n_voters: 0,
+            percent: None,
             voters: None,
         });
     }
     // Sort options, because the order in the html is not guaranteed
     options.sort_by_key(|option| option.value);
     // Parse terms
-    let terms_selector = Selector::parse("section .terms").unwrap();
-    let terms = element
-        .select(&terms_selector)
-        .next()
-        .context("Expected terms")?
+    let terms_selector = selector!("section .terms");
+    let terms = expect_one(element, terms_selector, "terms")?
         .text()
         .collect::<String>()
         .trim()
@@ -263,4 +531,190 @@ pub fn parse_questionnaire(element: ElementRef, reference_source: ReferenceSourc
         creation_date,
         options,
     })
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_user(username: &str, display_name: &str) -> User {
+        User {
+            display_name: display_name.to_string(),
+            username: username.to_string(),
+            avatar_src: None,
+            source: ReferenceSource::Unspecified,
+            user_id: None,
+        }
+    }
+
+    fn test_questionnaire(total_voters: usize, options: Vec<QuestionnaireOption>) -> Questionnaire {
+        Questionnaire {
+            id: "q1".to_string(),
+            reference_source: ReferenceSource::Unspecified,
+            title: "Lunch".to_string(),
+            description: "".to_string(),
+            author: test_user("author", "Author"),
+            kind: QuestionnaireKind::SingleChoice,
+            terms: "".to_string(),
+            total_voters,
+            creation_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            options,
+        }
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let questionnaire = test_questionnaire(4, vec![
+            QuestionnaireOption { text: "Pizza, Pasta".to_string(), value: 0, n_voters: 3, percent: None, voters: None },
+            QuestionnaireOption { text: "Salad".to_string(), value: 1, n_voters: 1, percent: None, voters: None },
+        ]);
+        let csv = questionnaire.export_results(ResultExport::Csv).unwrap();
+        assert_eq!(csv, "text,value,n_voters,percentage\n\"Pizza, Pasta\",0,3,75.00\nSalad,1,1,25.00\n");
+    }
+
+    #[test]
+    fn test_export_csv_long() {
+        let questionnaire = test_questionnaire(2, vec![
+            QuestionnaireOption { text: "Yes".to_string(), value: 0, n_voters: 2, percent: None, voters: Some(vec![test_user("jdoe", "Doe, John"), test_user("mmu", "Max")]) },
+            QuestionnaireOption { text: "No".to_string(), value: 1, n_voters: 0, percent: None, voters: None },
+        ]);
+        let csv = questionnaire.export_results(ResultExport::CsvLong).unwrap();
+        assert_eq!(csv, "text,value,username,display_name\nYes,0,jdoe,\"Doe, John\"\nYes,0,mmu,Max\n");
+    }
+
+    #[test]
+    fn test_export_zero_voters() {
+        let questionnaire = test_questionnaire(0, vec![
+            QuestionnaireOption { text: "Yes".to_string(), value: 0, n_voters: 0, percent: None, voters: None },
+        ]);
+        let csv = questionnaire.export_results(ResultExport::Csv).unwrap();
+        assert_eq!(csv, "text,value,n_voters,percentage\nYes,0,0,0.00\n");
+        let json: serde_json::Value = serde_json::from_str(&questionnaire.export_results(ResultExport::Json).unwrap()).unwrap();
+        assert_eq!(json["options"][0]["percentage"], 0.0);
+        assert_eq!(json["total_voters"], 0);
+    }
+
+    #[test]
+    fn test_parse_results_skips_header_and_footer_rows() {
+        let mut questionnaire = test_questionnaire(0, vec![
+            QuestionnaireOption { text: "Pizza".to_string(), value: 0, n_voters: 0, percent: None, voters: None },
+            QuestionnaireOption { text: "Salad".to_string(), value: 1, n_voters: 0, percent: None, voters: None },
+        ]);
+        // The rows are in a different order than the options, surrounded by a header and a summary row
+        let html = Html::parse_document(r#"
+            <table class="default">
+                <thead><tr><th>Answer</th><th>Votes</th></tr></thead>
+                <tbody>
+                    <tr>
+                        <td width="70%">
+                            <strong> salad </strong>
+                            <a href="https://studip.example.com/dispatch.php/profile?username=jdoe">
+                                <img class="avatar-small" title="John Doe" src="https://studip.example.com/pictures/user/jdoe_small.png">
+                            </a>
+                        </td>
+                        <td>(33% | 1/3)</td>
+                    </tr>
+                    <tr>
+                        <td width="70%"><strong>Pizza</strong></td>
+                        <td>(67% | 2/3)</td>
+                    </tr>
+                    <tr>
+                        <td width="70%"><strong>Soup</strong></td>
+                        <td>(0% | 0/3)</td>
+                    </tr>
+                </tbody>
+                <tfoot><tr><td colspan="2">3 answers</td></tr></tfoot>
+            </table>
+        "#);
+        questionnaire.parse_results(&StudIpClient::default(), &html).unwrap();
+        assert_eq!(questionnaire.total_voters, 3);
+        assert_eq!(questionnaire.options.len(), 3);
+        assert_eq!(questionnaire.options[0].text, "Pizza");
+        assert_eq!(questionnaire.options[0].n_voters, 2);
+        assert!(questionnaire.options[0].voters.is_none());
+        assert_eq!(questionnaire.options[1].text, "Salad");
+        assert_eq!(questionnaire.options[1].n_voters, 1);
+        assert_eq!(questionnaire.options[1].voters.as_ref().unwrap()[0].username, "jdoe");
+        assert_eq!(questionnaire.options[2].text, "Soup");
+        assert_eq!(questionnaire.options[2].value, 2);
+    }
+
+    #[test]
+    fn test_voters() {
+        let mut questionnaire = test_questionnaire(0, vec![]);
+        questionnaire.kind = QuestionnaireKind::MultipleChoice;
+        questionnaire.reference_source = ReferenceSource::Course("c1".into());
+        // John voted for both options
+        let html = Html::parse_document(r#"
+            <table class="default">
+                <tr>
+                    <td width="70%">
+                        <strong>Monday</strong>
+                        <a href="https://studip.example.com/dispatch.php/profile?username=jdoe"><img class="avatar-small" title="John Doe" src="/pictures/user/jdoe_small.png"></a>
+                        <a href="https://studip.example.com/dispatch.php/profile?username=mmustermann"><img class="avatar-small" title="Max Mustermann" src="/pictures/user/mmustermann_small.png"></a>
+                    </td>
+                    <td>(67% | 2/3)</td>
+                </tr>
+                <tr>
+                    <td width="70%">
+                        <strong>Tuesday</strong>
+                        <a href="https://studip.example.com/dispatch.php/profile?username=jdoe"><img class="avatar-small" title="John Doe" src="/pictures/user/jdoe_small.png"></a>
+                        <a href="https://studip.example.com/dispatch.php/profile?username=emuster"><img class="avatar-small" title="Erika Muster" src="/pictures/user/emuster_small.png"></a>
+                    </td>
+                    <td>(67% | 2/3)</td>
+                </tr>
+            </table>
+        "#);
+        let client = crate::StudIpClientBuilder::new("studip.example.com").build(crate::auth::AuthMethod::Session).unwrap();
+        questionnaire.parse_results(&client, &html).unwrap();
+        let usernames = |users: Vec<&User>| users.into_iter().map(|user| user.username.clone()).collect::<Vec<_>>();
+        assert_eq!(usernames(questionnaire.all_voters()), vec!["jdoe", "mmustermann", "emuster"]);
+        assert!(questionnaire.all_voters().iter().all(|voter| voter.source == ReferenceSource::Course("c1".into())));
+
+        let members = vec![test_user("jdoe", "John Doe"), test_user("akeller", "Anna Keller"), test_user("emuster", "Erika Muster")];
+        assert_eq!(usernames(questionnaire.non_voters(&members)), vec!["akeller"]);
+    }
+
+    #[test]
+    fn test_expand_truncated_voters() {
+        let server = crate::mock::MockServer::start();
+        server.route("GET", "/dispatch.php/questionnaire/evaluate/q1", 200, include_str!("../testdata/questionnaire/truncated.html"))
+            .route("GET", "/dispatch.php/questionnaire/evaluate/q1?option=0", 200, include_str!("../testdata/questionnaire/voters.html"));
+        let mut questionnaire = test_questionnaire(0, vec![]);
+        questionnaire.query_results(&server.client()).unwrap();
+        assert_eq!(questionnaire.total_voters, 8);
+        let monday = &questionnaire.options[0];
+        assert_eq!((monday.n_voters, monday.percent), (5, Some(63.0)));
+        let usernames = monday.voters.iter().flatten().map(|voter| voter.username.as_str()).collect::<Vec<_>>();
+        assert_eq!(usernames, vec!["u1", "u2", "u3", "u4", "u5"]);
+        // Complete lists are not expanded
+        assert_eq!(questionnaire.options[1].voters.as_ref().unwrap().len(), 3);
+        assert_eq!(questionnaire.options[1].percent, Some(38.0));
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].path, "/dispatch.php/questionnaire/evaluate/q1?option=0&all_voters=1");
+        assert_eq!(requests[1].header("X-Requested-With"), Some("XMLHttpRequest"));
+    }
+
+    #[test]
+    fn test_closes_at() {
+        let mut questionnaire = test_questionnaire(0, vec![]);
+        questionnaire.terms = "Die Ergebnisse sind sichtbar ab dem 01.03.2025. Die Befragung endet am 12.03.2025 um 14:00 Uhr.".to_string();
+        assert_eq!(questionnaire.closes_at(), NaiveDate::from_ymd_opt(2025, 3, 12).unwrap().and_hms_opt(14, 0, 0));
+        questionnaire.terms = "Die Ergebnisse sind sofort sichtbar.".to_string();
+        assert_eq!(questionnaire.closes_at(), None);
+    }
+
+    #[test]
+    fn test_find_created_questionnaire() {
+        let article = |id: &str, title: &str| format!(r#"<article class="studip" data-questionnaire_id="{}"><header><h1><a href="">{}</a></h1></header></article>"#, id, title);
+        let before = Html::parse_document(&[article("old", "Umfrage 2"), article("other", "Umfrage")].concat());
+        let existing_ids = questionnaire_ids(&before);
+        let after = Html::parse_document(&[article("old", "Umfrage 2"), article("new", "umfrage"), article("other", "Umfrage")].concat());
+        // Only new questionnaires with exactly the title are considered, not ones, that contain it
+        let created = find_created_questionnaire(&after, &existing_ids, "Umfrage").unwrap();
+        assert_eq!(created.attr("data-questionnaire_id"), Some("new"));
+        assert!(find_created_questionnaire(&before, &existing_ids, "Umfrage").is_none());
+        assert!(find_created_questionnaire(&after, &existing_ids, "Umfrage 3").is_none());
+    }
+}
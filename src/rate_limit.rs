@@ -0,0 +1,126 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Spaces the requests of one or more [`StudIpClient`](crate::StudIpClient)s, so that they do not overload the server \
+/// Every client owns a limiter behind an [`Arc`](std::sync::Arc), which all of its handles share. Other clients can share it too
+/// (see [`StudIpClientBuilder::rate_limiter()`](crate::StudIpClientBuilder::rate_limiter())).
+/// With a jitter, each interval is lengthened by a random duration up to it, so that the requests do not follow a fixed pattern.
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    jitter: Duration,
+    /// The point in time, at which the next request may be sent
+    next_free: Mutex<Option<Instant>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_INTERVAL)
+    }
+}
+
+impl RateLimiter {
+
+    /// The minimum interval between two requests, that is used by default
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(150);
+
+    /// Creates a limiter, which leaves at least the `interval` between two requests
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            jitter: Duration::ZERO,
+            next_free: Default::default(),
+        }
+    }
+
+    /// Lengthens every interval by a random duration between zero and the `jitter`
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    /// Reserves the next free slot for a request at `now` and returns how long to wait for it
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut next_free = self.next_free.lock().unwrap();
+        let start = next_free.map_or(now, |next_free| next_free.max(now));
+        let jitter = self.jitter.mul_f64(fastrand::f64());
+        *next_free = Some(start + self.interval + jitter);
+        start - now
+    }
+
+    /// Waits until the next request may be sent \
+    /// Every request of the clients is created through this, so only requests sent in another way
+    /// (e.g. with [`Client::execute()`](reqwest::blocking::Client::execute())) need to call it.
+    pub fn acquire(&self) {
+        let wait = self.reserve(Instant::now());
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_jitter_bounds() {
+        let limiter = RateLimiter::new(Duration::from_millis(100)).with_jitter(Duration::from_millis(50));
+        let now = Instant::now();
+        assert_eq!(limiter.reserve(now), Duration::ZERO);
+        let mut previous = Duration::ZERO;
+        let intervals = (0..200).map(|_| {
+            let wait = limiter.reserve(now);
+            let interval = wait - previous;
+            previous = wait;
+            interval
+        }).collect::<Vec<_>>();
+        assert!(intervals.iter().all(|interval| (Duration::from_millis(100)..=Duration::from_millis(150)).contains(interval)), "{:?}", intervals);
+        // The intervals actually vary
+        assert!(intervals.iter().any(|interval| *interval != intervals[0]));
+        // Idle time does not accumulate into a burst
+        let later = now + Duration::from_secs(60);
+        assert_eq!(limiter.reserve(later), Duration::ZERO);
+        assert!(limiter.reserve(later) >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_shared_budget() {
+        let server = MockServer::start();
+        server.route("GET", "/", 200, "");
+        let first = Arc::new(server.client_builder()
+            .request_max_speed(Duration::from_millis(100))
+            .build(crate::auth::AuthMethod::Session)
+            .unwrap());
+        let second = server.client_builder()
+            .rate_limiter(first.rate_limiter())
+            .build(crate::auth::AuthMethod::Session)
+            .unwrap();
+        assert_eq!(second.rate_limiter().interval(), Duration::from_millis(100));
+
+        let handles = [first.clone(), first.clone(), Arc::new(second)].map(|client| std::thread::spawn(move || {
+            for _ in 0..2 {
+                client.get("https://127.0.0.1/").send().unwrap();
+            }
+        }));
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let mut requests = server.requests();
+        requests.sort_by_key(|request| request.received_at);
+        assert_eq!(requests.len(), 6);
+        // All handles and the client sharing the limiter draw from the same budget
+        assert!(requests.windows(2).all(|pair| pair[1].received_at - pair[0].received_at >= Duration::from_millis(90)));
+    }
+}
@@ -0,0 +1,184 @@
+//! Recording of the requests and responses of a [`StudIpClient`](crate::StudIpClient), to reproduce parser failures on other installations \
+//! Every exchange is written as a numbered JSON file (e.g. `0001.json`), next to a file with the response body (e.g. `0001.html`).
+//! Only allowlisted headers are kept, cookies are never written and credentials and session tokens are redacted.
+
+use std::path::PathBuf;
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// The response headers, that are written to recordings, all others (especially cookies) are dropped
+const ALLOWED_HEADERS: [&str; 9] = [
+    "content-type",
+    "content-length",
+    "content-disposition",
+    "location",
+    "last-modified",
+    "etag",
+    "cache-control",
+    "date",
+    "expires",
+];
+/// Query parameters, which values are redacted
+const REDACTED_PARAMS: [&str; 8] = ["security_token", "samlresponse", "relaystate", "password", "access_token", "refresh_token", "token", "ticket"];
+const REDACTED: &str = "REDACTED";
+
+static SECURITY_TOKEN_INPUT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(name="security_token"\s+value=")[^"]*"#).unwrap());
+static SAML_INPUT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(?i)(name="(?:SAMLResponse|RelayState)"\s+value=")[^"]*"#).unwrap());
+
+/// A recorded request and its response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RecordedExchange {
+    pub method: String,
+    /// The requested url including the (redacted) query
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// The name of the file with the response body, relative to the recording directory
+    pub body_file: String,
+}
+
+/// Writes the exchanges of a client into a directory
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    dir: PathBuf,
+    n_recorded: usize,
+}
+
+impl Recorder {
+
+    pub fn new(dir: PathBuf) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir).with_context(|| format!("Could not create {}", dir.display()))?;
+        Ok(Self { dir, n_recorded: 0 })
+    }
+
+    /// Writes a single exchange, redacting its credentials and session tokens
+    pub fn record(&mut self, method: &Method, url: &Url, status: StatusCode, headers: &HeaderMap, body: &[u8]) -> anyhow::Result<()> {
+        self.n_recorded += 1;
+        let content_type = headers.get("Content-Type")
+            .and_then(|content_type| content_type.to_str().ok())
+            .unwrap_or_default();
+        let extension = if content_type.contains("html") {
+            "html"
+        } else if content_type.contains("json") {
+            "json.body"
+        } else {
+            "bin"
+        };
+        let body_file = format!("{:04}.{}", self.n_recorded, extension);
+        let body = match extension {
+            "bin" => body.to_vec(),
+            _ => redact_body(&String::from_utf8_lossy(body)).into_bytes(),
+        };
+        let exchange = RecordedExchange {
+            method: method.to_string(),
+            url: redact_url(url).to_string(),
+            status: status.as_u16(),
+            headers: headers.iter()
+                .filter(|(name, _)| ALLOWED_HEADERS.contains(&name.as_str()))
+                .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+                .map(|(name, value)| match name.as_str() {
+                    "location" => {
+                        let value = Url::parse(&value).map(|url| redact_url(&url).to_string()).unwrap_or(value);
+                        (name, value)
+                    },
+                    _ => (name, value),
+                })
+                .collect(),
+            body_file: body_file.clone(),
+        };
+        std::fs::write(self.dir.join(&body_file), body)?;
+        std::fs::write(self.dir.join(format!("{:04}.json", self.n_recorded)), serde_json::to_string_pretty(&exchange)?)?;
+        Ok(())
+    }
+
+}
+
+/// Reads all exchanges of a recording, ordered by their number, together with their bodies
+#[cfg(any(test, feature = "mock"))]
+pub(crate) fn read_recording(dir: &std::path::Path) -> anyhow::Result<Vec<(RecordedExchange, Vec<u8>)>> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.len() == 9 && name.ends_with(".json")));
+    paths.sort();
+    paths.into_iter().map(|path| {
+        let exchange: RecordedExchange = serde_json::from_str(&std::fs::read_to_string(&path)?)
+            .with_context(|| format!("Could not parse {}", path.display()))?;
+        let body = std::fs::read(dir.join(&exchange.body_file))?;
+        Ok((exchange, body))
+    }).collect()
+}
+
+fn redact_url(url: &Url) -> Url {
+    let mut redacted = url.clone();
+    if url.query().is_none() {
+        return redacted;
+    }
+    redacted.query_pairs_mut()
+        .clear()
+        .extend_pairs(url.query_pairs().map(|(key, value)| {
+            let value = if REDACTED_PARAMS.contains(&key.to_lowercase().as_str()) {REDACTED.into()} else {value};
+            (key, value)
+        }));
+    redacted
+}
+
+fn redact_body(body: &str) -> String {
+    let body = SECURITY_TOKEN_INPUT_REGEX.replace_all(body, format!("${{1}}{}", REDACTED));
+    SAML_INPUT_REGEX.replace_all(&body, format!("${{1}}{}", REDACTED)).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+    use crate::SendThrough;
+
+    #[test]
+    fn test_redaction() {
+        let url = Url::parse("https://studip.example.com/dispatch.php/start?cid=c1&security_token=abc%3D&SAMLResponse=xyz").unwrap();
+        assert_eq!(redact_url(&url).as_str(), "https://studip.example.com/dispatch.php/start?cid=c1&security_token=REDACTED&SAMLResponse=REDACTED");
+        let body = r#"<input type="hidden" name="security_token" value="abc="><input name="RelayState" value="cookie:123">"#;
+        assert_eq!(redact_body(body), r#"<input type="hidden" name="security_token" value="REDACTED"><input name="RelayState" value="REDACTED">"#);
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let page = r#"<html><body><form><input type="hidden" name="security_token" value="secret"></form><p>Hallo</p></body></html>"#;
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/start", 200, page);
+        let dir = std::env::temp_dir().join(format!("stud_ip_record_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let client = server.client();
+        client.record_to(dir.clone()).unwrap();
+        let response = client.get("https://studip.example.com/dispatch.php/start?security_token=secret&page=2").send_through(&client).unwrap();
+        // The caller still gets the unredacted response
+        assert!(response.status().is_success());
+        assert_eq!(response.text().unwrap(), page);
+
+        let recording = read_recording(&dir).unwrap();
+        assert_eq!(recording.len(), 1);
+        let (exchange, body) = &recording[0];
+        assert_eq!(exchange.method, "GET");
+        assert!(exchange.url.ends_with("/dispatch.php/start?security_token=REDACTED&page=2"), "{}", exchange.url);
+        assert_eq!(exchange.status, 200);
+        assert_eq!(exchange.body_file, "0001.html");
+        assert!(exchange.headers.iter().all(|(name, _)| ALLOWED_HEADERS.contains(&name.as_str())));
+        assert!(!exchange.headers.iter().any(|(name, _)| name == "connection"));
+        let body = String::from_utf8(body.clone()).unwrap();
+        assert!(!body.contains("secret"));
+        assert!(body.contains(r#"value="REDACTED""#));
+
+        let replay_server = MockServer::start();
+        replay_server.replay(&dir).unwrap();
+        let replay_client = replay_server.client();
+        let replayed = replay_client.get("https://studip.example.com/dispatch.php/start?security_token=REDACTED&page=2").send().unwrap();
+        assert_eq!(replayed.text().unwrap(), body);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
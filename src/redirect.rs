@@ -0,0 +1,69 @@
+//! The redirect policy of a [`StudIpClient`](crate::StudIpClient), which caps the number of redirects and detects loops between the SP and the IdP early
+
+use std::sync::{Arc, Mutex};
+use reqwest::redirect::Policy;
+use url::Url;
+use crate::error::StudIpError;
+
+/// The maximum number of redirects per request, if not configured otherwise
+pub(crate) const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// The urls, through which the last redirected request went, in order
+pub(crate) type RedirectChain = Arc<Mutex<Vec<Url>>>;
+
+/// Follows at most `max_redirects` redirects per request and records the urls into the `chain`
+pub(crate) fn policy(max_redirects: usize, chain: RedirectChain) -> Policy {
+    Policy::custom(move |attempt| {
+        let visited = attempt.previous().iter()
+            .chain([attempt.url()])
+            .cloned()
+            .collect::<Vec<_>>();
+        *chain.lock().unwrap() = visited.clone();
+        if is_loop(&visited) {
+            return attempt.error(StudIpError::SsoLoop { chain: to_strings(&visited) });
+        }
+        if attempt.previous().len() > max_redirects {
+            return attempt.error(StudIpError::TooManyRedirects { chain: to_strings(&visited) });
+        }
+        attempt.follow()
+    })
+}
+
+fn to_strings(urls: &[Url]) -> Vec<String> {
+    urls.iter().map(|url| url.to_string()).collect()
+}
+
+// The url without its query, which usually changes on every round of a loop (e.g. the SAMLRequest)
+fn without_query(url: &Url) -> &str {
+    &url[..url::Position::AfterPath]
+}
+
+/// Whether the chain ends with the same two urls alternating (A -> B -> A -> B)
+fn is_loop(chain: &[Url]) -> bool {
+    let [.., a, b, c, d] = chain else {
+        return false;
+    };
+    let (a, b, c, d) = (without_query(a), without_query(b), without_query(c), without_query(d));
+    a != b && a == c && b == d
+}
+
+/// Formats the chain as "a -> b -> c"
+pub(crate) fn format_chain<S: AsRef<str>>(chain: &[S]) -> String {
+    chain.iter().map(|url| url.as_ref()).collect::<Vec<_>>().join(" -> ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_loop() {
+        let urls = |paths: &[&str]| paths.iter()
+            .map(|path| Url::parse(&format!("https://studip.example.com{}", path)).unwrap())
+            .collect::<Vec<_>>();
+        assert!(is_loop(&urls(&["/start", "/Shibboleth.sso/Login?n=1", "/idp/sso", "/Shibboleth.sso/Login?n=2", "/idp/sso"])));
+        assert!(!is_loop(&urls(&["/Shibboleth.sso/Login", "/idp/sso", "/Shibboleth.sso/Login"])));
+        assert!(!is_loop(&urls(&["/a", "/a", "/a", "/a"])));
+        assert!(!is_loop(&urls(&["/a", "/b", "/c", "/d"])));
+    }
+}
@@ -2,18 +2,27 @@ use anyhow::Context;
 use serde::{Deserialize, Serialize};
 use url::Url;
 use crate::course::COURSE_URL;
+use crate::ids::{CourseId, InstituteId};
+use crate::institute::INSTITUTE_URL;
 use crate::user::PROFILE_URL;
 
 pub(crate) const START_URL: &str = "https://studip.example.com/dispatch.php/start";
+/// The listing of the news of the fixed "studip" range, which all system-wide announcements belong to
+pub(crate) const GLOBAL_NEWS_URL: &str = "https://studip.example.com/dispatch.php/news/display/studip";
 
 /// Stores source extra information for a piece of information \
-/// Sometimes necessary to make correct queries
+/// Sometimes necessary to make correct queries \
+/// New variants are only ever appended, so previously serialized values stay deserializable.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ReferenceSource {
     Unspecified,
     StartPage,
-    Course(String),
+    Course(CourseId),
+    /// The profile of a user, by their username
     Profile(String),
+    Institute(InstituteId),
+    /// System-wide announcements, which are displayed on the start page and listed by [global_news()](crate::news::global_news())
+    System,
 }
 
 impl ReferenceSource {
@@ -21,8 +30,9 @@ impl ReferenceSource {
     /// Gets additional query parameters for the reference source
     pub fn get_additional_query_params(&self) -> Option<(&'static str, &str)> {
         match self {
-            ReferenceSource::Unspecified | ReferenceSource::StartPage => None,
+            ReferenceSource::Unspecified | ReferenceSource::StartPage | ReferenceSource::System => None,
             ReferenceSource::Course(id) => Some(("cid", id)),
+            ReferenceSource::Institute(id) => Some(("cid", id)),
             ReferenceSource::Profile(id) => Some(("username", id))
         }
     }
@@ -32,6 +42,7 @@ impl ReferenceSource {
         match self {
             ReferenceSource::Unspecified => None,
             ReferenceSource::StartPage => Some(Url::parse(START_URL).unwrap()),
+            ReferenceSource::System => Some(Url::parse(GLOBAL_NEWS_URL).unwrap()),
             ReferenceSource::Course(id) => {
                 let mut url = Url::parse(COURSE_URL).unwrap();
                 url.query_pairs_mut().append_pair("cid", id);
@@ -42,6 +53,11 @@ impl ReferenceSource {
                 url.query_pairs_mut().append_pair("username", username);
                 Some(url)
             }
+            ReferenceSource::Institute(id) => {
+                let mut url = Url::parse(INSTITUTE_URL).unwrap();
+                url.query_pairs_mut().append_pair("cid", id);
+                Some(url)
+            }
         }
     }
 
@@ -55,4 +71,28 @@ impl TryFrom<&ReferenceSource> for Url {
         value.try_get_url()
             .context("Could not construct url from reference source")
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_backwards_compatible() {
+        let old = r#"["Unspecified","StartPage",{"Course":"c1"},{"Profile":"jdoe"}]"#;
+        let sources: Vec<ReferenceSource> = serde_json::from_str(old).unwrap();
+        assert_eq!(sources[2], ReferenceSource::Course("c1".into()));
+        assert_eq!(serde_json::to_string(&sources).unwrap(), old);
+        let new = vec![ReferenceSource::Institute("i1".into()), ReferenceSource::System];
+        let serialized = serde_json::to_string(&new).unwrap();
+        assert_eq!(serialized, r#"[{"Institute":"i1"},"System"]"#);
+        assert_eq!(serde_json::from_str::<Vec<ReferenceSource>>(&serialized).unwrap(), new);
+    }
+
+    #[test]
+    fn test_institute_url() {
+        let source = ReferenceSource::Institute("i1".into());
+        assert_eq!(source.get_additional_query_params(), Some(("cid", "i1")));
+        assert_eq!(source.try_get_url().unwrap().as_str(), "https://studip.example.com/dispatch.php/institute/overview?cid=i1");
+        assert_eq!(ReferenceSource::System.try_get_url().unwrap().as_str(), GLOBAL_NEWS_URL);
+    }
+}
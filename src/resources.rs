@@ -0,0 +1,261 @@
+use anyhow::{bail, Context};
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{ElementRef, Html};
+use serde::{Deserialize, Serialize};
+use crate::ids::{CourseId, RoomId};
+use crate::search::{global_search, strip_markings, SearchFilter};
+use crate::util::{local_to_utc, parse_localized_date, parse_localized_date_time, parse_page, selector};
+use crate::{SendThrough, StudIpClient};
+
+const BOOKING_LIST_URL: &str = "https://studip.example.com/dispatch.php/resources/room_planning/booking_list";
+/// The number of resources requested from the search
+const ROOM_SEARCH_LIMIT: usize = 50;
+
+static SEATS_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)(?P<seats>\d+)\s*(?:sitzplätze|plätze|seats)").unwrap());
+static TIME_RANGE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?P<start_hour>\d{1,2}):(?P<start_minute>\d{2})\s*(?:-|–|bis)\s*(?P<end_hour>\d{1,2}):(?P<end_minute>\d{2})").unwrap());
+/// Matches links to a room, in the path (e.g. "resources/room/index/<id>") or the query (e.g. "resource_id=<id>")
+static ROOM_LINK_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?:[?&](?:resource_id|room_id)=|/resources/(?:room|resource)/\w+/|/booking_plan/|/booking_list/)(?P<id>[0-9a-zA-Z]+)").unwrap());
+static COURSE_LINK_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"[?&](?:cid|sem_id)=(?P<id>[^&#]+)").unwrap());
+
+/// A room (or another resource), as found by [`find_room()`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Room {
+    pub id: RoomId,
+    pub name: String,
+    /// The building, that contains the room
+    pub building: Option<String>,
+    pub seats: Option<u32>,
+}
+
+/// A room, as referenced by a [CourseDate](crate::course::CourseDate), the [CourseDetails](crate::course::CourseDetails) or a [CalendarEntry](crate::calendar::CalendarEntry) \
+/// The id is only known, if the room links to its resource, which allows looking up its bookings with [`room_schedule()`].
+/// Otherwise only the free text is kept.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomRef {
+    pub id: Option<RoomId>,
+    pub name: String,
+}
+
+impl RoomRef {
+
+    /// The room of a free text, `None` if the text is empty
+    pub(crate) fn from_text(text: &str) -> Option<Self> {
+        let name = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!name.is_empty()).then_some(Self { id: None, name })
+    }
+
+    /// The room of an element, using the first link to a resource or the text of the element otherwise
+    pub(crate) fn from_element(element: ElementRef) -> Option<Self> {
+        for link in element.select(selector!("a[href]")) {
+            if let Some(captures) = ROOM_LINK_REGEX.captures(link.attr("href").unwrap()) {
+                return Some(Self {
+                    id: Some(captures["id"].into()),
+                    name: cell_text(link),
+                });
+            }
+        }
+        Self::from_text(&element.text().collect::<String>())
+    }
+
+    /// The room of a html fragment or a free text, like [`RoomRef::from_element()`]
+    pub(crate) fn from_html(html: &str) -> Option<Self> {
+        if !html.contains('<') {
+            return Self::from_text(html);
+        }
+        Self::from_element(Html::parse_fragment(html).root_element())
+    }
+
+}
+
+/// A booking of a room, as returned by [`room_schedule()`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Booking {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// The description of the booking, usually the name of the course
+    pub title: String,
+    /// The course, for which the room is booked
+    pub course_id: Option<CourseId>,
+}
+
+/// Finds rooms by (a part of) their name, using the resource search \
+/// The building and the number of seats are read from the additional info of the search entry, if present.
+pub fn find_room(client: &StudIpClient, query: &str) -> anyhow::Result<Vec<Room>> {
+    let result = global_search(client, query, ROOM_SEARCH_LIMIT, &SearchFilter::Resources)?;
+    Ok(result.resources
+        .map(|category| category.content)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| {
+            let additional = strip_markings(&entry.additional);
+            let seats = SEATS_REGEX.captures(&additional).and_then(|captures| captures["seats"].parse().ok());
+            let building = SEATS_REGEX.replace(&additional, "")
+                .trim_matches(|c: char| c.is_whitespace() || c == ',' || c == ';' || c == '·')
+                .to_string();
+            Room {
+                id: entry.id.into(),
+                name: strip_markings(&entry.name).trim().to_string(),
+                building: Some(building).filter(|building| !building.is_empty()),
+                seats,
+            }
+        })
+        .collect())
+}
+
+/// Returns the bookings of the room in the week (Monday to Sunday), that contains the date `week` \
+/// The bookings are read from the list view of the booking plan, as the layout of the grid view differs between installations.
+pub fn room_schedule(client: &StudIpClient, room_id: &RoomId, week: NaiveDate) -> anyhow::Result<Vec<Booking>> {
+    let monday = week - Days::new(week.weekday().num_days_from_monday() as u64);
+    let sunday = monday + Days::new(6);
+    let response = client.get(format!("{}/{}", BOOKING_LIST_URL, room_id))
+        .query(&[
+            ("begin", monday.format("%d.%m.%Y").to_string()),
+            ("end", sunday.format("%d.%m.%Y").to_string()),
+        ])
+        .send_through(client)?;
+    if !response.status().is_success() {
+        bail!("Could not get the booking plan of room {}. Status Code: {}", room_id, response.status());
+    }
+    let bookings = parse_booking_list(&parse_page(&response.text()?)?, client.timezone())?;
+    Ok(bookings.into_iter()
+        .filter(|booking| {
+            let start = booking.start.with_timezone(&client.timezone()).date_naive();
+            start >= monday && start <= sunday
+        })
+        .collect())
+}
+
+fn cell_text(element: ElementRef) -> String {
+    element.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The columns of the booking list, which are identified by their headers
+#[derive(Debug, Default)]
+struct Columns {
+    date: Option<usize>,
+    time: Option<usize>,
+    start: Option<usize>,
+    end: Option<usize>,
+    title: Option<usize>,
+}
+
+fn parse_columns(table: ElementRef) -> Columns {
+    let mut columns = Columns::default();
+    for (index, header) in table.select(selector!("thead th")).enumerate() {
+        let header = cell_text(header).to_lowercase();
+        let column = match header.as_str() {
+            "datum" | "date" | "tag" | "day" => &mut columns.date,
+            "zeit" | "time" | "uhrzeit" => &mut columns.time,
+            "beginn" | "start" | "von" => &mut columns.start,
+            "ende" | "end" | "bis" => &mut columns.end,
+            "titel" | "title" | "beschreibung" | "description" | "veranstaltung" | "course" => &mut columns.title,
+            _ => continue,
+        };
+        column.get_or_insert(index);
+    }
+    columns
+}
+
+// Lists either have a date and a time range column, or a start and an end column with both date and time
+fn parse_booking_list(html: &Html, timezone: Tz) -> anyhow::Result<Vec<Booking>> {
+    let Some(table) = html.select(selector!("#content table")).next() else {
+        if html.select(selector!(".messagebox_info")).next().is_some() {
+            return Ok(vec![]); // There are no bookings
+        }
+        bail!("Expected the booking list");
+    };
+    let columns = parse_columns(table);
+    let title_column = columns.title.context("Expected a title column in the booking list")?;
+    let mut bookings = vec![];
+    for row in table.select(selector!("tbody tr")) {
+        let cells = row.select(selector!("td")).collect::<Vec<_>>();
+        // Rows spanning the whole table (e.g. "Keine Buchungen") are skipped
+        if cells.len() <= title_column {
+            continue;
+        }
+        let text = |column: Option<usize>| column.and_then(|column| cells.get(column)).map(|cell| cell_text(*cell));
+        let (start, end) = match (columns.date, columns.time, columns.start, columns.end) {
+            (Some(_), Some(_), _, _) => {
+                let date_text = text(columns.date).unwrap_or_default();
+                let date = parse_localized_date(&date_text).with_context(|| format!("Expected a date, got {}", date_text))?;
+                let time_text = text(columns.time).unwrap_or_default();
+                let captures = TIME_RANGE_REGEX.captures(&time_text).with_context(|| format!("Expected a time range, got {}", time_text))?;
+                let time = |hour: &str, minute: &str| NaiveTime::from_hms_opt(captures[hour].parse().ok()?, captures[minute].parse().ok()?, 0);
+                let start = time("start_hour", "start_minute").context("Invalid start time")?;
+                let end = time("end_hour", "end_minute").context("Invalid end time")?;
+                (date.and_time(start), date.and_time(end))
+            },
+            (_, _, Some(_), Some(_)) => {
+                let parse = |column| {
+                    let date_time_text = text(column).unwrap_or_default();
+                    parse_localized_date_time(&date_time_text).with_context(|| format!("Expected a date, got {}", date_time_text))
+                };
+                (parse(columns.start)?, parse(columns.end)?)
+            },
+            _ => bail!("Expected date and time columns in the booking list"),
+        };
+        let course_id = row.select(selector!("a[href]"))
+            .filter_map(|link| COURSE_LINK_REGEX.captures(link.attr("href").unwrap()))
+            .map(|captures| CourseId::from(&captures["id"]))
+            .next();
+        bookings.push(Booking {
+            start: local_to_utc(start, timezone).context("Invalid start of booking")?,
+            end: local_to_utc(end, timezone).context("Invalid end of booking")?,
+            title: cell_text(cells[title_column]),
+            course_id,
+        });
+    }
+    Ok(bookings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use crate::mock::MockServer;
+    use crate::DEFAULT_TIMEZONE;
+
+    fn local(text: &str) -> DateTime<Utc> {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap().and_local_timezone(DEFAULT_TIMEZONE).unwrap().to_utc()
+    }
+
+    #[test]
+    fn test_find_room() {
+        let server = MockServer::start();
+        server.route_typed("GET", "/dispatch.php/globalsearch/find/50", 200, "application/json", include_str!("../testdata/resources/search.json"));
+        let rooms = find_room(&server.client(), "HS 1").unwrap();
+        assert_eq!(rooms, vec![
+            Room { id: "r1".into(), name: "HS 1".into(), building: Some("Hörsaalgebäude".into()), seats: Some(120) },
+            Room { id: "r2".into(), name: "HS 10".into(), building: None, seats: None },
+        ]);
+        assert!(server.requests()[0].path.contains("GlobalSearchResources"));
+    }
+
+    #[test]
+    fn test_room_schedule() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/resources/room_planning/booking_list/r1", 200, include_str!("../testdata/resources/booking_list.html"));
+        let client = server.client();
+        let bookings = room_schedule(&client, &"r1".into(), NaiveDate::from_ymd_opt(2024, 10, 16).unwrap()).unwrap();
+        assert_eq!(server.requests()[0].path, "/dispatch.php/resources/room_planning/booking_list/r1?begin=14.10.2024&end=20.10.2024");
+        assert_eq!(bookings, vec![
+            Booking { start: local("2024-10-14 10:00"), end: local("2024-10-14 12:00"), title: "Algorithmen".into(), course_id: Some("c1".into()) },
+            Booking { start: local("2024-10-17 08:15"), end: local("2024-10-17 09:45"), title: "Sperrzeit: Reinigung".into(), course_id: None },
+        ]);
+
+        // The print view lists the start and end of each booking
+        let html = Html::parse_document(r#"<div id="content"><table>
+            <thead><tr><th>Beginn</th><th>Ende</th><th>Beschreibung</th></tr></thead>
+            <tbody><tr><td>15.10.2024 14:00</td><td>15.10.2024 16:00</td><td><a href="/dispatch.php/course/details?sem_id=c2">Lineare Algebra</a></td></tr></tbody>
+        </table></div>"#);
+        let bookings = parse_booking_list(&html, DEFAULT_TIMEZONE).unwrap();
+        assert_eq!(bookings[0].end, local("2024-10-15 16:00"));
+        assert_eq!(bookings[0].course_id, Some("c2".into()));
+
+        let html = Html::parse_document(r#"<div id="content"><div class="messagebox messagebox_info">Keine Buchungen vorhanden</div></div>"#);
+        assert!(parse_booking_list(&html, DEFAULT_TIMEZONE).unwrap().is_empty());
+    }
+}
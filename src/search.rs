@@ -1,12 +1,23 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
 use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
+use regex::Regex;
 use reqwest::header::CONTENT_TYPE;
 use serde::{Deserialize, Serialize, Serializer};
 use serde::ser::SerializeMap;
 use serde_json::Value;
-use crate::institute::Institute;
+use url::Url;
+use crate::course_modules::file::download_file_by_id;
+use crate::error::WithUrl;
+use crate::institute::{Institute, INSTITUTE_URL};
 use crate::ref_source::ReferenceSource;
-use crate::StudIpClient;
-use crate::user::{get_username_from_url, User};
+use crate::{SendThrough, StudIpClient};
+use crate::course::{course_name_words, normalize_course_name, COURSE_URL};
+use crate::user::{get_username_from_url, User, PROFILE_URL};
+use crate::util::{decode_html_entities, expect_one, local_to_utc, normalize_text, parse_localized_date, parse_localized_date_time, parse_page, selector};
 
 /// The different ways in witch a Semester can be filtered in the search
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
@@ -42,7 +53,13 @@ pub enum SearchFilter {
     },
     Users,
     Institutions,
-    Messages
+    Messages,
+    Files {
+        semester: FilterSemester,
+    },
+    ForumPosts,
+    WikiPages,
+    Resources,
 }
 
 impl Default for SearchFilter {
@@ -84,13 +101,108 @@ impl Serialize for SearchFilter {
             SearchFilter::Messages => {
                 map.serialize_entry("category", "GlobalSearchMessages")?;
             }
+            SearchFilter::Files { semester } => {
+                map.serialize_entry("category", "GlobalSearchFiles")?;
+                map.serialize_entry("semester", semester)?;
+            }
+            SearchFilter::ForumPosts => {
+                map.serialize_entry("category", "GlobalSearchForum")?;
+            }
+            SearchFilter::WikiPages => {
+                map.serialize_entry("category", "GlobalSearchWiki")?;
+            }
+            SearchFilter::Resources => {
+                map.serialize_entry("category", "GlobalSearchResources")?;
+            }
         }
         map.end()
     }
 }
 
+const SEARCH_PAGE_URL: &str = "https://studip.example.com/dispatch.php/search/globalsearch";
+
+/// A type of courses (e.g. "Vorlesung"), by which a course search can be filtered (see [`SearchFilter::Courses`])
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeminarType {
+    /// The id, that is expected as the `seminar_type_id`
+    pub id: String,
+    pub name: String,
+    /// The category, in which the type is grouped (e.g. "Lehre")
+    pub category: Option<String>,
+}
+
+impl SeminarType {
+
+    /// Finds the type with the `name`, ignoring the case
+    pub fn find_by_name<'a>(types: &'a [SeminarType], name: &str) -> Option<&'a SeminarType> {
+        let name = normalize_text(name);
+        types.iter().find(|seminar_type| normalize_text(&seminar_type.name) == name)
+    }
+
+}
+
+/// Returns the [`SeminarType`]s offered by the type dropdown of the search page, to be used in a [`SearchFilter::Courses`] \
+/// The types are fetched once and then reused for the whole session, as they do not change.
+///
+/// ```no_run
+/// use stud_ip_scraper::StudIpClient;
+/// use stud_ip_scraper::search::{get_seminar_types, global_search, SearchFilter, SeminarType};
+///
+/// fn search_lectures(client: &StudIpClient, text: &str) -> anyhow::Result<()> {
+///     let seminar_types = get_seminar_types(client)?;
+///     let lecture = SeminarType::find_by_name(&seminar_types, "Vorlesung")
+///         .ok_or_else(|| anyhow::anyhow!("No lecture type"))?;
+///     let filter = SearchFilter::Courses {
+///         semester: Default::default(),
+///         seminar_type_id: Some(lecture.id.clone()),
+///         institute_id: None,
+///     };
+///     for course in global_search(client, text, 20, &filter)?.courses.into_iter().flat_map(|c| c.content) {
+///         println!("{}", course.name);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub fn get_seminar_types(client: &StudIpClient) -> anyhow::Result<Vec<SeminarType>> {
+    let mut seminar_types = client.seminar_types.lock().unwrap();
+    if let Some(seminar_types) = seminar_types.as_ref() {
+        return Ok(seminar_types.clone());
+    }
+    let response = client.get(SEARCH_PAGE_URL).send_through(client)?;
+    if !response.status().is_success() {
+        bail!("Could not get the search page. Status Code: {}", response.status());
+    }
+    let url = response.url().clone();
+    let types = parse_seminar_types(&parse_page(&response.text()?)?).with_url(url)?;
+    *seminar_types = Some(types.clone());
+    Ok(types)
+}
 
-const GLOBAL_SEARCH_URL: &'static str = "https://studip.example.com/dispatch.php/globalsearch/find";
+fn parse_seminar_types(html: &scraper::Html) -> anyhow::Result<Vec<SeminarType>> {
+    let select = expect_one(html.root_element(), selector!(r#"select[name="seminar_type"]"#), "seminar type select")?;
+    Ok(select.select(selector!("option"))
+        .filter_map(|option| {
+            let id = option.value().attr("value")?.trim();
+            if id.is_empty() {
+                return None; // All types
+            }
+            let category = option.parent()
+                .and_then(scraper::ElementRef::wrap)
+                .filter(|parent| parent.value().name() == "optgroup")
+                .and_then(|optgroup| optgroup.value().attr("label"))
+                .map(|label| label.trim().to_string());
+            Some(SeminarType {
+                id: id.to_string(),
+                name: option.text().collect::<String>().trim().to_string(),
+                category,
+            })
+        })
+        .collect())
+}
+
+const GLOBAL_SEARCH_URL: &str = "https://studip.example.com/dispatch.php/globalsearch/find";
+/// How many courses are searched for the same course in another semester, which also finds courses with similar names
+const COURSE_SEARCH_LIMIT: usize = 50;
 
 /// Represents the categorized results found by [`global_search()`]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -103,6 +215,140 @@ pub struct SearchResult {
     pub institutes: Option<SearchResultCategory<SearchEntryInstitute>>,
     #[serde(rename = "GlobalSearchMessages")]
     pub messages: Option<SearchResultCategory<SearchEntryMessage>>,
+    #[serde(rename = "GlobalSearchFiles")]
+    pub files: Option<SearchResultCategory<SearchEntryFile>>,
+    #[serde(rename = "GlobalSearchForum")]
+    pub forum_posts: Option<SearchResultCategory<SearchEntryForumPost>>,
+    #[serde(rename = "GlobalSearchWiki")]
+    pub wiki_pages: Option<SearchResultCategory<SearchEntryWikiPage>>,
+    #[serde(rename = "GlobalSearchResources")]
+    pub resources: Option<SearchResultCategory<SearchEntryResource>>,
+    /// Categories, which are not known to this crate (e.g. from plugins)
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl SearchResult {
+
+    /// Fetches up to `limit` further entries of the given `category`, like the "show more" button in the web UI. \
+    /// The `text` and `filter` need to be the same, that were used to obtain this result. \
+    /// New entries are appended to the category's `content`, skipping entries that are already present, and `more` is updated.
+    pub fn fetch_more(&mut self, client: &StudIpClient, text: &str, filter: &SearchFilter, category: SearchCategory, limit: usize) -> anyhow::Result<()> {
+        let offset = self.category_len(category);
+        let page = global_search_page(client, text, filter, category, offset, limit)?;
+        match category {
+            SearchCategory::Courses => merge_category(&mut self.courses, page.courses),
+            SearchCategory::Users => merge_category(&mut self.users, page.users),
+            SearchCategory::Institutes => merge_category(&mut self.institutes, page.institutes),
+            SearchCategory::Messages => merge_category(&mut self.messages, page.messages),
+            SearchCategory::Files => merge_category(&mut self.files, page.files),
+            SearchCategory::ForumPosts => merge_category(&mut self.forum_posts, page.forum_posts),
+            SearchCategory::WikiPages => merge_category(&mut self.wiki_pages, page.wiki_pages),
+            SearchCategory::Resources => merge_category(&mut self.resources, page.resources),
+        }
+        Ok(())
+    }
+
+    // Fills the parsed companions of the raw fields of the entries and strips the markings from their names
+    fn fill_parsed(&mut self, timezone: Tz) {
+        for course in self.courses.iter_mut().flat_map(|c| c.content.iter_mut()) {
+            course.fill_parsed();
+        }
+        for message in self.messages.iter_mut().flat_map(|c| c.content.iter_mut()) {
+            message.fill_parsed(timezone);
+        }
+    }
+
+    // Makes the image urls of the entries absolute, leaving the ones, that can not be resolved, unchanged
+    fn absolutize_images(&mut self, client: &StudIpClient) {
+        fn images<T>(category: &mut Option<SearchResultCategory<T>>, img: fn(&mut T) -> &mut String) -> impl Iterator<Item = &mut String> {
+            category.iter_mut().flat_map(|c| c.content.iter_mut()).map(img)
+        }
+        let images = images(&mut self.courses, |e| &mut e.img)
+            .chain(images(&mut self.users, |e| &mut e.img))
+            .chain(images(&mut self.institutes, |e| &mut e.img))
+            .chain(images(&mut self.messages, |e| &mut e.img))
+            .chain(images(&mut self.files, |e| &mut e.img))
+            .chain(images(&mut self.forum_posts, |e| &mut e.img))
+            .chain(images(&mut self.wiki_pages, |e| &mut e.img))
+            .chain(images(&mut self.resources, |e| &mut e.img));
+        for img in images.filter(|img| !img.is_empty()) {
+            if let Ok(url) = client.absolutize(img) {
+                *img = url.to_string();
+            }
+        }
+    }
+
+    fn category_len(&self, category: SearchCategory) -> usize {
+        match category {
+            SearchCategory::Courses => self.courses.as_ref().map(|c| c.content.len()),
+            SearchCategory::Users => self.users.as_ref().map(|c| c.content.len()),
+            SearchCategory::Institutes => self.institutes.as_ref().map(|c| c.content.len()),
+            SearchCategory::Messages => self.messages.as_ref().map(|c| c.content.len()),
+            SearchCategory::Files => self.files.as_ref().map(|c| c.content.len()),
+            SearchCategory::ForumPosts => self.forum_posts.as_ref().map(|c| c.content.len()),
+            SearchCategory::WikiPages => self.wiki_pages.as_ref().map(|c| c.content.len()),
+            SearchCategory::Resources => self.resources.as_ref().map(|c| c.content.len()),
+        }.unwrap_or(0)
+    }
+
+}
+
+// Appends the new entries to the target category, skipping duplicates
+fn merge_category<T: SearchEntry>(target: &mut Option<SearchResultCategory<T>>, new: Option<SearchResultCategory<T>>) {
+    let Some(new) = new else {
+        if let Some(target) = target {
+            target.more = false;
+        }
+        return;
+    };
+    let Some(target) = target else {
+        *target = Some(new);
+        return;
+    };
+    for entry in new.content {
+        if !target.content.iter().any(|existing| existing.key() == entry.key()) {
+            target.content.push(entry);
+        }
+    }
+    target.more = new.more;
+}
+
+/// A single category of the [`SearchResult`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SearchCategory {
+    Courses,
+    Users,
+    Institutes,
+    Messages,
+    Files,
+    ForumPosts,
+    WikiPages,
+    Resources,
+}
+
+impl SearchCategory {
+
+    /// The name of the category, as it is used by Stud.IP
+    pub fn id(&self) -> &'static str {
+        match self {
+            SearchCategory::Courses => "GlobalSearchCourses",
+            SearchCategory::Users => "GlobalSearchUsers",
+            SearchCategory::Institutes => "GlobalSearchInstitutes",
+            SearchCategory::Messages => "GlobalSearchMessages",
+            SearchCategory::Files => "GlobalSearchFiles",
+            SearchCategory::ForumPosts => "GlobalSearchForum",
+            SearchCategory::WikiPages => "GlobalSearchWiki",
+            SearchCategory::Resources => "GlobalSearchResources",
+        }
+    }
+
+}
+
+/// An entry of a [`SearchResultCategory`], that can be uniquely identified
+pub trait SearchEntry {
+    /// A key, that uniquely identifies the entry within its category
+    fn key(&self) -> &str;
 }
 
 /// A generic search category. Contains the found entries in `content`
@@ -130,15 +376,67 @@ pub struct SearchEntryCourse {
     pub name: String,
     pub url: String,
     pub date: String,
+    /// The parsed `date`, if it could be parsed
+    #[serde(default)]
+    pub date_parsed: Option<NaiveDate>,
     pub dates: String,
     pub has_children: bool,
     pub children: Vec<Value>,
     pub additional: String,
     pub expand: String,
+    /// The raw html of the admission state icon
     pub admission_state: String,
+    /// The parsed `admission_state`
+    #[serde(default)]
+    pub admission: AdmissionState,
     pub img: String,
 }
 
+impl SearchEntryCourse {
+    fn fill_parsed(&mut self) {
+        self.name = strip_markings(&self.name);
+        self.date_parsed = parse_localized_date(&self.date);
+        self.admission = AdmissionState::from_icon_html(&self.admission_state);
+    }
+}
+
+/// The admission state of a [`SearchEntryCourse`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdmissionState {
+    /// Anybody can join the course
+    Open,
+    /// Joining the course is restricted by an admission procedure
+    Restricted,
+    /// Joining the course is not possible
+    Locked,
+    /// The admission state could not be determined, contains the raw admission state
+    Unknown(String),
+}
+
+impl AdmissionState {
+
+    /// Determines the admission state from the icon (its class or source) contained in the `admission_state` html
+    pub fn from_icon_html(html: &str) -> Self {
+        let html = html.trim();
+        if html.is_empty() {
+            Self::Open
+        } else if html.contains("lock-locked") {
+            Self::Locked
+        } else if html.contains("lock-unlocked") || html.contains("admission") {
+            Self::Restricted
+        } else {
+            Self::Unknown(html.to_string())
+        }
+    }
+
+}
+
+impl Default for AdmissionState {
+    fn default() -> Self {
+        Self::Unknown(String::new())
+    }
+}
+
 /// A institute entry returned by [`global_search()`].
 ///
 /// Can be converted to a normal [`Institute`] using [`From`]
@@ -151,10 +449,22 @@ pub struct SearchEntryInstitute {
     pub img: String,
 }
 
+impl SearchEntry for SearchEntryCourse {
+    fn key(&self) -> &str {
+        &self.id
+    }
+}
+
+impl SearchEntry for SearchEntryInstitute {
+    fn key(&self) -> &str {
+        &self.id
+    }
+}
+
 impl From<SearchEntryInstitute> for Institute {
     fn from(value: SearchEntryInstitute) -> Self {
         Institute {
-            id: value.id,
+            id: value.id.into(),
             name: strip_markings(&value.name)
         }
     }
@@ -173,6 +483,12 @@ pub struct SearchEntryUser {
     pub img: String,
 }
 
+impl SearchEntry for SearchEntryUser {
+    fn key(&self) -> &str {
+        &self.id
+    }
+}
+
 impl From<SearchEntryUser> for User {
     fn from(value: SearchEntryUser) -> Self {
         User {
@@ -180,6 +496,7 @@ impl From<SearchEntryUser> for User {
             username: get_username_from_url(value.url).expect("Invalid User URL"),
             avatar_src: Some(value.img),
             source: ReferenceSource::Unspecified,
+            user_id: Some(value.id.into()),
         }
     }
 }
@@ -192,6 +509,9 @@ pub struct SearchEntryMessage {
     pub url: String,
     pub img: String,
     pub date: String,
+    /// The parsed `date`, if it could be parsed
+    #[serde(default)]
+    pub date_parsed: Option<DateTime<Utc>>,
     pub description: String,
     pub additional: String,
     pub expand: String,
@@ -199,16 +519,178 @@ pub struct SearchEntryMessage {
     pub user_name: String,
 }
 
+impl SearchEntryMessage {
+    fn fill_parsed(&mut self, timezone: Tz) {
+        self.name = strip_markings(&self.name);
+        self.description = strip_markings(&self.description);
+        self.additional = strip_markings(&self.additional);
+        self.user_name = strip_markings(&self.user_name);
+        self.date_parsed = parse_localized_date_time(&self.date).and_then(|date_time| local_to_utc(date_time, timezone));
+    }
+}
+
+impl SearchEntry for SearchEntryMessage {
+    fn key(&self) -> &str {
+        &self.url
+    }
+}
+
+/// A file entry returned by [`global_search()`].
+///
+/// Can be downloaded with [`SearchEntryFile::download()`]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchEntryFile {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub img: String,
+    pub date: String,
+    /// Usually the name of the course, the file belongs to
+    pub description: String,
+    pub additional: String,
+    pub expand: String,
+}
+
+impl SearchEntryFile {
+
+    /// Parses the id of the course, the file belongs to, from its url
+    pub fn course_id(&self) -> Option<String> {
+        Url::parse(&self.url).ok()?
+            .query_pairs()
+            .find_map(|(key, value)| (key == "cid").then(|| value.to_string()))
+    }
+
+    /// Downloads the file and returns its bytes
+    pub fn download(&self, client: &StudIpClient) -> anyhow::Result<Vec<u8>> {
+        download_file_by_id(client, &self.id, &strip_markings(&self.name))
+    }
+
+}
+
+impl SearchEntry for SearchEntryFile {
+    fn key(&self) -> &str {
+        &self.id
+    }
+}
+
+/// A forum post entry returned by [`global_search()`].
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchEntryForumPost {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub img: String,
+    pub date: String,
+    pub description: String,
+    pub additional: String,
+    pub expand: String,
+    #[serde(rename = "user")]
+    pub user_name: String,
+}
+
+impl SearchEntry for SearchEntryForumPost {
+    fn key(&self) -> &str {
+        &self.id
+    }
+}
+
+/// A wiki page entry returned by [`global_search()`].
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchEntryWikiPage {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub img: String,
+    pub date: String,
+    pub description: String,
+    pub additional: String,
+    pub expand: String,
+}
+
+impl SearchEntry for SearchEntryWikiPage {
+    fn key(&self) -> &str {
+        &self.url
+    }
+}
+
+/// A resource (e.g. a room) entry returned by [`global_search()`].
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchEntryResource {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub img: String,
+    pub additional: String,
+    pub expand: String,
+}
+
+impl SearchEntry for SearchEntryResource {
+    fn key(&self) -> &str {
+        &self.id
+    }
+}
+
 /// Does a global search for the given `text`, providing at most `max_results` results per category using the given [`SearchFilter`].
 pub fn global_search(client: &StudIpClient, text: &str, max_results: usize, filter: &SearchFilter) -> anyhow::Result<SearchResult> {
     let filter_string = serde_json::to_string(filter).context("Cannot convert filter to json")?;
+    search_request(client, text, max_results, &filter_string)
+}
 
+/// Searches the courses of the `semester` for the same course as the one with the given `name`, which may be of another semester \
+/// Only courses, whose names are equal after [`normalize_course_name()`], are returned. This also finds courses, the user is not enrolled in.
+pub fn find_course_in_semester(client: &StudIpClient, name: &str, semester: FilterSemester) -> anyhow::Result<Vec<SearchEntryCourse>> {
+    // The semester and number would not match the name of the course in the other semester
+    let text = course_name_words(name);
+    let filter = SearchFilter::Courses {
+        semester,
+        seminar_type_id: None,
+        institute_id: None,
+    };
+    let result = global_search(client, &text, COURSE_SEARCH_LIMIT, &filter)?;
+    let normalized = normalize_course_name(name);
+    Ok(result.courses
+        .map(|courses| courses.content)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|course| normalize_course_name(&course.name) == normalized)
+        .collect())
+}
+
+/// Does a global search for the given `text` in a single `category`, returning at most `limit` entries after skipping the first `offset` entries. \
+/// The category is searched with the remaining options (like the semester) of the given [`SearchFilter`].
+///
+/// *Note: The search endpoint can only limit the number of results, so the skipped entries are still transferred.*
+pub fn global_search_page(client: &StudIpClient, text: &str, filter: &SearchFilter, category: SearchCategory, offset: usize, limit: usize) -> anyhow::Result<SearchResult> {
+    let mut filter_value = serde_json::to_value(filter).context("Cannot convert filter to json")?;
+    filter_value["category"] = Value::from(category.id());
+    let mut result = search_request(client, text, offset + limit, &filter_value.to_string())?;
+    fn skip<T>(category: &mut Option<SearchResultCategory<T>>, offset: usize) {
+        if let Some(category) = category {
+            category.content.drain(..offset.min(category.content.len()));
+        }
+    }
+    skip(&mut result.courses, offset);
+    skip(&mut result.users, offset);
+    skip(&mut result.institutes, offset);
+    skip(&mut result.messages, offset);
+    skip(&mut result.files, offset);
+    skip(&mut result.forum_posts, offset);
+    skip(&mut result.wiki_pages, offset);
+    skip(&mut result.resources, offset);
+    Ok(result)
+}
+
+fn search_request(client: &StudIpClient, text: &str, max_results: usize, filter_string: &str) -> anyhow::Result<SearchResult> {
     let response = client.get(format!("{}/{}", GLOBAL_SEARCH_URL, max_results))
         .query(&[
             ("search", text),
-            ("filter", filter_string.as_str()),
+            ("filter", filter_string),
         ])
-        .send()?;
+        .send_through(client)?;
 
     if !response.status().is_success() {
         bail!("Could not search. Status Code: {}", response.status());
@@ -220,21 +702,151 @@ pub fn global_search(client: &StudIpClient, text: &str, max_results: usize, filt
     if !content_type.starts_with("application/json") {
         bail!("Expected JSON. Got ContentType: {:?}", content_type);
     }
+    parse_search_json(&response.text()?, client)
+}
+
+/// Parses the JSON response of the global search (`dispatch.php/globalsearch/find`) into a [`SearchResult`], without requesting anything \
+/// Relative image urls are resolved against the placeholder host `studip.example.com`, dates are converted to UTC in the [`DEFAULT_TIMEZONE`](crate::DEFAULT_TIMEZONE).
+pub fn parse_search_response(json: &str) -> anyhow::Result<SearchResult> {
+    parse_search_json(json, StudIpClient::offline())
+}
+
+fn parse_search_json(json: &str, client: &StudIpClient) -> anyhow::Result<SearchResult> {
     // Check if the response is `[]` (WHY? WHY WOULD YOU RESPOND WITH THIS!?? You don't even return an array when you found something)
-    let text = response.text()?;
-    if text.trim() == "[]" {
+    if json.trim() == "[]" {
         return Ok(Default::default());
     }
-    Ok(serde_json::from_str(&text).context("Could not parse search response json")?)
+    let mut result: SearchResult = serde_json::from_str(json).context("Could not parse search response json")?;
+    result.fill_parsed(client.timezone());
+    result.absolutize_images(client);
+    Ok(result)
+}
+
+static MARK_TAG_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)</?mark\b[^>]*>").unwrap());
+
+const QUICKSEARCH_URL: &str = "https://studip.example.com/dispatch.php/quicksearch/response";
+
+/// The kind of entity, that is looked up by [`quicksearch()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuickSearchKind {
+    User,
+    Course,
+    Institute,
+}
+
+impl QuickSearchKind {
+
+    /// The name of the quicksearch, as it is registered by Stud.IP
+    fn search_name(&self) -> &'static str {
+        match self {
+            QuickSearchKind::User => "username",
+            QuickSearchKind::Course => "Seminar_id",
+            QuickSearchKind::Institute => "Institut_id",
+        }
+    }
+
+    fn url_for(&self, id: &str) -> anyhow::Result<Url> {
+        let (base, key) = match self {
+            QuickSearchKind::User => (PROFILE_URL, "username"),
+            QuickSearchKind::Course => (COURSE_URL, "cid"),
+            QuickSearchKind::Institute => (INSTITUTE_URL, "cid"),
+        };
+        let mut url = Url::parse(base)?;
+        url.query_pairs_mut().append_pair(key, id);
+        Ok(url)
+    }
+
+}
+
+/// A single hit returned by [`quicksearch()`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuickHit {
+    /// The id of the entity. For users this is the username
+    pub id: String,
+    /// The display name of the entity
+    pub name: String,
+    /// The url to the entity's page
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TheirQuickHit {
+    item_id: String,
+    item_name: String,
+}
+
+/// Looks up entities of the given [`QuickSearchKind`] by `text`, using the lightweight autocomplete endpoint of the top bar. \
+/// This is a lot faster than [`global_search()`], but only returns ids and names.
+pub fn quicksearch(client: &StudIpClient, text: &str, kind: QuickSearchKind) -> anyhow::Result<Vec<QuickHit>> {
+    quicksearch_by_name(client, kind.search_name(), text)?
+        .into_iter()
+        .map(|(id, name)| Ok(QuickHit {
+            url: kind.url_for(&id)?.to_string(),
+            id,
+            name,
+        }))
+        .collect()
+}
+
+/// Queries the quicksearch registered under `search_name` and returns the ids and (unmarked) names of its hits \
+/// Forms register their own quicksearches (e.g. the message adressees), which are not covered by [`QuickSearchKind`].
+pub(crate) fn quicksearch_by_name(client: &StudIpClient, search_name: &str, text: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let security_token = client.security_token()?;
+    let response = client.get(format!("{}/{}", QUICKSEARCH_URL, search_name))
+        .query(&[("request", text), ("security_token", security_token.as_str())])
+        .header("X-Requested-With", "XMLHttpRequest")
+        .send_through(client)?;
+    if !response.status().is_success() {
+        bail!("Could not quicksearch. Status Code: {}", response.status());
+    }
+    // Same as in the global search, nothing found is sometimes signaled by an empty array
+    let text = response.text()?;
+    if text.trim() == "[]" || text.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    let their_hits: Vec<TheirQuickHit> = serde_json::from_str(&text).context("Could not parse quicksearch response json")?;
+    Ok(their_hits.into_iter()
+        .map(|hit| (hit.item_id, strip_markings(&hit.item_name)))
+        .collect())
+}
+
+// Picks the hit, which name matches the text best (exact, then prefix, then containing), `None` if no name matches
+fn best_quick_hit(hits: Vec<QuickHit>, text: &str) -> Option<QuickHit> {
+    let normalized_text = normalize_text(text);
+    let normalized_names = hits.iter().map(|hit| normalize_text(&hit.name)).collect::<Vec<_>>();
+    let index = normalized_names.iter().position(|name| *name == normalized_text)
+        .or_else(|| normalized_names.iter().position(|name| name.starts_with(&normalized_text)))
+        .or_else(|| normalized_names.iter().position(|name| name.contains(&normalized_text)))?;
+    hits.into_iter().nth(index)
+}
+
+/// Resolves a users display name to its username, by picking the best match of a [`quicksearch()`] \
+/// Returns `None`, if no name of the hits is equal to, starts with or contains the `display_name`.
+pub fn resolve_username(client: &StudIpClient, display_name: &str) -> anyhow::Result<Option<String>> {
+    let hits = quicksearch(client, display_name, QuickSearchKind::User)?;
+    Ok(best_quick_hit(hits, display_name).map(|hit| hit.id))
+}
+
+/// Resolves a course name to its course id, by picking the best match of a [`quicksearch()`] \
+/// Returns `None`, if no name of the hits is equal to, starts with or contains the `name`.
+pub fn resolve_course(client: &StudIpClient, name: &str) -> anyhow::Result<Option<String>> {
+    let hits = quicksearch(client, name, QuickSearchKind::Course)?;
+    Ok(best_quick_hit(hits, name).map(|hit| hit.id))
 }
 
 /// Strips the html <mark> tag from the given string.
 ///
 /// This exists, because [`SearchResult`] might contain this tag around text that matched the search text. \
-/// Note: This does **NOT** handle nested <mark> tags.
+/// Nested and repeated <mark> tags are all removed and html entities are decoded afterward. \
+/// Because the search sometimes encodes entities more than once (e.g. "M&amp;uuml;ller"), they are decoded until the text stops changing.
 pub fn strip_markings(str: &str) -> String {
-    let regex = regex::Regex::new(r"(.*?)<mark>(.*?)</mark>(.*?)").unwrap();
-    regex.replace_all(str, "$1$2$3").to_string()
+    let mut decoded = MARK_TAG_REGEX.replace_all(str, "").into_owned();
+    loop {
+        match decode_html_entities(&decoded) {
+            Cow::Owned(next) if next != decoded => decoded = next,
+            _ => return decoded,
+        }
+    }
 }
 
 
@@ -242,13 +854,42 @@ pub fn strip_markings(str: &str) -> String {
 mod tests {
     use super::*;
     use serde_json;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_seminar_types() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/search/globalsearch", 200, include_str!("../testdata/search/globalsearch.html"));
+        let client = server.client();
+        let seminar_types = get_seminar_types(&client).unwrap();
+        assert_eq!(seminar_types.len(), 5);
+        assert_eq!(seminar_types[0], SeminarType { id: "1".to_string(), name: "Vorlesung".to_string(), category: Some("Lehre".to_string()) });
+        assert_eq!(seminar_types[3].category.as_deref(), Some("Studentische Arbeitsgruppen"));
+        assert_eq!(seminar_types[4].category, None);
+        assert_eq!(SeminarType::find_by_name(&seminar_types, " übung").map(|seminar_type| seminar_type.id.as_str()), Some("3"));
+        assert!(SeminarType::find_by_name(&seminar_types, "Kolloquium").is_none());
+        // The types are only fetched once
+        assert_eq!(get_seminar_types(&client).unwrap(), seminar_types);
+        assert_eq!(server.requests().len(), 1);
+    }
 
     #[test]
     fn test_strip_markings() {
         assert_eq!(strip_markings("Mark<mark> Ole</mark> Peter"), "Mark Ole Peter");
         assert_eq!(strip_markings("<mark>Mark</mark> Ole <mark>Peter</mark>"), "Mark Ole Peter");
         assert_eq!(strip_markings("Max Counterman"), "Max Counterman");
-        assert_eq!(strip_markings("John <mark><mark>Connman</mark></mark>"), "John <mark>Connman</mark>");
+        assert_eq!(strip_markings("John <mark><mark>Connman</mark></mark>"), "John Connman");
+        assert_eq!(strip_markings("<mark>Max Counterman</mark>"), "Max Counterman");
+        assert_eq!(strip_markings("<mark>Ma</mark><mark>x</mark>"), "Max");
+    }
+
+    #[test]
+    fn test_strip_markings_decodes_entities() {
+        assert_eq!(strip_markings("M&uuml;ller &amp; S&ouml;hne"), "Müller & Söhne");
+        assert_eq!(strip_markings("M&amp;uuml;ller"), "Müller");
+        assert_eq!(strip_markings("M&amp;amp;uuml;ller"), "Müller");
+        assert_eq!(strip_markings("O&#039;Brien <mark>&lt;3</mark>"), "O'Brien <3");
+        assert_eq!(strip_markings("&#x41;&unknown;"), "A&unknown;");
     }
 
     #[test]
@@ -262,4 +903,119 @@ mod tests {
         let expected = r#"{"category":"GlobalSearchCourses","semester":"","seminar_type":"1","institute":"2123"}"#;
         assert_eq!(serialized, expected);
     }
-}
\ No newline at end of file
+
+    fn test_institute_entry(id: &str) -> SearchEntryInstitute {
+        SearchEntryInstitute {
+            id: id.to_string(),
+            name: id.to_string(),
+            url: "".to_string(),
+            expand: "".to_string(),
+            img: "".to_string(),
+        }
+    }
+
+    fn test_institute_category(ids: &[&str], more: bool) -> SearchResultCategory<SearchEntryInstitute> {
+        SearchResultCategory {
+            name: "Institutes".to_string(),
+            fullsearch: "".to_string(),
+            content: ids.iter().map(|id| test_institute_entry(id)).collect(),
+            more,
+            plus: false,
+        }
+    }
+
+    #[test]
+    fn test_merge_category_deduplicates() {
+        let mut target = Some(test_institute_category(&["a", "b"], true));
+        merge_category(&mut target, Some(test_institute_category(&["b", "c"], false)));
+        let target = target.unwrap();
+        assert_eq!(target.content.iter().map(|e| e.key()).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        assert!(!target.more);
+
+        let mut empty = None;
+        merge_category(&mut empty, Some(test_institute_category(&["a"], true)));
+        assert_eq!(empty.unwrap().content.len(), 1);
+    }
+
+    #[test]
+    fn test_unknown_categories_are_kept() {
+        let json = r#"{
+            "GlobalSearchFiles": {
+                "name": "Dateien",
+                "fullsearch": "",
+                "content": [{"id": "f1", "name": "<mark>Sheet</mark> 1.pdf", "url": "https://studip.example.com/dispatch.php/course/files?cid=c1", "img": "", "date": "12.03.2025"}],
+                "more": false,
+                "plus": false
+            },
+            "GlobalSearchMeetings": {"name": "Meetings", "content": []}
+        }"#;
+        let result: SearchResult = serde_json::from_str(json).unwrap();
+        let files = result.files.unwrap();
+        assert_eq!(files.content[0].id, "f1");
+        assert_eq!(files.content[0].course_id().as_deref(), Some("c1"));
+        assert!(result.extra.contains_key("GlobalSearchMeetings"));
+        assert!(result.courses.is_none());
+    }
+
+    #[test]
+    fn test_fill_parsed() {
+        let json = r#"{
+            "GlobalSearchCourses": {
+                "name": "Veranstaltungen",
+                "fullsearch": "",
+                "content": [{
+                    "id": "c1", "number": "", "name": "Algorithms", "url": "", "date": "Mi., 12.03.2025", "dates": "",
+                    "has_children": false, "children": [], "additional": "", "expand": "",
+                    "admission_state": "<img class=\"icon-role-info icon-shape-lock-locked\">", "img": "/pictures/course/c1_small.png"
+                }],
+                "more": false,
+                "plus": false
+            }
+        }"#;
+        let result = parse_search_response(json).unwrap();
+        let course = &result.courses.unwrap().content[0];
+        assert_eq!(course.img, "https://studip.example.com/pictures/course/c1_small.png");
+        assert_eq!(course.date_parsed, NaiveDate::from_ymd_opt(2025, 3, 12));
+        assert_eq!(course.date, "Mi., 12.03.2025");
+        assert_eq!(course.admission, AdmissionState::Locked);
+        assert_eq!(AdmissionState::from_icon_html(""), AdmissionState::Open);
+        assert_eq!(AdmissionState::from_icon_html("<img src=\"star.svg\">"), AdmissionState::Unknown("<img src=\"star.svg\">".to_string()));
+        // Empty results are sent as an empty array
+        assert!(parse_search_response(" [] ").unwrap().courses.is_none());
+        assert!(parse_search_response("{\"GlobalSearchCourses\": 1}").is_err());
+    }
+
+    #[test]
+    fn test_find_course_in_semester() {
+        let server = MockServer::start();
+        let course = |id: &str, name: &str| format!(r#"{{"id": "{}", "number": "", "name": "{}", "url": "", "date": "", "dates": "",
+            "has_children": false, "children": [], "additional": "", "expand": "", "admission_state": "", "img": ""}}"#, id, name);
+        server.route_typed("GET", "/dispatch.php/globalsearch/find/50", 200, "application/json", format!(
+            r#"{{"GlobalSearchCourses": {{"name": "Veranstaltungen", "fullsearch": "", "content": [{}, {}, {}], "more": false, "plus": false}}}}"#,
+            course("c1", "Übung zu Algorithmen und Datenstrukturen (SoSe 2024)"),
+            course("c2", "<mark>Algorithmen und Datenstrukturen</mark> (SoSe 2024)"),
+            course("c3", "Algorithmen und Datenstrukturen II (SoSe 2024)"),
+        ));
+        let client = server.client();
+        let courses = find_course_in_semester(&client, "B.Inf.1201: Algorithmen und Datenstrukturen (SoSe 2025)", FilterSemester::Specific { unix_timestamp: 1712008800 }).unwrap();
+        let ids = courses.iter().map(|course| course.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["c2"]);
+
+        let request = &server.requests()[0];
+        let query = url::form_urlencoded::parse(request.path.split_once('?').unwrap().1.as_bytes()).collect::<HashMap<_, _>>();
+        assert_eq!(query["search"], "Algorithmen und Datenstrukturen");
+        let filter = serde_json::from_str::<Value>(&query["filter"]).unwrap();
+        assert_eq!(filter, serde_json::json!({"category": "GlobalSearchCourses", "semester": "1712008800"}));
+    }
+
+    #[test]
+    fn test_best_quick_hit() {
+        let hit = |id: &str, name: &str| QuickHit { id: id.to_string(), name: name.to_string(), url: "".to_string() };
+        let hits = vec![hit("a", "Anna Müllerson"), hit("b", "Anna Müller"), hit("c", "Max")];
+        assert_eq!(best_quick_hit(hits.clone(), "anna muller").unwrap().id, "b");
+        assert_eq!(best_quick_hit(hits.clone(), "Max").unwrap().id, "c");
+        // Hits, that only the server considers matching, are not picked
+        assert!(best_quick_hit(hits.clone(), "Unknown").is_none());
+        assert!(best_quick_hit(vec![], "Max").is_none());
+    }
+}
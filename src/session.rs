@@ -0,0 +1,133 @@
+//! Metadata of the session of a logged-in user, like their id, global permission and the expiry of the SSO session
+
+use std::collections::HashMap;
+use anyhow::Context;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::ids::UserId;
+use crate::{SAMLAssertionData, SendThrough, StudIpClient};
+
+const USERS_ME_URL: &str = "https://studip.example.com/jsonapi.php/v1/users/me";
+
+static SESSION_NOT_ON_OR_AFTER_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"SessionNotOnOrAfter="([^"]+)""#).unwrap());
+
+/// The logged-in user and their session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    pub user_id: UserId,
+    pub username: String,
+    pub display_name: String,
+    /// The global permission of the user (e.g. "autor", "dozent" or "root")
+    pub perm: String,
+    /// When the session at the IdP ends, if the IdP exposed it in its SAML assertion
+    pub idp_session_expires: Option<DateTime<Utc>>,
+}
+
+/// Attributes of a SAML assertion, that an [`IdentityProvider`](crate::IdentityProvider) could parse
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SAMLAttributes {
+    /// The `SessionNotOnOrAfter` of the assertion's `AuthnStatement`
+    pub session_not_on_or_after: Option<DateTime<Utc>>,
+    /// Any released attributes by their name, e.g. `urn:oid:0.9.2342.19200300.100.1.3` (mail)
+    pub attributes: HashMap<String, Vec<String>>,
+}
+
+impl SAMLAttributes {
+
+    /// Parses the attributes from the (base64 encoded) `SAMLResponse`, which is only possible if its assertion is not encrypted
+    pub fn from_saml_response(saml_assertion: &SAMLAssertionData) -> Self {
+        let Ok(xml) = base64::engine::general_purpose::STANDARD.decode(saml_assertion.saml_response.trim()) else {
+            return Self::default();
+        };
+        let xml = String::from_utf8_lossy(&xml);
+        let session_not_on_or_after = SESSION_NOT_ON_OR_AFTER_REGEX.captures(&xml)
+            .and_then(|captures| DateTime::parse_from_rfc3339(&captures[1]).ok())
+            .map(|date_time| date_time.with_timezone(&Utc));
+        Self {
+            session_not_on_or_after,
+            attributes: HashMap::new(),
+        }
+    }
+
+}
+
+#[derive(Debug, Deserialize)]
+struct UserDocument {
+    data: UserResource,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserResource {
+    id: UserId,
+    attributes: UserAttributes,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserAttributes {
+    username: String,
+    #[serde(rename = "formatted-name")]
+    formatted_name: String,
+    permission: String,
+}
+
+/// Queries the logged-in user through the JSON:API, which also accepts the session cookie
+pub(crate) fn query_session(client: &StudIpClient, idp_session_expires: Option<DateTime<Utc>>) -> anyhow::Result<Session> {
+    let response = client.get(USERS_ME_URL)
+        .header("Accept", "application/vnd.api+json")
+        .send_through(client)?;
+    if !response.status().is_success() {
+        anyhow::bail!("Querying the logged-in user returned {}", response.status());
+    }
+    let document: UserDocument = serde_json::from_str(&response.text()?)
+        .context("Could not parse the logged-in user")?;
+    Ok(Session {
+        user_id: document.data.id,
+        username: document.data.attributes.username,
+        display_name: document.data.attributes.formatted_name,
+        perm: document.data.attributes.permission,
+        idp_session_expires,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_saml_attributes() {
+        let xml = r#"<samlp:Response><saml:Assertion><saml:AuthnStatement AuthnInstant="2025-03-12T08:00:00Z" SessionNotOnOrAfter="2025-03-12T16:00:00Z" SessionIndex="_1"/></saml:Assertion></samlp:Response>"#;
+        let assertion = SAMLAssertionData {
+            relay_state: "cookie:1".to_string(),
+            saml_response: base64::engine::general_purpose::STANDARD.encode(xml),
+        };
+        assert_eq!(
+            SAMLAttributes::from_saml_response(&assertion).session_not_on_or_after,
+            Some(Utc.with_ymd_and_hms(2025, 3, 12, 16, 0, 0).unwrap())
+        );
+        // An encrypted assertion does not expose it
+        let encrypted = SAMLAssertionData {
+            relay_state: "cookie:1".to_string(),
+            saml_response: base64::engine::general_purpose::STANDARD.encode("<samlp:Response><saml:EncryptedAssertion/></samlp:Response>"),
+        };
+        assert_eq!(SAMLAttributes::from_saml_response(&encrypted), SAMLAttributes::default());
+    }
+
+    #[test]
+    fn test_query_session() {
+        let server = MockServer::start();
+        server.route("GET", "/jsonapi.php/v1/users/me", 200, r#"{"data": {"type": "users", "id": "u1", "attributes": {"username": "mmuster", "formatted-name": "Max Muster", "permission": "autor"}}}"#);
+        let session = query_session(&server.client(), None).unwrap();
+        assert_eq!(session, Session {
+            user_id: "u1".into(),
+            username: "mmuster".to_string(),
+            display_name: "Max Muster".to_string(),
+            perm: "autor".to_string(),
+            idp_session_expires: None,
+        });
+    }
+}
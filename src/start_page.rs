@@ -0,0 +1,243 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use crate::error::WithUrl;
+use crate::ids::CourseId;
+use crate::news::{parse_news_box, NewsArticle};
+use crate::questionnaire::{parse_questionnaire, Questionnaire};
+use crate::ref_source::{ReferenceSource, START_URL};
+use crate::{SendThrough, StudIpClient};
+use crate::user::{get_username_from_url, User};
+use crate::util::{expect_one, local_to_utc, parse_localized_date_time, parse_page, selector};
+
+static DATE_HEADER_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(?P<when>.*?\d{1,2}:\d{2}(?:\s*-\s*\d{1,2}:\d{2})?)\s*[,:]?\s*(?P<title>.*)$").unwrap());
+
+/// A date of the "Meine aktuellen Termine" widget
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpcomingDate {
+    pub time: Option<DateTime<Utc>>,
+    pub title: String,
+    /// The course of the date, if it is a course date
+    pub course_id: Option<String>,
+}
+
+/// A post of the "Neueste Forenbeiträge" widget, which lists the latest forum posts of all courses of the user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentForumPost {
+    pub course_id: CourseId,
+    pub course_name: String,
+    /// The id of the forum entry, which is also the anchor of the post in the forum of the course
+    pub topic_id: String,
+    /// The title of the thread or posting
+    pub topic: String,
+    /// Anonymous posts have an empty username
+    pub author: User,
+    /// The beginning of the post, as shown by the widget
+    pub excerpt: String,
+    /// The date as displayed (e.g. "Heute, 14:05")
+    pub posted_at_raw: String,
+    pub posted_at: Option<DateTime<Utc>>,
+}
+
+/// The contents of the widgets on the start page \
+/// Widgets, that the user has removed, yield empty vectors
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartPage {
+    /// The global announcements and news
+    pub news: Vec<NewsArticle>,
+    pub upcoming_dates: Vec<UpcomingDate>,
+    pub questionnaires: Vec<Questionnaire>,
+    #[serde(default)]
+    pub forum_posts: Vec<RecentForumPost>,
+}
+
+/// Queries and parses the [`StartPage`]
+pub(crate) fn get_start_page(client: &StudIpClient) -> anyhow::Result<StartPage> {
+    let response = client.get(START_URL).send_through(client)?;
+    let url = response.url().clone();
+    parse_start_page(&parse_page(&response.text()?)?, client.timezone()).with_url(url)
+}
+
+// Finds the widget, whose header contains the given icon
+fn find_widget<'a>(html: &'a Html, icon_selector: &Selector) -> Option<ElementRef<'a>> {
+    let widget_selector = selector!(".studip-widget, #content > article.studip:not([id])");
+    html.select(widget_selector)
+        .find(|widget| widget.select(icon_selector).next().is_some())
+}
+
+fn parse_start_page(html: &Html, timezone: Tz) -> anyhow::Result<StartPage> {
+    let news_icon_selector = selector!("header .icon-shape-news");
+    let dates_icon_selector = selector!("header .icon-shape-date, header .icon-shape-schedule");
+    let questionnaire_selector = selector!("#questionnaire_area > article[data-questionnaire_id]");
+    let news = match find_widget(html, news_icon_selector) {
+        Some(news_elem) => parse_news_box(news_elem, &ReferenceSource::System)?,
+        None => vec![],
+    };
+    let upcoming_dates = match find_widget(html, dates_icon_selector) {
+        Some(dates_elem) => parse_upcoming_dates(dates_elem, timezone)?,
+        None => vec![],
+    };
+    let questionnaires = html.select(questionnaire_selector)
+        .map(|elem| parse_questionnaire(elem, ReferenceSource::StartPage))
+        .collect::<Result<_, _>>()?;
+    let forum_posts = match find_widget(html, selector!("header .icon-shape-forum")) {
+        Some(forum_elem) => parse_forum_posts(forum_elem, timezone)?,
+        None => vec![],
+    };
+    Ok(StartPage {
+        news,
+        upcoming_dates,
+        questionnaires,
+        forum_posts,
+    })
+}
+
+// The text of the element with collapsed whitespace
+fn text_of(element: ElementRef) -> String {
+    element.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Each post links to its entry in the forum of the course (e.g. ".../coreforum/index/index/{topic_id}?cid={course_id}#{topic_id}")
+fn parse_forum_posts(element: ElementRef, timezone: Tz) -> anyhow::Result<Vec<RecentForumPost>> {
+    let mut posts = vec![];
+    for post_elem in element.select(selector!("article.forum-posting")) {
+        let topic_link = expect_one(post_elem, selector!("header h1 a[href]"), "link to forum post")?;
+        let topic_url = url::Url::parse(topic_link.attr("href").unwrap())?;
+        let course_id = topic_url.query_pairs()
+            .find_map(|(key, value)| (key == "cid").then(|| value.to_string()))
+            .context("Expected course of forum post")?;
+        let topic_id = topic_url.fragment()
+            .or_else(|| topic_url.path_segments()?.next_back())
+            .unwrap_or_default()
+            .to_string();
+        let course_name = post_elem.select(selector!(".forum-course"))
+            .next()
+            .map(text_of)
+            .unwrap_or_default();
+        let author_elem = expect_one(post_elem, selector!(".forum-posting-author"), "author of forum post")?;
+        let author = match author_elem.select(selector!("a[href*=\"username=\"]")).next() {
+            Some(link) => User {
+                display_name: text_of(link),
+                username: get_username_from_url(link.attr("href").unwrap())?,
+                avatar_src: None,
+                source: ReferenceSource::StartPage,
+                user_id: None,
+            },
+            None => User {
+                display_name: author_elem.select(selector!("span:not(.forum-posting-date)"))
+                    .next()
+                    .map(text_of)
+                    .unwrap_or_default(),
+                username: String::new(),
+                avatar_src: None,
+                source: ReferenceSource::StartPage,
+                user_id: None,
+            },
+        };
+        let posted_at_raw = author_elem.select(selector!(".forum-posting-date"))
+            .next()
+            .map(text_of)
+            .unwrap_or_default();
+        posts.push(RecentForumPost {
+            course_id: course_id.into(),
+            course_name,
+            topic_id,
+            topic: text_of(topic_link),
+            author,
+            excerpt: post_elem.select(selector!(".forum-posting-excerpt"))
+                .next()
+                .map(text_of)
+                .unwrap_or_default(),
+            posted_at: parse_localized_date_time(&posted_at_raw).and_then(|date_time| local_to_utc(date_time, timezone)),
+            posted_at_raw,
+        });
+    }
+    Ok(posts)
+}
+
+// Each date is a collapsible article, which header starts with the time followed by the title
+fn parse_upcoming_dates(element: ElementRef, timezone: Tz) -> anyhow::Result<Vec<UpcomingDate>> {
+    let date_selector = selector!("article.studip");
+    let header_selector = selector!("header h1");
+    let course_link_selector = selector!("a[href*=\"cid=\"]");
+    let mut dates = vec![];
+    for date_elem in element.select(date_selector) {
+        let header = expect_one(date_elem, header_selector, "date header")?
+            .text()
+            .collect::<String>()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let (time, title) = match DATE_HEADER_REGEX.captures(&header) {
+            Some(captures) => (
+                parse_localized_date_time(&captures["when"]).and_then(|date_time| local_to_utc(date_time, timezone)),
+                captures["title"].to_string(),
+            ),
+            None => (None, header),
+        };
+        let course_id = date_elem.select(course_link_selector)
+            .filter_map(|link| url::Url::parse(link.attr("href")?).ok())
+            .find_map(|url| url.query_pairs().find_map(|(key, value)| (key == "cid").then(|| value.to_string())));
+        dates.push(UpcomingDate {
+            time,
+            title,
+            course_id,
+        });
+    }
+    Ok(dates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::DEFAULT_TIMEZONE;
+
+    #[test]
+    fn test_parse_upcoming_dates() {
+        let html = Html::parse_document(r##"
+            <div class="studip-widget">
+                <header><img class="icon-shape-date"><h1>Meine aktuellen Termine</h1></header>
+                <article class="studip toggle">
+                    <header><h1><a href="#">Mi., 12.03.2025 10:00 - 12:00, Algorithms</a></h1></header>
+                    <section><a href="https://studip.example.com/dispatch.php/course/dates?cid=c1">Zur Veranstaltung</a></section>
+                </article>
+                <article class="studip toggle">
+                    <header><h1>Team meeting</h1></header>
+                </article>
+            </div>
+        "##);
+        let start_page = parse_start_page(&html, DEFAULT_TIMEZONE).unwrap();
+        assert!(start_page.news.is_empty());
+        assert!(start_page.questionnaires.is_empty());
+        assert_eq!(start_page.upcoming_dates.len(), 2);
+        let date = &start_page.upcoming_dates[0];
+        assert_eq!(date.title, "Algorithms");
+        assert_eq!(date.course_id.as_deref(), Some("c1"));
+        assert_eq!(date.time, Some(Utc.with_ymd_and_hms(2025, 3, 12, 9, 0, 0).unwrap()));
+        assert_eq!(start_page.upcoming_dates[1], UpcomingDate { time: None, title: "Team meeting".to_string(), course_id: None });
+        assert!(start_page.forum_posts.is_empty());
+    }
+
+    #[test]
+    fn test_parse_forum_posts() {
+        let html = Html::parse_document(include_str!("../testdata/start_page/forum.html"));
+        let posts = parse_start_page(&html, DEFAULT_TIMEZONE).unwrap().forum_posts;
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[0].course_id, "c1");
+        assert_eq!(posts[0].course_name, "Algorithmen und Datenstrukturen");
+        assert_eq!(posts[0].topic_id, "8b1f2c4d");
+        assert_eq!(posts[0].topic, "Re: Klausurtermin");
+        assert_eq!(posts[0].author.username, "jdoe");
+        assert_eq!(posts[0].excerpt, "Die Klausur findet am 24.03. um 10 Uhr im Audimax statt.");
+        assert_eq!(posts[0].posted_at_raw, "Heute, 14:05");
+        assert_eq!(posts[0].posted_at, parse_localized_date_time("Heute, 14:05").and_then(|date_time| local_to_utc(date_time, DEFAULT_TIMEZONE)));
+        assert_eq!(posts[1].author.display_name, "Anonym");
+        assert_eq!(posts[1].author.username, "");
+        assert_eq!(posts[1].posted_at, Some(Utc.with_ymd_and_hms(2025, 3, 11, 8, 30, 0).unwrap()));
+    }
+}
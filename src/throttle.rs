@@ -0,0 +1,95 @@
+use std::io::{Read, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The size of the chunks, in which throttled downloads are read
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// Paces downloads, so that their average rate stays under a maximum number of bytes per second \
+/// A single limiter can be shared by concurrent downloads, which then share the rate. Idle time does not accumulate into a burst.
+#[derive(Debug)]
+pub(crate) struct BandwidthLimiter {
+    max_bytes_per_sec: u64,
+    /// The point in time, at which all bytes consumed so far are paid off
+    next_free: Mutex<Option<Instant>>,
+}
+
+impl BandwidthLimiter {
+
+    pub fn new(max_bytes_per_sec: u64) -> Self {
+        Self {
+            max_bytes_per_sec: max_bytes_per_sec.max(1),
+            next_free: Default::default(),
+        }
+    }
+
+    pub fn max_bytes_per_sec(&self) -> u64 {
+        self.max_bytes_per_sec
+    }
+
+    /// Accounts for `n_bytes`, that were received at `now` and returns how long to wait, before receiving more
+    fn reserve(&self, n_bytes: usize, now: Instant) -> Duration {
+        let mut next_free = self.next_free.lock().unwrap();
+        let start = next_free.map_or(now, |next_free| next_free.max(now));
+        let next = start + Duration::from_secs_f64(n_bytes as f64 / self.max_bytes_per_sec as f64);
+        *next_free = Some(next);
+        next - now
+    }
+
+    /// Accounts for `n_bytes`, that were just received and sleeps to keep the rate under the maximum
+    pub fn consume(&self, n_bytes: usize) {
+        let wait = self.reserve(n_bytes, Instant::now());
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// Copies the `reader` into the `writer` in chunks, pacing the reads \
+    /// Returns the number of copied bytes.
+    pub fn copy(&self, reader: &mut impl Read, writer: &mut (impl Write + ?Sized)) -> std::io::Result<u64> {
+        let mut buffer = vec![0; CHUNK_SIZE];
+        let mut total = 0;
+        loop {
+            let n_bytes = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n_bytes) => n_bytes,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            writer.write_all(&buffer[..n_bytes])?;
+            total += n_bytes as u64;
+            self.consume(n_bytes);
+        }
+        Ok(total)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pacing() {
+        let limiter = BandwidthLimiter::new(1000);
+        let start = Instant::now();
+        assert_eq!(limiter.reserve(500, start), Duration::from_millis(500));
+        // A second download at the same time has to wait for the first one
+        assert_eq!(limiter.reserve(500, start), Duration::from_millis(1000));
+        assert_eq!(limiter.reserve(1000, start + Duration::from_millis(1000)), Duration::from_millis(1000));
+        // Idle time is not saved up for a later burst
+        assert_eq!(limiter.reserve(250, start + Duration::from_secs(10)), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_copy() {
+        let limiter = BandwidthLimiter::new(100_000);
+        let data = vec![7; 3 * CHUNK_SIZE + 10];
+        let mut copied = vec![];
+        let start = Instant::now();
+        assert_eq!(limiter.copy(&mut data.as_slice(), &mut copied).unwrap(), data.len() as u64);
+        assert_eq!(copied, data);
+        // ~0.49s at 100kB/s
+        assert!(start.elapsed() >= Duration::from_millis(400), "{:?}", start.elapsed());
+    }
+}
@@ -1,18 +1,29 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::path::Path;
-use anyhow::{anyhow, Context};
+use std::sync::Mutex;
+use anyhow::{anyhow, bail, Context};
 use itertools::Itertools;
+use once_cell::sync::Lazy;
+use percent_encoding::percent_decode_str;
+use regex::Regex;
 use reqwest::IntoUrl;
-use scraper::{Element, ElementRef, Html, Selector};
+use scraper::{Element, ElementRef, Html};
 use scraper::selectable::Selectable;
 use serde::{Deserialize, Serialize};
-use url::Url;
+use url::{form_urlencoded, Url};
+use crate::content::{html_to_markdown, html_to_text};
+use crate::error::{check_not_found, check_status, StudIpError};
+use crate::ids::UserId;
 use crate::institute::Institute;
 use crate::news::{NewsArticle, parse_news_box};
 use crate::questionnaire::{parse_questionnaire, Questionnaire};
 use crate::ref_source::ReferenceSource;
-use crate::StudIpClient;
+use crate::search::{global_search, SearchFilter};
+use crate::{SendThrough, StudIpClient};
+use crate::util::{expect_one, parse_page, selector, trimmed_text};
+use crate::logging::log_parse;
 
 pub(crate) const PROFILE_URL: &str = "https://studip.example.com/dispatch.php/profile";
 
@@ -22,7 +33,10 @@ pub struct User {
     pub display_name: String,
     pub username: String,
     pub avatar_src: Option<String>,
-    pub source: ReferenceSource
+    pub source: ReferenceSource,
+    /// The internal id of the user, if the page it was parsed from contains it (see [`resolve_user_id()`] otherwise)
+    #[serde(default)]
+    pub user_id: Option<UserId>,
 }
 
 impl PartialEq for User {
@@ -56,12 +70,29 @@ pub struct ProfileCategory {
     pub html_content: String,
 }
 
+impl ProfileCategory {
+
+    /// The content of the category as plain text
+    pub fn text_content(&self) -> String {
+        html_to_text(&self.html_content)
+    }
+
+    /// The content of the category as Markdown
+    pub fn markdown_content(&self) -> String {
+        html_to_markdown(&self.html_content)
+    }
+
+}
+
 /// The profile of a user \
 /// Contains allot more information about the user then [`User`]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Profile {
     pub display_name: String,
     pub username: String,
+    /// The internal id of the user, if the profile page contains it
+    #[serde(default)]
+    pub user_id: Option<UserId>,
     pub avatar_src: String,
     pub visits: usize,
     pub points: Option<usize>,
@@ -88,186 +119,182 @@ impl User {
         query_params.extend(self.source.get_additional_query_params());
         let response = stud_ip_client.get(PROFILE_URL)
             .query(&query_params)
-            .send()?;
-        let response_text = response.text()?;
-
-        // Grab base profile information
-        let html = Html::parse_document(&response_text);
-        // Parse avatar src
-        let avatar_src_selector = Selector::parse("#sidebar .avatar-widget img").unwrap();
-        let avatar_src = html.select(&avatar_src_selector)
-            .next()
-            .context("Expected avatar image")?
-            .attr("src")
-            .unwrap()
-            .trim()
-            .to_string();
-        // Parse display name
-        let display_name_selector = Selector::parse("#sidebar .sidebar-widget-header").unwrap();
-        let display_name = html.select(&display_name_selector)
-            .next()
-            .context("Expected display name")?
-            .text()
-            .collect::<String>()
-            .trim()
-            .to_string();
+            .send_through(stud_ip_client)?;
+        let profile = parse_profile(&Html::parse_document(&response.text()?), stud_ip_client, &self.username)?;
+        if let Some(user_id) = &profile.user_id {
+            stud_ip_client.user_ids.insert(&self.username, user_id.clone());
+        }
+        Ok(profile)
+    }
 
-        // Parse profile visits points and rank
-        let key_value_regex = regex::Regex::new(r"(?m)^ *(?P<key>.+):\s*(?P<value>[._\- 0-9\w]+?) *$").unwrap();
-        let minor_details_selector = Selector::parse("#sidebar .profile-sidebar-details .minor").unwrap();
-        let mut minor_details = html.select(&minor_details_selector);
-        // Profile visits
-        let profile_visits_str = minor_details.next()
-            .context("Expected profile visits")?
-            .text()
-            .collect::<String>()
+}
+
+/// Parses the profile page of the user with the `username` into a [`Profile`], without requesting anything \
+/// The avatar is resolved against the placeholder host `studip.example.com`.
+pub fn parse_profile_page(html: &str, username: &str) -> anyhow::Result<Profile> {
+    parse_profile(&Html::parse_document(html), StudIpClient::offline(), username)
+}
+
+fn parse_profile(html: &Html, client: &StudIpClient, username: &str) -> anyhow::Result<Profile> {
+    // Parse avatar src
+    let avatar_src_selector = selector!("#sidebar .avatar-widget img");
+    let avatar_src = expect_one(html.root_element(), avatar_src_selector, "avatar image")?
+        .attr("src")
+        .unwrap();
+    let avatar_src = client.absolutize(avatar_src)?.to_string();
+    // Parse display name
+    let display_name_selector = selector!("#sidebar .sidebar-widget-header");
+    let display_name = expect_one(html.root_element(), display_name_selector, "display name")?
+        .text()
+        .collect::<String>()
+        .trim()
+        .to_string();
+
+    // Parse profile visits points and rank
+    let key_value_regex = regex::Regex::new(r"(?m)^ *(?P<key>.+):\s*(?P<value>[._\- 0-9\w]+?) *$").unwrap();
+    let minor_details_selector = selector!("#sidebar .profile-sidebar-details .minor");
+    let mut minor_details = html.select(minor_details_selector);
+    // Profile visits
+    let profile_visits_str = minor_details.next()
+        .context("Expected profile visits")?
+        .text()
+        .collect::<String>()
+        .trim()
+        .to_string();
+    let profile_visits_captures = key_value_regex.captures(&profile_visits_str)
+        .context("Could not capture profile visits")?;
+    let profile_visits : usize  = profile_visits_captures.name("value")
+        .context("Expected profile visits capture")?
+        .as_str()
+        .replace('.', "")
+        .parse()?;
+    // Construct base profile, with only the required fields first
+    let user_id = parse_embedded_user_id(html);
+    let mut profile = Profile {
+        display_name,
+        username: username.to_string(),
+        user_id,
+        avatar_src,
+        visits: profile_visits,
+        points: None,
+        rank: None,
+        email: None,
+        mobile_phone_number: None,
+        home_telephone_number: None,
+        address: None,
+        motto: None,
+        homepage: None,
+        study_institutes: vec![],
+        work_institute: vec![],
+        news: vec![],
+        questionnaires: vec![],
+        categories: vec![],
+    };
+
+    // Fill optional fields
+
+    // Points and rank
+    let source = ReferenceSource::Profile(username.to_string());
+    if let Some(element) = minor_details.next() {
+        let rank_data = element.text().collect::<String>()
             .trim()
             .to_string();
-        let profile_visits_captures = key_value_regex.captures(&profile_visits_str)
-            .context("Could not capture profile visits")?;
-        let profile_visits : usize  = profile_visits_captures.name("value")
-            .context("Expected profile visits capture")?
+        let captures : [_; 2]  = key_value_regex
+            .captures_iter(&rank_data)
+            .collect_vec()
+            .try_into()
+            .map_err(|_| anyhow!("Expected 2 captures"))?;
+        profile.points = Some(captures[0].name("value")
+            .context("Expected points")?
             .as_str()
             .replace('.', "")
-            .parse()?;
-        // Construct base profile, with only the required fields first
-        let mut profile = Profile {
-            display_name,
-            username: self.username.clone(),
-            avatar_src,
-            visits: profile_visits,
-            points: None,
-            rank: None,
-            email: None,
-            mobile_phone_number: None,
-            home_telephone_number: None,
-            address: None,
-            motto: None,
-            homepage: None,
-            study_institutes: vec![],
-            work_institute: vec![],
-            news: vec![],
-            questionnaires: vec![],
-            categories: vec![],
-        };
-
-        // Fill optional fields
-
-        // Points and rank
-        let source = ReferenceSource::Profile(self.username.clone());
-        if let Some(element) = minor_details.next() {
-            let rank_data = element.text().collect::<String>()
-                .trim()
-                .to_string();
-            let captures : [_; 2]  = key_value_regex
-                .captures_iter(&rank_data)
-                .collect_vec()
-                .try_into()
-                .map_err(|_| anyhow!("Expected 2 captures"))?;
-            profile.points = Some(captures[0].name("value")
-                .context("Expected points")?
-                .as_str()
-                .replace('.', "")
-                .parse()?
-            );
-            profile.rank = Some(captures[1].name("value")
-                .context("Expected rank name")?
-                .as_str()
-                .to_string()
-            )
-        }
+            .parse()?
+        );
+        profile.rank = Some(captures[1].name("value")
+            .context("Expected rank name")?
+            .as_str()
+            .to_string()
+        )
+    }
 
-        // Motto
-        let motto_selector = Selector::parse("#sidebar .sidebar-widget:nth-last-child(1)").unwrap();
-        if let Some(motto_widget) = html.select(&motto_selector).next() {
-            let header_selector = Selector::parse(".sidebar-widget-header").unwrap();
-            let header_text = motto_widget.select(&header_selector)
-                .next()
-                .context("Expected widget header")?
+    // Motto
+    let motto_selector = selector!("#sidebar .sidebar-widget:nth-last-child(1)");
+    if let Some(motto_widget) = html.select(motto_selector).next() {
+        let header_selector = selector!(".sidebar-widget-header");
+        let header_text = expect_one(motto_widget, header_selector, "widget header")?
+            .text()
+            .collect::<String>()
+            .to_lowercase();
+        if header_text.contains("motto") {
+            let header_selector = selector!(".sidebar-widget-content");
+            profile.motto = Some(expect_one(motto_widget, header_selector, "motto content")?
                 .text()
                 .collect::<String>()
-                .to_lowercase();
-            if header_text.contains("motto") {
-                let header_selector = Selector::parse(".sidebar-widget-content").unwrap();
-                profile.motto = Some(motto_widget.select(&header_selector)
-                    .next()
-                    .context("Expected motto content")?
-                    .text()
-                    .collect::<String>()
-                    .trim()
-                    .to_string()
-                );
-            }
-        }
-
-        // General info
-        let general_info_selector = Selector::parse("#content .contentbox section dl").unwrap();
-        let general_info_elem = html.select(&general_info_selector).next()
-            .context("Expected general information content box")?;
-        let dt_dd_selector = Selector::parse("dt, dd").unwrap();
-        for (key_elem, value_elem) in general_info_elem.select(&dt_dd_selector).tuples() {
-            let key = key_elem.text().collect::<String>().trim().to_string().to_lowercase();
-            if key.contains("e-mail") {
-                profile.email = Some(value_elem.text().collect::<String>().trim().to_string());
-            } else if key.contains("home telephone number") || key.contains("telefon (privat)") {
-                profile.home_telephone_number = Some(value_elem.text().collect::<String>().trim().to_string());
-            } else if key.contains("mobile telephone") || key.contains("mobiltelefon") {
-                profile.mobile_phone_number = Some(value_elem.text().collect::<String>().trim().to_string());
-            } else if key.contains("address") {
-                profile.address = Some(value_elem.text().collect::<String>().trim().to_string());
-            } else if key.contains("homepage") {
-                profile.homepage = Some(value_elem.text().collect::<String>().trim().to_string());
-            } else if key.contains("work") || key.contains("arbeite") {
-                profile.work_institute = parse_profile_institutes(value_elem)?;
-            } else if key.contains("study") || key.contains("studiere") {
-                profile.study_institutes = parse_profile_institutes(value_elem)?;
-            }
+                .trim()
+                .to_string()
+            );
         }
+    }
 
-        // News
-        let article_selector = Selector::parse("#content > article.studip:not([id])").unwrap();
-        let news_header_selector = Selector::parse("header .icon-shape-news").unwrap();
-        let news_elem = html.select(&article_selector)
-            .find(|elem| elem.select(&news_header_selector).next().is_some());
-        if let Some(news_elem) = news_elem {
-            profile.news = parse_news_box(news_elem, &source)?;
+    // General info
+    let general_info_selector = selector!("#content .contentbox section dl");
+    let general_info_elem = expect_one(html.root_element(), general_info_selector, "general information content box")?;
+    let dt_dd_selector = selector!("dt, dd");
+    for (key_elem, value_elem) in general_info_elem.select(dt_dd_selector).tuples() {
+        let key = key_elem.text().collect::<String>().trim().to_string().to_lowercase();
+        if key.contains("e-mail") {
+            profile.email = Some(value_elem.text().collect::<String>().trim().to_string());
+        } else if key.contains("home telephone number") || key.contains("telefon (privat)") {
+            profile.home_telephone_number = Some(value_elem.text().collect::<String>().trim().to_string());
+        } else if key.contains("mobile telephone") || key.contains("mobiltelefon") {
+            profile.mobile_phone_number = Some(value_elem.text().collect::<String>().trim().to_string());
+        } else if key.contains("address") {
+            profile.address = Some(value_elem.text().collect::<String>().trim().to_string());
+        } else if key.contains("homepage") {
+            profile.homepage = Some(value_elem.text().collect::<String>().trim().to_string());
+        } else if key.contains("work") || key.contains("arbeite") {
+            profile.work_institute = parse_profile_institutes(value_elem)?;
+        } else if key.contains("study") || key.contains("studiere") {
+            profile.study_institutes = parse_profile_institutes(value_elem)?;
         }
+    }
 
-        // Questionnaires
-        let questionnaire_selector = Selector::parse("#questionnaire_area > article[data-questionnaire_id]").unwrap();
-        for questionnaire_elem in html.select(&questionnaire_selector) {
-            profile.questionnaires.push(parse_questionnaire(questionnaire_elem, source.clone())?);
-        }
+    // News
+    let article_selector = selector!("#content > article.studip:not([id])");
+    let news_header_selector = selector!("header .icon-shape-news");
+    let news_elem = html.select(article_selector)
+        .find(|elem| elem.select(news_header_selector).next().is_some());
+    if let Some(news_elem) = news_elem {
+        profile.news = parse_news_box(news_elem, &source)?;
+    }
 
-        // User custom categories
-        let custom_category_abort_selector = Selector::parse("nav").unwrap();
-        let article_header_selector = Selector::parse("#content > article.studip:not([id]) > header").unwrap();
-        // Find articles, which headers descendants don't contain the abort selector (nav)
-        let category_elements = html.select(&article_header_selector)
-            .filter(|elem| elem.select(&custom_category_abort_selector).next().is_none())
-            .map(|elem| elem.parent_element().unwrap());
-        let category_name_selector = Selector::parse("header > h1").unwrap();
-        let category_content_selector = Selector::parse("section").unwrap();
-        for category_elem in category_elements {
-            let name = category_elem
-                .select(&category_name_selector)
-                .next()
-                .context("Expected category name")?
-                .text()
-                .collect::<String>()
-                .trim()
-                .to_string();
-            let content = category_elem
-                .select(&category_content_selector)
-                .next()
-                .context("Expected category content")?
-                .inner_html();
-            profile.categories.push(ProfileCategory { name, html_content: content });
-        }
+    // Questionnaires
+    let questionnaire_selector = selector!("#questionnaire_area > article[data-questionnaire_id]");
+    for questionnaire_elem in html.select(questionnaire_selector) {
+        profile.questionnaires.push(parse_questionnaire(questionnaire_elem, source.clone())?);
+    }
 
-        Ok(profile)
+    // User custom categories
+    let custom_category_abort_selector = selector!("nav");
+    let article_header_selector = selector!("#content > article.studip:not([id]) > header");
+    // Find articles, which headers descendants don't contain the abort selector (nav)
+    let category_elements = html.select(article_header_selector)
+        .filter(|elem| elem.select(custom_category_abort_selector).next().is_none())
+        .map(|elem| elem.parent_element().unwrap());
+    let category_name_selector = selector!("header > h1");
+    let category_content_selector = selector!("section");
+    for category_elem in category_elements {
+        let name = expect_one(category_elem, category_name_selector, "category name")?
+            .text()
+            .collect::<String>()
+            .trim()
+            .to_string();
+        let content = expect_one(category_elem, category_content_selector, "category content")?
+            .inner_html();
+        profile.categories.push(ProfileCategory { name, html_content: content });
     }
 
+    Ok(profile)
 }
 
 impl Profile {
@@ -278,7 +305,7 @@ impl Profile {
             return Ok(None); // Default avatar
         }
         let img_bytes = client.get(&self.avatar_src)
-            .send()?
+            .send_through(client)?
             .bytes()?;
         Ok(Some(img_bytes.to_vec()))
     }
@@ -293,6 +320,65 @@ impl Profile {
         Ok(())
     }
 
+    /// Exports the contact data of the profile as a vCard 4.0 (RFC 6350) \
+    /// Empty fields are omitted, the organization is taken from the first work institute.
+    pub fn to_vcard(&self) -> String {
+        fn non_empty(value: &Option<String>) -> Option<&str> {
+            value.as_deref().map(str::trim).filter(|value| !value.is_empty())
+        }
+        let mut lines = vec!["BEGIN:VCARD".to_string(), "VERSION:4.0".to_string()];
+        lines.push(format!("FN:{}", escape_vcard_text(&self.display_name)));
+        if let Some(email) = non_empty(&self.email) {
+            lines.push(format!("EMAIL:{}", escape_vcard_text(email)));
+        }
+        if let Some(mobile) = non_empty(&self.mobile_phone_number) {
+            lines.push(format!("TEL;TYPE=cell:{}", escape_vcard_text(mobile)));
+        }
+        if let Some(home) = non_empty(&self.home_telephone_number) {
+            lines.push(format!("TEL;TYPE=home:{}", escape_vcard_text(home)));
+        }
+        if let Some(address) = non_empty(&self.address) {
+            // The address is not structured on the profile, so it is stored as the street component
+            lines.push(format!("ADR:;;{};;;;", escape_vcard_text(address)));
+        }
+        if let Some(homepage) = non_empty(&self.homepage) {
+            lines.push(format!("URL:{}", homepage));
+        }
+        if let Some(work_institute) = self.work_institute.first() {
+            lines.push(format!("ORG:{}", escape_vcard_text(&work_institute.institute.name)));
+        }
+        if !self.avatar_src.is_empty() && !self.avatar_src.contains("nobody_normal") {
+            lines.push(format!("PHOTO:{}", self.avatar_src));
+        }
+        lines.push("END:VCARD".to_string());
+        lines.iter().map(|line| fold_vcard_line(line)).collect()
+    }
+
+}
+
+// Escapes the characters, that have a special meaning in vCard text values
+fn escape_vcard_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace("\r\n", "\\n")
+        .replace(['\n', '\r'], "\\n")
+}
+
+// Terminates the line with CRLF and folds it, so that no line is longer than 75 octets
+fn fold_vcard_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut line_len = 0;
+    for c in line.chars() {
+        if line_len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            line_len = 1;
+        }
+        folded.push(c);
+        line_len += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
 }
 
 impl PartialEq for Profile {
@@ -301,48 +387,174 @@ impl PartialEq for Profile {
     }
 }
 
-/// Parses the username from a url
+/// Parses the username from a url \
+/// The username is percent-decoded and trimmed, empty usernames are rejected.
 pub fn get_username_from_url(user_url: impl IntoUrl) -> anyhow::Result<String> {
-    let user_url = user_url.into_url()?;
-    user_url.query_pairs()
-        .find_map(|(key, value)| (key == "username").then(|| value.to_string()))
-        .context("Expected username in user href")
+    get_user_query_value(user_url, "username")
+}
+
+/// Parses the user id from a url, for links that carry the `user_id` instead of the username (e.g. from some plugins)
+pub fn get_user_id_from_url(user_url: impl IntoUrl) -> anyhow::Result<UserId> {
+    get_user_query_value(user_url, "user_id").map(UserId::from)
+}
+
+fn get_user_query_value(user_url: impl IntoUrl, key: &str) -> anyhow::Result<String> {
+    get_query_value(user_url.into_url()?.query().unwrap_or_default(), key)
+}
+
+// Returns the decoded and trimmed value of the parameter `key` of the `query`
+fn get_query_value(query: &str, key: &str) -> anyhow::Result<String> {
+    let value = form_urlencoded::parse(query.as_bytes())
+        .find_map(|(k, value)| (k == key).then_some(value))
+        .with_context(|| format!("Expected {} in user href", key))?;
+    // Some links are encoded twice, which leaves escapes like "%40" in the once decoded value
+    let has_escapes = value.as_bytes().windows(3)
+        .any(|w| w[0] == b'%' && w[1].is_ascii_hexdigit() && w[2].is_ascii_hexdigit());
+    let value = if has_escapes {
+        Cow::Owned(percent_decode_str(&value).decode_utf8()
+            .with_context(|| format!("Invalid {} in user href", key))?
+            .into_owned())
+    } else {
+        value
+    };
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        bail!("Empty {} in user href", key);
+    }
+    // Most values are not padded, then the decoded value is reused
+    Ok(match trimmed.len() == value.len() {
+        true => value.into_owned(),
+        false => trimmed.to_string(),
+    })
+}
+
+/// The number of usernames, which ids are remembered by each client
+const USER_ID_CACHE_SIZE: usize = 256;
+
+static AVATAR_USER_ID_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"/user/(?P<id>[0-9a-f]{32})_").unwrap());
+
+#[derive(Debug, Default)]
+struct UserIdEntries {
+    /// Incremented on every use, to find the longest unused entry
+    tick: u64,
+    /// The last use and the id by username
+    by_username: HashMap<String, (u64, UserId)>,
+}
+
+/// Remembers the ids of at most [`USER_ID_CACHE_SIZE`] usernames, forgetting the longest unused one first
+#[derive(Debug, Default)]
+pub(crate) struct UserIdCache {
+    entries: Mutex<UserIdEntries>,
+}
+
+impl UserIdCache {
+
+    fn get(&self, username: &str) -> Option<UserId> {
+        let mut entries = self.entries.lock().unwrap();
+        let UserIdEntries { tick, by_username } = &mut *entries;
+        let (used, user_id) = by_username.get_mut(username)?;
+        *tick += 1;
+        *used = *tick;
+        Some(user_id.clone())
+    }
+
+    pub fn insert(&self, username: &str, user_id: UserId) {
+        let mut entries = self.entries.lock().unwrap();
+        let UserIdEntries { tick, by_username } = &mut *entries;
+        if !by_username.contains_key(username) && by_username.len() >= USER_ID_CACHE_SIZE {
+            let oldest = by_username.iter().min_by_key(|(_, (used, _))| *used).map(|(username, _)| username.clone());
+            if let Some(oldest) = oldest {
+                by_username.remove(&oldest);
+            }
+        }
+        *tick += 1;
+        by_username.insert(username.to_string(), (*tick, user_id));
+    }
+
 }
 
-/// Parses the username from a link (a tag) element
+/// Resolves a username to the internal id of the user, which is expected by many JSON and XHR endpoints \
+/// The id is read from the profile page, or found with the global search if the profile does not contain it. Resolved ids are cached by the client.
+/// Fails with [`StudIpError::NotFound`], if there is no user with the username.
+pub fn resolve_user_id(client: &StudIpClient, username: &str) -> anyhow::Result<UserId> {
+    if let Some(user_id) = client.user_ids.get(username) {
+        return Ok(user_id);
+    }
+    let response = client.get(PROFILE_URL)
+        .query(&[("username", username)])
+        .send_through(client)?;
+    check_status(response.status())?;
+    let html = parse_page(&response.text()?)?;
+    check_not_found(&html)?;
+    let user_id = match parse_embedded_user_id(&html) {
+        Some(user_id) => user_id,
+        None => {
+            log_parse!(debug, "The profile of {} does not contain its user id, searching for it", username);
+            global_search(client, username, 10, &SearchFilter::Users)?
+            .users
+            .into_iter()
+            .flat_map(|category| category.content)
+            .find(|entry| get_username_from_url(entry.url.as_str()).is_ok_and(|entry_username| entry_username == username))
+            .map(|entry| UserId::from(entry.id))
+            .ok_or_else(|| StudIpError::NotFound { message: Some(format!("There is no user with the username {}", username)) })?
+        },
+    };
+    // Only found ids are cached, so that failed lookups are retried
+    client.user_ids.insert(username, user_id.clone());
+    Ok(user_id)
+}
+
+/// Parses the user id from the src of an avatar (e.g. "/pictures/user/{user_id}_normal.png") \
+/// Default avatars do not contain it.
+pub(crate) fn user_id_from_avatar_src(avatar_src: &str) -> Option<UserId> {
+    AVATAR_USER_ID_REGEX.captures(avatar_src).map(|captures| UserId::from(&captures["id"]))
+}
+
+// The profile embeds the id in its avatar and in the actions of the sidebar
+fn parse_embedded_user_id(html: &Html) -> Option<UserId> {
+    html.select(selector!("#sidebar [data-user-id]"))
+        .find_map(|elem| elem.attr("data-user-id"))
+        .map(UserId::from)
+        .or_else(|| html.select(selector!("#sidebar a[href*=\"user_id=\"]"))
+            .find_map(|link| get_user_id_from_url(link.attr("href").unwrap()).ok()))
+        .or_else(|| html.select(selector!("#sidebar .avatar-widget img"))
+            .find_map(|img| img.attr("src").and_then(user_id_from_avatar_src)))
+}
+
+/// Parses the username from a link (a tag) element \
+/// Only the query of the href is read, so that the whole url does not have to be parsed for every link (e.g. of a large members page).
 pub fn get_username_from_link_element(link_element: ElementRef) -> anyhow::Result<String> {
-    let user_url = Url::parse(link_element.attr("href")
-        .context("Expected user link href")?)?;
-    get_username_from_url(user_url)
+    let href = link_element.attr("href").context("Expected user link href")?;
+    let query = href.split('#')
+        .next()
+        .and_then(|href| href.split_once('?'))
+        .map_or("", |(_, query)| query);
+    get_query_value(query, "username")
 }
 
 /// Parses a [`User`] from html. \
 /// Accepts html in this format: <a href="https://studip.example.com/something?username={some-username}">display name</a>
 pub fn parse_simple_user(link_element: ElementRef) -> anyhow::Result<User> {
-    let display_name = link_element.text()
-        .collect::<String>()
-        .trim()
-        .to_string();
+    let display_name = trimmed_text(link_element);
     let username = get_username_from_link_element(link_element)?;
     Ok(User {
         display_name,
         username,
         avatar_src: None,
         source: ReferenceSource::Unspecified,
+        user_id: None,
     })
 }
 
 // Helper function to parse profile institutes
 fn parse_profile_institutes(element: ElementRef) -> anyhow::Result<Vec<ProfileInstituteData>> {
     let mut institutes = vec![];
-    let list_item_selector = Selector::parse("li").unwrap();
-    let a_tag_selector = Selector::parse("a").unwrap();
-    let sub_flags_selector = Selector::parse("table td:nth-last-child(1)").unwrap();
-    let strong_selector = Selector::parse("strong").unwrap();
-    for profile_institute_elem in element.select(&list_item_selector) {
-        let institute_link_elem = profile_institute_elem.select(&a_tag_selector)
-            .next()
-            .context("Expected institute link")?;
+    let list_item_selector = selector!("li");
+    let a_tag_selector = selector!("a");
+    let sub_flags_selector = selector!("table td:nth-last-child(1)");
+    let strong_selector = selector!("strong");
+    for profile_institute_elem in element.select(list_item_selector) {
+        let institute_link_elem = expect_one(profile_institute_elem, a_tag_selector, "institute link")?;
         let institute_name = institute_link_elem.text()
             .collect::<String>()
             .trim()
@@ -355,7 +567,7 @@ fn parse_profile_institutes(element: ElementRef) -> anyhow::Result<Vec<ProfileIn
             .then(|| value.to_string()))
             .context("Expected institute id")?;
 
-        let sub_flags: Vec<String> = profile_institute_elem.select(&sub_flags_selector)
+        let sub_flags: Vec<String> = profile_institute_elem.select(sub_flags_selector)
             .map(|sub_flag_elem| sub_flag_elem.text()
                 .collect::<String>()
                 .trim()
@@ -364,7 +576,7 @@ fn parse_profile_institutes(element: ElementRef) -> anyhow::Result<Vec<ProfileIn
             .collect();
 
         // Get extra data, the format in the html is this: <strong>key:</strong> then a number of simple text elements
-        let extra_data = profile_institute_elem.select(&strong_selector).map(|key_elem| {
+        let extra_data = profile_institute_elem.select(strong_selector).map(|key_elem| {
             let mut value_text = String::new();
             for sibling in key_elem.next_siblings() {
                 let sibling_value = sibling.value();
@@ -383,11 +595,124 @@ fn parse_profile_institutes(element: ElementRef) -> anyhow::Result<Vec<ProfileIn
         institutes.push(ProfileInstituteData {
             institute: Institute {
                 name: institute_name,
-                id: institute_id
+                id: institute_id.into()
             },
             sub_flags,
             extra_data
         });
     }
     Ok(institutes)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn test_get_username_from_url() {
+        let url = |query: &str| format!("https://studip.example.com/dispatch.php/profile?{}", query);
+        assert_eq!(get_username_from_url(url("username=max.mustermann")).unwrap(), "max.mustermann");
+        assert_eq!(get_username_from_url(url("username=max%40example.com")).unwrap(), "max@example.com");
+        assert_eq!(get_username_from_url(url("username=max%2540example.com")).unwrap(), "max@example.com");
+        assert_eq!(get_username_from_url(url("username=max+mustermann+")).unwrap(), "max mustermann");
+        assert!(get_username_from_url(url("username=+")).is_err());
+        assert!(get_username_from_url(url("user_id=abc123")).is_err());
+        assert_eq!(get_user_id_from_url(url("cid=c1&user_id=abc123")).unwrap(), "abc123");
+    }
+
+    #[test]
+    fn test_resolve_user_id() {
+        let server = MockServer::start();
+        server.route("GET", "/dispatch.php/profile?username=mmu", 200, r#"<div id="sidebar">
+                <div class="sidebar-widget avatar-widget"><img src="/pictures/user/0123456789abcdef0123456789abcdef_normal.png?d=1"></div></div>"#)
+            .route("GET", "/dispatch.php/profile?username=jdoe", 500, "")
+            .route("GET", "/dispatch.php/profile?username=ghost", 404, "");
+        let client = server.client();
+        assert_eq!(resolve_user_id(&client, "mmu").unwrap(), "0123456789abcdef0123456789abcdef");
+        // The second lookup is answered by the cache
+        let n_requests = server.requests().len();
+        assert_eq!(resolve_user_id(&client, "mmu").unwrap(), "0123456789abcdef0123456789abcdef");
+        assert_eq!(server.requests().len(), n_requests);
+
+        let error = resolve_user_id(&client, "ghost").unwrap_err();
+        assert!(matches!(error.downcast_ref::<StudIpError>(), Some(StudIpError::NotFound { .. })), "{:?}", error);
+
+        // A transient error is not cached, the id is then found by the global search, as the profile has a default avatar
+        assert!(resolve_user_id(&client, "jdoe").is_err());
+        server.route("GET", "/dispatch.php/profile?username=jdoe", 200, r#"<div id="sidebar">
+                <div class="sidebar-widget avatar-widget"><img src="/pictures/user/nobody_normal.webp"></div></div>"#)
+            .route_typed("GET", "/dispatch.php/globalsearch/find", 200, "application/json", r#"{"GlobalSearchUsers": {"name": "Personen", "fullsearch": "", "more": false, "plus": false, "content": [
+                {"id": "fedcba9876543210fedcba9876543210", "name": "<mark>jdoe</mark>x", "url": "https://studip.example.com/dispatch.php/profile?username=jdoex", "additional": "", "expand": "", "img": ""},
+                {"id": "aaaabbbbccccddddeeeeffff00001111", "name": "John Doe", "url": "https://studip.example.com/dispatch.php/profile?username=jdoe", "additional": "", "expand": "", "img": ""}]}}"#);
+        assert_eq!(resolve_user_id(&client, "jdoe").unwrap(), "aaaabbbbccccddddeeeeffff00001111");
+    }
+
+    fn profile() -> Profile {
+        Profile {
+            display_name: "Max Mustermann".to_string(),
+            username: "mmu".to_string(),
+            user_id: None,
+            avatar_src: "https://studip.example.com/pictures/user/mmu_normal.png".to_string(),
+            visits: 0,
+            points: None,
+            rank: None,
+            email: None,
+            mobile_phone_number: None,
+            home_telephone_number: None,
+            address: None,
+            motto: None,
+            homepage: None,
+            study_institutes: vec![],
+            work_institute: vec![],
+            news: vec![],
+            questionnaires: vec![],
+            categories: vec![],
+        }
+    }
+
+    #[test]
+    fn test_to_vcard() {
+        let mut full = profile();
+        full.display_name = "Mustermann, Max; Dr.".to_string();
+        full.email = Some("max@example.com".to_string());
+        full.mobile_phone_number = Some("+49 170 1234567".to_string());
+        full.home_telephone_number = Some("+49 30 123456".to_string());
+        full.address = Some("Musterstraße 1\n12345 Musterstadt".to_string());
+        full.homepage = Some("https://example.com/~max".to_string());
+        full.work_institute = vec![ProfileInstituteData {
+            institute: Institute { id: "i1".into(), name: "Institut für Informatik, Abteilung 2".to_string() },
+            extra_data: Default::default(),
+            sub_flags: vec![],
+        }];
+        assert_eq!(full.to_vcard(), "BEGIN:VCARD\r\nVERSION:4.0\r\nFN:Mustermann\\, Max\\; Dr.\r\nEMAIL:max@example.com\r\n\
+            TEL;TYPE=cell:+49 170 1234567\r\nTEL;TYPE=home:+49 30 123456\r\nADR:;;Musterstraße 1\\n12345 Musterstadt;;;;\r\n\
+            URL:https://example.com/~max\r\nORG:Institut für Informatik\\, Abteilung 2\r\n\
+            PHOTO:https://studip.example.com/pictures/user/mmu_normal.png\r\nEND:VCARD\r\n");
+
+        let mut sparse = profile();
+        sparse.avatar_src = "https://studip.example.com/pictures/user/nobody_normal.webp".to_string();
+        sparse.email = Some(" ".to_string());
+        assert_eq!(sparse.to_vcard(), "BEGIN:VCARD\r\nVERSION:4.0\r\nFN:Max Mustermann\r\nEND:VCARD\r\n");
+    }
+
+    #[test]
+    fn test_parse_profile_page() {
+        let profile = parse_profile_page(include_str!("../testdata/profile/full.html"), "emusterfrau").unwrap();
+        assert_eq!(profile.avatar_src, "https://studip.example.com/pictures/user/fedcba9876543210fedcba9876543210_normal.png");
+        assert_eq!(profile.user_id.as_deref(), Some("fedcba9876543210fedcba9876543210"));
+        assert_eq!(profile.to_vcard().lines().nth(3), Some("EMAIL:erika.musterfrau@studip.example.com"));
+        // The name of the general information box is not a custom category
+        let categories = profile.categories.iter().map(|category| category.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(categories, vec!["Hobbys"]);
+        assert!(parse_profile_page("<div id=\"content\"></div>", "emusterfrau").is_err());
+    }
+
+    #[test]
+    fn test_fold_vcard_line() {
+        let line = format!("NOTE:{}", "ä".repeat(40));
+        let folded = fold_vcard_line(&line);
+        assert!(folded.split("\r\n").all(|line| line.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+}
@@ -0,0 +1,469 @@
+use std::borrow::Cow;
+use anyhow::Context;
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use scraper::selector::ToCss;
+
+/// Parses a css selector literal once and returns a `&'static Selector` to it
+macro_rules! selector {
+    ($selector:literal) => {{
+        static SELECTOR: once_cell::sync::Lazy<scraper::Selector> = once_cell::sync::Lazy::new(|| scraper::Selector::parse($selector).unwrap());
+        &*SELECTOR
+    }};
+}
+pub(crate) use selector;
+
+static DATE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?P<day>\d{1,2})\.(?P<month>\d{1,2})\.(?P<year>\d{4}|\d{2})\b").unwrap());
+static ISO_DATE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?P<year>\d{4})-(?P<month>\d{2})-(?P<day>\d{2})").unwrap());
+static HTML_ENTITY_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"&(#[xX][0-9a-fA-F]+|#[0-9]+|[a-zA-Z][a-zA-Z0-9]*);").unwrap());
+static SIZE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)(?P<number>\d+(?:[.,]\d+)?)\s*(?P<unit>[kmgt]i?b|bytes?|b)\b").unwrap());
+static TIME_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?P<hour>\d{1,2}):(?P<minute>\d{2})").unwrap());
+
+/// Escapes a single CSV field according to RFC 4180. \
+/// Fields containing a separator, a quote or a line break are quoted, with inner quotes doubled.
+pub(crate) fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Joins the given fields into a single, newline terminated CSV row
+pub(crate) fn csv_row<S: AsRef<str>>(fields: &[S]) -> String {
+    let mut row = fields.iter()
+        .map(|field| csv_field(field.as_ref()))
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push('\n');
+    row
+}
+
+/// Parses CSV, as written by [`csv_row()`], back into its rows of fields
+#[cfg(test)]
+pub(crate) fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            },
+            ('"', _) => quoted = !quoted,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            },
+            (c, _) => field.push(c),
+        }
+    }
+    rows
+}
+
+/// Escapes the characters, that have a special meaning in html
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The names, that Windows reserves for devices, even if they have an extension (e.g. `CON.pdf`)
+const RESERVED_FILE_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+/// The maximum length of a file name on common file systems, in bytes
+const MAX_FILE_NAME_BYTES: usize = 255;
+/// Longer suffixes are not treated as an extension, when a name is shortened
+const MAX_EXTENSION_BYTES: usize = 16;
+
+/// Turns a name into a single file name, that is valid on common file systems \
+/// Path separators and characters, that are not allowed (e.g. on Windows), are replaced with `_`, trailing dots and spaces are removed,
+/// names reserved by Windows (e.g. `CON.pdf`) are prefixed with `_` and long names are shortened, keeping their extension.
+pub(crate) fn sanitize_file_name(name: &str) -> String {
+    let sanitized = name.trim()
+        .chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .collect::<String>();
+    // Windows drops trailing dots and spaces, so that "a." would overwrite "a"
+    let mut sanitized = sanitized.trim_end_matches(['.', ' ']).to_string();
+    if sanitized.is_empty() {
+        return "_".to_string();
+    }
+    let stem = sanitized.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_FILE_NAMES.iter().any(|reserved| stem.eq_ignore_ascii_case(reserved)) {
+        sanitized.insert(0, '_');
+    }
+    shorten_file_name(sanitized)
+}
+
+// Cuts the part before the extension, so that the name fits into MAX_FILE_NAME_BYTES
+fn shorten_file_name(name: String) -> String {
+    if name.len() <= MAX_FILE_NAME_BYTES {
+        return name;
+    }
+    let (stem, extension) = match name.rfind('.') {
+        Some(index) if index > 0 && name.len() - index <= MAX_EXTENSION_BYTES => name.split_at(index),
+        _ => (name.as_str(), ""),
+    };
+    let mut end = MAX_FILE_NAME_BYTES - extension.len();
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", stem[..end].trim_end_matches(['.', ' ']), extension)
+}
+
+/// Matches a name against a glob pattern, which supports `*` (any characters) and `?` (a single character) \
+/// The comparison ignores the case.
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let name = name.to_lowercase().chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // The position of the last star in the pattern and the position in the name it was tried at
+    let mut backtrack = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, tried)) = backtrack {
+            // Let the star consume one more character
+            p = star + 1;
+            n = tried + 1;
+            backtrack = Some((star, tried + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Decodes html entities (numeric ones and the most common named ones) \
+/// Unknown entities are left as they are.
+pub(crate) fn decode_html_entities(text: &str) -> Cow<'_, str> {
+    HTML_ENTITY_REGEX.replace_all(text, |captures: &regex::Captures| {
+        let entity = &captures[1];
+        let decoded = if let Some(hex) = entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+            u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+        } else if let Some(decimal) = entity.strip_prefix('#') {
+            decimal.parse().ok().and_then(char::from_u32)
+        } else {
+            named_html_entity(entity)
+        };
+        match decoded {
+            Some(c) => c.to_string(),
+            None => captures[0].to_string(),
+        }
+    })
+}
+
+fn named_html_entity(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "auml" => 'ä',
+        "ouml" => 'ö',
+        "uuml" => 'ü',
+        "Auml" => 'Ä',
+        "Ouml" => 'Ö',
+        "Uuml" => 'Ü',
+        "szlig" => 'ß',
+        "eacute" => 'é',
+        "egrave" => 'è',
+        "aacute" => 'á',
+        "agrave" => 'à',
+        "ccedil" => 'ç',
+        "euro" => '€',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "laquo" => '«',
+        "raquo" => '»',
+        "bdquo" => '„',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "copy" => '©',
+        "reg" => '®',
+        _ => return None,
+    })
+}
+
+/// Returns the trimmed text of the `element` \
+/// Most elements contain a single text node (next to e.g. an icon), which is then copied once, instead of collecting all nodes first.
+pub(crate) fn trimmed_text(element: ElementRef) -> String {
+    let mut texts = element.text().filter(|text| !text.trim().is_empty());
+    match (texts.next(), texts.next()) {
+        (None, _) => String::new(),
+        (Some(text), None) => text.trim().to_string(),
+        _ => element.text().collect::<String>().trim().to_string(),
+    }
+}
+
+/// Normalizes a text for comparisons \
+/// Folds case, common diacritics (e.g. "Ü" -> "u", "ß" -> "ss") and whitespace
+pub(crate) fn normalize_text(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    for c in text.split_whitespace().collect::<Vec<_>>().join(" ").chars().flat_map(char::to_lowercase) {
+        match c {
+            'ä' | 'á' | 'à' | 'â' | 'ã' | 'å' => normalized.push('a'),
+            'ö' | 'ó' | 'ò' | 'ô' | 'õ' | 'ø' => normalized.push('o'),
+            'ü' | 'ú' | 'ù' | 'û' => normalized.push('u'),
+            'é' | 'è' | 'ê' | 'ë' => normalized.push('e'),
+            'í' | 'ì' | 'î' | 'ï' => normalized.push('i'),
+            'ç' | 'č' | 'ć' => normalized.push('c'),
+            'ñ' | 'ń' => normalized.push('n'),
+            'š' | 'ś' => normalized.push('s'),
+            'ž' | 'ź' | 'ż' => normalized.push('z'),
+            'ß' => normalized.push_str("ss"),
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+/// Parses the CSRF security token, that Stud.IP embeds into every form
+pub(crate) fn parse_security_token(html: &Html) -> anyhow::Result<String> {
+    let token_selector = selector!("input[name=\"security_token\"]");
+    html.select(token_selector)
+        .find_map(|elem| elem.attr("value"))
+        .map(|token| token.to_string())
+        .context("Expected security token")
+}
+
+/// The highest page number linked in the pagination, or 1 if there is none
+pub(crate) fn parse_last_page(html: &Html) -> usize {
+    let pagination_selector = selector!(".pagination a, .pagination span");
+    html.select(pagination_selector)
+        .filter_map(|elem| elem.text().collect::<String>().trim().parse().ok())
+        .max()
+        .unwrap_or(1)
+}
+
+/// Returns the first element inside the `scope`, that matches the `selector` \
+/// Fails with a [`StudIpError::Parse`](crate::error::StudIpError::Parse) naming the expected `what`, which also contains a snippet of the `scope`.
+pub(crate) fn expect_one<'a>(scope: ElementRef<'a>, selector: &Selector, what: &str) -> Result<ElementRef<'a>, crate::error::StudIpError> {
+    scope.select(selector).next().ok_or_else(|| crate::error::StudIpError::Parse {
+        what: what.to_string(),
+        selector: selector.to_css_string(),
+        url: None,
+        snippet: crate::error::html_snippet(scope),
+    })
+}
+
+/// Parses a fetched page, failing with a [`StudIpError`](crate::error::StudIpError) if Stud.IP served one of its error pages instead
+pub(crate) fn parse_page(body: &str) -> anyhow::Result<Html> {
+    let html = Html::parse_document(body);
+    crate::error::check_page(&html)?;
+    Ok(html)
+}
+
+/// Parses a localized date, like "Mi., 12.03.2025", "12.03.25", "2025-03-12" or "Heute"/"Today" \
+/// Any surrounding text (like weekdays) is ignored.
+pub(crate) fn parse_localized_date(text: &str) -> Option<NaiveDate> {
+    let today = Local::now().date_naive();
+    parse_localized_date_relative_to(text, today)
+}
+
+fn parse_localized_date_relative_to(text: &str, today: NaiveDate) -> Option<NaiveDate> {
+    if let Some(captures) = DATE_REGEX.captures(text).or_else(|| ISO_DATE_REGEX.captures(text)) {
+        let mut year: i32 = captures.name("year")?.as_str().parse().ok()?;
+        if year < 100 {
+            year += 2000;
+        }
+        let month = captures.name("month")?.as_str().parse().ok()?;
+        let day = captures.name("day")?.as_str().parse().ok()?;
+        return NaiveDate::from_ymd_opt(year, month, day);
+    }
+    let lowercase = text.to_lowercase();
+    if lowercase.contains("heute") || lowercase.contains("today") {
+        Some(today)
+    } else if lowercase.contains("gestern") || lowercase.contains("yesterday") {
+        today.checked_sub_days(Days::new(1))
+    } else {
+        None
+    }
+}
+
+/// Parses a localized date with an optional time, like "12.03.2025 14:00" or "Heute, 14:00" \
+/// If only a time is given, the date is assumed to be today.
+pub(crate) fn parse_localized_date_time(text: &str) -> Option<NaiveDateTime> {
+    let time = TIME_REGEX.captures(text).and_then(|captures| NaiveTime::from_hms_opt(
+        captures.name("hour")?.as_str().parse().ok()?,
+        captures.name("minute")?.as_str().parse().ok()?,
+        0
+    ));
+    let date = match parse_localized_date(text) {
+        Some(date) => date,
+        None if time.is_some() => Local::now().date_naive(),
+        None => return None,
+    };
+    Some(date.and_time(time.unwrap_or_default()))
+}
+
+/// Parses a human-readable size, like "12,5 KB", "1.2 GB" or "300 Bytes", into bytes \
+/// Both comma and dot are accepted as the decimal separator, units are interpreted as powers of 1024.
+pub(crate) fn parse_size(text: &str) -> Option<u64> {
+    let captures = SIZE_REGEX.captures(text)?;
+    let number: f64 = captures.name("number")?.as_str().replace(',', ".").parse().ok()?;
+    let unit = captures.name("unit")?.as_str().to_lowercase();
+    let exponent = match unit.chars().next()? {
+        'k' => 1,
+        'm' => 2,
+        'g' => 3,
+        't' => 4,
+        _ => 0,
+    };
+    Some((number * 1024f64.powi(exponent)).round() as u64)
+}
+
+/// Converts a date time, as rendered by Stud.IP in the campus `timezone` (see [`StudIpClient::timezone()`](crate::StudIpClient::timezone())), to UTC \
+/// Times skipped by a change to daylight saving time do not exist, times repeated by the change back are resolved to their first occurrence.
+pub(crate) fn local_to_utc(date_time: NaiveDateTime, timezone: Tz) -> Option<DateTime<Utc>> {
+    date_time.and_local_timezone(timezone)
+        .earliest()
+        .map(|local| local.to_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_csv_row_escaping() {
+        assert_eq!(csv_row(&["a", "b c", "1"]), "a,b c,1\n");
+        assert_eq!(csv_row(&["Doe, John", "say \"hi\"", "multi\nline"]), "\"Doe, John\",\"say \"\"hi\"\"\",\"multi\nline\"\n");
+        assert_eq!(csv_row::<&str>(&[]), "\n");
+        let fields = ["Doe, John", "say \"hi\"", "multi\nline", ""];
+        assert_eq!(parse_csv(&csv_row(&fields)), vec![fields.to_vec()]);
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("Blatt 1: Lösung?.pdf"), "Blatt 1_ Lösung_.pdf");
+        assert_eq!(sanitize_file_name("a/b\\c"), "a_b_c");
+        assert_eq!(sanitize_file_name(".."), "_");
+        assert_eq!(escape_html("<a href=\"x\">&</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;");
+    }
+
+    #[test]
+    fn test_sanitize_nasty_file_names() {
+        // Path traversal stays within a single name
+        assert_eq!(sanitize_file_name("../../etc/passwd"), ".._.._etc_passwd");
+        assert_eq!(sanitize_file_name("..\\..\\Windows\\win.ini"), ".._.._Windows_win.ini");
+        assert_eq!(sanitize_file_name("/etc/passwd"), "_etc_passwd");
+        assert_eq!(sanitize_file_name("C:\\boot.ini"), "C__boot.ini");
+        assert_eq!(sanitize_file_name("."), "_");
+        assert_eq!(sanitize_file_name(" ... "), "_");
+        assert_eq!(sanitize_file_name(""), "_");
+        assert_eq!(sanitize_file_name(".bashrc"), ".bashrc");
+        // Windows reserved names, also with an extension or in lowercase
+        assert_eq!(sanitize_file_name("CON"), "_CON");
+        assert_eq!(sanitize_file_name("con.pdf"), "_con.pdf");
+        assert_eq!(sanitize_file_name("Aux.tar.gz"), "_Aux.tar.gz");
+        assert_eq!(sanitize_file_name("COM1 .txt"), "_COM1 .txt");
+        assert_eq!(sanitize_file_name("lpt9"), "_lpt9");
+        assert_eq!(sanitize_file_name("CONSOLE.txt"), "CONSOLE.txt");
+        assert_eq!(sanitize_file_name("COM10"), "COM10");
+        // Trailing dots and spaces, control characters
+        assert_eq!(sanitize_file_name("Blatt 1. "), "Blatt 1");
+        assert_eq!(sanitize_file_name("notes..."), "notes");
+        assert_eq!(sanitize_file_name("a\u{0}b\tc\n.txt"), "a_b_c_.txt");
+        assert_eq!(sanitize_file_name("Übung 3 – Lösung 😀.pdf"), "Übung 3 – Lösung 😀.pdf");
+        // Long names are shortened on a character boundary, keeping the extension
+        let long = sanitize_file_name(&format!("{}.pdf", "a".repeat(300)));
+        assert_eq!((long.len(), long.ends_with(".pdf")), (255, true));
+        let long = sanitize_file_name(&format!("{}.pdf", "ä".repeat(200)));
+        assert_eq!(long, format!("{}.pdf", "ä".repeat(125)));
+        let long = sanitize_file_name(&format!("{}.{}", "a".repeat(200), "b".repeat(100)));
+        assert_eq!(long.len(), 255);
+        assert!(sanitize_file_name(&"€".repeat(100)).len() <= 255);
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.pdf", "Blatt 1.PDF"));
+        assert!(glob_match("blatt_??.pdf", "Blatt_03.pdf"));
+        assert!(glob_match("*lösung*", "Übung 3 Lösung.zip"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("*.pdf", "slides.pdf.zip"));
+        assert!(!glob_match("blatt_?.pdf", "Blatt_03.pdf"));
+    }
+
+    #[test]
+    fn test_parse_security_token() {
+        let html = Html::parse_document(r#"<form><input type="hidden" name="security_token" value="abc123="></form>"#);
+        assert_eq!(parse_security_token(&html).unwrap(), "abc123=");
+        assert!(parse_security_token(&Html::parse_document("<form></form>")).is_err());
+    }
+
+    #[test]
+    fn test_parse_localized_date() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 14).unwrap();
+        let expected = NaiveDate::from_ymd_opt(2025, 3, 12);
+        assert_eq!(parse_localized_date_relative_to("Mi., 12.03.2025", today), expected);
+        assert_eq!(parse_localized_date_relative_to("Wed, 12.3.25", today), expected);
+        assert_eq!(parse_localized_date_relative_to("2025-03-12", today), expected);
+        assert_eq!(parse_localized_date_relative_to("Heute", today), Some(today));
+        assert_eq!(parse_localized_date_relative_to("Yesterday, 10:00", today), NaiveDate::from_ymd_opt(2025, 3, 13));
+        assert_eq!(parse_localized_date_relative_to("SoSe 2025", today), None);
+    }
+
+    #[test]
+    fn test_parse_localized_date_time() {
+        let parsed = parse_localized_date_time("12.03.2025 14:05").unwrap();
+        assert_eq!(parsed, NaiveDate::from_ymd_opt(2025, 3, 12).unwrap().and_hms_opt(14, 5, 0).unwrap());
+        let parsed = parse_localized_date_time("12.03.2025").unwrap();
+        assert_eq!(parsed.time(), NaiveTime::MIN);
+        assert!(parse_localized_date_time("unknown").is_none());
+    }
+
+    #[test]
+    fn test_normalize_text() {
+        assert_eq!(normalize_text("  Müller,   Jürgen "), "muller, jurgen");
+        assert_eq!(normalize_text("Straße"), "strasse");
+        assert_eq!(normalize_text("ÉCOLE"), "ecole");
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("(12,5 KB)"), Some(12800));
+        assert_eq!(parse_size("1.5 MB"), Some(1572864));
+        assert_eq!(parse_size("2 GB von 5 GB"), Some(2147483648));
+        assert_eq!(parse_size("300 Bytes"), Some(300));
+        assert_eq!(parse_size("1 KiB"), Some(1024));
+        assert_eq!(parse_size("no size"), None);
+    }
+
+    #[test]
+    fn test_local_to_utc() {
+        let date_time = |text| NaiveDateTime::parse_from_str(text, "%d.%m.%Y %H:%M").unwrap();
+        let utc = |y, m, d, h, min| Some(Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap());
+        let berlin = chrono_tz::Europe::Berlin;
+        // Independent of the timezone of the machine running the tests
+        assert_eq!(local_to_utc(date_time("12.03.2025 14:05"), berlin), utc(2025, 3, 12, 13, 5));
+        assert_eq!(local_to_utc(date_time("01.04.2025 10:00"), berlin), utc(2025, 4, 1, 8, 0));
+        assert_eq!(local_to_utc(date_time("12.03.2025 14:05"), chrono_tz::America::New_York), utc(2025, 3, 12, 18, 5));
+        // Skipped and repeated by the changes of daylight saving time
+        assert_eq!(local_to_utc(date_time("30.03.2025 02:30"), berlin), None);
+        assert_eq!(local_to_utc(date_time("26.10.2025 02:30"), berlin), utc(2025, 10, 26, 0, 30));
+    }
+}
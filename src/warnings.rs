@@ -0,0 +1,95 @@
+use std::fmt::Display;
+use serde::{Deserialize, Serialize};
+use crate::logging::log_parse;
+
+/// Something unexpected, because of which a part of a page (e.g. a row of a table) was skipped while parsing it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseWarning {
+    /// Where the part was found, like "members table students"
+    pub context: String,
+    /// Why the part was skipped
+    pub detail: String,
+}
+
+/// A parsed value together with the warnings of the parts, that were left out of it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Parsed<T> {
+    pub value: T,
+    pub warnings: Vec<ParseWarning>,
+}
+
+impl<T> Parsed<T> {
+
+    /// Logs one line per context of the warnings, with the number of skipped parts (see [`logging::PARSE`](crate::logging::PARSE))
+    pub fn log_summary(&self) {
+        log_summary(&self.warnings);
+    }
+
+}
+
+// One line per context, in the order of their first warning, naming the first detail
+fn summarize(warnings: &[ParseWarning]) -> Vec<String> {
+    let mut contexts: Vec<(&ParseWarning, usize)> = vec![];
+    for warning in warnings {
+        match contexts.iter_mut().find(|(first, _)| first.context == warning.context) {
+            Some((_, count)) => *count += 1,
+            None => contexts.push((warning, 1)),
+        }
+    }
+    contexts.into_iter()
+        .map(|(first, count)| format!("Skipped {} part(s) of {}, the first because: {}", count, first.context, first.detail))
+        .collect()
+}
+
+fn log_summary(warnings: &[ParseWarning]) {
+    for line in summarize(warnings) {
+        log_parse!(warn, "{}", line);
+    }
+}
+
+/// Collects the [`ParseWarning`]s, while a page is parsed \
+/// With the `verbose` feature, each warning is also logged at the debug level, their summary at the warn level.
+#[derive(Debug, Default)]
+pub(crate) struct ParseWarnings(Vec<ParseWarning>);
+
+impl ParseWarnings {
+
+    pub fn push(&mut self, context: impl Into<String>, detail: impl Display) {
+        let warning = ParseWarning { context: context.into(), detail: detail.to_string() };
+        log_parse!(debug, "Skipped part of {}: {}", warning.context, warning.detail);
+        self.0.push(warning);
+    }
+
+    /// Returns the value of the `result`, or records its error and returns `None`
+    pub fn skip_err<T>(&mut self, context: impl Into<String>, result: anyhow::Result<T>) -> Option<T> {
+        result.map_err(|error| self.push(context, format!("{:#}", error))).ok()
+    }
+
+    pub fn into_parsed<T>(self, value: T) -> Parsed<T> {
+        Parsed { value, warnings: self.0 }
+    }
+
+    /// Logs one line per context of the warnings collected so far, e.g. at the end of a run
+    pub fn log_summary(&self) {
+        log_summary(&self.0);
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let mut warnings = ParseWarnings::default();
+        warnings.push("members table studierende", "Expected link to member");
+        warnings.push("group 2", "Expected group name");
+        warnings.push("members table studierende", "Expected avatar of member max");
+        assert_eq!(summarize(&warnings.0), vec![
+            "Skipped 2 part(s) of members table studierende, the first because: Expected link to member".to_string(),
+            "Skipped 1 part(s) of group 2, the first because: Expected group name".to_string(),
+        ]);
+        assert!(summarize(&[]).is_empty());
+    }
+}
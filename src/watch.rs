@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use crate::diff::{AnnouncementSnapshot, CourseSnapshot, DateSnapshot, FileSnapshot, GroupSnapshot};
+use crate::ids::CourseId;
+use crate::StudIp;
+
+/// The maximum time to wait between polls, when backing off after failures
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+/// How often a sleeping watcher checks, if it should stop
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// An event of a watched course, as emitted by the [`Watcher`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CourseEvent {
+    NewFile { course_id: CourseId, file: FileSnapshot },
+    NewAnnouncement { course_id: CourseId, announcement: AnnouncementSnapshot },
+    GroupOpened { course_id: CourseId, group: GroupSnapshot },
+    DateCancelled { course_id: CourseId, date: DateSnapshot },
+}
+
+type EventCallback = Box<dyn FnMut(CourseEvent)>;
+type ErrorCallback = Box<dyn FnMut(&str, anyhow::Error)>;
+
+/// Stops a running [`Watcher`], can be obtained with [`Watcher::handle()`]
+#[derive(Debug, Clone)]
+pub struct WatcherHandle {
+    stopped: Arc<AtomicBool>,
+}
+
+impl WatcherHandle {
+    /// Stops the watcher after its current poll
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Periodically polls courses and invokes a callback for every [`CourseEvent`] \
+/// The snapshots are persisted to the state path (if set), so restarts don't emit old events again.
+pub struct Watcher {
+    stud_ip: StudIp,
+    course_ids: Vec<CourseId>,
+    interval: Duration,
+    state_path: Option<PathBuf>,
+    on_event: EventCallback,
+    on_error: ErrorCallback,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Watcher {
+
+    /// Creates a watcher, that polls every 15 minutes and ignores all events until [`Watcher::on_event()`] is set
+    pub fn new(stud_ip: StudIp) -> Self {
+        Self {
+            stud_ip,
+            course_ids: vec![],
+            interval: Duration::from_secs(15 * 60),
+            state_path: None,
+            on_event: Box::new(|_| {}),
+            on_error: Box::new(|_, _| {}),
+            stopped: Default::default(),
+        }
+    }
+
+    /// Sets the ids of the courses to watch
+    pub fn courses(mut self, course_ids: Vec<CourseId>) -> Self {
+        self.course_ids = course_ids;
+        self
+    }
+
+    /// Sets the time between polls
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the path, to which the snapshots are persisted
+    pub fn state_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_path = Some(path.into());
+        self
+    }
+
+    /// Sets the callback, that is invoked for every event
+    pub fn on_event(mut self, on_event: impl FnMut(CourseEvent) + 'static) -> Self {
+        self.on_event = Box::new(on_event);
+        self
+    }
+
+    /// Sets the callback, that is invoked with the course id, when polling a course fails
+    pub fn on_error(mut self, on_error: impl FnMut(&str, anyhow::Error) + 'static) -> Self {
+        self.on_error = Box::new(on_error);
+        self
+    }
+
+    /// Returns a handle, that can stop the watcher from another thread
+    pub fn handle(&self) -> WatcherHandle {
+        WatcherHandle { stopped: self.stopped.clone() }
+    }
+
+    fn load_state(&self) -> anyhow::Result<BTreeMap<CourseId, CourseSnapshot>> {
+        let Some(path) = &self.state_path else {
+            return Ok(Default::default());
+        };
+        if !path.exists() {
+            return Ok(Default::default());
+        }
+        let json = std::fs::read_to_string(path).context("Could not read watcher state")?;
+        serde_json::from_str(&json).context("Could not parse watcher state")
+    }
+
+    fn save_state(&self, state: &BTreeMap<CourseId, CourseSnapshot>) -> anyhow::Result<()> {
+        if let Some(path) = &self.state_path {
+            std::fs::write(path, serde_json::to_string(state)?).context("Could not write watcher state")?;
+        }
+        Ok(())
+    }
+
+    // Polls every course once, returns if all courses could be polled
+    fn poll(&mut self, state: &mut BTreeMap<CourseId, CourseSnapshot>) -> bool {
+        if self.stud_ip.my_courses.courses.is_empty() {
+            if let Err(e) = self.stud_ip.my_courses.query() {
+                (self.on_error)("", e);
+                return false;
+            }
+        }
+        let mut success = true;
+        for course_id in self.course_ids.clone() {
+            let snapshot = self.stud_ip.my_courses.courses.get_mut(&course_id)
+                .with_context(|| format!("Course {} not found", course_id))
+                .and_then(CourseSnapshot::capture);
+            let snapshot = match snapshot {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    (self.on_error)(&course_id, e);
+                    success = false;
+                    continue;
+                }
+            };
+            // The first snapshot of a course is only the baseline
+            if let Some(older) = state.get(&course_id) {
+                for event in events_between(older, &snapshot) {
+                    (self.on_event)(event);
+                }
+            }
+            state.insert(course_id, snapshot);
+        }
+        success
+    }
+
+    /// Runs the watcher, until it is stopped through a [`WatcherHandle`] \
+    /// On repeated failures, the time between polls is doubled up to an hour.
+    pub fn run(mut self) -> anyhow::Result<()> {
+        let mut state = self.load_state()?;
+        let mut consecutive_failures = 0;
+        while !self.stopped.load(Ordering::SeqCst) {
+            if self.poll(&mut state) {
+                consecutive_failures = 0;
+            } else {
+                consecutive_failures += 1;
+            }
+            self.save_state(&state)?;
+            let wait_time = backoff(self.interval, consecutive_failures);
+            let start = Instant::now();
+            while !self.stopped.load(Ordering::SeqCst) {
+                // Computed once, as the deadline can pass between two reads of the clock
+                let remaining = wait_time.saturating_sub(start.elapsed());
+                if remaining.is_zero() {
+                    break;
+                }
+                std::thread::sleep(STOP_CHECK_INTERVAL.min(remaining));
+            }
+        }
+        Ok(())
+    }
+
+}
+
+fn backoff(interval: Duration, consecutive_failures: u32) -> Duration {
+    interval.saturating_mul(2u32.saturating_pow(consecutive_failures.min(16)))
+        .min(MAX_BACKOFF.max(interval))
+}
+
+/// Computes the events between two snapshots of the same course, in a deterministic order
+pub fn events_between(older: &CourseSnapshot, newer: &CourseSnapshot) -> Vec<CourseEvent> {
+    let diff = older.diff(newer);
+    let course_id = &newer.course_id;
+    let mut events = vec![];
+    events.extend(diff.new_files.into_iter().map(|file| CourseEvent::NewFile { course_id: course_id.clone(), file }));
+    events.extend(diff.new_announcements.into_iter().map(|announcement| CourseEvent::NewAnnouncement { course_id: course_id.clone(), announcement }));
+    events.extend(diff.opened_groups.into_iter().map(|group| CourseEvent::GroupOpened { course_id: course_id.clone(), group }));
+    events.extend(diff.cancelled_dates.into_iter().map(|date| CourseEvent::DateCancelled { course_id: course_id.clone(), date }));
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_events_between() {
+        let older = CourseSnapshot {
+            course_id: "c1".into(),
+            captured_at: Utc.timestamp_opt(0, 0).unwrap(),
+            files: Default::default(),
+            announcements: Default::default(),
+            members: Default::default(),
+            groups: Default::default(),
+            dates: Default::default(),
+        };
+        let mut newer = older.clone();
+        newer.captured_at = Utc.timestamp_opt(100, 0).unwrap();
+        let file = FileSnapshot { id: "f1".to_string(), name: "a.pdf".to_string(), path: "".to_string(), change_date: newer.captured_at };
+        newer.files.insert("f1".to_string(), file.clone());
+        let date = DateSnapshot { id: "d1".to_string(), title: "Lecture".to_string(), start: None, cancelled: true };
+        newer.dates.insert("d1".to_string(), date.clone());
+        assert_eq!(events_between(&older, &newer), vec![
+            CourseEvent::NewFile { course_id: "c1".into(), file },
+            CourseEvent::DateCancelled { course_id: "c1".into(), date },
+        ]);
+        assert!(events_between(&newer, &newer).is_empty());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let interval = Duration::from_secs(60);
+        assert_eq!(backoff(interval, 0), interval);
+        assert_eq!(backoff(interval, 2), Duration::from_secs(240));
+        assert_eq!(backoff(interval, 30), MAX_BACKOFF);
+    }
+}
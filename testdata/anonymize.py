@@ -0,0 +1,87 @@
+#!/usr/bin/env python3
+"""Anonymizes pages saved from a Stud.IP installation, so that they can be contributed as fixtures.
+
+Usage:
+    python3 testdata/anonymize.py --host studip.uni-example.de [--name "Real Name=Fake Name" ...] page.html [more.html ...]
+
+The files are rewritten in place. All files given in one run share the same replacements,
+so that ids and usernames still match across the pages of one fixture set.
+
+Replaced are:
+- the host of the installation (by studip.example.com)
+- ids (32 hex digits, e.g. of users, courses and files) by stable placeholder ids
+- usernames in links (username=...) by user1, user2, ...
+- e-mail addresses by userN@studip.example.com
+- security tokens and other hidden form values named *token*
+- names given with --name (as they can not be detected reliably)
+
+Always read the result before committing it: free text (news, forum posts, names in titles) is not detected.
+"""
+
+import argparse
+import re
+import sys
+
+ID_PATTERN = re.compile(r"(?<![0-9a-fA-F])[0-9a-f]{32}(?![0-9a-fA-F])")
+USERNAME_PATTERN = re.compile(r"(username=)([^&\"'#\s<>]+)")
+EMAIL_PATTERN = re.compile(r"[\w.+-]+@[\w-]+(?:\.[\w-]+)+")
+TOKEN_PATTERN = re.compile(r"(name=\"[^\"]*token[^\"]*\"\s+value=\")[^\"]*(\")", re.IGNORECASE)
+
+
+class Replacements:
+    def __init__(self):
+        self.ids = {}
+        self.usernames = {}
+        self.emails = {}
+
+    def id(self, match):
+        original = match.group(0)
+        # Placeholder ids stay recognizable and keep the length of real ones
+        return self.ids.setdefault(original, "%032x" % (len(self.ids) + 1))
+
+    def username(self, match):
+        original = match.group(2)
+        replacement = self.usernames.setdefault(original, "user%d" % (len(self.usernames) + 1))
+        return match.group(1) + replacement
+
+    def email(self, match):
+        original = match.group(0)
+        return self.emails.setdefault(original, "user%d@studip.example.com" % (len(self.emails) + 1))
+
+
+def anonymize(text, host, names, replacements):
+    text = text.replace(host, "studip.example.com")
+    for real, fake in names:
+        text = text.replace(real, fake)
+    text = ID_PATTERN.sub(replacements.id, text)
+    text = USERNAME_PATTERN.sub(replacements.username, text)
+    text = EMAIL_PATTERN.sub(replacements.email, text)
+    text = TOKEN_PATTERN.sub(r"\1token\2", text)
+    return text
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--host", required=True, help="the host of the installation, e.g. studip.uni-example.de")
+    parser.add_argument("--name", action="append", default=[], help="a name to replace, as \"Real Name=Fake Name\"")
+    parser.add_argument("files", nargs="+")
+    args = parser.parse_args()
+
+    names = []
+    for name in args.name:
+        real, separator, fake = name.partition("=")
+        if not separator or not real:
+            sys.exit("Expected --name \"Real Name=Fake Name\", got %r" % name)
+        names.append((real, fake))
+
+    replacements = Replacements()
+    for path in args.files:
+        with open(path, encoding="utf-8") as file:
+            text = file.read()
+        with open(path, "w", encoding="utf-8") as file:
+            file.write(anonymize(text, args.host, names, replacements))
+    print("Replaced %d ids, %d usernames and %d e-mail addresses" % (len(replacements.ids), len(replacements.usernames), len(replacements.emails)))
+
+
+if __name__ == "__main__":
+    main()
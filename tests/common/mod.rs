@@ -0,0 +1,42 @@
+//! Helpers shared by the integration tests, which run the public API against canned pages served by a [`MockServer`] \
+//! The pages are anonymized fixtures under `testdata/` (see `testdata/README.md` on how to add new ones).
+
+#![allow(dead_code)]
+
+use stud_ip_scraper::course::Course;
+use stud_ip_scraper::mock::MockServer;
+use stud_ip_scraper::StudIp;
+
+/// The id of the course of the `course/` fixtures
+pub const COURSE_ID: &str = "a1b2c3d4e5f60718293a4b5c6d7e8f90";
+
+/// Includes a page from `testdata/` as a `&'static str`, e.g. `fixture_html!("members/groups.html")`
+macro_rules! fixture_html {
+    ($path:literal) => {
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/", $path))
+    };
+}
+
+/// Includes a JSON response from `testdata/` as a `&'static str`, e.g. `fixture_json!("search/courses.json")`
+macro_rules! fixture_json {
+    ($path:literal) => {
+        fixture_html!($path)
+    };
+}
+
+/// Starts a server, that serves the courses of the user, the tabs of the course and its overview page
+pub fn course_server() -> MockServer {
+    let server = MockServer::start();
+    server.route("GET", "/dispatch.php/my_courses", 200, fixture_html!("course/my_courses.html"))
+        .route("GET", "/seminar_main.php", 200, fixture_html!("course/tabs.html"))
+        .route("GET", "/dispatch.php/course?cid=", 200, fixture_html!("course/overview.html"));
+    server
+}
+
+/// Queries the courses of the user and the modules of the course of the fixtures
+pub fn query_course(stud_ip: &mut StudIp) -> &mut Course {
+    stud_ip.my_courses.query().unwrap();
+    let course = stud_ip.my_courses.courses.get_mut(COURSE_ID).expect("Expected the course of the fixtures");
+    course.query_modules().unwrap();
+    course
+}
@@ -0,0 +1,191 @@
+//! End-to-end tests, from the canned pages of a course to the typed structs of the public API
+
+#[macro_use]
+mod common;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use stud_ip_scraper::course_modules::{FileModule, MembersModule};
+use stud_ip_scraper::get_module;
+use stud_ip_scraper::mock::MockServer;
+use stud_ip_scraper::questionnaire::QuestionnaireKind;
+use stud_ip_scraper::ref_source::ReferenceSource;
+use stud_ip_scraper::search::{AdmissionState, FilterSemester, SearchFilter};
+use stud_ip_scraper::user::User;
+use common::{course_server, query_course, COURSE_ID};
+
+#[test]
+fn test_my_courses() {
+    let server = course_server();
+    let mut stud_ip = server.stud_ip();
+    let course = query_course(&mut stud_ip);
+    assert_eq!(course.name, "Algorithmen und Datenstrukturen (SoSe 2025)");
+    // Only the registered modules are constructed, the meetings plugin is not
+    assert_eq!(course.modules.len(), 2);
+
+    let predecessors = stud_ip.my_courses.find_predecessors(&stud_ip.my_courses.courses[COURSE_ID]);
+    let names = predecessors.iter().map(|course| course.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["Algorithmen und Datenstrukturen (SoSe 2024)"]);
+}
+
+#[test]
+fn test_my_courses_newer_store() {
+    let server = course_server();
+    let page = format!("<script type=\"text/javascript\">\n    window.STUDIP.MyCoursesData = {};\n</script>", fixture_json!("course/store_5_x.json"));
+    server.route("GET", "/dispatch.php/my_courses", 200, page);
+    let mut stud_ip = server.stud_ip();
+    let warnings = stud_ip.my_courses.query_with_warnings().unwrap();
+    assert!(!warnings.is_empty());
+    assert_eq!(stud_ip.my_courses.courses.len(), 3);
+    // The courses read field by field still work as usual
+    let course = stud_ip.my_courses.courses.get_mut(COURSE_ID).unwrap();
+    assert_eq!(course.name, "Algorithmen und Datenstrukturen (SoSe 2025)");
+    course.query_modules().unwrap();
+    assert_eq!(course.modules.len(), 2);
+}
+
+#[test]
+fn test_members() {
+    let server = course_server();
+    server.route("GET", "/dispatch.php/course/members", 200, fixture_html!("members/malformed_row.html"));
+    let mut stud_ip = server.stud_ip();
+    let course = query_course(&mut stud_ip);
+    let members_module = get_module!(course, MembersModule).unwrap();
+
+    let members = members_module.get_members_with_warnings().unwrap();
+    let usernames = members.value.students.iter().map(|user| user.username.as_str()).collect::<Vec<_>>();
+    assert_eq!(usernames, vec!["jdoe", "erika", "lmeier"]);
+    assert_eq!(members.value.lecturers[0].display_name, "Prof. Max Mustermann");
+    assert_eq!(members.value.lecturers[0].source, ReferenceSource::Course(COURSE_ID.into()));
+    assert!(members.value.tutors.is_empty());
+    assert_eq!(members.warnings.len(), 1);
+    assert!(server.requests().iter().any(|request| request.path == format!("/dispatch.php/course/members?cid={}", COURSE_ID)));
+}
+
+#[test]
+fn test_groups() {
+    let server = course_server();
+    server.route("GET", "/dispatch.php/course/statusgroups", 200, fixture_html!("members/groups.html"));
+    let mut stud_ip = server.stud_ip();
+    let course = query_course(&mut stud_ip);
+    let members_module = get_module!(course, MembersModule).unwrap();
+
+    let groups = members_module.get_groups_with_warnings().unwrap();
+    assert!(groups.warnings.is_empty());
+    let groups = groups.value;
+    let names = groups.iter().map(|group| group.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["Mo 10 Übungsgruppe 1", "Di 14 Übungsgruppe 2", "Fr 8 Übungsgruppe 3"]);
+
+    assert_eq!(groups[0].id.as_str(), "1a2b3c4d5e6f708192a3b4c5d6e7f801");
+    assert!(groups[0].is_full());
+    let waitlist = groups[0].waitlist.as_ref().unwrap();
+    assert_eq!((waitlist.length, waitlist.enabled), (3, true));
+    assert!(groups[1].entered && !groups[0].entered);
+    assert_eq!((groups[1].members, groups[1].max_members), (12, 20));
+    // 10:00 in Germany, in summer time
+    assert_eq!(groups[2].enables_entry_at, Some(Utc.with_ymd_and_hms(2025, 4, 1, 8, 0, 0).unwrap()));
+}
+
+#[test]
+fn test_files() {
+    let server = course_server();
+    server.route("GET", "/dispatch.php/course/files", 200, fixture_html!("files/localized_counts.html"));
+    let mut stud_ip = server.stud_ip();
+    let course = query_course(&mut stud_ip);
+    let file_module = get_module!(course, FileModule).unwrap();
+
+    let contents = file_module.get_root_with_warnings().unwrap();
+    assert!(contents.warnings.is_empty());
+    let counts = contents.value.files.iter().map(|file| (file.downloads, file.size)).collect::<Vec<_>>();
+    assert_eq!(counts, vec![(1234, 2097152), (0, 12800), (0, 512)]);
+    assert_eq!(contents.value.folders[0].object_count, 1024);
+}
+
+#[test]
+fn test_news_and_questionnaires() {
+    let server = course_server();
+    let mut stud_ip = server.stud_ip();
+    let course = query_course(&mut stud_ip);
+
+    let news = course.query_news().unwrap();
+    assert_eq!(news.len(), 1);
+    assert_eq!(news[0].title, "Erster Übungszettel online");
+    assert_eq!(news[0].author.username, "mmustermann");
+    assert_eq!(news[0].date, NaiveDate::from_ymd_opt(2025, 4, 7).unwrap());
+    assert_eq!((news[0].visits, news[0].n_comments), (87, 1));
+    assert_eq!(news[0].source, ReferenceSource::Course(COURSE_ID.into()));
+
+    let questionnaires = course.query_questionnaires().unwrap();
+    assert_eq!(questionnaires.len(), 1);
+    let questionnaire = &questionnaires[0];
+    assert_eq!(questionnaire.title, "Termin der Klausureinsicht");
+    assert_eq!(questionnaire.author.display_name, "Prof. Max Mustermann");
+    assert_eq!(questionnaire.kind, QuestionnaireKind::MultipleChoice);
+    assert_eq!(questionnaire.total_voters, 1024);
+    assert_eq!(questionnaire.creation_date, NaiveDate::from_ymd_opt(2025, 4, 8).unwrap());
+    // The options are ordered by their value
+    let options = questionnaire.options.iter().map(|option| (option.value, option.text.as_str())).collect::<Vec<_>>();
+    assert_eq!(options, vec![(1, "Dienstag, 14 Uhr"), (2, "Mittwoch, 10 Uhr")]);
+    assert_eq!(questionnaire.terms, "Die Ergebnisse sind nach dem Ende sichtbar.");
+
+    // The overview is only requested once for both
+    let overview_requests = server.requests().iter().filter(|request| request.path.starts_with("/dispatch.php/course?")).count();
+    assert_eq!(overview_requests, 1);
+}
+
+#[test]
+fn test_profile() {
+    let server = MockServer::start();
+    server.route("GET", "/dispatch.php/profile?username=emusterfrau", 200, fixture_html!("profile/full.html"));
+    let stud_ip = server.stud_ip();
+    let user = User {
+        display_name: "Erika Musterfrau".to_string(),
+        username: "emusterfrau".to_string(),
+        avatar_src: None,
+        source: ReferenceSource::Unspecified,
+        user_id: None,
+    };
+
+    let profile = user.query_profile(&stud_ip.client).unwrap();
+    assert_eq!(profile.display_name, "Erika Musterfrau");
+    assert_eq!(profile.user_id.as_deref(), Some("fedcba9876543210fedcba9876543210"));
+    assert!(profile.avatar_src.ends_with("/pictures/user/fedcba9876543210fedcba9876543210_normal.png"), "{}", profile.avatar_src);
+    assert_eq!((profile.visits, profile.points, profile.rank.as_deref()), (1234, Some(2048), Some("Meisterin")));
+    assert_eq!(profile.motto.as_deref(), Some("Erst denken, dann committen."));
+    assert_eq!(profile.email.as_deref(), Some("erika.musterfrau@studip.example.com"));
+    assert_eq!(profile.homepage.as_deref(), Some("https://musterfrau.example.com"));
+    assert_eq!(profile.study_institutes[0].institute.name, "Institut für Informatik");
+    let work = &profile.work_institute[0];
+    assert_eq!(work.institute.id.as_str(), "aa11bb22cc33dd44ee55ff6600771122");
+    assert_eq!(work.sub_flags, vec!["Studentische Hilfskraft"]);
+    assert_eq!(work.extra_data["Sprechstunde"], "Mi 10-12 Uhr\nund nach Vereinbarung");
+    assert_eq!(profile.news[0].title, "Tutorium fällt aus");
+    assert_eq!(profile.news[0].source, ReferenceSource::Profile("emusterfrau".to_string()));
+    assert_eq!(profile.categories.len(), 1);
+    assert_eq!(profile.categories[0].name, "Hobbys");
+    assert_eq!(profile.categories[0].markdown_content().trim(), "Klettern und **Schach**");
+}
+
+#[test]
+fn test_search() {
+    let server = MockServer::start();
+    server.route_typed("GET", "/dispatch.php/globalsearch/find/", 200, "application/json", fixture_json!("search/courses.json"));
+    let stud_ip = server.stud_ip();
+
+    let filter = SearchFilter::Courses {
+        semester: FilterSemester::All,
+        seminar_type_id: None,
+        institute_id: None,
+    };
+    let result = stud_ip.global_search("Algorithmen", 10, &filter).unwrap();
+    let courses = result.courses.unwrap().content;
+    // The markings of the search term are removed
+    assert_eq!(courses[0].name, "Algorithmen und Datenstrukturen (SoSe 2024)");
+    assert_eq!((&courses[0].admission, &courses[1].admission), (&AdmissionState::Open, &AdmissionState::Locked));
+    let user = User::from(result.users.unwrap().content.remove(0));
+    assert_eq!((user.username.as_str(), user.user_id.as_deref()), ("emusterfrau", Some("fedcba9876543210fedcba9876543210")));
+
+    let semester = FilterSemester::Specific { unix_timestamp: Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap().timestamp() as u64 };
+    let previous = stud_ip.find_course_in_semester("4.01.123 Algorithmen und Datenstrukturen (SoSe 2025)", semester).unwrap();
+    let ids = previous.iter().map(|course| course.id.as_str()).collect::<Vec<_>>();
+    assert_eq!(ids, vec!["0f1e2d3c4b5a69788796a5b4c3d2e1f0"]);
+}